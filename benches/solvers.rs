@@ -0,0 +1,52 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use p424::{
+  bench_support::{empty_sudoku, kakuro_fixture, n_queens},
+  kakuro::CombinationCache,
+};
+
+/// Enumerating solutions to an empty grid, which is dominated by the search
+/// itself rather than by building the model.
+fn sudoku(c: &mut Criterion) {
+  let mut group = c.benchmark_group("sudoku");
+  let sudoku = empty_sudoku();
+  for limit in [1, 100, 1000] {
+    group.bench_with_input(
+      BenchmarkId::new("empty_grid", limit),
+      &limit,
+      |b, &limit| b.iter(|| sudoku.count_solutions(black_box(limit))),
+    );
+  }
+  group.finish();
+}
+
+fn queens(c: &mut Criterion) {
+  let mut group = c.benchmark_group("n_queens");
+  for n in [6, 8, 10] {
+    group.bench_with_input(BenchmarkId::new("count", n), &n, |b, &n| {
+      b.iter(|| n_queens(black_box(n)).count_solutions(usize::MAX))
+    });
+  }
+  group.bench_function("first_of_32", |b| {
+    b.iter(|| n_queens(black_box(32)).find_first_solution_names())
+  });
+  group.finish();
+}
+
+/// Solving with a fresh `CombinationCache` includes the cost of enumerating
+/// and filtering the combinations of every run, which a warm cache skips.
+fn kakuro(c: &mut Criterion) {
+  let mut group = c.benchmark_group("kakuro");
+  group.sample_size(20);
+  let kakuro = kakuro_fixture();
+  group.bench_function("solve_cold_cache", |b| {
+    b.iter(|| kakuro.solve_first_with_cache(&mut CombinationCache::new()))
+  });
+  let mut cache = CombinationCache::new();
+  group.bench_function("solve_warm_cache", |b| {
+    b.iter(|| kakuro.solve_first_with_cache(&mut cache))
+  });
+  group.finish();
+}
+
+criterion_group!(benches, sudoku, queens, kakuro);
+criterion_main!(benches);
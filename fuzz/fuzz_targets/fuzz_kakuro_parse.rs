@@ -0,0 +1,31 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use p424::{
+  kakuro::Kakuro,
+  paren_tree::{parse_tree, Node},
+};
+
+/// Checks that every leaf's span points at its text in `source`.
+fn check_spans(node: &Node, source: &str) {
+  match node.children() {
+    Some(children) => children.iter().for_each(|child| check_spans(child, source)),
+    None => assert_eq!(Some(&source[node.span()]), node.as_leaf()),
+  }
+}
+
+fuzz_target!(|data: &[u8]| {
+  // Reading lines rejects invalid UTF-8 with an error of its own, so this
+  // covers every byte string.
+  let _ = Kakuro::from_reader(data);
+
+  if let Ok(line) = std::str::from_utf8(data) {
+    if let Ok(kakuro) = line.parse::<Kakuro>() {
+      // Anything parsed must survive a round trip.
+      assert_eq!(kakuro.to_line().parse::<Kakuro>().as_ref(), Ok(&kakuro));
+    }
+    if let Ok(tree) = parse_tree(line) {
+      check_spans(&tree, line);
+    }
+  }
+});
@@ -0,0 +1,36 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use p424::parenthesis_split::{ParenthesesAwareSplit, SplitOptions, PARENTHESES};
+
+const BRACKETS: &[(char, char)] = &[('(', ')'), ('[', ']'), ('{', '}')];
+
+fuzz_target!(|text: &str| {
+  let options = [
+    SplitOptions::new(',', PARENTHESES),
+    // A multi-byte delimiter checks that no split lands inside a character.
+    SplitOptions::new('·', BRACKETS).quotes('"').escape('\\'),
+  ];
+  for options in options {
+    for (range, field) in text.split_with(options).indices() {
+      assert_eq!(&text[range], field);
+    }
+    for (range, field) in text.split_with(options).keep_empty().indices().rev() {
+      assert_eq!(&text[range], field);
+    }
+
+    // The checked splitter agrees with the unchecked one up to its first
+    // error.
+    let checked = text.try_split_with(options).collect::<Vec<_>>();
+    let unchecked = text.split_with(options).collect::<Vec<_>>();
+    for (checked, unchecked) in checked.iter().zip(&unchecked) {
+      match checked {
+        Ok(field) => assert_eq!(field, unchecked),
+        Err(_) => break,
+      }
+    }
+  }
+
+  let _ = text.split_paren_once();
+  let _ = text.splitn_paren(3).count();
+});
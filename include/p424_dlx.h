@@ -0,0 +1,36 @@
+/* C interface to the p424 DLX solver, built with the `ffi` feature. See
+ * src/ffi.rs for the details of each function. */
+#ifndef P424_DLX_H
+#define P424_DLX_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#define DLX_OK 0
+#define DLX_NO_SOLUTION 1
+#define DLX_NULL_POINTER -1
+#define DLX_INVALID_UTF8 -2
+#define DLX_DUPLICATE_NAME -3
+#define DLX_UNKNOWN_ITEM -4
+#define DLX_INVALID_COLOR -5
+#define DLX_BUFFER_TOO_SMALL -6
+#define DLX_INTERNAL_ERROR -7
+
+typedef struct DlxHandle DlxHandle;
+
+DlxHandle *dlx_new(void);
+
+int dlx_add_item(DlxHandle *handle, const char *name, bool is_primary);
+
+/* `colors` may be NULL, and negative colors leave an item uncolored. */
+int dlx_add_subset(DlxHandle *handle, uint64_t name, const char *const *items,
+                   const int64_t *colors, size_t len);
+
+/* `*out_len` is the capacity of `out_names` on entry, and the length of the
+ * solution on return. */
+int dlx_solve(const DlxHandle *handle, uint64_t *out_names, size_t *out_len);
+
+void dlx_free(DlxHandle *handle);
+
+#endif
@@ -0,0 +1,61 @@
+//! Canonical instances for the benchmarks in `benches/`, kept in the library so
+//! that the benchmarks measure the same models as the solvers use.
+
+use crate::{
+  dlx::Dlx,
+  kakuro::Kakuro,
+  nqueens::{self, QueenItem},
+  sudoku::Sudoku,
+};
+
+/// A 7x7 Project Euler 424 puzzle, which takes tens of milliseconds to solve.
+pub const KAKURO_FIXTURE: &str = "7,X,(vI),(vDH),X,X,X,X,(hB),O,I,X,X,X,X,(hC),O,O,(vA),(vI),(vDJ),X,(hDH),O,B,O,O,O,(vDH),X,(hGG),O,O,O,J,O,X,X,X,X,(hHH),B,O,X,X,X,X,(hHE),O,O";
+
+/// The value of the solution to `KAKURO_FIXTURE`.
+pub const KAKURO_FIXTURE_VALUE: u64 = 8752403169;
+
+pub fn kakuro_fixture() -> Kakuro {
+  KAKURO_FIXTURE.parse().unwrap()
+}
+
+/// A sudoku with no givens, which has far too many solutions to enumerate, so
+/// benchmarks cap how many they look for.
+pub fn empty_sudoku() -> Sudoku {
+  Sudoku::new([[0; 9]; 9])
+}
+
+/// The `n` queens model the `nqueens` solver searches.
+pub fn n_queens(n: usize) -> Dlx<QueenItem, (usize, usize)> {
+  nqueens::build_dlx(n)
+}
+
+#[cfg(test)]
+mod test {
+  use crate::kakuro::CombinationCache;
+
+  use super::{empty_sudoku, kakuro_fixture, n_queens, KAKURO_FIXTURE_VALUE};
+
+  #[test]
+  fn test_kakuro_fixture() {
+    let kakuro = kakuro_fixture();
+    assert_eq!(kakuro.rows(), 7);
+    let letters = kakuro
+      .solve_first_with_cache(&mut CombinationCache::new())
+      .unwrap()
+      .unwrap();
+    assert_eq!(letters.int_value(), KAKURO_FIXTURE_VALUE);
+  }
+
+  #[test]
+  fn test_empty_sudoku() {
+    let sudoku = empty_sudoku();
+    assert_eq!(sudoku.givens().count(), 0);
+    assert_eq!(sudoku.count_solutions(100), 100);
+  }
+
+  #[test]
+  fn test_n_queens() {
+    assert_eq!(n_queens(8).count_solutions(usize::MAX), 92);
+    assert_eq!(n_queens(3).find_first_solution_names(), None);
+  }
+}
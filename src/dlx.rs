@@ -1,11 +1,22 @@
 use std::{
   collections::{HashMap, HashSet},
-  fmt::{self, Debug, Formatter},
+  error::Error,
+  fmt::{self, Debug, Display, Formatter},
   hash::Hash,
   iter,
+  ops::{ControlFlow, Range},
+  sync::Arc,
+  time::Instant,
 };
 
-#[derive(Debug)]
+use itertools::Itertools;
+use log::{debug, log_enabled, trace, Level};
+
+/// How many subsets a search tries between checks of its deadline, since
+/// reading the clock on every node would slow it down.
+const DEADLINE_CHECK_INTERVAL: u64 = 1024;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
 pub struct ColorItem<I> {
   item: I,
   color: u32,
@@ -17,22 +28,28 @@ impl<I> ColorItem<I> {
   }
 }
 
-#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq, Debug)]
 pub enum Constraint<I> {
   Primary(I),
   Secondary(ColorItem<I>),
+  /// A secondary item without a color, which at most one subset in a solution
+  /// may contain. An item must either always or never be given a color.
+  Uncolored(I),
 }
 
 impl<I> Constraint<I> {
-  fn item(&self) -> &I {
+  pub fn item(&self) -> &I {
     match self {
-      Constraint::Primary(item) | Constraint::Secondary(ColorItem { item, .. }) => item,
+      Constraint::Primary(item)
+      | Constraint::Secondary(ColorItem { item, .. })
+      | Constraint::Uncolored(item) => item,
     }
   }
 
-  fn color(&self) -> Option<u32> {
+  /// The color of the item, if it's a colored secondary item.
+  pub fn color(&self) -> Option<u32> {
     match self {
-      Constraint::Primary(_) => None,
+      Constraint::Primary(_) | Constraint::Uncolored(_) => None,
       Constraint::Secondary(ColorItem { color, .. }) => Some(*color),
     }
   }
@@ -50,6 +67,50 @@ impl<I> From<ColorItem<I>> for Constraint<I> {
   }
 }
 
+/// An error from changing which subsets a `Dlx` may choose.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DlxError {
+  /// No subset has the given name.
+  UnknownSubset(String),
+  /// The subset can't be chosen alongside those already chosen, either
+  /// because it shares an item or color with them or because it has no
+  /// primary items, which the search never chooses.
+  UnavailableSubset(String),
+  /// No item has the given name.
+  UnknownItem(String),
+  /// A subset with the given name already exists.
+  DuplicateSubset(String),
+  /// The item is used unlike in the other subsets: a primary item as
+  /// secondary or the other way around, or a secondary item both with and
+  /// without colors.
+  InvalidConstraint(String),
+  /// The subsets can't be changed during a search, or while subsets are
+  /// chosen with `require_subset`.
+  Searching,
+}
+
+impl Display for DlxError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self {
+      DlxError::UnknownSubset(name) => write!(f, "No subset named {name}"),
+      DlxError::UnavailableSubset(name) => write!(f, "Subset {name} can't be chosen"),
+      DlxError::UnknownItem(item) => write!(f, "No item named {item}"),
+      DlxError::DuplicateSubset(name) => write!(f, "Subset {name} already exists"),
+      DlxError::InvalidConstraint(item) => {
+        write!(f, "Item {item} is used unlike in the other subsets")
+      }
+      DlxError::Searching => write!(f, "Can't change the subsets mid-search"),
+    }
+  }
+}
+
+impl Error for DlxError {}
+
+/// A point to return to with `Dlx::rollback`, from `Dlx::begin`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Checkpoint(usize);
+
+#[derive(Clone)]
 struct ListNodeI<I> {
   prev: I,
   next: I,
@@ -57,16 +118,19 @@ struct ListNodeI<I> {
 
 type HeaderListNode = ListNodeI<u32>;
 
-#[derive(Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum HeaderType {
   Primary,
   Secondary,
 }
 
+#[derive(Clone)]
 struct Header<I> {
   item: Option<I>,
   node: HeaderListNode,
   header_type: HeaderType,
+  /// Whether this is a secondary item used without colors.
+  uncolored: bool,
 }
 
 impl<I> Header<I> {
@@ -76,6 +140,12 @@ impl<I> Header<I> {
       HeaderType::Secondary => false,
     }
   }
+
+  /// Whether choosing a subset with this item removes every other subset
+  /// with it, rather than only those with a different color.
+  fn is_covered(&self) -> bool {
+    self.is_primary() || self.uncolored
+  }
 }
 
 impl<I> Debug for Header<I>
@@ -102,6 +172,7 @@ where
 
 type ListNode = ListNodeI<usize>;
 
+#[derive(Clone)]
 enum NodeType {
   Header {
     /// Number of constraints that have this item.
@@ -115,6 +186,7 @@ enum NodeType {
   },
 }
 
+#[derive(Clone)]
 enum Node<N> {
   Boundary {
     /// The name of the subset listed to the left of this boundary.
@@ -254,10 +326,271 @@ where
   }
 }
 
+/// One solution found by a search on a `Dlx`.
+#[derive(Clone, Debug)]
+pub struct Selection<I, N> {
+  /// The names of the chosen subsets, in the order the search chose them.
+  pub names: Vec<N>,
+  /// The colors the chosen subsets assign to secondary items, along with any
+  /// required colors.
+  pub colors: HashMap<I, u32>,
+}
+
+/// Counters describing the work done by searches on a `Dlx`.
+#[derive(Clone, Debug)]
+pub struct DlxStats<I> {
+  /// The number of subsets tried as part of a partial solution.
+  pub nodes: u64,
+  /// The number of solutions found.
+  pub solutions: u64,
+  /// For each primary item, the number of times the search chose to cover it
+  /// and found no subsets left which could.
+  pub dead_ends: HashMap<I, u64>,
+}
+
+impl<I> Default for DlxStats<I> {
+  fn default() -> Self {
+    Self {
+      nodes: 0,
+      solutions: 0,
+      dead_ends: HashMap::new(),
+    }
+  }
+}
+
+impl<I: Hash + Eq> DlxStats<I> {
+  /// The primary item the search most often failed to cover, with the number
+  /// of times it did.
+  pub fn worst_dead_end(&self) -> Option<(&I, u64)> {
+    self
+      .dead_ends
+      .iter()
+      .max_by_key(|(_, &count)| count)
+      .map(|(item, &count)| (item, count))
+  }
+}
+
+/// An item of a subset kept by `Problem`: the item's index in
+/// `Problem::items`, and its color if it has one.
+type RetainedConstraint = (u32, Option<u32>);
+
+/// The items and subsets a `Dlx` was built from, kept by
+/// `Dlx::new_retaining`. Each item of a subset is stored as its index in
+/// `items` and its color, taking 12 bytes per node on top of one copy of each
+/// item and subset name. Clones of a `Dlx` share this.
+struct Problem<I, N> {
+  items: Vec<(I, HeaderType)>,
+  subsets: Vec<(N, Vec<RetainedConstraint>)>,
+}
+
+impl<I, N> Problem<I, N>
+where
+  I: Hash + Eq + Clone + Debug,
+{
+  fn new<U, S, C, D>(items: U, subsets: S) -> Self
+  where
+    U: IntoIterator<Item = (I, HeaderType)>,
+    S: IntoIterator<Item = (N, C)>,
+    C: IntoIterator<Item = D>,
+    D: Into<Constraint<I>>,
+  {
+    let items: Vec<_> = items.into_iter().collect();
+    let item_map: HashMap<_, _> = items
+      .iter()
+      .enumerate()
+      .map(|(idx, (item, _))| (item.clone(), idx as u32))
+      .collect();
+    let subsets = subsets
+      .into_iter()
+      .map(|(name, constraints)| {
+        let constraints = constraints
+          .into_iter()
+          .map(|constraint| {
+            let constraint: Constraint<I> = constraint.into();
+            let idx = *item_map
+              .get(constraint.item())
+              .unwrap_or_else(|| panic!("Unknown item {:?}", constraint.item()));
+            (idx, constraint.color())
+          })
+          .collect();
+        (name, constraints)
+      })
+      .collect();
+    Self { items, subsets }
+  }
+
+  fn constraints<'a>(
+    &'a self,
+    constraints: &'a [RetainedConstraint],
+  ) -> impl Iterator<Item = Constraint<I>> + 'a {
+    constraints.iter().map(|&(idx, color)| {
+      let (item, header_type) = &self.items[idx as usize];
+      match (header_type, color) {
+        (HeaderType::Primary, _) => Constraint::Primary(item.clone()),
+        (HeaderType::Secondary, Some(color)) => {
+          Constraint::Secondary(ColorItem::new(item.clone(), color))
+        }
+        (HeaderType::Secondary, None) => Constraint::Uncolored(item.clone()),
+      }
+    })
+  }
+}
+
+/// The colors of the key items of a search for distinct colorings, from
+/// `Dlx::find_all_distinct_colorings`, under the partial solution being
+/// explored.
+struct DistinctColorings {
+  /// The position of each key in `colors`, by the index of its header.
+  keys: HashMap<usize, usize>,
+  /// The color of each key, if a chosen subset or required color has set it.
+  colors: Vec<Option<u32>>,
+  /// The positions in `colors` set by the partial solution, in the order they
+  /// were set.
+  assigned: Vec<usize>,
+  /// The length of `assigned` before each subset of the partial solution was
+  /// chosen.
+  depths: Vec<usize>,
+  /// The colors of the keys in every solution reported so far.
+  reported: HashSet<Vec<Option<u32>>>,
+}
+
+impl DistinctColorings {
+  /// Records the colors of a newly chosen subset, given as the header index
+  /// of each of its items with the item's color.
+  fn choose(&mut self, items: impl IntoIterator<Item = (usize, Option<u32>)>) {
+    self.depths.push(self.assigned.len());
+    for (top, color) in items {
+      if let (Some(&key), Some(color)) = (self.keys.get(&top), color) {
+        if self.colors[key].is_none() {
+          self.colors[key] = Some(color);
+          self.assigned.push(key);
+        }
+      }
+    }
+  }
+
+  /// Forgets the colors set by the most recently chosen subset.
+  fn unchoose(&mut self) {
+    let depth = self.depths.pop().unwrap();
+    for key in self.assigned.drain(depth..) {
+      self.colors[key] = None;
+    }
+  }
+
+  /// True if every key has been colored, like a solution already reported,
+  /// so nothing below the partial solution can be reported.
+  fn is_reported(&self) -> bool {
+    self.colors.iter().all(Option::is_some) && self.reported.contains(&self.colors)
+  }
+
+  /// Records the colors of a solution, returning true if they haven't been
+  /// reported before.
+  fn report(&mut self) -> bool {
+    self.reported.insert(self.colors.clone())
+  }
+}
+
+/// Where a search from `Dlx::start_search` left off, so `Dlx::search_next` can
+/// resume it.
+struct SearchState {
+  distinct: Option<DistinctColorings>,
+  /// The number of solutions found so far.
+  found: usize,
+  /// The number of subsets tried so far.
+  nodes: u64,
+  /// The items covered and subsets chosen on the path to the current node.
+  solution: Vec<usize>,
+  /// The nodes of the subsets in the last solution found, including those
+  /// chosen by `require_subset`.
+  nodes_found: Vec<usize>,
+  /// Which members of each symmetry group are chosen.
+  symmetry_chosen: Vec<Vec<bool>>,
+  /// Set after a solution is found, so the search resumes by backtracking.
+  resume: bool,
+}
+
+/// Groups of interchangeable subsets from `Dlx::add_symmetry_group`.
+#[derive(Clone, Default)]
+struct SymmetryGroups {
+  /// The boundary following each member of each group, in canonical order.
+  groups: Vec<Vec<usize>>,
+  /// The group and position within it of each membership of a subset, by the
+  /// boundary following the subset.
+  members: HashMap<usize, Vec<(usize, usize)>>,
+}
+
+impl SymmetryGroups {
+  /// Marks whether the subset before `boundary` is chosen in `chosen`, which
+  /// holds a flag for each member of each group.
+  fn mark(&self, chosen: &mut [Vec<bool>], boundary: usize, value: bool) {
+    for &(group, pos) in self.members.get(&boundary).into_iter().flatten() {
+      chosen[group][pos] = value;
+    }
+  }
+}
+
+/// The solutions of a `Dlx`, from `Dlx::into_solution_names`.
+pub struct SolutionNames<I, N> {
+  dlx: Dlx<I, N>,
+  /// The search in progress, or None once it's finished.
+  state: Option<SearchState>,
+}
+
+impl<I, N> Iterator for SolutionNames<I, N>
+where
+  I: Hash + Eq + Clone + Debug,
+  N: Hash + Eq + Clone + Debug,
+{
+  type Item = Vec<N>;
+
+  fn next(&mut self) -> Option<Vec<N>> {
+    let state = self.state.as_mut()?;
+    if self.dlx.search_next(state) {
+      Some(
+        state
+          .nodes_found
+          .iter()
+          .map(|&p| self.dlx.set_name_for_node(p))
+          .collect(),
+      )
+    } else {
+      self.state = None;
+      None
+    }
+  }
+}
+
+/// Cloning copies the whole structure, including any constraints retained by
+/// `new_retaining`, which the clone shares.
+#[derive(Clone)]
 pub struct Dlx<I, N> {
   num_primary_items: usize,
   headers: Vec<Header<I>>,
   body: Vec<Node<N>>,
+  /// Colors fixed for secondary items before the search, via
+  /// `require_color`.
+  required: Vec<(I, u32)>,
+  stats: DlxStats<I>,
+  node_limit: Option<u64>,
+  deadline: Option<Instant>,
+  /// Set if the last search stopped before exploring every choice.
+  interrupted: bool,
+  /// The problem this was built from, if built with `new_retaining`.
+  problem: Option<Arc<Problem<I, N>>>,
+  /// The index of each item's header.
+  item_indices: HashMap<I, usize>,
+  /// The index of the boundary following each subset.
+  subset_indices: HashMap<N, usize>,
+  /// A node from each subset chosen by `require_subset` and not yet rolled
+  /// back, in the order they were chosen.
+  chosen: Vec<usize>,
+  /// Set while a search is running.
+  searching: bool,
+  /// The nodes `disable_subset` unlinked from the lists of their items, by
+  /// the index of the boundary following their subset.
+  disabled: HashMap<usize, Vec<usize>>,
+  /// Groups of subsets whose members searches only choose in order.
+  symmetry: SymmetryGroups,
 }
 
 impl<I, N> Dlx<I, N>
@@ -275,6 +608,63 @@ where
     Self::construct(items, subsets)
   }
 
+  /// Like `new`, but also keeps a compact copy of the items and subsets, so
+  /// the original problem can be listed with `original_subsets` or restored
+  /// with `rebuild`. This costs 12 bytes for each item of each subset, plus a
+  /// copy of every item and subset name.
+  pub fn new_retaining<U, S, C, D>(items: U, subsets: S) -> Self
+  where
+    U: IntoIterator<Item = (I, HeaderType)>,
+    S: IntoIterator<Item = (N, C)>,
+    C: IntoIterator<Item = D>,
+    D: Into<Constraint<I>>,
+  {
+    Self::from_problem(Arc::new(Problem::new(items, subsets)))
+  }
+
+  fn from_problem(problem: Arc<Problem<I, N>>) -> Self {
+    let mut dlx = Self::construct(
+      problem.items.iter().cloned(),
+      problem
+        .subsets
+        .iter()
+        .map(|(name, constraints)| (name.clone(), problem.constraints(constraints))),
+    );
+    dlx.problem = Some(problem);
+    dlx
+  }
+
+  /// The subsets this was built from, in the order they were given, or None
+  /// if it wasn't built with `new_retaining`. Colors fixed later by
+  /// `require_color` aren't reflected.
+  pub fn original_subsets(
+    &self,
+  ) -> Option<impl Iterator<Item = (&N, impl Iterator<Item = Constraint<I>> + '_)> + '_> {
+    let problem = self.problem.as_ref()?;
+    Some(
+      problem
+        .subsets
+        .iter()
+        .map(|(name, constraints)| (name, problem.constraints(constraints))),
+    )
+  }
+
+  /// Restores the structure to how `new_retaining` built it, undoing any
+  /// `require_color`, `require_subset` and `add_symmetry_group` calls and
+  /// clearing the stats. Search limits are kept.
+  /// Panics if this wasn't built with `new_retaining`.
+  pub fn rebuild(&mut self) {
+    let problem = self
+      .problem
+      .clone()
+      .expect("Only a Dlx built with new_retaining can be rebuilt");
+    *self = Self {
+      node_limit: self.node_limit,
+      deadline: self.deadline,
+      ..Self::from_problem(problem)
+    };
+  }
+
   fn construct<U, S, C, D>(items: U, subsets: S) -> Self
   where
     U: IntoIterator<Item = (I, HeaderType)>,
@@ -286,11 +676,10 @@ where
       item: None,
       node: ListNodeI { prev: 0, next: 1 },
       header_type: HeaderType::Primary,
+      uncolored: false,
     }];
     let mut item_map = HashMap::new();
     let mut body = Vec::new();
-    let mut last_start_index;
-    let mut subset_names = HashSet::new();
 
     // Push phony node to first element of body.
     body.push(Node::Boundary {
@@ -333,6 +722,7 @@ where
               next: new_idx as u32 + 1,
             },
             header_type,
+            uncolored: false,
           }
         }),
     );
@@ -344,6 +734,7 @@ where
         next: primary_headers_len + 1,
       },
       header_type: HeaderType::Secondary,
+      uncolored: false,
     });
     headers.get_mut(0).unwrap().node.prev = primary_headers_len;
     headers
@@ -364,78 +755,468 @@ where
       last_for_next: 0,
     });
 
+    let num_primary_items = headers.first().unwrap().node.prev as usize;
+    let mut dlx = Dlx {
+      headers,
+      body,
+      num_primary_items,
+      required: Vec::new(),
+      stats: DlxStats::default(),
+      node_limit: None,
+      deadline: None,
+      interrupted: false,
+      problem: None,
+      item_indices: item_map,
+      subset_indices: HashMap::new(),
+      chosen: Vec::new(),
+      searching: false,
+      disabled: HashMap::new(),
+      symmetry: SymmetryGroups::default(),
+    };
     for (name, constraints) in subsets {
-      if !subset_names.insert(name.clone()) {
-        panic!("Duplicate subset name: {name:?}");
+      dlx.push_subset(name, constraints);
+    }
+    dlx
+  }
+
+  /// Adds the subset `name` covering `constraints` after all the others, so
+  /// later searches may choose it. As with the subsets given to `new`, it's
+  /// removed straight away if it colors an item differently than required by
+  /// `require_color`. It isn't retained for `rebuild`. Fails without changing
+  /// anything if the name is taken, an item is unknown or used unlike in the
+  /// other subsets, or a search is running or subsets are required.
+  pub fn add_subset(
+    &mut self,
+    name: N,
+    constraints: impl IntoIterator<Item = Constraint<I>>,
+  ) -> Result<(), DlxError> {
+    if self.searching || !self.chosen.is_empty() {
+      return Err(DlxError::Searching);
+    }
+    if self.subset_indices.contains_key(&name) {
+      return Err(DlxError::DuplicateSubset(format!("{name:?}")));
+    }
+    let constraints = constraints.into_iter().collect_vec();
+    for constraint in &constraints {
+      let item = constraint.item();
+      let idx = *self
+        .item_indices
+        .get(item)
+        .ok_or_else(|| DlxError::UnknownItem(format!("{item:?}")))?;
+      let header = self.header(idx);
+      let valid = match constraint {
+        Constraint::Primary(_) => header.is_primary(),
+        Constraint::Secondary(_) | Constraint::Uncolored(_) => {
+          !header.is_primary()
+            && (header.uncolored == matches!(constraint, Constraint::Uncolored(_))
+              || self.body_header(idx).len() == 0)
+        }
+      };
+      if !valid {
+        return Err(DlxError::InvalidConstraint(format!("{item:?}")));
       }
+    }
 
-      last_start_index = body.len();
-      constraints.into_iter().for_each(|constraint| {
-        let constraint: Constraint<I> = constraint.into();
-        let idx = body.len();
-
-        let header_idx = *item_map
-          .get(constraint.item())
-          .unwrap_or_else(|| panic!("Unknown item {:?}", constraint.item()));
-        let header = body.get_mut(header_idx).unwrap();
-        let prev_idx = header.prev();
-
-        debug_assert!(
-          matches!(
-            (headers.get(header_idx).unwrap(), &constraint),
-            (
-              Header {
-                header_type: HeaderType::Primary,
-                ..
-              },
-              Constraint::Primary(_),
-            ) | (
-              Header {
-                header_type: HeaderType::Secondary,
-                ..
-              },
-              Constraint::Secondary(_),
-            )
-          ),
-          "Expect constraint type to match item type (primary vs. secondary)"
-        );
+    let removed = constraints.iter().any(|constraint| {
+      self
+        .required
+        .iter()
+        .any(|(item, color)| item == constraint.item() && constraint.color() != Some(*color))
+    });
+    self.push_subset(name.clone(), constraints);
+    if removed {
+      self.unlink_subset(self.subset_indices[&name]);
+    }
+    Ok(())
+  }
 
-        header.set_prev(idx);
-        *header.len_mut() += 1;
-        body.get_mut(prev_idx).unwrap().set_next(idx);
+  /// Appends the subset `name` after all the others. Panics if the name is
+  /// taken or an item is unknown.
+  fn push_subset<C, D>(&mut self, name: N, constraints: C)
+  where
+    C: IntoIterator<Item = D>,
+    D: Into<Constraint<I>>,
+  {
+    if self.subset_indices.contains_key(&name) {
+      panic!("Duplicate subset name: {name:?}");
+    }
 
-        body.push(Node::Normal {
-          item_node: ListNodeI {
-            prev: prev_idx,
-            next: header_idx,
-          },
-          node_type: NodeType::Body {
-            color: constraint.color(),
-            top: header_idx as u32,
-          },
-        });
+    let start_index = self.body.len();
+    constraints.into_iter().for_each(|constraint| {
+      let constraint: Constraint<I> = constraint.into();
+      let idx = self.body.len();
+
+      let header_idx = *self
+        .item_indices
+        .get(constraint.item())
+        .unwrap_or_else(|| panic!("Unknown item {:?}", constraint.item()));
+      let uncolored = matches!(constraint, Constraint::Uncolored(_));
+      let header_len = self.body.get(header_idx).unwrap().len();
+      let item_header = self.headers.get_mut(header_idx).unwrap();
+      if header_len > 0 && item_header.uncolored != uncolored {
+        panic!(
+          "Secondary item {:?} used both with and without colors",
+          constraint.item()
+        );
+      }
+      item_header.uncolored = uncolored;
+
+      debug_assert!(
+        matches!(
+          (self.headers.get(header_idx).unwrap(), &constraint),
+          (
+            Header {
+              header_type: HeaderType::Primary,
+              ..
+            },
+            Constraint::Primary(_),
+          ) | (
+            Header {
+              header_type: HeaderType::Secondary,
+              ..
+            },
+            Constraint::Secondary(_) | Constraint::Uncolored(_),
+          )
+        ),
+        "Expect constraint type to match item type (primary vs. secondary)"
+      );
+
+      let header = self.body.get_mut(header_idx).unwrap();
+      let prev_idx = header.prev();
+      header.set_prev(idx);
+      *header.len_mut() += 1;
+      self.body.get_mut(prev_idx).unwrap().set_next(idx);
+
+      self.body.push(Node::Normal {
+        item_node: ListNodeI {
+          prev: prev_idx,
+          next: header_idx,
+        },
+        node_type: NodeType::Body {
+          color: constraint.color(),
+          top: header_idx as u32,
+        },
       });
+    });
 
-      let last_idx = body.len() - 1;
-      if let Some(Node::Boundary { last_for_next, .. }) = body.get_mut(last_start_index - 1) {
-        *last_for_next = last_idx;
-      } else {
-        unreachable!();
+    let last_idx = self.body.len() - 1;
+    if let Some(Node::Boundary { last_for_next, .. }) = self.body.get_mut(start_index - 1) {
+      *last_for_next = last_idx;
+    } else {
+      unreachable!();
+    }
+
+    self.subset_indices.insert(name.clone(), self.body.len());
+    self.body.push(Node::Boundary {
+      name: Some(name),
+      first_for_prev: start_index,
+      last_for_next: 0,
+    });
+  }
+
+  /// Unlinks the subset `name` from the lists of its items for good, so no
+  /// later search chooses it, and frees its name for `add_subset`. Its nodes
+  /// stay in the grid, unreachable. Returns false if there's no such subset.
+  /// Must not be called during a search, or while any subsets are required.
+  pub(crate) fn remove_subset(&mut self, name: &N) -> bool {
+    let Some(boundary) = self.subset_indices.remove(name) else {
+      return false;
+    };
+    self.disabled.remove(&boundary);
+    self.unlink_subset(boundary);
+    true
+  }
+
+  /// Stops later searches from choosing the subset `name`, until it's enabled
+  /// again by `enable_subset`. Disabling a disabled subset does nothing.
+  /// Fails if there's no such subset, or a search is running or subsets are
+  /// required, as the lists of items are then only partly linked.
+  pub fn disable_subset(&mut self, name: &N) -> Result<(), DlxError> {
+    let boundary = self.changeable_subset(name)?;
+    if !self.disabled.contains_key(&boundary) {
+      let unlinked = self.unlink_subset(boundary);
+      self.disabled.insert(boundary, unlinked);
+    }
+    Ok(())
+  }
+
+  /// Lets later searches choose the subset `name` again after
+  /// `disable_subset`. A subset removed by `require_color` stays removed, and
+  /// enabling a subset which isn't disabled does nothing. Fails like
+  /// `disable_subset`.
+  pub fn enable_subset(&mut self, name: &N) -> Result<(), DlxError> {
+    let boundary = self.changeable_subset(name)?;
+    for p in self.disabled.remove(&boundary).unwrap_or_default() {
+      self.relink(p);
+    }
+    Ok(())
+  }
+
+  /// Declares the subsets `names` interchangeable: any permutation of them
+  /// can be extended to a symmetry of the whole problem, mapping solutions to
+  /// solutions. Searches then only report solutions whose chosen members of
+  /// the group come first in `names`, skipping any choice after which a
+  /// member is chosen while one before it is neither chosen nor still
+  /// available. Each solution left out is a permutation of one reported, so
+  /// this prunes solutions which only differ by relabeling interchangeable
+  /// objects. Members should each contain a primary item. Like
+  /// `require_color`, this can't be undone. Fails if a subset doesn't exist.
+  pub fn add_symmetry_group(&mut self, names: Vec<N>) -> Result<(), DlxError> {
+    let group = names
+      .iter()
+      .map(|name| {
+        self
+          .subset_indices
+          .get(name)
+          .copied()
+          .ok_or_else(|| DlxError::UnknownSubset(format!("{name:?}")))
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+    let idx = self.symmetry.groups.len();
+    for (pos, &boundary) in group.iter().enumerate() {
+      self
+        .symmetry
+        .members
+        .entry(boundary)
+        .or_default()
+        .push((idx, pos));
+    }
+    self.symmetry.groups.push(group);
+    Ok(())
+  }
+
+  /// True if, with the members of each symmetry group marked in `chosen`, some
+  /// chosen member follows one which isn't chosen and can no longer be, so
+  /// the partial solution can't lead to a solution in canonical order.
+  fn breaks_symmetry(&self, chosen: &[Vec<bool>]) -> bool {
+    self
+      .symmetry
+      .groups
+      .iter()
+      .zip(chosen)
+      .any(|(group, chosen)| {
+        let Some(last) = chosen.iter().rposition(|&c| c) else {
+          return false;
+        };
+        (0..last).any(|pos| !chosen[pos] && !self.is_subset_available(group[pos]))
+      })
+  }
+
+  /// The boundary following the subset `name`, if the subsets can be changed.
+  fn changeable_subset(&self, name: &N) -> Result<usize, DlxError> {
+    if self.searching || !self.chosen.is_empty() {
+      return Err(DlxError::Searching);
+    }
+    self
+      .subset_indices
+      .get(name)
+      .copied()
+      .ok_or_else(|| DlxError::UnknownSubset(format!("{name:?}")))
+  }
+
+  /// Links the body node `p` back into the list of its item, which outside of
+  /// a search is ordered by index.
+  fn relink(&mut self, p: usize) {
+    let top = self.top(p);
+    let mut next = self.body_header(top).next();
+    while next != top && next < p {
+      next = self.body_node(next).next();
+    }
+    let prev = self.node(next).prev();
+    self.node_mut(prev).set_next(p);
+    self.node_mut(next).set_prev(p);
+    self.body_node_mut(p).set_prev(prev);
+    self.body_node_mut(p).set_next(next);
+    *self.body_header_mut(top).len_mut() += 1;
+  }
+
+  /// Unlinks the nodes of the subset before `boundary` from the lists of
+  /// their items, returning those which were still linked.
+  fn unlink_subset(&mut self, boundary: usize) -> Vec<usize> {
+    let first = match self.body_node(boundary) {
+      Node::Boundary { first_for_prev, .. } => *first_for_prev,
+      Node::Normal { .. } => unreachable!(),
+    };
+    let mut unlinked = Vec::new();
+    for p in first..boundary {
+      let prev = self.body_node(p).prev();
+      let next = self.body_node(p).next();
+      // Subsets removed by `require_color` are already unlinked.
+      if self.node(prev).next() == p {
+        self.node_mut(prev).set_next(next);
+        self.node_mut(next).set_prev(prev);
+        *self.body_header_mut(self.top(p)).len_mut() -= 1;
+        unlinked.push(p);
       }
+    }
+    unlinked
+  }
 
-      body.push(Node::Boundary {
-        name: Some(name),
-        first_for_prev: last_start_index,
-        last_for_next: 0,
-      });
+  /// The number of nodes in the grid, counting one for each item of each
+  /// subset along with the headers and subset boundaries.
+  pub fn num_nodes(&self) -> usize {
+    self.body.len()
+  }
+
+  /// Statistics accumulated over all searches so far.
+  pub fn stats(&self) -> &DlxStats<I> {
+    &self.stats
+  }
+
+  /// Stops each search after it has tried `limit` subsets, or never if None.
+  pub fn set_node_limit(&mut self, limit: Option<u64>) {
+    self.node_limit = limit;
+  }
+
+  /// Stops searches once `deadline` has passed, or never if None. The clock
+  /// is only checked every few thousand subsets, so searches may run slightly
+  /// past it.
+  pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+    self.deadline = deadline;
+  }
+
+  /// True if the last search ran out of nodes or time before exploring every
+  /// choice, in which case it only returned the solutions found up to then.
+  /// The structure is restored either way, so it can be searched again.
+  pub fn interrupted(&self) -> bool {
+    self.interrupted
+  }
+
+  /// The primary items left to cover, in the order they were given, each
+  /// with the number of subsets still available to cover it.
+  pub fn active_primary_items(&self) -> impl Iterator<Item = (&I, usize)> + '_ {
+    self.active_primary_headers().map(|idx| {
+      (
+        self.header(idx).item.as_ref().unwrap(),
+        self.body_header(idx).len(),
+      )
+    })
+  }
+
+  /// The number of subsets which could still be chosen, i.e. those covering
+  /// some primary item left to cover. Subsets with only secondary items are
+  /// never counted.
+  pub fn active_subset_count(&self) -> usize {
+    let mut boundaries = HashSet::new();
+    for top in self.active_primary_headers() {
+      let mut p = self.body_header(top).next();
+      while p != top {
+        boundaries.insert(self.boundary_for_node(p));
+        p = self.body_node(p).next();
+      }
+    }
+    boundaries.len()
+  }
+
+  /// True if `item` has been removed from the items left to cover, as happens
+  /// to primary and uncolored secondary items once a chosen subset contains
+  /// them. Colored secondary items are never covered, only restricted to the
+  /// chosen color. Panics if there is no such item.
+  pub fn is_item_covered(&self, item: &I) -> bool {
+    let idx = *self
+      .item_indices
+      .get(item)
+      .unwrap_or_else(|| panic!("Unknown item {item:?}"));
+    self.is_header_covered(idx)
+  }
+
+  fn is_header_covered(&self, idx: usize) -> bool {
+    // A covered item is unlinked from its neighbors, though it keeps its own
+    // links to restore itself when uncovered.
+    self.header(self.header(idx).node.prev as usize).node.next as usize != idx
+  }
+
+  /// Marks the current state, so the subsets required after can be undone
+  /// with `rollback`.
+  pub fn begin(&self) -> Checkpoint {
+    Checkpoint(self.chosen.len())
+  }
+
+  /// Chooses the subset `name` as part of every solution, as if the search
+  /// had chosen it: its items are covered and every subset conflicting with
+  /// it removed. Later searches only look for ways to complete the subsets
+  /// chosen so far, and report them in each solution. Fails without changing
+  /// anything if there is no such subset or it can't be chosen.
+  pub fn require_subset(&mut self, name: &N) -> Result<(), DlxError> {
+    let boundary = *self
+      .subset_indices
+      .get(name)
+      .ok_or_else(|| DlxError::UnknownSubset(format!("{name:?}")))?;
+    let available = self.is_subset_available(boundary);
+    let primary = self
+      .subset_nodes(boundary)
+      .find(|&p| self.header(self.top(p)).is_primary());
+    let Some(p) = primary.filter(|_| available) else {
+      return Err(DlxError::UnavailableSubset(format!("{name:?}")));
+    };
+
+    self.cover(self.top(p));
+    self.cover_remaining_choices(p);
+    self.chosen.push(p);
+    Ok(())
+  }
+
+  /// The body nodes of the subset before `boundary`.
+  fn subset_nodes(&self, boundary: usize) -> Range<usize> {
+    match self.body_node(boundary) {
+      Node::Boundary { first_for_prev, .. } => *first_for_prev..boundary,
+      Node::Normal { .. } => unreachable!(),
     }
+  }
 
-    let num_primary_items = headers.first().unwrap().node.prev as usize;
-    Dlx {
-      headers,
-      body,
-      num_primary_items,
+  /// True if the subset before `boundary` can still be chosen under the
+  /// current partial solution.
+  fn is_subset_available(&self, boundary: usize) -> bool {
+    // A subset removed by an earlier choice has all of its nodes unlinked,
+    // other than the one in the item which was covered or purified, so it's
+    // enough to check that each node is still linked and each of its covered
+    // items is still left to cover.
+    self.subset_nodes(boundary).all(|p| {
+      let top = self.top(p);
+      self.node(self.body_node(p).prev()).next() == p
+        && !(self.header(top).is_covered() && self.is_header_covered(top))
+    })
+  }
+
+  /// Undoes every `require_subset` since `checkpoint` was made, in reverse
+  /// order. Rolling back to a checkpoint undoes those made after it too, so
+  /// they do nothing if rolled back to later.
+  pub fn rollback(&mut self, checkpoint: Checkpoint) {
+    while self.chosen.len() > checkpoint.0 {
+      let p = self.chosen.pop().unwrap();
+      self.uncover_remaining_choices(p);
+      self.uncover(self.top(p));
+    }
+  }
+
+  /// Fixes the secondary item `item` to `color` before searching, removing
+  /// every subset which gives it a different color. The color is reported in
+  /// all solutions, even those which don't choose a subset containing `item`.
+  /// This can't be undone.
+  pub fn require_color(&mut self, item: &I, color: u32) {
+    let top = self
+      .item_indices
+      .get(item)
+      .copied()
+      .filter(|&idx| idx > self.num_primary_items)
+      .unwrap_or_else(|| panic!("No secondary item {item:?} to require a color for"));
+
+    let mut p = self.body_header(top).next();
+    while p != top {
+      let next = self.body_node(p).next();
+      if self.body_node(p).color() != Some(color) {
+        self.hide(p);
+        // Also unlink the node from its own item, so later purifications of
+        // this item don't see it.
+        let prev = self.body_node(p).prev();
+        self.node_mut(prev).set_next(next);
+        self.node_mut(next).set_prev(prev);
+        *self.body_header_mut(top).len_mut() -= 1;
+      }
+      p = next;
     }
+
+    self.required.push((item.clone(), color));
   }
 
   fn header(&self, idx: usize) -> &Header<I> {
@@ -537,7 +1318,7 @@ where
 
   /// Remove the subset containing the node at `idx` from the grid.
   fn hide(&mut self, idx: usize) {
-    // println!("Hiding {idx}");
+    trace!("Hiding {idx}");
     let mut q = idx + 1;
     while q != idx {
       match self.body_node(q) {
@@ -550,7 +1331,7 @@ where
         } => {
           let top = *top as usize;
 
-          if self.header(top).is_primary() || color.is_some() {
+          if self.header(top).is_covered() || color.is_some() {
             let prev_idx = item_node.prev;
             let next_idx = item_node.next;
             self.node_mut(prev_idx).set_next(next_idx);
@@ -582,7 +1363,7 @@ where
         } => {
           let top = *top as usize;
 
-          if self.header(top).is_primary() || color.is_some() {
+          if self.header(top).is_covered() || color.is_some() {
             let prev_idx = item_node.prev;
             let next_idx = item_node.next;
             self.node_mut(prev_idx).set_next(q);
@@ -597,14 +1378,14 @@ where
         } => unreachable!("Unexpected header encountered in unhide() at index {q}"),
       }
     }
-    // println!("Unhiding {idx}");
+    trace!("Unhiding {idx}");
   }
 
   /// Remove all subsets which contain the header item `idx`, and hide the item
   /// from the items list.
   fn cover(&mut self, idx: usize) {
-    // println!("Covering {:?}", self.header(idx).item.as_ref().unwrap());
-    debug_assert!((1..=self.num_primary_items).contains(&idx));
+    trace!("Covering {:?}", self.header(idx).item.as_ref().unwrap());
+    debug_assert!(self.header(idx).is_covered());
     let mut p = self.body_header(idx).next();
     while p != idx {
       self.hide(p);
@@ -622,7 +1403,7 @@ where
   /// Reverts `cover(idx)`, assuming the state of Dlx was exactly as it was
   /// when `cover(idx)` was called.
   fn uncover(&mut self, idx: usize) {
-    debug_assert!((1..=self.num_primary_items).contains(&idx));
+    debug_assert!(self.header(idx).is_covered());
     // Put this item back in the items list.
     let header = self.header(idx);
     let prev_idx = header.node.prev;
@@ -635,7 +1416,7 @@ where
       self.unhide(p);
       p = self.body_node(p).prev();
     }
-    // println!("Uncovering {:?}", self.header(idx).item.as_ref().unwrap());
+    trace!("Uncovering {:?}", self.header(idx).item.as_ref().unwrap());
   }
 
   /// Covers all subsets with secondary constraints which don't have the same
@@ -651,12 +1432,11 @@ where
       } => (*color, *top as usize),
       _ => unreachable!("Unexpected uncolored node for secondary constraint at index {idx}."),
     };
-    // println!("Purifying {idx} (top {top}, color {color})");
+    trace!("Purifying {idx} (top {top}, color {color})");
 
     let mut p = self.body_header(top).next();
     while p != top {
       let p_color = self.body_node_mut(p).color_mut();
-      // println!("Looking at {p} ({p_color:?})");
       if *p_color == Some(color) {
         *p_color = None;
       } else {
@@ -690,12 +1470,12 @@ where
       }
       p = self.body_node(p).prev();
     }
-    // println!("Unpurifying {idx}");
+    trace!("Unpurifying {idx}");
   }
 
   fn commit(&mut self, idx: usize, top: usize) {
-    // println!("Committing {idx} (top: {top})");
-    if self.header(top).is_primary() {
+    trace!("Committing {idx} (top: {top})");
+    if self.header(top).is_covered() {
       self.cover(top);
     } else if self.body_node(idx).color().is_some() {
       self.purify(idx);
@@ -703,17 +1483,17 @@ where
   }
 
   fn uncommit(&mut self, idx: usize, top: usize) {
-    if self.header(top).is_primary() {
+    if self.header(top).is_covered() {
       self.uncover(top);
     } else if self.body_node(idx).color().is_some() {
       self.unpurify(idx);
     }
-    // println!("Uncommitting {idx} (top: {top})");
+    trace!("Uncommitting {idx} (top: {top})");
   }
 
   /// Covers all other items take by the subset containing the node at `idx`.
   fn cover_remaining_choices(&mut self, idx: usize) {
-    // println!("Covering remaining for {idx}");
+    trace!("Covering remaining for {idx}");
     let mut p = idx + 1;
     while p != idx {
       match self.body_node(p) {
@@ -760,7 +1540,7 @@ where
         }
       }
     }
-    // println!("Uncovering remaining for {idx}");
+    trace!("Uncovering remaining for {idx}");
   }
 
   /// Chooses the index of the next item to try covering, using the LRV
@@ -788,58 +1568,215 @@ where
     best_opt.0
   }
 
-  fn set_name_for_node(&self, idx: usize) -> N {
+  /// The header indices of the primary items left to cover.
+  fn active_primary_headers(&self) -> impl Iterator<Item = usize> + '_ {
+    iter::successors(Some(self.header(0).node.next as usize), |&idx| {
+      Some(self.header(idx).node.next as usize)
+    })
+    .take_while(|&idx| idx != 0)
+  }
+
+  /// The index of the boundary following the subset containing the node at
+  /// `idx`.
+  fn boundary_for_node(&self, idx: usize) -> usize {
     ((idx + 1)..)
-      .find_map(|q| match self.body_node(q) {
-        Node::Boundary { name, .. } => Some(name.clone().unwrap()),
-        Node::Normal { .. } => None,
-      })
+      .find(|&q| matches!(self.body_node(q), Node::Boundary { .. }))
       .unwrap()
   }
 
+  fn set_name_for_node(&self, idx: usize) -> N {
+    match self.body_node(self.boundary_for_node(idx)) {
+      Node::Boundary { name, .. } => name.clone().unwrap(),
+      Node::Normal { .. } => unreachable!(),
+    }
+  }
+
   fn items_for_node(&self, idx: usize) -> impl Iterator<Item = Constraint<I>> + '_ {
     self
       .iterate_items(idx)
       .map(move |item_idx| match self.body_node(item_idx).color() {
         Some(color) => ColorItem::new(self.item_name(item_idx), color).into(),
+        None if self.header(self.top(item_idx)).uncolored => {
+          Constraint::Uncolored(self.item_name(item_idx))
+        }
         None => self.item_name(item_idx).into(),
       })
   }
 
+  /// The header index and color of each item of the subset containing the
+  /// node at `idx`, including the node itself.
+  fn node_colors(&self, idx: usize) -> impl Iterator<Item = (usize, Option<u32>)> + '_ {
+    iter::once(idx)
+      .chain(self.iterate_items(idx))
+      .map(|q| (self.top(q), self.body_node(q).color()))
+  }
+
   fn find_all_solutions_idx(&mut self) -> Vec<Vec<usize>>
   where
     I: Debug,
     N: Debug,
   {
-    let mut solutions = Vec::new();
-    let mut solution = Vec::new();
+    self.find_solutions_idx(None)
+  }
 
-    'cover_new_item: loop {
-      match self.choose_item() {
-        Some(item) => {
-          let item = item as usize;
-          solution.push(item);
-          self.cover(item);
-        }
-        None => {
-          // Undo all changes we've made to the data structure.
-          // solution.iter().rev().for_each(|&p| {
-          //   self.uncover_remaining_choices(p);
-          //   let top = self.body_node(p).top() as usize;
-          //   self.uncover(top);
-          // });
-          solutions.push(solution.clone());
-        }
+  /// Searches for solutions, stopping early once `max_solutions` have been
+  /// found.
+  fn find_solutions_idx(&mut self, max_solutions: Option<usize>) -> Vec<Vec<usize>>
+  where
+    I: Debug,
+    N: Debug,
+  {
+    self.search(max_solutions, None)
+  }
+
+  /// Searches for solutions, stopping early once `max_solutions` have been
+  /// found. With `distinct`, only solutions coloring its keys differently from
+  /// every solution before them are reported, and partial solutions which
+  /// already color every key like a reported solution aren't explored.
+  fn search(
+    &mut self,
+    max_solutions: Option<usize>,
+    distinct: Option<DistinctColorings>,
+  ) -> Vec<Vec<usize>>
+  where
+    I: Debug,
+    N: Debug,
+  {
+    let mut solutions = Vec::new();
+    if max_solutions == Some(0) {
+      return solutions;
+    }
+    let _ = self.search_with(distinct, |_, solution| {
+      solutions.push(solution.to_vec());
+      if max_solutions.is_some_and(|max| solutions.len() >= max) {
+        ControlFlow::Break(())
+      } else {
+        ControlFlow::Continue(())
       }
-      // println!("d{} for {}", solution.len(), solution.last().unwrap());
+    });
+    solutions
+  }
 
-      while let Some(p) = solution.pop() {
+  /// Searches for solutions, calling `on_solution` with the nodes of each as
+  /// soon as it's found, and stopping once it breaks. Returns whether the
+  /// search was stopped by `on_solution`.
+  fn search_with(
+    &mut self,
+    distinct: Option<DistinctColorings>,
+    mut on_solution: impl FnMut(&Self, &[usize]) -> ControlFlow<()>,
+  ) -> ControlFlow<()>
+  where
+    I: Debug,
+    N: Debug,
+  {
+    let mut state = self.start_search(distinct);
+    while self.search_next(&mut state) {
+      if on_solution(self, &state.nodes_found).is_break() {
+        self.unwind(&mut state.solution);
+        self.finish_search(&state);
+        return ControlFlow::Break(());
+      }
+    }
+    ControlFlow::Continue(())
+  }
+
+  /// Begins a search, which `search_next` then advances one solution at a
+  /// time.
+  fn start_search(&mut self, distinct: Option<DistinctColorings>) -> SearchState {
+    self.interrupted = false;
+    self.searching = true;
+    let mut symmetry_chosen = self
+      .symmetry
+      .groups
+      .iter()
+      .map(|group| vec![false; group.len()])
+      .collect_vec();
+    for &p in &self.chosen {
+      self
+        .symmetry
+        .mark(&mut symmetry_chosen, self.boundary_for_node(p), true);
+    }
+    SearchState {
+      distinct,
+      found: 0,
+      nodes: 0,
+      solution: Vec::new(),
+      nodes_found: Vec::new(),
+      symmetry_chosen,
+      resume: false,
+    }
+  }
+
+  /// Continues the search in `state` up to its next solution, whose nodes are
+  /// left in `state.nodes_found`. Returns false, having restored the
+  /// structure, once there are no more solutions or the search runs out of
+  /// budget.
+  fn search_next(&mut self, state: &mut SearchState) -> bool
+  where
+    I: Debug,
+    N: Debug,
+  {
+    let SearchState {
+      distinct,
+      found,
+      nodes,
+      solution,
+      nodes_found,
+      symmetry_chosen,
+      resume,
+    } = state;
+
+    'cover_new_item: loop {
+      // A search resuming from a solution carries on by backtracking from it.
+      if !std::mem::take(resume) {
+        match self.choose_item() {
+          Some(item) => {
+            let item = item as usize;
+            if self.body_header(item).len() == 0 {
+              let name = self.header(item).item.clone().unwrap();
+              *self.stats.dead_ends.entry(name).or_default() += 1;
+            }
+            solution.push(item);
+            self.cover(item);
+          }
+          None if self.breaks_symmetry(symmetry_chosen) => {}
+          None if !distinct.as_mut().is_none_or(DistinctColorings::report) => {}
+          None => {
+            self.stats.solutions += 1;
+            *found += 1;
+            if log_enabled!(Level::Trace) {
+              trace!(
+                "Solution {found} at depth {}: {:?}",
+                solution.len(),
+                solution
+                  .iter()
+                  .map(|&p| self.set_name_for_node(p))
+                  .collect::<Vec<_>>()
+              );
+            }
+            nodes_found.clear();
+            nodes_found.extend(self.chosen.iter().chain(solution.iter()));
+            *resume = true;
+            return true;
+          }
+        }
+      }
+
+      while let Some(p) = solution.pop() {
         if let Node::Normal {
           node_type: NodeType::Body { .. },
           ..
         } = self.node(p)
         {
           self.uncover_remaining_choices(p);
+          if let Some(distinct) = distinct {
+            distinct.unchoose();
+          }
+          if !self.symmetry.members.is_empty() {
+            self
+              .symmetry
+              .mark(symmetry_chosen, self.boundary_for_node(p), false);
+          }
         }
 
         // Try exploring the next choice.
@@ -858,9 +1795,40 @@ where
             node_type: NodeType::Body { .. },
             ..
           } => {
+            if self.out_of_budget(*nodes) {
+              self.interrupted = true;
+              // Abandon this item along with every choice made so far.
+              self.uncover(self.top(p));
+              self.unwind(solution);
+              break;
+            }
+
             // We can try exploring this subset.
+            *nodes += 1;
+            self.stats.nodes += 1;
             solution.push(p);
+            if let Some(distinct) = distinct {
+              distinct.choose(self.node_colors(p));
+            }
             self.cover_remaining_choices(p);
+            if distinct
+              .as_ref()
+              .is_some_and(DistinctColorings::is_reported)
+            {
+              // Every solution below this choice would repeat one already
+              // reported, so move straight on to the next choice.
+              continue;
+            }
+            if !self.symmetry.members.is_empty() {
+              self
+                .symmetry
+                .mark(symmetry_chosen, self.boundary_for_node(p), true);
+              if self.breaks_symmetry(symmetry_chosen) {
+                // Every solution below this choice is a permutation of one in
+                // canonical order.
+                continue;
+              }
+            }
             continue 'cover_new_item;
           }
           Node::Boundary { .. } => unreachable!("Unexpected boundary node found in queue: {p}"),
@@ -870,7 +1838,98 @@ where
       break;
     }
 
-    solutions
+    self.finish_search(state);
+    false
+  }
+
+  /// Marks the search in `state` as over, once the structure is restored.
+  fn finish_search(&mut self, state: &SearchState) {
+    self.searching = false;
+    debug!(
+      "Search found {} solutions after trying {} subsets{}",
+      state.found,
+      state.nodes,
+      if self.interrupted {
+        ", before being interrupted"
+      } else {
+        ""
+      }
+    );
+  }
+
+  /// Undoes every choice in the partial solution `solution`, restoring the
+  /// structure to how it was before the search.
+  fn unwind(&mut self, solution: &mut Vec<usize>) {
+    while let Some(p) = solution.pop() {
+      self.uncover_remaining_choices(p);
+      self.uncover(self.top(p));
+    }
+  }
+
+  /// True if a search which has tried `nodes` subsets has to stop.
+  fn out_of_budget(&self, nodes: u64) -> bool {
+    self.node_limit.is_some_and(|limit| nodes >= limit)
+      || (nodes.is_multiple_of(DEADLINE_CHECK_INTERVAL)
+        && self
+          .deadline
+          .is_some_and(|deadline| Instant::now() >= deadline))
+  }
+
+  /// The item header of the body node `idx`.
+  fn top(&self, idx: usize) -> usize {
+    match self.body_node(idx) {
+      Node::Normal {
+        node_type: NodeType::Body { top, .. },
+        ..
+      } => *top as usize,
+      _ => unreachable!("Unexpected top() called on non-body node"),
+    }
+  }
+
+  /// The names of the subsets chosen in each solution.
+  pub fn find_all_solution_names(&mut self) -> impl Iterator<Item = Vec<N>> + '_
+  where
+    I: Debug,
+    N: Debug,
+  {
+    self.find_all_solutions_idx().into_iter().map(|solution| {
+      solution
+        .into_iter()
+        .map(|p| self.set_name_for_node(p))
+        .collect()
+    })
+  }
+
+  /// The names of the subsets chosen in each solution, like
+  /// `find_all_solution_names`, but searching for each solution only as the
+  /// iterator is advanced.
+  pub fn into_solution_names(mut self) -> SolutionNames<I, N> {
+    let state = self.start_search(None);
+    SolutionNames {
+      dlx: self,
+      state: Some(state),
+    }
+  }
+
+  /// Counts solutions, stopping early once `limit` have been found.
+  pub fn count_solutions(&mut self, limit: usize) -> usize
+  where
+    I: Debug,
+    N: Debug,
+  {
+    let mut count = 0;
+    if limit == 0 {
+      return count;
+    }
+    let _ = self.search_with(None, |_, _| {
+      count += 1;
+      if count >= limit {
+        ControlFlow::Break(())
+      } else {
+        ControlFlow::Continue(())
+      }
+    });
+    count
   }
 
   pub fn find_solution_names(&mut self) -> Option<impl Iterator<Item = N> + '_>
@@ -879,35 +1938,157 @@ where
     N: Debug,
   {
     let mut solutions = self.find_all_solutions_idx();
-    debug_assert_eq!(solutions.len(), 1);
+    debug_assert!(solutions.len() <= 1);
     solutions
       .pop()
       .map(|solution| solution.into_iter().map(|p| self.set_name_for_node(p)))
   }
 
+  /// The colors assigned to secondary items by the subsets in `solution`,
+  /// along with any required colors.
+  fn solution_colors(&self, solution: &[usize]) -> HashMap<I, u32> {
+    solution.iter().fold(
+      self.required.iter().cloned().collect(),
+      |secondary_assignments, &p| {
+        self
+          .items_for_node(p)
+          .fold(secondary_assignments, |mut secondary_assignments, c| {
+            if let Constraint::Secondary(ColorItem { item, color }) = c {
+              if let Some(prev_color) = secondary_assignments.insert(item, color) {
+                debug_assert_eq!(color, prev_color);
+              }
+            }
+            secondary_assignments
+          })
+      },
+    )
+  }
+
   pub fn find_all_solution_colors(&mut self) -> impl Iterator<Item = HashMap<I, u32>> + '_
   where
     I: Debug,
     N: Debug,
   {
-    self.find_all_solutions_idx().into_iter().map(|solution| {
-      solution
+    self
+      .find_all_solutions_idx()
+      .into_iter()
+      .map(|solution| self.solution_colors(&solution))
+  }
+
+  /// Both the names and colors of each solution, stopping once `limit` have
+  /// been found if one is given.
+  pub fn find_selections(
+    &mut self,
+    limit: Option<usize>,
+  ) -> impl Iterator<Item = Selection<I, N>> + '_
+  where
+    I: Debug,
+    N: Debug,
+  {
+    self
+      .find_solutions_idx(limit)
+      .into_iter()
+      .map(|solution| self.selection(solution))
+  }
+
+  /// Calls `visit` with the names of the subsets in each solution, in the
+  /// order they were chosen, as soon as the search finds it. The search stops
+  /// as soon as `visit` breaks, leaving the structure as it was before, and
+  /// the break is returned.
+  pub fn visit_solutions(
+    &mut self,
+    mut visit: impl FnMut(&[N]) -> ControlFlow<()>,
+  ) -> ControlFlow<()>
+  where
+    I: Debug,
+    N: Debug,
+  {
+    let mut names = Vec::new();
+    self.search_with(None, |dlx, solution| {
+      names.clear();
+      names.extend(solution.iter().map(|&p| dlx.set_name_for_node(p)));
+      visit(&names)
+    })
+  }
+
+  /// One solution for each distinct way the solutions color the secondary
+  /// items `keys`, in the order they were found. A key which a solution
+  /// leaves uncolored counts as one more color. Once a partial solution has
+  /// colored every key like a solution already found, the choices below it
+  /// are skipped rather than searched. Panics if any of `keys` isn't a
+  /// secondary item.
+  pub fn find_all_distinct_colorings(
+    &mut self,
+    keys: &[I],
+  ) -> impl Iterator<Item = Selection<I, N>> + '_
+  where
+    I: Debug,
+    N: Debug,
+  {
+    let initial = self.solution_colors(&self.chosen);
+    let distinct = DistinctColorings {
+      keys: keys
         .iter()
-        .fold(HashMap::new(), |secondary_assignments, &p| {
-          self
-            .items_for_node(p)
-            .fold(secondary_assignments, |mut secondary_assignments, c| {
-              if let Constraint::Secondary(ColorItem { item, color }) = c {
-                if let Some(prev_color) = secondary_assignments.insert(item, color) {
-                  debug_assert_eq!(color, prev_color);
-                }
-              }
-              secondary_assignments
-            })
+        .enumerate()
+        .map(|(key, item)| {
+          let top = self
+            .item_indices
+            .get(item)
+            .copied()
+            .filter(|&idx| idx > self.num_primary_items)
+            .unwrap_or_else(|| panic!("No secondary item {item:?} to find colorings of"));
+          (top, key)
         })
+        .collect(),
+      colors: keys.iter().map(|item| initial.get(item).copied()).collect(),
+      assigned: Vec::new(),
+      depths: Vec::new(),
+      reported: HashSet::new(),
+    };
+    self
+      .search(None, Some(distinct))
+      .into_iter()
+      .map(|solution| self.selection(solution))
+  }
+
+  fn selection(&self, solution: Vec<usize>) -> Selection<I, N> {
+    Selection {
+      colors: self.solution_colors(&solution),
+      names: solution
+        .into_iter()
+        .map(|p| self.set_name_for_node(p))
+        .collect(),
+    }
+  }
+
+  /// The names of the subsets in the first solution found, in the order they
+  /// were chosen, without searching for any others.
+  pub fn find_first_solution_names(&mut self) -> Option<Vec<N>>
+  where
+    I: Debug,
+    N: Debug,
+  {
+    self.find_solutions_idx(Some(1)).pop().map(|solution| {
+      solution
+        .into_iter()
+        .map(|p| self.set_name_for_node(p))
+        .collect()
     })
   }
 
+  /// The colors of the first solution found, without searching for any
+  /// others.
+  pub fn find_first_solution_colors(&mut self) -> Option<HashMap<I, u32>>
+  where
+    I: Debug,
+    N: Debug,
+  {
+    self
+      .find_solutions_idx(Some(1))
+      .pop()
+      .map(|solution| self.solution_colors(&solution))
+  }
+
   pub fn find_solution_colors(&mut self) -> Option<HashMap<I, u32>>
   where
     I: Debug,
@@ -915,22 +2096,9 @@ where
   {
     let mut solutions = self.find_all_solutions_idx();
     debug_assert_eq!(solutions.len(), 1);
-    solutions.pop().map(|solution| {
-      solution
-        .iter()
-        .fold(HashMap::new(), |secondary_assignments, &p| {
-          self
-            .items_for_node(p)
-            .fold(secondary_assignments, |mut secondary_assignments, c| {
-              if let Constraint::Secondary(ColorItem { item, color }) = c {
-                if let Some(prev_color) = secondary_assignments.insert(item, color) {
-                  debug_assert_eq!(color, prev_color);
-                }
-              }
-              secondary_assignments
-            })
-        })
-    })
+    solutions
+      .pop()
+      .map(|solution| self.solution_colors(&solution))
   }
 }
 
@@ -946,17 +2114,84 @@ where
     for (idx, node) in self.body.iter().enumerate() {
       writeln!(f, "{idx:<3} N: {:?}", node)?;
     }
+    if !self.required.is_empty() {
+      writeln!(f, "Required colors: {:?}", self.required)?;
+    }
+    if !self.chosen.is_empty() {
+      writeln!(f, "Required subsets: {:?}", self.chosen)?;
+    }
     Ok(())
   }
 }
 
 #[cfg(test)]
 mod test {
+  use std::{ops::ControlFlow, time::Instant};
+
   use itertools::Itertools;
 
   use crate::dlx::{ColorItem, Constraint};
 
-  use super::{Dlx, HeaderType};
+  use super::{Dlx, DlxError, HeaderType};
+
+  /// The active items, each with its size and the nodes left in its list
+  /// along with their colors.
+  type Lists = Vec<(usize, usize, Vec<(usize, Option<u32>)>)>;
+
+  /// Checks that every list of active items, and each of their lists of
+  /// nodes, is consistently linked in both directions and has the size
+  /// recorded in its header. Returns the lists, to compare against another
+  /// instance.
+  fn check_invariants(dlx: &Dlx<char, u32>) -> Lists {
+    let last = dlx.headers.len() - 1;
+    let mut lists = Vec::new();
+    for root in [0, last] {
+      let mut idx = dlx.header(root).node.next as usize;
+      while idx != root {
+        let header = dlx.header(idx);
+        assert_eq!(
+          dlx.header(header.node.prev as usize).node.next as usize,
+          idx
+        );
+        assert_eq!(
+          dlx.header(header.node.next as usize).node.prev as usize,
+          idx
+        );
+        assert_eq!(header.is_primary(), root == 0);
+
+        let mut nodes = Vec::new();
+        let mut p = dlx.body_header(idx).next();
+        while p != idx {
+          let node = dlx.body_node(p);
+          assert_eq!(dlx.node(node.prev()).next(), p);
+          assert_eq!(dlx.node(node.next()).prev(), p);
+          assert_eq!(dlx.top(p), idx);
+          nodes.push((p, node.color()));
+          p = node.next();
+        }
+        assert_eq!(dlx.body_header(idx).len(), nodes.len());
+        lists.push((idx, nodes.len(), nodes));
+        idx = header.node.next as usize;
+      }
+    }
+    lists
+  }
+
+  /// The exact cover problem from Knuth's Dancing Links paper, whose only
+  /// solution is {a, d, f}, {b, g}, {c, e}.
+  fn knuth_example() -> Dlx<char, u32> {
+    Dlx::new(
+      ('a'..='g').map(|item| (item, HeaderType::Primary)),
+      vec![
+        (0, vec!['c', 'e']),
+        (1, vec!['a', 'd', 'g']),
+        (2, vec!['b', 'c', 'f']),
+        (3, vec!['a', 'd', 'f']),
+        (4, vec!['b', 'g']),
+        (5, vec!['d', 'e', 'g']),
+      ],
+    )
+  }
 
   #[test]
   fn test_empty() {
@@ -1020,4 +2255,952 @@ mod test {
       .find_solution_names()
       .is_some_and(|solution| { solution.sorted().eq(vec![0, 3].into_iter()) }));
   }
+
+  #[test]
+  fn test_add_subset() {
+    // Knuth's example without {b, g}, which is needed to cover b and g.
+    let mut dlx = Dlx::new(
+      ('a'..='g').map(|item| (item, HeaderType::Primary)),
+      vec![
+        (0, vec!['c', 'e']),
+        (1, vec!['a', 'd', 'g']),
+        (2, vec!['b', 'c', 'f']),
+        (3, vec!['a', 'd', 'f']),
+        (5, vec!['d', 'e', 'g']),
+      ],
+    );
+    assert_eq!(dlx.find_all_solution_names().count(), 0);
+    check_invariants(&dlx);
+
+    dlx
+      .add_subset(4, ['b', 'g'].map(Constraint::Primary))
+      .unwrap();
+    check_invariants(&dlx);
+    assert_eq!(
+      dlx
+        .find_all_solution_names()
+        .map(|names| names.into_iter().sorted().collect_vec())
+        .collect_vec(),
+      vec![vec![0, 3, 4]]
+    );
+    check_invariants(&dlx);
+  }
+
+  #[test]
+  fn test_add_subset_errors() {
+    let mut dlx = Dlx::new(
+      vec![('p', HeaderType::Primary), ('x', HeaderType::Secondary)],
+      vec![(0, vec![Constraint::Primary('p')])],
+    );
+    let lists = check_invariants(&dlx);
+    assert_eq!(
+      dlx.add_subset(0, [Constraint::Primary('p')]),
+      Err(DlxError::DuplicateSubset("0".to_string()))
+    );
+    assert_eq!(
+      dlx.add_subset(1, [Constraint::Primary('p'), Constraint::Primary('q')]),
+      Err(DlxError::UnknownItem("'q'".to_string()))
+    );
+    assert_eq!(
+      dlx.add_subset(1, [Constraint::Primary('x')]),
+      Err(DlxError::InvalidConstraint("'x'".to_string()))
+    );
+    assert_eq!(
+      dlx.add_subset(1, [Constraint::Uncolored('p')]),
+      Err(DlxError::InvalidConstraint("'p'".to_string()))
+    );
+    assert_eq!(check_invariants(&dlx), lists);
+
+    // Once x is used with colors, it can't be used without them.
+    dlx
+      .add_subset(1, [Constraint::Primary('p'), ColorItem::new('x', 1).into()])
+      .unwrap();
+    assert_eq!(
+      dlx.add_subset(2, [Constraint::Primary('p'), Constraint::Uncolored('x')]),
+      Err(DlxError::InvalidConstraint("'x'".to_string()))
+    );
+
+    let checkpoint = dlx.begin();
+    dlx.require_subset(&0).unwrap();
+    assert_eq!(
+      dlx.add_subset(2, [Constraint::Primary('p')]),
+      Err(DlxError::Searching)
+    );
+    dlx.rollback(checkpoint);
+    assert_eq!(dlx.count_solutions(10), 2);
+
+    let _ = dlx.search_with(None, |dlx, _| {
+      assert!(dlx.searching);
+      ControlFlow::Continue(())
+    });
+    assert!(!dlx.searching);
+  }
+
+  #[test]
+  fn test_add_subset_required_color() {
+    let mut dlx = Dlx::new(
+      vec![('p', HeaderType::Primary), ('x', HeaderType::Secondary)],
+      vec![(
+        0,
+        vec![Constraint::Primary('p'), ColorItem::new('x', 1).into()],
+      )],
+    );
+    dlx.require_color(&'x', 1);
+    dlx
+      .add_subset(1, [Constraint::Primary('p'), ColorItem::new('x', 2).into()])
+      .unwrap();
+    dlx
+      .add_subset(2, [Constraint::Primary('p'), ColorItem::new('x', 1).into()])
+      .unwrap();
+    check_invariants(&dlx);
+    assert_eq!(
+      dlx.find_all_solution_names().sorted().collect_vec(),
+      vec![vec![0], vec![2]]
+    );
+    assert_eq!(
+      dlx.require_subset(&1),
+      Err(DlxError::UnavailableSubset("1".to_string()))
+    );
+  }
+
+  #[test]
+  fn test_into_solution_names() {
+    let mut dlx = Dlx::new(
+      ('a'..='c').map(|item| (item, HeaderType::Primary)),
+      vec![
+        (0, vec!['a']),
+        (1, vec!['b']),
+        (2, vec!['c']),
+        (3, vec!['a', 'b']),
+        (4, vec!['b', 'c']),
+      ],
+    );
+    let all = dlx.find_all_solution_names().collect_vec();
+    assert_eq!(all.len(), 3);
+
+    let mut solutions = dlx.clone().into_solution_names();
+    assert_eq!(solutions.next().as_ref(), all.first());
+    assert_eq!(solutions.collect_vec(), all[1..]);
+    assert_eq!(dlx.into_solution_names().collect_vec(), all);
+  }
+
+  #[test]
+  fn test_disable_subset() {
+    let mut dlx = Dlx::new(
+      ('a'..='c').map(|item| (item, HeaderType::Primary)),
+      vec![
+        (0, vec!['a']),
+        (1, vec!['b']),
+        (2, vec!['c']),
+        (3, vec!['a', 'b']),
+      ],
+    );
+    let lists = check_invariants(&dlx);
+    assert_eq!(dlx.count_solutions(10), 2);
+
+    // Subset 2 is the only one covering c.
+    dlx.disable_subset(&2).unwrap();
+    check_invariants(&dlx);
+    assert_eq!(dlx.count_solutions(10), 0);
+    dlx.disable_subset(&2).unwrap();
+    check_invariants(&dlx);
+    dlx.enable_subset(&2).unwrap();
+    assert_eq!(check_invariants(&dlx), lists);
+    assert_eq!(dlx.count_solutions(10), 2);
+
+    // Subsets are relinked in order, whatever order they're enabled in.
+    for name in [0, 3, 1] {
+      dlx.disable_subset(&name).unwrap();
+      check_invariants(&dlx);
+    }
+    assert_eq!(dlx.count_solutions(10), 0);
+    dlx.enable_subset(&3).unwrap();
+    check_invariants(&dlx);
+    assert_eq!(
+      dlx.find_all_solution_names().collect_vec(),
+      vec![vec![3, 2]]
+    );
+    for name in [0, 1, 1] {
+      dlx.enable_subset(&name).unwrap();
+      check_invariants(&dlx);
+    }
+    assert_eq!(check_invariants(&dlx), lists);
+    assert_eq!(dlx.count_solutions(10), 2);
+  }
+
+  #[test]
+  fn test_debug() {
+    let mut dlx = knuth_example();
+    let dump = format!("{dlx:?}");
+    assert_eq!(dump.lines().count(), dlx.headers.len() + dlx.body.len());
+    assert!(dump.starts_with("0   H: "));
+
+    dlx.require_subset(&0).unwrap();
+    assert!(format!("{dlx:?}").contains("Required subsets: "));
+  }
+
+  #[test]
+  fn test_symmetry_group() {
+    // Subsets 0, 1 and 2 are three copies of the same subset.
+    let subsets = vec![
+      (0, vec!['a']),
+      (1, vec!['a']),
+      (2, vec!['a']),
+      (3, vec!['b']),
+      (4, vec!['c']),
+      (5, vec!['b', 'c']),
+    ];
+    let items = || ('a'..='c').map(|item| (item, HeaderType::Primary));
+    let mut dlx = Dlx::new(items(), subsets.clone());
+    let all = sorted_solutions(&mut dlx);
+    assert_eq!(all.len(), 6);
+
+    let mut dlx = Dlx::new(items(), subsets);
+    dlx.add_symmetry_group(vec![0, 1, 2]).unwrap();
+    let canonical = sorted_solutions(&mut dlx);
+    assert_eq!(canonical.len() * 3, all.len());
+    assert_eq!(
+      canonical,
+      all
+        .into_iter()
+        .filter(|names| names.contains(&0))
+        .collect_vec()
+    );
+  }
+
+  #[test]
+  fn test_symmetry_group_prefix() {
+    // Permuting a, b and c along with 0, 1 and 2 and with 3, 4 and 5 is a
+    // symmetry, so only solutions choosing a prefix of 0, 1, 2 are reported.
+    let mut dlx = Dlx::new(
+      ('a'..='c').map(|item| (item, HeaderType::Primary)),
+      vec![
+        (0, vec!['a']),
+        (1, vec!['b']),
+        (2, vec!['c']),
+        (3, vec!['a']),
+        (4, vec!['b']),
+        (5, vec!['c']),
+      ],
+    );
+    assert_eq!(dlx.count_solutions(100), 8);
+    assert_eq!(dlx.count_solutions(3), 3);
+    assert_eq!(dlx.count_solutions(0), 0);
+    dlx.add_symmetry_group(vec![0, 1, 2]).unwrap();
+    assert_eq!(
+      sorted_solutions(&mut dlx),
+      vec![vec![0, 1, 2], vec![0, 1, 5], vec![0, 4, 5], vec![3, 4, 5]]
+    );
+
+    // Required subsets count as chosen.
+    dlx.require_subset(&1).unwrap();
+    assert_eq!(
+      sorted_solutions(&mut dlx),
+      vec![vec![0, 1, 2], vec![0, 1, 5]]
+    );
+
+    assert_eq!(
+      dlx.add_symmetry_group(vec![0, 6]),
+      Err(DlxError::UnknownSubset("6".to_owned()))
+    );
+  }
+
+  #[test]
+  fn test_disable_subset_errors() {
+    let mut dlx = knuth_example();
+    assert_eq!(
+      dlx.disable_subset(&7),
+      Err(DlxError::UnknownSubset("7".to_string()))
+    );
+    assert_eq!(
+      dlx.enable_subset(&7),
+      Err(DlxError::UnknownSubset("7".to_string()))
+    );
+
+    let lists = check_invariants(&dlx);
+    let checkpoint = dlx.begin();
+    dlx.require_subset(&0).unwrap();
+    assert_eq!(dlx.disable_subset(&4), Err(DlxError::Searching));
+    assert_eq!(dlx.enable_subset(&4), Err(DlxError::Searching));
+    dlx.rollback(checkpoint);
+    assert_eq!(check_invariants(&dlx), lists);
+
+    dlx.disable_subset(&4).unwrap();
+    assert_eq!(
+      dlx.require_subset(&4),
+      Err(DlxError::UnavailableSubset("4".to_string()))
+    );
+  }
+
+  #[test]
+  fn test_disable_subset_required_color() {
+    let mut dlx = Dlx::new(
+      vec![('p', HeaderType::Primary), ('x', HeaderType::Secondary)],
+      vec![
+        (
+          0,
+          vec![Constraint::Primary('p'), ColorItem::new('x', 1).into()],
+        ),
+        (
+          1,
+          vec![Constraint::Primary('p'), ColorItem::new('x', 2).into()],
+        ),
+      ],
+    );
+    dlx.require_color(&'x', 1);
+    let lists = check_invariants(&dlx);
+
+    // The subset removed by the required color stays removed.
+    dlx.disable_subset(&1).unwrap();
+    dlx.enable_subset(&1).unwrap();
+    assert_eq!(check_invariants(&dlx), lists);
+    assert_eq!(dlx.find_all_solution_names().collect_vec(), vec![vec![0]]);
+
+    dlx.disable_subset(&0).unwrap();
+    assert_eq!(dlx.count_solutions(10), 0);
+    dlx.enable_subset(&0).unwrap();
+    assert_eq!(check_invariants(&dlx), lists);
+    assert_eq!(dlx.count_solutions(10), 1);
+  }
+
+  #[test]
+  fn test_remove_subset() {
+    let mut dlx = knuth_example();
+
+    // {b, g} is the only subset which completes the solution.
+    assert!(dlx.remove_subset(&4));
+    assert!(!dlx.remove_subset(&4));
+    check_invariants(&dlx);
+    assert_eq!(dlx.find_all_solution_names().count(), 0);
+
+    dlx
+      .add_subset(4, ['b', 'g'].map(Constraint::Primary))
+      .unwrap();
+    let lists = check_invariants(&dlx);
+    assert_eq!(dlx.count_solutions(10), 1);
+
+    // Removing a subset just added leaves every list as it was.
+    dlx
+      .add_subset(6, ['b', 'e'].map(Constraint::Primary))
+      .unwrap();
+    assert!(dlx.remove_subset(&6));
+    assert_eq!(check_invariants(&dlx), lists);
+  }
+
+  #[test]
+  fn test_visit_solutions() {
+    let mut dlx = Dlx::new(
+      vec![('p', HeaderType::Primary), ('q', HeaderType::Primary)],
+      vec![
+        (0, vec!['p']),
+        (1, vec!['p']),
+        (2, vec!['q']),
+        (3, vec!['q']),
+      ],
+    );
+    let lists = check_invariants(&dlx);
+
+    let mut visited = Vec::new();
+    assert_eq!(
+      dlx.visit_solutions(|names| {
+        visited.push(names.to_vec());
+        ControlFlow::Continue(())
+      }),
+      ControlFlow::Continue(())
+    );
+    assert_eq!(visited, dlx.find_all_solution_names().collect_vec());
+    assert_eq!(visited.len(), 4);
+    assert_eq!(check_invariants(&dlx), lists);
+
+    // Breaking stops the search straight away.
+    let mut count = 0;
+    assert_eq!(
+      dlx.visit_solutions(|_| {
+        count += 1;
+        if count == 2 {
+          ControlFlow::Break(())
+        } else {
+          ControlFlow::Continue(())
+        }
+      }),
+      ControlFlow::Break(())
+    );
+    assert_eq!(count, 2);
+    assert_eq!(check_invariants(&dlx), lists);
+    assert_eq!(dlx.find_all_solution_names().count(), 4);
+  }
+
+  #[test]
+  fn test_distinct_colorings() {
+    let mut dlx = Dlx::new(
+      vec![
+        ('p', HeaderType::Primary),
+        ('q', HeaderType::Primary),
+        ('a', HeaderType::Secondary),
+      ],
+      vec![
+        (0, vec!['p'.into(), ColorItem::new('a', 0).into()]),
+        (1, vec!['p'.into(), ColorItem::new('a', 1).into()]),
+        (2, vec![Constraint::Primary('q')]),
+        (3, vec![Constraint::Primary('q')]),
+      ],
+    );
+    let lists = check_invariants(&dlx);
+    assert_eq!(dlx.clone().find_all_solution_names().count(), 4);
+
+    let colorings = dlx
+      .find_all_distinct_colorings(&['a'])
+      .map(|selection| {
+        (
+          selection.names.into_iter().sorted().collect_vec(),
+          selection.colors,
+        )
+      })
+      .collect_vec();
+    assert_eq!(
+      colorings,
+      vec![
+        (vec![0, 2], [('a', 0)].into()),
+        (vec![1, 2], [('a', 1)].into()),
+      ]
+    );
+    assert_eq!(dlx.stats().solutions, 2);
+    assert_eq!(check_invariants(&dlx), lists);
+  }
+
+  #[test]
+  fn test_distinct_colorings_prune() {
+    let mut dlx = Dlx::new(
+      vec![
+        ('p', HeaderType::Primary),
+        ('q', HeaderType::Primary),
+        ('r', HeaderType::Primary),
+        ('a', HeaderType::Secondary),
+      ],
+      vec![
+        (0, vec!['p'.into(), ColorItem::new('a', 0).into()]),
+        (1, vec!['p'.into(), ColorItem::new('a', 1).into()]),
+        (2, vec![Constraint::Primary('q')]),
+        (3, vec![Constraint::Primary('q')]),
+        (4, vec![Constraint::Primary('r')]),
+        (5, vec![Constraint::Primary('r')]),
+      ],
+    );
+    let mut full = dlx.clone();
+    assert_eq!(full.find_all_solution_names().count(), 8);
+    assert_eq!(dlx.find_all_distinct_colorings(&['a']).count(), 2);
+    // Once 'a' is colored like a reported solution, the rest of the subsets
+    // aren't searched.
+    assert!(dlx.stats().nodes < full.stats().nodes);
+    // Without keys, every solution colors them the same.
+    assert_eq!(dlx.find_all_distinct_colorings(&[]).count(), 1);
+  }
+
+  #[test]
+  fn test_distinct_colorings_uncolored_keys() {
+    let dlx = Dlx::new(
+      vec![
+        ('p', HeaderType::Primary),
+        ('a', HeaderType::Secondary),
+        ('b', HeaderType::Secondary),
+      ],
+      vec![
+        (0, vec!['p'.into(), ColorItem::new('a', 0).into()]),
+        (
+          1,
+          vec![
+            'p'.into(),
+            ColorItem::new('a', 0).into(),
+            ColorItem::new('b', 1).into(),
+          ],
+        ),
+        (2, vec![Constraint::Primary('p')]),
+      ],
+    );
+    assert_eq!(
+      dlx.clone().find_all_distinct_colorings(&['a', 'b']).count(),
+      3
+    );
+    assert_eq!(dlx.clone().find_all_distinct_colorings(&['a']).count(), 2);
+
+    // A required color counts for solutions which don't choose the item.
+    let mut required = dlx.clone();
+    required.require_color(&'a', 0);
+    assert_eq!(
+      required
+        .find_all_distinct_colorings(&['a'])
+        .map(|selection| selection.colors)
+        .collect_vec(),
+      vec![[('a', 0)].into()]
+    );
+
+    // So does a color set by a required subset.
+    let mut chosen = dlx;
+    chosen.require_subset(&1).unwrap();
+    assert_eq!(chosen.find_all_distinct_colorings(&['a', 'b']).count(), 1);
+  }
+
+  #[test]
+  #[should_panic]
+  fn test_distinct_colorings_primary_key() {
+    knuth_example().find_all_distinct_colorings(&['a']).count();
+  }
+
+  #[test]
+  fn test_uncolored_secondary() {
+    let mut dlx = Dlx::new(
+      vec![
+        ('p', HeaderType::Primary),
+        ('q', HeaderType::Primary),
+        ('a', HeaderType::Secondary),
+        ('b', HeaderType::Secondary),
+      ],
+      vec![
+        (0, vec!['p'.into(), Constraint::Uncolored('a')]),
+        (1, vec!['q'.into(), Constraint::Uncolored('a')]),
+        (2, vec!['p'.into(), Constraint::Uncolored('b')]),
+        (3, vec!['q'.into(), Constraint::Uncolored('b')]),
+        (4, vec!['q'.into()]),
+      ],
+    );
+
+    // `a` and `b` may each be left out, but not chosen twice.
+    let solutions = dlx
+      .find_all_solution_names()
+      .map(|solution| solution.into_iter().sorted().collect_vec())
+      .sorted()
+      .collect_vec();
+    assert_eq!(
+      solutions,
+      vec![vec![0, 3], vec![0, 4], vec![1, 2], vec![2, 4]]
+    );
+  }
+
+  #[test]
+  #[should_panic(expected = "used both with and without colors")]
+  fn test_mixed_uncolored_secondary() {
+    Dlx::new(
+      vec![('p', HeaderType::Primary), ('a', HeaderType::Secondary)],
+      vec![
+        (0, vec!['p'.into(), Constraint::Uncolored('a')]),
+        (1, vec!['p'.into(), ColorItem::new('a', 1).into()]),
+      ],
+    );
+  }
+
+  #[test]
+  fn test_require_color() {
+    let build = || {
+      Dlx::new(
+        vec![
+          ('p', HeaderType::Primary),
+          ('q', HeaderType::Primary),
+          ('a', HeaderType::Secondary),
+          ('b', HeaderType::Secondary),
+        ],
+        vec![
+          (
+            0,
+            vec![Constraint::Primary('p'), ColorItem::new('a', 1).into()],
+          ),
+          (1, vec!['p'.into(), ColorItem::new('a', 2).into()]),
+          (2, vec!['q'.into(), ColorItem::new('a', 2).into()]),
+          (3, vec!['q'.into()]),
+        ],
+      )
+    };
+
+    let mut dlx = build();
+    assert_eq!(dlx.find_all_solution_colors().count(), 3);
+
+    let mut dlx = build();
+    dlx.require_color(&'a', 2);
+    dlx.require_color(&'b', 5);
+    let solutions = dlx.find_all_solution_colors().collect_vec();
+    assert_eq!(solutions.len(), 2);
+    assert!(solutions
+      .iter()
+      .all(|colors| colors.get(&'a') == Some(&2) && colors.get(&'b') == Some(&5)));
+
+    let mut dlx = build();
+    dlx.require_color(&'a', 3);
+    assert_eq!(dlx.find_all_solution_colors().count(), 0);
+  }
+
+  #[test]
+  fn test_stats() {
+    let mut dlx = Dlx::new(
+      vec![
+        ('p', HeaderType::Primary),
+        ('q', HeaderType::Primary),
+        ('r', HeaderType::Primary),
+      ],
+      vec![
+        (0, vec!['p', 'q']),
+        (1, vec!['p', 'r']),
+        (2, vec!['q', 'r']),
+        (3, vec!['q']),
+      ],
+    );
+
+    assert_eq!(dlx.find_all_solution_colors().count(), 1);
+    let stats = dlx.stats();
+    assert_eq!(stats.solutions, 1);
+    assert!(stats.nodes >= 2);
+    // Choosing {p, q} leaves nothing to cover r.
+    assert_eq!(stats.worst_dead_end(), Some((&'r', 1)));
+  }
+
+  #[test]
+  fn test_node_limit() {
+    let new_dlx = || {
+      Dlx::new(
+        vec![
+          ('p', HeaderType::Primary),
+          ('q', HeaderType::Primary),
+          ('r', HeaderType::Primary),
+          ('s', HeaderType::Primary),
+        ],
+        vec![
+          (0, vec!['p', 'q']),
+          (1, vec!['r', 's']),
+          (2, vec!['p', 'r']),
+          (3, vec!['q', 's']),
+          (4, vec!['p']),
+          (5, vec!['q']),
+          (6, vec!['r']),
+          (7, vec!['s']),
+        ],
+      )
+    };
+    let expected = new_dlx().find_all_solutions_idx().len();
+    assert!(expected > 1);
+
+    let mut dlx = new_dlx();
+    dlx.set_node_limit(Some(3));
+    assert!(dlx.find_all_solutions_idx().len() < expected);
+    assert!(dlx.interrupted());
+
+    // The interrupted search leaves the structure intact for a retry.
+    dlx.set_node_limit(None);
+    assert_eq!(dlx.find_all_solutions_idx().len(), expected);
+    assert!(!dlx.interrupted());
+  }
+
+  #[test]
+  fn test_deadline() {
+    let mut dlx = Dlx::new(
+      vec![('p', HeaderType::Primary), ('q', HeaderType::Primary)],
+      vec![(0, vec!['p']), (1, vec!['q']), (2, vec!['p', 'q'])],
+    );
+    dlx.set_deadline(Some(Instant::now()));
+    assert_eq!(dlx.find_all_solutions_idx().len(), 0);
+    assert!(dlx.interrupted());
+
+    dlx.set_deadline(None);
+    assert_eq!(dlx.find_all_solutions_idx().len(), 2);
+  }
+
+  #[test]
+  fn test_find_first_solution() {
+    let mut dlx = Dlx::new(
+      vec![
+        ('p', HeaderType::Primary),
+        ('q', HeaderType::Primary),
+        ('x', HeaderType::Secondary),
+      ],
+      vec![
+        (
+          0,
+          vec![Constraint::Primary('p'), ColorItem::new('x', 1).into()],
+        ),
+        (
+          1,
+          vec![Constraint::Primary('p'), ColorItem::new('x', 2).into()],
+        ),
+        (2, vec![Constraint::Primary('q')]),
+      ],
+    );
+    assert!(dlx.find_first_solution_colors().is_some());
+    assert_eq!(dlx.stats().solutions, 1);
+    assert!(!dlx.interrupted());
+    // Stopping at the first solution leaves the rest to be found later.
+    assert_eq!(dlx.find_all_solution_colors().count(), 2);
+  }
+
+  /// A problem with several solutions, using each kind of constraint.
+  fn new_retaining_dlx() -> Dlx<char, u32> {
+    Dlx::new_retaining(
+      vec![
+        ('p', HeaderType::Primary),
+        ('q', HeaderType::Primary),
+        ('r', HeaderType::Primary),
+        ('x', HeaderType::Secondary),
+        ('y', HeaderType::Secondary),
+      ],
+      vec![
+        (0, vec!['p'.into(), ColorItem::new('x', 1).into()]),
+        (1, vec!['q'.into(), ColorItem::new('x', 1).into()]),
+        (2, vec!['q'.into(), ColorItem::new('x', 2).into()]),
+        (3, vec!['p'.into(), 'q'.into(), Constraint::Uncolored('y')]),
+        (4, vec!['r'.into(), Constraint::Uncolored('y')]),
+        (5, vec![Constraint::Primary('r')]),
+      ],
+    )
+  }
+
+  fn sorted_solutions(dlx: &mut Dlx<char, u32>) -> Vec<Vec<u32>> {
+    dlx
+      .find_all_solution_names()
+      .map(|names| names.into_iter().sorted().collect_vec())
+      .sorted()
+      .collect()
+  }
+
+  #[test]
+  fn test_clone() {
+    let mut dlx = new_retaining_dlx();
+    let mut clone = dlx.clone();
+    let expected = vec![vec![0, 1, 4], vec![0, 1, 5], vec![3, 5]];
+    assert_eq!(sorted_solutions(&mut dlx), expected);
+    assert_eq!(sorted_solutions(&mut clone), expected);
+    assert_eq!(clone.stats().solutions, 3);
+  }
+
+  #[test]
+  fn test_rebuild() {
+    let mut dlx = new_retaining_dlx();
+    let expected = sorted_solutions(&mut dlx);
+
+    dlx.require_color(&'x', 2);
+    dlx.set_node_limit(Some(1));
+    assert_eq!(dlx.find_first_solution_names(), None);
+    assert!(dlx.interrupted());
+
+    dlx.rebuild();
+    assert_eq!(dlx.stats().nodes, 0);
+    assert!(!dlx.interrupted());
+    // The node limit is kept, but the required color is not.
+    assert_eq!(sorted_solutions(&mut dlx).len(), 0);
+    assert!(dlx.interrupted());
+    dlx.set_node_limit(None);
+    assert_eq!(sorted_solutions(&mut dlx), expected);
+    assert_eq!(
+      dlx.find_first_solution_colors(),
+      new_retaining_dlx().find_first_solution_colors()
+    );
+  }
+
+  #[test]
+  fn test_original_subsets() {
+    let dlx = new_retaining_dlx();
+    let subsets = dlx
+      .original_subsets()
+      .unwrap()
+      .map(|(&name, constraints)| {
+        let constraints = constraints
+          .map(|constraint| (*constraint.item(), constraint.color()))
+          .collect_vec();
+        (name, constraints)
+      })
+      .collect_vec();
+    assert_eq!(subsets.len(), 6);
+    assert_eq!(subsets[0], (0, vec![('p', None), ('x', Some(1))]));
+    assert_eq!(subsets[3], (3, vec![('p', None), ('q', None), ('y', None)]));
+    assert!(matches!(
+      dlx.original_subsets().unwrap().nth(4).unwrap().1.nth(1),
+      Some(Constraint::Uncolored('y'))
+    ));
+
+    let dlx: Dlx<char, u32> = Dlx::new(vec![('p', HeaderType::Primary)], vec![(0, vec!['p'])]);
+    assert!(dlx.original_subsets().is_none());
+  }
+
+  #[test]
+  #[should_panic]
+  fn test_rebuild_without_retaining() {
+    let mut dlx: Dlx<char, u32> = Dlx::new(vec![('p', HeaderType::Primary)], vec![(0, vec!['p'])]);
+    dlx.rebuild();
+  }
+
+  #[test]
+  fn test_active_items() {
+    let mut dlx = Dlx::new(
+      vec![
+        ('p', HeaderType::Primary),
+        ('q', HeaderType::Primary),
+        ('r', HeaderType::Primary),
+      ],
+      vec![
+        (0, vec!['p', 'q']),
+        (1, vec!['p', 'r']),
+        (2, vec!['p']),
+        (3, vec!['q']),
+      ],
+    );
+    let snapshot = |dlx: &Dlx<char, u32>| {
+      dlx
+        .active_primary_items()
+        .map(|(&item, size)| (item, size))
+        .collect_vec()
+    };
+    let initial = vec![('p', 3), ('q', 2), ('r', 1)];
+    assert_eq!(snapshot(&dlx), initial);
+    assert_eq!(dlx.active_subset_count(), 4);
+    assert!(!dlx.is_item_covered(&'p'));
+
+    let p = dlx.item_indices[&'p'];
+    dlx.cover(p);
+    // Covering p removes every subset with it, leaving only {q}.
+    assert_eq!(snapshot(&dlx), vec![('q', 1), ('r', 0)]);
+    assert_eq!(dlx.active_subset_count(), 1);
+    assert!(dlx.is_item_covered(&'p'));
+    assert!(!dlx.is_item_covered(&'q'));
+
+    let q = dlx.item_indices[&'q'];
+    dlx.cover(q);
+    assert_eq!(snapshot(&dlx), vec![('r', 0)]);
+    assert_eq!(dlx.active_subset_count(), 0);
+    assert!(dlx.is_item_covered(&'q'));
+
+    dlx.uncover(q);
+    dlx.uncover(p);
+    assert_eq!(snapshot(&dlx), initial);
+    assert_eq!(dlx.active_subset_count(), 4);
+    assert!(!dlx.is_item_covered(&'p'));
+
+    // Searching leaves the structure as it found it.
+    assert_eq!(dlx.count_solutions(usize::MAX), 1);
+    assert_eq!(snapshot(&dlx), initial);
+    assert_eq!(dlx.active_subset_count(), 4);
+  }
+
+  #[test]
+  fn test_covered_secondary_items() {
+    let mut dlx = new_retaining_dlx();
+    assert_eq!(dlx.active_subset_count(), 6);
+
+    // Covering the uncolored y removes {p, q, y} and {r, y}.
+    let y = dlx.item_indices[&'y'];
+    dlx.cover(y);
+    assert!(dlx.is_item_covered(&'y'));
+    assert_eq!(dlx.active_subset_count(), 4);
+    dlx.uncover(y);
+    assert!(!dlx.is_item_covered(&'y'));
+
+    // Colored items are purified, never covered.
+    dlx.require_color(&'x', 1);
+    assert!(!dlx.is_item_covered(&'x'));
+    assert_eq!(dlx.active_subset_count(), 5);
+  }
+
+  #[test]
+  #[should_panic]
+  fn test_is_unknown_item_covered() {
+    new_retaining_dlx().is_item_covered(&'z');
+  }
+
+  #[test]
+  fn test_require_subset() {
+    let fresh = check_invariants(&knuth_example());
+    let mut dlx = knuth_example();
+    let checkpoint = dlx.begin();
+    dlx.require_subset(&4).unwrap();
+    check_invariants(&dlx);
+    assert!(dlx.is_item_covered(&'b'));
+    assert!(dlx.is_item_covered(&'g'));
+    // {b, g} conflicts with every subset but {c, e} and {a, d, f}.
+    assert_eq!(dlx.active_subset_count(), 2);
+    assert_eq!(
+      dlx.find_all_solution_names().collect_vec(),
+      vec![vec![4, 3, 0]]
+    );
+    check_invariants(&dlx);
+
+    assert_eq!(
+      dlx.require_subset(&2),
+      Err(DlxError::UnavailableSubset("2".to_string()))
+    );
+    assert_eq!(
+      dlx.require_subset(&9),
+      Err(DlxError::UnknownSubset("9".to_string()))
+    );
+    assert_eq!(
+      dlx.require_subset(&5).unwrap_err().to_string(),
+      "Subset 5 can't be chosen"
+    );
+    assert_eq!(dlx.active_subset_count(), 2);
+
+    dlx.require_subset(&0).unwrap();
+    dlx.require_subset(&3).unwrap();
+    assert_eq!(dlx.active_primary_items().count(), 0);
+    assert_eq!(dlx.find_first_solution_names(), Some(vec![4, 0, 3]));
+
+    dlx.rollback(checkpoint);
+    assert_eq!(check_invariants(&dlx), fresh);
+  }
+
+  #[test]
+  fn test_nested_checkpoints() {
+    let fresh = check_invariants(&knuth_example());
+    let mut dlx = knuth_example();
+
+    let outer = dlx.begin();
+    dlx.require_subset(&0).unwrap();
+    let after_first = check_invariants(&dlx);
+    assert_eq!(dlx.count_solutions(usize::MAX), 1);
+
+    let inner = dlx.begin();
+    dlx.require_subset(&1).unwrap();
+    check_invariants(&dlx);
+    // {c, e} and {a, d, g} leave nothing to cover f with {b}.
+    assert_eq!(dlx.count_solutions(usize::MAX), 0);
+    assert!(dlx.require_subset(&4).is_err());
+
+    dlx.rollback(inner);
+    assert_eq!(check_invariants(&dlx), after_first);
+    dlx.require_subset(&3).unwrap();
+    let innermost = dlx.begin();
+    dlx.require_subset(&4).unwrap();
+    assert_eq!(dlx.find_first_solution_names(), Some(vec![0, 3, 4]));
+
+    // Rolling back to the outer checkpoint undoes the inner ones too.
+    dlx.rollback(outer);
+    assert_eq!(check_invariants(&dlx), fresh);
+    dlx.rollback(innermost);
+    assert_eq!(check_invariants(&dlx), fresh);
+    assert_eq!(
+      dlx.find_all_solution_names().collect_vec(),
+      knuth_example().find_all_solution_names().collect_vec()
+    );
+  }
+
+  #[test]
+  fn test_require_colored_subset() {
+    let mut dlx = new_retaining_dlx();
+    let fresh = check_invariants(&dlx);
+    let checkpoint = dlx.begin();
+    dlx.require_subset(&0).unwrap();
+    check_invariants(&dlx);
+    // With x colored 1, q can only be covered by {q, x: 1}.
+    assert!(dlx.require_subset(&2).is_err());
+    assert_eq!(
+      dlx
+        .find_all_solution_names()
+        .map(|names| names.into_iter().sorted().collect_vec())
+        .sorted()
+        .collect_vec(),
+      vec![vec![0, 1, 4], vec![0, 1, 5]]
+    );
+    dlx.require_subset(&1).unwrap();
+    dlx.require_subset(&4).unwrap();
+    assert_eq!(
+      dlx.find_first_solution_colors(),
+      Some([('x', 1)].into_iter().collect())
+    );
+
+    dlx.rollback(checkpoint);
+    assert_eq!(check_invariants(&dlx), fresh);
+    assert_eq!(sorted_solutions(&mut dlx).len(), 3);
+  }
 }
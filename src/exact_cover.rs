@@ -0,0 +1,243 @@
+use std::{fmt::Debug, hash::Hash, time::Instant};
+
+use itertools::Itertools;
+
+use crate::dlx::{Constraint, Dlx, DlxStats, HeaderType, Selection};
+
+/// A puzzle described as an exact cover problem, which `solve_model` turns
+/// into a `Dlx` and searches.
+///
+/// As an example, a toy model choosing dominoes to tile a row of cells:
+///
+/// ```
+/// use p424::{
+///   dlx::{Constraint, HeaderType, Selection},
+///   exact_cover::{solve_model, ExactCoverModel},
+/// };
+///
+/// struct Dominoes(usize);
+///
+/// impl ExactCoverModel for Dominoes {
+///   type Item = usize;
+///   type Choice = usize;
+///   type Solution = Vec<usize>;
+///
+///   fn items(&self) -> impl Iterator<Item = (usize, HeaderType)> + '_ {
+///     (0..self.0).map(|cell| (cell, HeaderType::Primary))
+///   }
+///
+///   fn choices(
+///     &self,
+///   ) -> impl Iterator<Item = (usize, impl IntoIterator<Item = Constraint<usize>>)> + '_ {
+///     (1..self.0).map(|cell| (cell - 1, [cell - 1, cell].map(Constraint::Primary)))
+///   }
+///
+///   fn decode(&self, selection: Selection<usize, usize>) -> Vec<usize> {
+///     let mut starts = selection.names;
+///     starts.sort();
+///     starts
+///   }
+/// }
+///
+/// assert_eq!(solve_model(&Dominoes(4)).solutions, vec![vec![0, 2]]);
+/// assert!(solve_model(&Dominoes(5)).solutions.is_empty());
+/// ```
+pub trait ExactCoverModel {
+  type Item: Hash + Eq + Clone + Debug;
+  type Choice: Hash + Eq + Clone + Debug;
+  type Solution;
+
+  /// Every item of the problem, and whether it must be covered.
+  fn items(&self) -> impl Iterator<Item = (Self::Item, HeaderType)> + '_;
+
+  /// Every subset which may be chosen, along with the items it covers. The
+  /// names of the subsets must be distinct.
+  fn choices(
+    &self,
+  ) -> impl Iterator<
+    Item = (
+      Self::Choice,
+      impl IntoIterator<Item = Constraint<Self::Item>>,
+    ),
+  > + '_;
+
+  /// Adjusts the freshly built `dlx` before it's searched, e.g. to require
+  /// colors of secondary items.
+  fn prepare(&self, _dlx: &mut Dlx<Self::Item, Self::Choice>) {}
+
+  /// Turns the subsets and colors of one solution into a solution of the
+  /// puzzle.
+  fn decode(&self, selection: Selection<Self::Item, Self::Choice>) -> Self::Solution;
+}
+
+/// Bounds on the search run by `solve_model_with`. The search is unbounded in
+/// any respect left as None.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SearchLimits {
+  /// Stop once this many solutions have been found.
+  pub solutions: Option<usize>,
+  /// Give up once this many subsets have been tried.
+  pub nodes: Option<u64>,
+  /// Give up once this time has passed.
+  pub deadline: Option<Instant>,
+}
+
+impl SearchLimits {
+  /// Limits which stop the search at the first solution.
+  pub fn first() -> Self {
+    Self {
+      solutions: Some(1),
+      ..Self::default()
+    }
+  }
+}
+
+/// The outcome of searching a model.
+pub struct ModelSolutions<M: ExactCoverModel> {
+  /// The decoded solutions, in the order they were found.
+  pub solutions: Vec<M::Solution>,
+  pub stats: DlxStats<M::Item>,
+  /// Whether the search gave up at its node limit or deadline, in which case
+  /// `solutions` may be incomplete.
+  pub interrupted: bool,
+}
+
+/// Builds the `Dlx` for `model`, without searching it.
+pub fn build_dlx<M: ExactCoverModel>(model: &M) -> Dlx<M::Item, M::Choice> {
+  let mut dlx = Dlx::new(model.items(), model.choices());
+  model.prepare(&mut dlx);
+  dlx
+}
+
+/// Finds every solution of `model`.
+pub fn solve_model<M: ExactCoverModel>(model: &M) -> ModelSolutions<M> {
+  solve_model_with(model, SearchLimits::default())
+}
+
+/// Searches for solutions of `model`, stopping at the first of `limits`
+/// reached.
+pub fn solve_model_with<M: ExactCoverModel>(model: &M, limits: SearchLimits) -> ModelSolutions<M> {
+  let mut dlx = build_dlx(model);
+  dlx.set_node_limit(limits.nodes);
+  dlx.set_deadline(limits.deadline);
+
+  let solutions = dlx
+    .find_selections(limits.solutions)
+    .map(|selection| model.decode(selection))
+    .collect_vec();
+  ModelSolutions {
+    solutions,
+    stats: dlx.stats().clone(),
+    interrupted: dlx.interrupted(),
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::dlx::{ColorItem, Constraint, Dlx, HeaderType, Selection};
+
+  use super::{solve_model, solve_model_with, ExactCoverModel, SearchLimits};
+
+  /// Pairs of distinct letters from `word`, colored by whether the first
+  /// comes first in the alphabet, where every pair must agree.
+  struct Pairs {
+    word: &'static str,
+    ascending: Option<bool>,
+  }
+
+  impl ExactCoverModel for Pairs {
+    type Item = char;
+    type Choice = (char, char);
+    type Solution = Vec<(char, char)>;
+
+    fn items(&self) -> impl Iterator<Item = (char, HeaderType)> + '_ {
+      self
+        .word
+        .chars()
+        .map(|c| (c, HeaderType::Primary))
+        .chain([('<', HeaderType::Secondary)])
+    }
+
+    fn choices(
+      &self,
+    ) -> impl Iterator<Item = ((char, char), impl IntoIterator<Item = Constraint<char>>)> + '_ {
+      self.word.chars().flat_map(move |a| {
+        self.word.chars().filter(move |&b| a != b).map(move |b| {
+          (
+            (a, b),
+            [
+              Constraint::Primary(a),
+              Constraint::Primary(b),
+              ColorItem::new('<', (a < b) as u32).into(),
+            ],
+          )
+        })
+      })
+    }
+
+    fn prepare(&self, dlx: &mut Dlx<char, (char, char)>) {
+      if let Some(ascending) = self.ascending {
+        dlx.require_color(&'<', ascending as u32);
+      }
+    }
+
+    fn decode(&self, selection: Selection<char, (char, char)>) -> Vec<(char, char)> {
+      let mut pairs = selection.names;
+      pairs.sort();
+      pairs
+    }
+  }
+
+  #[test]
+  fn test_solve_model() {
+    let solutions = solve_model(&Pairs {
+      word: "abcd",
+      ascending: None,
+    });
+    assert_eq!(solutions.solutions.len(), 6);
+    assert_eq!(solutions.stats.solutions, 6);
+    assert!(!solutions.interrupted);
+
+    let mut ascending = solve_model(&Pairs {
+      word: "abcd",
+      ascending: Some(true),
+    })
+    .solutions;
+    ascending.sort();
+    assert_eq!(
+      ascending,
+      vec![
+        vec![('a', 'b'), ('c', 'd')],
+        vec![('a', 'c'), ('b', 'd')],
+        vec![('a', 'd'), ('b', 'c')],
+      ]
+    );
+  }
+
+  #[test]
+  fn test_solve_model_limits() {
+    let model = Pairs {
+      word: "abcdef",
+      ascending: None,
+    };
+    let limited = solve_model_with(
+      &model,
+      SearchLimits {
+        solutions: Some(4),
+        ..SearchLimits::default()
+      },
+    );
+    assert_eq!(limited.solutions.len(), 4);
+    assert!(!limited.interrupted);
+
+    let interrupted = solve_model_with(
+      &model,
+      SearchLimits {
+        nodes: Some(1),
+        ..SearchLimits::default()
+      },
+    );
+    assert!(interrupted.solutions.is_empty());
+    assert!(interrupted.interrupted);
+  }
+}
@@ -0,0 +1,390 @@
+//! A C interface to `Dlx`, declared in `include/p424_dlx.h`. Items are named
+//! by C strings and subsets by integers. Every function returns one of the
+//! `DLX_*` codes, and never unwinds into the caller.
+
+use std::{
+  collections::{HashMap, HashSet},
+  ffi::{c_char, c_int, CStr},
+  panic::{self, AssertUnwindSafe},
+  ptr, slice,
+};
+
+use crate::dlx::{ColorItem, Constraint, Dlx, HeaderType};
+
+pub const DLX_OK: c_int = 0;
+/// The search finished without finding a solution.
+pub const DLX_NO_SOLUTION: c_int = 1;
+/// A required pointer argument was null.
+pub const DLX_NULL_POINTER: c_int = -1;
+/// A name wasn't valid UTF-8.
+pub const DLX_INVALID_UTF8: c_int = -2;
+/// An item or subset with this name was already added.
+pub const DLX_DUPLICATE_NAME: c_int = -3;
+/// A subset names an item which wasn't added, or names one twice.
+pub const DLX_UNKNOWN_ITEM: c_int = -4;
+/// A subset colors a primary item, or colors a secondary item which other
+/// subsets leave uncolored, or the other way around.
+pub const DLX_INVALID_COLOR: c_int = -5;
+/// The output buffer is too short for the solution. The required length is
+/// written to `out_len`.
+pub const DLX_BUFFER_TOO_SMALL: c_int = -6;
+/// The solver panicked. The handle must not be used again, other than to be
+/// freed.
+pub const DLX_INTERNAL_ERROR: c_int = -7;
+
+/// An item of a subset, with its color if it has one.
+type ColoredItem = (String, Option<u32>);
+
+/// A problem being built up through the C interface, which is only turned into
+/// a `Dlx` once it's solved.
+pub struct DlxHandle {
+  items: Vec<(String, HeaderType)>,
+  /// The header type of each item, and for secondary items, whether they're
+  /// colored, once a subset has used them.
+  item_types: HashMap<String, (HeaderType, Option<bool>)>,
+  /// The items of each subset, with their colors.
+  subsets: Vec<(u64, Vec<ColoredItem>)>,
+  names: HashSet<u64>,
+}
+
+impl DlxHandle {
+  fn add_item(&mut self, name: String, header_type: HeaderType) -> c_int {
+    if self.item_types.contains_key(&name) {
+      return DLX_DUPLICATE_NAME;
+    }
+    self.item_types.insert(name.clone(), (header_type, None));
+    self.items.push((name, header_type));
+    DLX_OK
+  }
+
+  fn add_subset(&mut self, name: u64, items: Vec<ColoredItem>) -> c_int {
+    if self.names.contains(&name) {
+      return DLX_DUPLICATE_NAME;
+    }
+    if items
+      .iter()
+      .enumerate()
+      .any(|(idx, (item, _))| items[..idx].iter().any(|(prev, _)| prev == item))
+    {
+      return DLX_UNKNOWN_ITEM;
+    }
+    // Check every item before changing anything, so a rejected subset leaves
+    // the problem as it was.
+    for (item, color) in &items {
+      match self.item_types.get(item) {
+        None => return DLX_UNKNOWN_ITEM,
+        Some((HeaderType::Primary, _)) if color.is_some() => return DLX_INVALID_COLOR,
+        Some((HeaderType::Secondary, Some(colored))) if *colored != color.is_some() => {
+          return DLX_INVALID_COLOR
+        }
+        _ => {}
+      }
+    }
+
+    for (item, color) in &items {
+      if let Some((HeaderType::Secondary, colored)) = self.item_types.get_mut(item) {
+        *colored = Some(color.is_some());
+      }
+    }
+    self.names.insert(name);
+    self.subsets.push((name, items));
+    DLX_OK
+  }
+
+  /// The names of the subsets in the first solution found, in increasing
+  /// order.
+  fn solve(&self) -> Option<Vec<u64>> {
+    let subsets = self.subsets.iter().map(|(name, items)| {
+      let constraints = items
+        .iter()
+        .map(|(item, color)| match (self.item_types[item].0, color) {
+          (HeaderType::Primary, _) => Constraint::Primary(item.clone()),
+          (HeaderType::Secondary, Some(color)) => ColorItem::new(item.clone(), *color).into(),
+          (HeaderType::Secondary, None) => Constraint::Uncolored(item.clone()),
+        });
+      (*name, constraints)
+    });
+    let mut dlx = Dlx::new(self.items.iter().cloned(), subsets);
+    dlx.find_first_solution_names().map(|mut names| {
+      names.sort();
+      names
+    })
+  }
+}
+
+/// Runs `f`, turning a panic into `DLX_INTERNAL_ERROR`.
+fn guard(f: impl FnOnce() -> c_int) -> c_int {
+  panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(DLX_INTERNAL_ERROR)
+}
+
+/// Reads the C string at `name`.
+///
+/// # Safety
+///
+/// `name` must be null or point to a nul-terminated string.
+unsafe fn read_name(name: *const c_char) -> Result<String, c_int> {
+  if name.is_null() {
+    return Err(DLX_NULL_POINTER);
+  }
+  CStr::from_ptr(name)
+    .to_str()
+    .map(str::to_string)
+    .map_err(|_| DLX_INVALID_UTF8)
+}
+
+/// Creates an empty problem, to be freed with `dlx_free`.
+#[no_mangle]
+pub extern "C" fn dlx_new() -> *mut DlxHandle {
+  panic::catch_unwind(|| {
+    Box::into_raw(Box::new(DlxHandle {
+      items: Vec::new(),
+      item_types: HashMap::new(),
+      subsets: Vec::new(),
+      names: HashSet::new(),
+    }))
+  })
+  .unwrap_or(ptr::null_mut())
+}
+
+/// Adds an item named `name`, which must be covered exactly once if
+/// `is_primary`, and otherwise at most once or by subsets agreeing on its
+/// color.
+///
+/// # Safety
+///
+/// `handle` must come from `dlx_new`, and `name` must be a nul-terminated
+/// string.
+#[no_mangle]
+pub unsafe extern "C" fn dlx_add_item(
+  handle: *mut DlxHandle,
+  name: *const c_char,
+  is_primary: bool,
+) -> c_int {
+  guard(|| {
+    let Some(handle) = handle.as_mut() else {
+      return DLX_NULL_POINTER;
+    };
+    let header_type = if is_primary {
+      HeaderType::Primary
+    } else {
+      HeaderType::Secondary
+    };
+    match read_name(name) {
+      Ok(name) => handle.add_item(name, header_type),
+      Err(code) => code,
+    }
+  })
+}
+
+/// Adds the subset `name` covering the `len` items named in `items`. If
+/// `colors` isn't null, it holds a color for each item, where negative colors
+/// leave the item uncolored. Primary items can't have colors.
+///
+/// # Safety
+///
+/// `handle` must come from `dlx_new`, `items` must point to `len`
+/// nul-terminated strings, and `colors` must be null or point to `len`
+/// colors.
+#[no_mangle]
+pub unsafe extern "C" fn dlx_add_subset(
+  handle: *mut DlxHandle,
+  name: u64,
+  items: *const *const c_char,
+  colors: *const i64,
+  len: usize,
+) -> c_int {
+  guard(|| {
+    let Some(handle) = handle.as_mut() else {
+      return DLX_NULL_POINTER;
+    };
+    if items.is_null() && len > 0 {
+      return DLX_NULL_POINTER;
+    }
+    let names = if len == 0 {
+      &[]
+    } else {
+      slice::from_raw_parts(items, len)
+    };
+    let colors = if colors.is_null() {
+      vec![-1; len]
+    } else {
+      slice::from_raw_parts(colors, len).to_vec()
+    };
+
+    let items = names
+      .iter()
+      .zip(colors)
+      .map(|(&item, color)| {
+        let color = match u32::try_from(color) {
+          Ok(color) => Some(color),
+          Err(_) if color < 0 => None,
+          Err(_) => return Err(DLX_INVALID_COLOR),
+        };
+        Ok((read_name(item)?, color))
+      })
+      .collect::<Result<Vec<_>, _>>();
+    match items {
+      Ok(items) => handle.add_subset(name, items),
+      Err(code) => code,
+    }
+  })
+}
+
+/// Searches for a solution, writing the names of its subsets in increasing
+/// order to `out_names`. `out_len` holds the length of `out_names` on entry,
+/// and the number of subsets in the solution on return, even if they didn't
+/// fit.
+///
+/// # Safety
+///
+/// `handle` must come from `dlx_new`, `out_len` must point to a valid length,
+/// and `out_names` must point to at least that many names, or may be null if
+/// it's 0.
+#[no_mangle]
+pub unsafe extern "C" fn dlx_solve(
+  handle: *const DlxHandle,
+  out_names: *mut u64,
+  out_len: *mut usize,
+) -> c_int {
+  guard(|| {
+    let (Some(handle), Some(out_len)) = (handle.as_ref(), out_len.as_mut()) else {
+      return DLX_NULL_POINTER;
+    };
+    let Some(names) = handle.solve() else {
+      *out_len = 0;
+      return DLX_NO_SOLUTION;
+    };
+
+    let capacity = *out_len;
+    *out_len = names.len();
+    if names.len() > capacity {
+      return DLX_BUFFER_TOO_SMALL;
+    }
+    if !names.is_empty() {
+      if out_names.is_null() {
+        return DLX_NULL_POINTER;
+      }
+      slice::from_raw_parts_mut(out_names, names.len()).copy_from_slice(&names);
+    }
+    DLX_OK
+  })
+}
+
+/// Frees a problem created by `dlx_new`. Does nothing if `handle` is null.
+///
+/// # Safety
+///
+/// `handle` must come from `dlx_new`, and not have been freed already.
+#[no_mangle]
+pub unsafe extern "C" fn dlx_free(handle: *mut DlxHandle) {
+  if !handle.is_null() {
+    // Dropping only frees memory, which doesn't panic.
+    drop(Box::from_raw(handle));
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::{ffi::CString, ptr};
+
+  use super::{
+    dlx_add_item, dlx_add_subset, dlx_free, dlx_new, dlx_solve, DLX_BUFFER_TOO_SMALL,
+    DLX_DUPLICATE_NAME, DLX_INVALID_COLOR, DLX_NO_SOLUTION, DLX_NULL_POINTER, DLX_OK,
+    DLX_UNKNOWN_ITEM,
+  };
+
+  #[test]
+  fn test_choose_two() {
+    let names = ["p", "q", "r"].map(|name| CString::new(name).unwrap());
+    unsafe {
+      let handle = dlx_new();
+      for name in &names {
+        assert_eq!(dlx_add_item(handle, name.as_ptr(), true), DLX_OK);
+      }
+      let [p, q, r] = [0, 1, 2].map(|idx| names[idx].as_ptr());
+      for (name, items) in [(0, vec![p, q]), (1, vec![p, r]), (2, vec![p]), (3, vec![q])] {
+        assert_eq!(
+          dlx_add_subset(handle, name, items.as_ptr(), ptr::null(), items.len()),
+          DLX_OK
+        );
+      }
+
+      let mut len = 1;
+      let mut out = [0u64; 4];
+      assert_eq!(
+        dlx_solve(handle, out.as_mut_ptr(), &mut len),
+        DLX_BUFFER_TOO_SMALL
+      );
+      assert_eq!(len, 2);
+
+      len = out.len();
+      assert_eq!(dlx_solve(handle, out.as_mut_ptr(), &mut len), DLX_OK);
+      assert_eq!(&out[..len], &[1, 3]);
+      dlx_free(handle);
+    }
+  }
+
+  #[test]
+  fn test_colors() {
+    let names = ["p", "q", "x"].map(|name| CString::new(name).unwrap());
+    unsafe {
+      let handle = dlx_new();
+      assert_eq!(dlx_add_item(handle, names[0].as_ptr(), true), DLX_OK);
+      assert_eq!(dlx_add_item(handle, names[1].as_ptr(), true), DLX_OK);
+      assert_eq!(dlx_add_item(handle, names[2].as_ptr(), false), DLX_OK);
+      let [p, q, x] = [0, 1, 2].map(|idx| names[idx].as_ptr());
+      for (name, items, colors) in [
+        (0, [p, x], [-1, 1]),
+        (1, [q, x], [-1, 2]),
+        (2, [q, x], [-1, 1]),
+      ] {
+        assert_eq!(
+          dlx_add_subset(handle, name, items.as_ptr(), colors.as_ptr(), 2),
+          DLX_OK
+        );
+      }
+
+      let mut out = [0u64; 2];
+      let mut len = out.len();
+      assert_eq!(dlx_solve(handle, out.as_mut_ptr(), &mut len), DLX_OK);
+      assert_eq!(&out[..len], &[0, 2]);
+      dlx_free(handle);
+    }
+  }
+
+  #[test]
+  fn test_errors() {
+    let names = ["p", "x", "z"].map(|name| CString::new(name).unwrap());
+    unsafe {
+      let handle = dlx_new();
+      let [p, x, z] = [0, 1, 2].map(|idx| names[idx].as_ptr());
+      assert_eq!(dlx_add_item(handle, p, true), DLX_OK);
+      assert_eq!(dlx_add_item(handle, p, false), DLX_DUPLICATE_NAME);
+      assert_eq!(dlx_add_item(handle, x, false), DLX_OK);
+      assert_eq!(dlx_add_item(ptr::null_mut(), z, true), DLX_NULL_POINTER);
+      assert_eq!(dlx_add_item(handle, ptr::null(), true), DLX_NULL_POINTER);
+
+      let add = |name, items: &[_], colors: &[i64]| {
+        dlx_add_subset(handle, name, items.as_ptr(), colors.as_ptr(), items.len())
+      };
+      assert_eq!(add(0, &[p, z], &[-1, -1]), DLX_UNKNOWN_ITEM);
+      assert_eq!(add(0, &[p, p], &[-1, -1]), DLX_UNKNOWN_ITEM);
+      assert_eq!(add(0, &[p], &[3]), DLX_INVALID_COLOR);
+      assert_eq!(add(0, &[x], &[3]), DLX_OK);
+      assert_eq!(add(0, &[p], &[-1]), DLX_DUPLICATE_NAME);
+      assert_eq!(add(1, &[x], &[-1]), DLX_INVALID_COLOR);
+
+      // Nothing covers p.
+      let mut len = 0;
+      assert_eq!(
+        dlx_solve(handle, ptr::null_mut(), &mut len),
+        DLX_NO_SOLUTION
+      );
+      assert_eq!(
+        dlx_solve(handle, ptr::null_mut(), ptr::null_mut()),
+        DLX_NULL_POINTER
+      );
+      dlx_free(handle);
+    }
+  }
+}
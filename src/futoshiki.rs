@@ -0,0 +1,413 @@
+use std::{error::Error, fmt::Display, str::FromStr};
+
+use itertools::Itertools;
+
+use crate::{
+  dlx::{ColorItem, Constraint, HeaderType, Selection},
+  exact_cover::{build_dlx, solve_model_with, ExactCoverModel, SearchLimits},
+};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum FutoshikiError {
+  /// The grid has `rows` rows, but row `row` has `len` cells.
+  NotSquare { rows: usize, row: usize, len: usize },
+  /// The character `c` at (`row`, `col`) is neither a digit nor a blank.
+  InvalidChar { row: usize, col: usize, c: char },
+  /// The given at (`row`, `col`) is larger than the grid.
+  InvalidGiven { row: usize, col: usize, digit: u32 },
+  /// A line of the constraint list isn't of the form `r,c < r,c`.
+  InvalidConstraint(String),
+  /// The cells of an inequality aren't next to each other in the grid.
+  NotAdjacent {
+    first: (usize, usize),
+    second: (usize, usize),
+  },
+}
+
+impl Display for FutoshikiError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      FutoshikiError::NotSquare { rows, row, len } => {
+        write!(f, "Row {row} has {len} cells, expected {rows}")
+      }
+      FutoshikiError::InvalidChar { row, col, c } => {
+        write!(f, "Invalid character '{c}' at row {row}, column {col}")
+      }
+      FutoshikiError::InvalidGiven { row, col, digit } => {
+        write!(f, "Invalid given {digit} at row {row}, column {col}")
+      }
+      FutoshikiError::InvalidConstraint(line) => {
+        write!(f, "Invalid constraint \"{line}\"")
+      }
+      FutoshikiError::NotAdjacent { first, second } => {
+        write!(f, "Cells {first:?} and {second:?} aren't adjacent")
+      }
+    }
+  }
+}
+
+impl Error for FutoshikiError {}
+
+/// The constraint that the digit in `less` is smaller than the digit in
+/// `greater`, where both are `(row, col)` of adjacent cells.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Inequality {
+  pub less: (usize, usize),
+  pub greater: (usize, usize),
+}
+
+/// The items of a futoshiki's exact cover model.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum FutoshikiItem {
+  Cell {
+    row: usize,
+    col: usize,
+  },
+  Row {
+    row: usize,
+    digit: u32,
+  },
+  Col {
+    col: usize,
+    digit: u32,
+  },
+  Inequality(usize),
+  /// The digit in a cell, as a color.
+  Digit {
+    row: usize,
+    col: usize,
+  },
+}
+
+/// The subsets of a futoshiki's exact cover model.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum FutoshikiChoice {
+  Place {
+    row: usize,
+    col: usize,
+    digit: u32,
+  },
+  /// Satisfies inequality `idx` with the pair of digits `less < greater`.
+  Relation {
+    idx: usize,
+    less: u32,
+    greater: u32,
+  },
+}
+
+/// An `n`x`n` latin square where some pairs of adjacent cells must also
+/// satisfy an inequality.
+///
+/// Rows and columns are modeled as in a latin square. Each inequality is a
+/// primary item, with one subset for every pair of digits satisfying it, which
+/// colors the digit of both of its cells. Placements color the digit of their
+/// cell as well, so only placements agreeing with the inequalities survive.
+#[derive(Clone, Debug)]
+pub struct Futoshiki {
+  grid: Vec<Vec<u32>>,
+  inequalities: Vec<Inequality>,
+}
+
+impl Futoshiki {
+  /// A puzzle with the given digits in `grid`, where 0 is blank.
+  pub fn new(grid: Vec<Vec<u32>>, inequalities: Vec<Inequality>) -> Result<Self, FutoshikiError> {
+    let n = grid.len();
+    if let Some((row, cells)) = grid.iter().find_position(|cells| cells.len() != n) {
+      return Err(FutoshikiError::NotSquare {
+        rows: n,
+        row,
+        len: cells.len(),
+      });
+    }
+    for (row, cells) in grid.iter().enumerate() {
+      if let Some((col, &digit)) = cells.iter().find_position(|&&digit| digit > n as u32) {
+        return Err(FutoshikiError::InvalidGiven { row, col, digit });
+      }
+    }
+    for &Inequality { less, greater } in &inequalities {
+      let in_grid = |(row, col): (usize, usize)| row < n && col < n;
+      let distance = less.0.abs_diff(greater.0) + less.1.abs_diff(greater.1);
+      if !in_grid(less) || !in_grid(greater) || distance != 1 {
+        return Err(FutoshikiError::NotAdjacent {
+          first: less,
+          second: greater,
+        });
+      }
+    }
+
+    Ok(Self { grid, inequalities })
+  }
+
+  pub fn size(&self) -> usize {
+    self.grid.len()
+  }
+
+  /// The first solution found, as a filled-in grid.
+  pub fn solve(&self) -> Option<Vec<Vec<u32>>> {
+    solve_model_with(self, SearchLimits::first())
+      .solutions
+      .pop()
+  }
+
+  /// Counts the solutions, stopping once `limit` have been found.
+  pub fn count_solutions(&self, limit: u64) -> u64 {
+    build_dlx(self).count_solutions(limit as usize) as u64
+  }
+
+  /// Whether this puzzle has exactly one solution.
+  pub fn is_unique(&self) -> bool {
+    self.count_solutions(2) == 1
+  }
+}
+
+impl ExactCoverModel for Futoshiki {
+  type Item = FutoshikiItem;
+  type Choice = FutoshikiChoice;
+  type Solution = Vec<Vec<u32>>;
+
+  fn items(&self) -> impl Iterator<Item = (FutoshikiItem, HeaderType)> + '_ {
+    let n = self.size();
+    let digits = 1..=n as u32;
+    let cells = (0..n).cartesian_product(0..n);
+    let primary = cells
+      .clone()
+      .map(|(row, col)| FutoshikiItem::Cell { row, col })
+      .chain((0..n).cartesian_product(digits).flat_map(|(line, digit)| {
+        [
+          FutoshikiItem::Row { row: line, digit },
+          FutoshikiItem::Col { col: line, digit },
+        ]
+      }))
+      .chain((0..self.inequalities.len()).map(FutoshikiItem::Inequality))
+      .map(|item| (item, HeaderType::Primary));
+    let secondary = cells
+      .map(|(row, col)| FutoshikiItem::Digit { row, col })
+      .map(|item| (item, HeaderType::Secondary));
+    primary.chain(secondary)
+  }
+
+  fn choices(
+    &self,
+  ) -> impl Iterator<
+    Item = (
+      FutoshikiChoice,
+      impl IntoIterator<Item = Constraint<FutoshikiItem>>,
+    ),
+  > + '_ {
+    let size = self.size();
+    let n = size as u32;
+    let placements = (0..size)
+      .cartesian_product(0..size)
+      .flat_map(move |(row, col)| {
+        let given = self.grid[row][col];
+        (1..=n)
+          .filter(move |&digit| given == 0 || given == digit)
+          .map(move |digit| {
+            (
+              FutoshikiChoice::Place { row, col, digit },
+              vec![
+                FutoshikiItem::Cell { row, col }.into(),
+                FutoshikiItem::Row { row, digit }.into(),
+                FutoshikiItem::Col { col, digit }.into(),
+                ColorItem::new(FutoshikiItem::Digit { row, col }, digit).into(),
+              ],
+            )
+          })
+      });
+
+    let relations = self
+      .inequalities
+      .iter()
+      .enumerate()
+      .flat_map(move |(idx, inequality)| {
+        let (a, b) = (inequality.less, inequality.greater);
+        (1..=n).tuple_combinations().map(move |(less, greater)| {
+          (
+            FutoshikiChoice::Relation { idx, less, greater },
+            vec![
+              FutoshikiItem::Inequality(idx).into(),
+              ColorItem::new(FutoshikiItem::Digit { row: a.0, col: a.1 }, less).into(),
+              ColorItem::new(FutoshikiItem::Digit { row: b.0, col: b.1 }, greater).into(),
+            ],
+          )
+        })
+      });
+
+    placements.chain(relations)
+  }
+
+  fn decode(&self, selection: Selection<FutoshikiItem, FutoshikiChoice>) -> Vec<Vec<u32>> {
+    let mut grid = self.grid.clone();
+    for choice in selection.names {
+      if let FutoshikiChoice::Place { row, col, digit } = choice {
+        grid[row][col] = digit;
+      }
+    }
+    grid
+  }
+}
+
+/// Parses a grid with one line per row, using `.` or `0` for blanks, followed
+/// by one inequality per line written as `r,c < r,c` or `r,c > r,c`, with rows
+/// and columns counted from 1.
+impl FromStr for Futoshiki {
+  type Err = FutoshikiError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut lines = s.lines().map(str::trim).filter(|line| !line.is_empty());
+    let first = lines.clone().next().unwrap_or_default();
+    let n = first.chars().count();
+
+    let grid = lines
+      .by_ref()
+      .take(n)
+      .enumerate()
+      .map(|(row, line)| {
+        line
+          .chars()
+          .enumerate()
+          .map(|(col, c)| match c {
+            '.' => Ok(0),
+            _ => c
+              .to_digit(10)
+              .ok_or(FutoshikiError::InvalidChar { row, col, c }),
+          })
+          .collect::<Result<Vec<_>, _>>()
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+
+    let cell = |cell: &str| {
+      let (row, col) = cell.trim().split_once(',')?;
+      let row = row.trim().parse::<usize>().ok()?.checked_sub(1)?;
+      let col = col.trim().parse::<usize>().ok()?.checked_sub(1)?;
+      Some((row, col))
+    };
+    let inequalities = lines
+      .map(|line| {
+        let invalid = || FutoshikiError::InvalidConstraint(line.to_owned());
+        let (op_pos, op) = line
+          .char_indices()
+          .find(|(_, c)| matches!(c, '<' | '>'))
+          .ok_or_else(invalid)?;
+        let first = cell(&line[..op_pos]).ok_or_else(invalid)?;
+        let second = cell(&line[op_pos + 1..]).ok_or_else(invalid)?;
+        Ok(if op == '<' {
+          Inequality {
+            less: first,
+            greater: second,
+          }
+        } else {
+          Inequality {
+            less: second,
+            greater: first,
+          }
+        })
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+
+    Self::new(grid, inequalities)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{Futoshiki, FutoshikiError, Inequality};
+
+  const PUZZLE: &str = "
+    .....
+    1...4
+    .....
+    .....
+    .....
+    5,1 < 4,1
+    5,2 < 5,1
+    5,4 < 5,5
+    1,3 < 2,3
+    3,4 < 3,5
+    2,4 < 3,4
+    4,5 > 5,5
+  ";
+
+  #[test]
+  fn test_solve() {
+    let puzzle: Futoshiki = PUZZLE.parse().unwrap();
+    assert_eq!(
+      puzzle.solve().unwrap(),
+      vec![
+        vec![3, 4, 2, 5, 1],
+        vec![1, 5, 3, 2, 4],
+        vec![2, 1, 4, 3, 5],
+        vec![5, 2, 1, 4, 3],
+        vec![4, 3, 5, 1, 2],
+      ]
+    );
+    assert!(puzzle.is_unique());
+  }
+
+  #[test]
+  fn test_inequalities_disambiguate() {
+    let givens: String = PUZZLE.lines().take(6).collect::<Vec<_>>().join("\n");
+    let latin: Futoshiki = givens.parse().unwrap();
+    assert_eq!(latin.count_solutions(u64::MAX), 8064);
+
+    // In a 2x2 square, one inequality picks between the two solutions.
+    let square = Futoshiki::new(vec![vec![0; 2]; 2], vec![]).unwrap();
+    assert_eq!(square.count_solutions(u64::MAX), 2);
+    let square = Futoshiki::new(
+      vec![vec![0; 2]; 2],
+      vec![Inequality {
+        less: (0, 1),
+        greater: (0, 0),
+      }],
+    )
+    .unwrap();
+    assert_eq!(square.solve(), Some(vec![vec![2, 1], vec![1, 2]]));
+    assert!(square.is_unique());
+  }
+
+  #[test]
+  fn test_contradiction() {
+    let puzzle: Futoshiki = "..\n..\n1,1 < 1,2\n1,2 < 2,2\n2,2 < 2,1".parse().unwrap();
+    assert_eq!(puzzle.solve(), None);
+    assert_eq!(puzzle.count_solutions(2), 0);
+  }
+
+  #[test]
+  fn test_parse_errors() {
+    assert_eq!(
+      "..\n.x".parse::<Futoshiki>().unwrap_err(),
+      FutoshikiError::InvalidChar {
+        row: 1,
+        col: 1,
+        c: 'x'
+      }
+    );
+    assert_eq!(
+      "..\n.3".parse::<Futoshiki>().unwrap_err(),
+      FutoshikiError::InvalidGiven {
+        row: 1,
+        col: 1,
+        digit: 3
+      }
+    );
+    assert_eq!(
+      "..\n...".parse::<Futoshiki>().unwrap_err(),
+      FutoshikiError::NotSquare {
+        rows: 2,
+        row: 1,
+        len: 3
+      }
+    );
+    assert_eq!(
+      "..\n..\n1,1 = 1,2".parse::<Futoshiki>().unwrap_err(),
+      FutoshikiError::InvalidConstraint("1,1 = 1,2".to_owned())
+    );
+    assert_eq!(
+      "..\n..\n1,1 < 2,2".parse::<Futoshiki>().unwrap_err(),
+      FutoshikiError::NotAdjacent {
+        first: (0, 0),
+        second: (1, 1)
+      }
+    );
+  }
+}
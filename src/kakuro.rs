@@ -1,75 +1,325 @@
+pub mod cache;
+pub mod session;
+
 use std::{
-  collections::HashMap,
+  cell::{Cell, RefCell},
+  collections::{hash_map::Entry, HashMap, HashSet},
+  error::Error,
   fmt::{self, Display},
-  fs::File,
-  io::{self, BufRead, BufReader},
+  fs::{File, OpenOptions},
+  io::{self, BufRead, BufReader, BufWriter, Write},
   iter,
-  ops::ControlFlow,
+  ops::{ControlFlow, Index},
+  str::FromStr,
+  sync::Arc,
+  time::{Duration, Instant},
 };
 
-use itertools::Itertools;
+use itertools::{Either, Itertools};
+use log::{debug, log_enabled, trace, Level};
+use serde::Serialize;
 
 use crate::{
-  dlx::{ColorItem, Constraint, Dlx, HeaderType},
+  dlx::{ColorItem, Constraint, Dlx, DlxError, HeaderType, Selection},
+  exact_cover::{build_dlx, solve_model, solve_model_with, ExactCoverModel, SearchLimits},
+  kakuro::cache::CacheDir,
+  linear_solver::{to_dlx_choices, EquationSystem, LinearSolver},
+  paren_tree::{parse_node, Node},
   parenthesis_split::ParenthesesAwareSplit,
+  util::DigitSet,
 };
 
-#[derive(Clone)]
+/// The `index`th permutation of all of `items`, in the order
+/// `Itertools::permutations` generates them.
+fn nth_permutation<T: Clone>(items: &[T], mut index: usize) -> Vec<T> {
+  let mut remaining = items.to_vec();
+  (1..=items.len())
+    .rev()
+    .map(|len| {
+      let block = (1..len).product::<usize>();
+      let item = remaining.remove(index / block);
+      index %= block;
+      item
+    })
+    .collect()
+}
+
+/// True if each digit in `digits` can be placed in a distinct tile, where each
+/// tile can only take the digits in its `candidates`, filling every tile.
+fn fits(candidates: &[DigitSet], digits: DigitSet) -> bool {
+  match candidates.split_first() {
+    Some((first, rest)) => first
+      .intersection(digits)
+      .iter()
+      .any(|digit| fits(rest, digits.without(digit))),
+    None => digits.is_empty(),
+  }
+}
+
+/// An iterator adapter which reports an exact `size_hint`, for iterators
+/// whose length is known before iterating.
+struct KnownLen<I> {
+  inner: I,
+  remaining: usize,
+}
+
+impl<I: Iterator> Iterator for KnownLen<I> {
+  type Item = I::Item;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let item = self.inner.next();
+    debug_assert_eq!(item.is_some(), self.remaining > 0);
+    self.remaining = self.remaining.saturating_sub(1);
+    item
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    (self.remaining, Some(self.remaining))
+  }
+}
+
+impl<I: Iterator> ExactSizeIterator for KnownLen<I> {}
+
+trait WithKnownLen: Iterator + Sized {
+  fn with_known_len(self, len: usize) -> KnownLen<Self> {
+    KnownLen {
+      inner: self,
+      remaining: len,
+    }
+  }
+}
+
+impl<I: Iterator> WithKnownLen for I {}
+
+/// One digit of a `TotalClue::Mixed` clue.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ClueDigit {
+  Literal(u32),
+  Letter(char),
+}
+
+impl ClueDigit {
+  fn parse(c: char) -> ClueDigit {
+    match c.to_digit(10) {
+      Some(digit) => ClueDigit::Literal(digit),
+      None => ClueDigit::Letter(c),
+    }
+  }
+
+  fn letter(&self) -> Option<char> {
+    match self {
+      ClueDigit::Literal(_) => None,
+      ClueDigit::Letter(letter) => Some(*letter),
+    }
+  }
+
+  fn known_value(&self, assignment: &LetterAssignment) -> Option<u32> {
+    match self {
+      ClueDigit::Literal(digit) => Some(*digit),
+      ClueDigit::Letter(letter) => assignment.letter_value(*letter),
+    }
+  }
+}
+
+impl Display for ClueDigit {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ClueDigit::Literal(digit) => write!(f, "{digit}"),
+      ClueDigit::Letter(letter) => write!(f, "{letter}"),
+    }
+  }
+}
+
+/// Why `TotalClue::parse` rejected the text of a clue.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ClueParseError {
+  Empty,
+  /// The clue has a character other than the letters A-J and the digits.
+  InvalidCharacter {
+    clue: String,
+    character: char,
+  },
+  /// The clue uses letters, but has more than two digits.
+  TooLong(String),
+  /// The clue's first digit is a literal 0.
+  LeadingZero(String),
+  /// The clue is a number too large to be a total.
+  TooLarge(String),
+}
+
+impl Display for ClueParseError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ClueParseError::Empty => write!(f, "Empty clue"),
+      ClueParseError::InvalidCharacter { clue, character } => {
+        write!(f, "Invalid character '{character}' in clue \"{clue}\"")
+      }
+      ClueParseError::TooLong(clue) => {
+        write!(f, "Clue \"{clue}\" has more than two letters or digits")
+      }
+      ClueParseError::LeadingZero(clue) => write!(f, "Clue \"{clue}\" has a leading zero"),
+      ClueParseError::TooLarge(clue) => write!(f, "Clue \"{clue}\" is too large"),
+    }
+  }
+}
+
+impl Error for ClueParseError {}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum TotalClue {
   OneDigit(char),
-  TwoDigit { ones: char, tens: char },
+  TwoDigit {
+    ones: char,
+    tens: char,
+  },
+  /// A two-digit clue with one digit written as a letter and the other as a
+  /// literal digit, e.g. `1A` for 10 + A.
+  Mixed {
+    tens: ClueDigit,
+    ones: ClueDigit,
+  },
+  /// A clue whose total is written out as a number, as in classic kakuro.
+  Numeric(u32),
 }
 
 impl TotalClue {
-  fn new(clue: &str) -> TotalClue {
-    if clue.len() == 1 {
-      TotalClue::OneDigit(clue.chars().next().unwrap())
-    } else if clue.len() == 2 {
-      let mut chars = clue.chars();
-      TotalClue::TwoDigit {
-        tens: chars.next().unwrap(),
-        ones: chars.next().unwrap(),
+  /// Parses the text of a clue, as a number or one or two of the letters A-J,
+  /// where a two-digit clue may mix a letter with a literal digit. Only text
+  /// which `to_string` would give back is accepted, so numbers can't have
+  /// leading zeros and letters must be uppercase.
+  pub fn parse(clue: &str) -> Result<TotalClue, ClueParseError> {
+    if clue.is_empty() {
+      return Err(ClueParseError::Empty);
+    }
+    if let Some(c) = clue
+      .chars()
+      .find(|c| !('A'..='J').contains(c) && !c.is_ascii_digit())
+    {
+      return Err(ClueParseError::InvalidCharacter {
+        clue: clue.to_string(),
+        character: c,
+      });
+    }
+
+    let is_numeric = clue.chars().all(|c| c.is_ascii_digit());
+    if clue.len() > 2 && !is_numeric {
+      return Err(ClueParseError::TooLong(clue.to_string()));
+    }
+    // A tens digit, literal or not, can't be a leading zero.
+    if clue.len() > 1 && clue.starts_with('0') {
+      return Err(ClueParseError::LeadingZero(clue.to_string()));
+    }
+    if is_numeric {
+      return clue
+        .parse()
+        .map(TotalClue::Numeric)
+        .map_err(|_| ClueParseError::TooLarge(clue.to_string()));
+    }
+
+    let mut digits = clue.chars().map(ClueDigit::parse);
+    Ok(match (digits.next(), digits.next()) {
+      (Some(ClueDigit::Letter(letter)), None) => TotalClue::OneDigit(letter),
+      (Some(ClueDigit::Letter(tens)), Some(ClueDigit::Letter(ones))) => {
+        TotalClue::TwoDigit { tens, ones }
       }
-    } else {
-      unreachable!("Tried to construct clue with wrong number of digits: \"{clue}\"")
+      (Some(tens), Some(ones)) => TotalClue::Mixed { tens, ones },
+      _ => unreachable!("Non-numeric clue \"{clue}\" has one or two digits"),
+    })
+  }
+
+  /// The letters this clue is written with, from most to least significant
+  /// digit.
+  pub fn letters(&self) -> Vec<char> {
+    match self {
+      TotalClue::OneDigit(letter) => vec![*letter],
+      TotalClue::TwoDigit { ones, tens } => vec![*tens, *ones],
+      TotalClue::Mixed { tens, ones } => tens.letter().into_iter().chain(ones.letter()).collect(),
+      TotalClue::Numeric(_) => vec![],
     }
   }
 
-  fn sum_range(&self) -> (u32, u32) {
+  /// The range of totals this clue could stand for.
+  pub fn sum_range(&self) -> (u32, u32) {
     match self {
       TotalClue::OneDigit(_) => (0, 9),
       TotalClue::TwoDigit { .. } => (10, 45),
+      TotalClue::Mixed {
+        tens: ClueDigit::Literal(tens),
+        ..
+      } => (10 * tens, 10 * tens + 9),
+      TotalClue::Mixed {
+        ones: ClueDigit::Literal(ones),
+        ..
+      } => (10 + ones, 40 + ones),
+      TotalClue::Mixed { .. } => (10, 45),
+      TotalClue::Numeric(total) => (*total, *total),
     }
   }
 
+  /// The smallest and largest sums attainable by `num_tiles` distinct digits
+  /// 1-9, or None if no run can have `num_tiles` tiles.
+  fn attainable_sum_range(num_tiles: u32) -> Option<(u32, u32)> {
+    (1..=9).contains(&num_tiles).then(|| {
+      (
+        num_tiles * (num_tiles + 1) / 2,
+        num_tiles * (19 - num_tiles) / 2,
+      )
+    })
+  }
+
+  /// Enumerates every set of `num_tiles` distinct digits 1-9 whose sum lies in
+  /// `min..=max`, as `(sum, digits)` pairs with the digits in increasing order.
+  ///
+  /// Runs are between 1 and 9 tiles long, so any other `num_tiles` yields no
+  /// combinations. An empty range (`min > max`), or a range which no set of
+  /// `num_tiles` digits can sum into, also yields no combinations.
   pub fn all_combinations_for_range(
     (min, max): (u32, u32),
     num_tiles: u32,
-  ) -> impl Iterator<Item = (u32, Vec<u32>)> {
-    debug_assert!((1..=9).contains(&num_tiles));
-    let mut choices = Vec::with_capacity(num_tiles as usize);
+  ) -> impl ExactSizeIterator<Item = (u32, Vec<u32>)> {
+    let len = Self::combination_count_for_range((min, max), num_tiles) as usize;
+
+    // Narrow the range to the sums which are actually attainable, so the
+    // generator below never has to reason about infeasible ranges.
+    let feasible_range = Self::attainable_sum_range(num_tiles)
+      .map(|(lowest, highest)| (min.max(lowest), max.min(highest)))
+      .filter(|(min, max)| min <= max);
+    let mut choices = Vec::with_capacity(9);
 
     // Slack is the amount of extra value we have to add above the min possible
     // permutation (1, 2, 3, 4, ...) to sum to `max`. Slack cannot fall below
     // 0, else the sum of numbers would be larger than `max`.
-    let mut slack = max as i32 - (num_tiles * (num_tiles + 1) / 2) as i32;
+    let mut slack = 0;
     // Air is the amount of extra value we have to add above the min possible
     // permutation (1, 2, 3, 4, ...) to sum to `min`. Air must be <= 0, else
     // the sum of numbers would be less than `min`.
-    let mut air = min as i32 - (num_tiles * (num_tiles + 1) / 2) as i32;
+    let mut air = 0;
+
+    // If the range is infeasible, `choices` is left empty and nothing is
+    // generated.
+    if let Some((min, max)) = feasible_range {
+      let min_permutation_sum = (num_tiles * (num_tiles + 1) / 2) as i32;
+      slack = max as i32 - min_permutation_sum;
+      air = min as i32 - min_permutation_sum;
 
-    {
       let max_extra_from_remainder =
         9 * (num_tiles - 1) - (num_tiles - 1) * (num_tiles.wrapping_sub(2)) / 2;
       let extra = (air.max(0) as u32).saturating_sub(max_extra_from_remainder);
 
       slack -= (extra * num_tiles) as i32;
       air -= (extra * num_tiles) as i32;
+      // The range is feasible, so the lifted first digit always leaves room for
+      // the remaining digits above it.
+      debug_assert!(extra < 10 - num_tiles);
       choices.push(1 + extra);
     }
+    let (min, max) = feasible_range.unwrap_or_default();
 
     iter::once(
-      if choices.len() == num_tiles as usize && (air..=slack).contains(&0) {
+      if feasible_range.is_some()
+        && choices.len() == num_tiles as usize
+        && (air..=slack).contains(&0)
+      {
         Some((*choices.first().unwrap(), choices.clone()))
       } else {
         None
@@ -134,36 +384,292 @@ impl TotalClue {
       }),
     )
     .flatten()
+    .with_known_len(len)
+  }
+
+  /// Enumerates every set of `num_tiles` distinct digits 1-9 summing to
+  /// exactly `total`, with the digits in increasing order.
+  pub fn all_combinations_for_total(total: u32, num_tiles: u32) -> impl Iterator<Item = Vec<u32>> {
+    // With `min == max`, slack and air coincide, so the range generator only
+    // ever explores combinations which hit `total` exactly.
+    Self::all_combinations_for_range((total, total), num_tiles).map(|(_, digits)| digits)
+  }
+
+  /// Like `all_combinations_for_range`, but only yields combinations which
+  /// contain every digit in `required` and no digit in `forbidden`. Branches
+  /// which can't satisfy the filters are pruned during generation, so long runs
+  /// don't enumerate combinations only to discard them.
+  pub fn all_combinations_for_range_filtered(
+    (min, max): (u32, u32),
+    num_tiles: u32,
+    required: DigitSet,
+    forbidden: DigitSet,
+  ) -> impl Iterator<Item = (u32, Vec<u32>)> {
+    let feasible = Self::attainable_sum_range(num_tiles).is_some()
+      && !required.contains(0)
+      && required.intersection(forbidden).is_empty()
+      && required.len() <= num_tiles;
+
+    // `choices` is the increasing sequence of digits picked so far, and
+    // `next` is the next digit to try in the following position. Once `next`
+    // passes 9, we backtrack.
+    let mut choices: Vec<u32> = Vec::with_capacity(9);
+    let mut next = if feasible { 1 } else { 10 };
+
+    iter::from_fn(move || loop {
+      if next > 9 {
+        next = choices.pop()? + 1;
+        continue;
+      }
+
+      let digit = next;
+      next += 1;
+      let prev = choices.last().copied().unwrap_or(0);
+      let sum = choices.iter().sum::<u32>() + digit;
+      let remaining = num_tiles - choices.len() as u32 - 1;
+
+      // Digits are chosen in increasing order, so skipping over a required
+      // digit, running out of digits, or overshooting `max` with the smallest
+      // possible remainder all rule out every larger digit in this position
+      // too.
+      if (prev + 1..digit).any(|skipped| required.contains(skipped))
+        || digit + remaining > 9
+        || sum + remaining * digit + remaining * (remaining + 1) / 2 > max
+      {
+        next = 10;
+        continue;
+      }
+
+      if forbidden.contains(digit)
+        || sum + remaining * (19 - remaining) / 2 < min
+        || required.iter().filter(|&d| d > digit).count() as u32 > remaining
+      {
+        continue;
+      }
+
+      if remaining == 0 {
+        if sum >= min {
+          return Some((
+            sum,
+            choices.iter().copied().chain(iter::once(digit)).collect(),
+          ));
+        }
+      } else {
+        choices.push(digit);
+        next = digit + 1;
+      }
+    })
+  }
+
+  /// Reference implementation of `all_combinations_for_range`, which filters
+  /// every `num_tiles`-subset of 1-9 by its sum. Yields the same combinations
+  /// in the same order.
+  #[cfg(test)]
+  pub fn brute_force_combinations_for_range(
+    (min, max): (u32, u32),
+    num_tiles: u32,
+  ) -> Vec<(u32, Vec<u32>)> {
+    if Self::attainable_sum_range(num_tiles).is_none() {
+      return Vec::new();
+    }
+    (0u32..(1 << 9))
+      .filter(|mask| mask.count_ones() == num_tiles)
+      .map(|mask| {
+        (1..=9)
+          .filter(|digit| mask & (1 << (digit - 1)) != 0)
+          .collect_vec()
+      })
+      .map(|digits| (digits.iter().sum(), digits))
+      .filter(|(total, _)| (min..=max).contains(total))
+      .sorted_by(|(_, digits1), (_, digits2)| digits1.cmp(digits2))
+      .collect()
+  }
+
+  /// Counts the sets of `num_tiles` distinct digits 1-9 whose sum lies in
+  /// `min..=max`, without enumerating them. Agrees with the number of items
+  /// yielded by `all_combinations_for_range`.
+  pub fn combination_count_for_range((min, max): (u32, u32), num_tiles: u32) -> u64 {
+    if Self::attainable_sum_range(num_tiles).is_none() {
+      return 0;
+    }
+    (min as usize..=(max as usize).min(45))
+      .map(|sum| SUBSET_SUM_COUNTS[num_tiles as usize][sum])
+      .sum()
+  }
+
+  /// The number of digit combinations this clue admits over a run of
+  /// `num_tiles`, given the letters already assigned in `partial`. This
+  /// doesn't account for the digits of the clue itself being distinct.
+  fn combination_count(&self, num_tiles: u32, partial: &LetterAssignment) -> u64 {
+    let (min, max) = self.sum_range();
+    match self.known_total(partial) {
+      Some(total) if (min..=max).contains(&total) => {
+        Self::combination_count_for_range((total, total), num_tiles)
+      }
+      Some(_) => 0,
+      None => Self::combination_count_for_range((min, max), num_tiles),
+    }
+  }
+
+  /// The value of this clue under `assignment`, if all of its letters have
+  /// been assigned.
+  pub fn known_total(&self, assignment: &LetterAssignment) -> Option<u32> {
+    match self {
+      TotalClue::OneDigit(letter) => assignment.letter_value(*letter),
+      TotalClue::TwoDigit { ones, tens } => assignment
+        .letter_value(*tens)
+        .zip(assignment.letter_value(*ones))
+        .map(|(tens, ones)| 10 * tens + ones),
+      TotalClue::Mixed { tens, ones } => tens
+        .known_value(assignment)
+        .zip(ones.known_value(assignment))
+        .map(|(tens, ones)| 10 * tens + ones),
+      TotalClue::Numeric(total) => Some(*total),
+    }
   }
 
   fn all_combinations(
     &self,
     num_tiles: u32,
-  ) -> impl Iterator<Item = (Vec<(DlxItem, u32)>, Vec<u32>)> {
+    partial: &LetterAssignment,
+    cache: &mut CombinationCache,
+  ) -> impl Iterator<Item = ClueCombination> {
     let (min, max) = self.sum_range();
+    let combinations = match self.known_total(partial) {
+      Some(total) if (min..=max).contains(&total) => cache.combinations_for_total(total, num_tiles),
+      Some(_) => Combinations::default(),
+      None => cache.combinations_for_range((min, max), num_tiles),
+    };
     let self_copy = self.clone();
-    Self::all_combinations_for_range((min, max), num_tiles).filter_map(
-      move |(total, combination)| match self_copy {
-        TotalClue::OneDigit(letter) => {
-          Some((vec![(DlxItem::Letter { letter }, total)], combination))
-        }
+    (0..combinations.len()).filter_map(move |idx| {
+      let (total, ref combination) = combinations[idx];
+      match self_copy {
+        TotalClue::OneDigit(letter) => Some((
+          vec![(DlxItem::Letter { letter }, total)],
+          combination.clone(),
+        )),
         TotalClue::TwoDigit { ones, tens } => {
-          if (ones == tens) == (total % 11 == 0) {
-            let ones_value = total % 10;
-            let tens_value = total / 10;
+          let ones_value = total % 10;
+          let tens_value = total / 10;
+          // The tens digit can't be a leading zero, and the two letters take
+          // the same value exactly when they are the same letter.
+          if tens_value != 0 && (ones == tens) == (ones_value == tens_value) {
             Some((
               vec![
                 (DlxItem::Letter { letter: ones }, ones_value),
                 (DlxItem::Letter { letter: tens }, tens_value),
               ],
-              combination,
+              combination.clone(),
             ))
           } else {
             None
           }
         }
-      },
-    )
+        TotalClue::Mixed { tens, ones } => {
+          // Only the letter is free, so the literal digit must match.
+          let letters = [(tens, total / 10), (ones, total % 10)]
+            .into_iter()
+            .map(|(digit, value)| match digit {
+              ClueDigit::Literal(literal) => (literal == value).then_some(None),
+              ClueDigit::Letter(letter) => Some(Some((DlxItem::Letter { letter }, value))),
+            })
+            .collect::<Option<Vec<_>>>()?;
+          (total / 10 != 0).then(|| (letters.into_iter().flatten().collect(), combination.clone()))
+        }
+        TotalClue::Numeric(_) => Some((vec![], combination.clone())),
+      }
+    })
+  }
+
+  /// Every combination of `num_tiles` distinct digits, with no letters
+  /// assigned, as if the run had no clue.
+  fn unconstrained_combinations(
+    num_tiles: u32,
+    cache: &mut CombinationCache,
+  ) -> impl Iterator<Item = ClueCombination> {
+    let combinations = cache.combinations_for_range((0, 45), num_tiles);
+    (0..combinations.len()).map(move |idx| (vec![], combinations[idx].1.clone()))
+  }
+
+  fn uses_letters(&self) -> bool {
+    !matches!(self, TotalClue::Numeric(_))
+  }
+}
+
+/// Hit/miss counters for a `CombinationCache`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+  /// Number of lookups answered from the cache.
+  pub hits: u64,
+  /// Number of lookups which had to generate the combinations.
+  pub misses: u64,
+}
+
+/// A shared list of `(total, digits)` combinations for one sum range and run
+/// length.
+pub type Combinations = Arc<Vec<(u32, Vec<u32>)>>;
+
+/// Memoizes the output of `TotalClue::all_combinations_for_range`, keyed by
+/// `(min, max, num_tiles)`. Many runs across a puzzle (and across puzzles)
+/// share the same sum range and length, so a single cache can be reused for
+/// an entire batch of puzzles.
+#[derive(Default)]
+pub struct CombinationCache {
+  combinations: HashMap<(u32, u32, u32), Combinations>,
+  stats: CacheStats,
+}
+
+impl CombinationCache {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Returns all combinations of `num_tiles` distinct digits summing to a
+  /// value in `(min, max)`, generating them only if this key hasn't been seen
+  /// before.
+  pub fn combinations_for_range(&mut self, (min, max): (u32, u32), num_tiles: u32) -> Combinations {
+    match self.combinations.get(&(min, max, num_tiles)) {
+      Some(combinations) => {
+        self.stats.hits += 1;
+        combinations.clone()
+      }
+      None => {
+        self.stats.misses += 1;
+        let combinations =
+          Arc::new(TotalClue::all_combinations_for_range((min, max), num_tiles).collect_vec());
+        self
+          .combinations
+          .insert((min, max, num_tiles), combinations.clone());
+        combinations
+      }
+    }
+  }
+
+  /// Returns all combinations of `num_tiles` distinct digits summing to exactly
+  /// `total`, sharing cache entries with the range `(total, total)`.
+  pub fn combinations_for_total(&mut self, total: u32, num_tiles: u32) -> Combinations {
+    self.combinations_for_range((total, total), num_tiles)
+  }
+
+  /// The number of distinct `(min, max, num_tiles)` keys cached.
+  pub fn len(&self) -> usize {
+    self.combinations.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.combinations.is_empty()
+  }
+
+  pub fn stats(&self) -> CacheStats {
+    self.stats
+  }
+}
+
+impl FromStr for TotalClue {
+  type Err = ClueParseError;
+
+  fn from_str(clue: &str) -> Result<Self, Self::Err> {
+    TotalClue::parse(clue)
   }
 }
 
@@ -172,52 +678,88 @@ impl Display for TotalClue {
     match self {
       TotalClue::OneDigit(digit) => write!(f, "{digit}"),
       TotalClue::TwoDigit { ones, tens } => write!(f, "{tens}{ones}"),
+      TotalClue::Mixed { tens, ones } => write!(f, "{tens}{ones}"),
+      TotalClue::Numeric(total) => write!(f, "{total}"),
     }
   }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct TotalTile {
   horizontal: Option<TotalClue>,
   vertical: Option<TotalClue>,
 }
 
 impl TotalTile {
-  fn map_horizontal<F, V>(&self, callback: F) -> Option<V>
-  where
-    F: FnOnce(TotalClue) -> V,
-  {
-    if let TotalTile {
-      horizontal: Some(horizontal),
-      vertical: _,
-    } = self
-    {
-      Some(callback(horizontal.clone()))
-    } else {
-      None
+  pub fn new(horizontal: Option<TotalClue>, vertical: Option<TotalClue>) -> Self {
+    Self {
+      horizontal,
+      vertical,
     }
   }
 
-  fn map_vertical<F, V>(&self, callback: F) -> Option<V>
-  where
-    F: FnOnce(TotalClue) -> V,
-  {
-    if let TotalTile {
-      horizontal: _,
-      vertical: Some(vertical),
-    } = self
-    {
-      Some(callback(vertical.clone()))
-    } else {
-      None
+  /// This tile with `clue` for the run to its right.
+  pub fn with_horizontal(self, clue: TotalClue) -> Self {
+    Self {
+      horizontal: Some(clue),
+      ..self
+    }
+  }
+
+  /// This tile with `clue` for the run below it.
+  pub fn with_vertical(self, clue: TotalClue) -> Self {
+    Self {
+      vertical: Some(clue),
+      ..self
+    }
+  }
+
+  /// The clue for the run to the right of this tile.
+  pub fn horizontal(&self) -> Option<&TotalClue> {
+    self.horizontal.as_ref()
+  }
+
+  /// The clue for the run below this tile.
+  pub fn vertical(&self) -> Option<&TotalClue> {
+    self.vertical.as_ref()
+  }
+
+  pub fn clue(&self, direction: Direction) -> Option<&TotalClue> {
+    match direction {
+      Direction::Horizontal => self.horizontal(),
+      Direction::Vertical => self.vertical(),
     }
   }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum UnknownTile {
   Blank,
-  Prefilled { hint: char },
+  Prefilled {
+    hint: char,
+  },
+  /// A tile whose digit is given outright.
+  Given {
+    digit: u32,
+  },
+}
+
+impl UnknownTile {
+  /// The letter prefilled in this tile, if any.
+  pub fn hint(&self) -> Option<char> {
+    match self {
+      UnknownTile::Prefilled { hint } => Some(*hint),
+      _ => None,
+    }
+  }
+
+  /// The digit given in this tile, if any.
+  pub fn given_digit(&self) -> Option<u32> {
+    match self {
+      UnknownTile::Given { digit } => Some(*digit),
+      _ => None,
+    }
+  }
 }
 
 impl fmt::Display for UnknownTile {
@@ -225,11 +767,12 @@ impl fmt::Display for UnknownTile {
     match self {
       UnknownTile::Blank => "_".fmt(f),
       UnknownTile::Prefilled { hint } => hint.fmt(f),
+      UnknownTile::Given { digit } => digit.fmt(f),
     }
   }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum Tile {
   Empty,
   Unknown(UnknownTile),
@@ -237,14 +780,77 @@ pub enum Tile {
 }
 
 impl Tile {
-  fn map_total<F, V>(&self, callback: F) -> Option<V>
-  where
-    F: FnOnce(TotalTile) -> V,
-  {
-    if let Tile::Total(total) = self {
-      Some(callback(total.clone()))
-    } else {
-      None
+  /// Parses a tile from its field `field` in the p424 line format, ignoring
+  /// case. Clue tiles are parenthesized lists of rules.
+  fn from_node(node: &Node, field: &str) -> Result<Tile, String> {
+    let invalid = || format!("Invalid tile \"{field}\"");
+    if let Some(rules) = node.children() {
+      return rules
+        .iter()
+        .try_fold(TotalTile::new(None, None), |total_tile, rule| {
+          let rule = rule.as_leaf().ok_or_else(invalid)?.to_ascii_uppercase();
+          let parse = |clue: &str| {
+            TotalClue::parse(clue.trim()).map_err(|err| format!("{err} in tile \"{field}\""))
+          };
+          if let Some(vert) = rule.strip_prefix('V') {
+            Ok(total_tile.with_vertical(parse(vert)?))
+          } else if let Some(hori) = rule.strip_prefix('H') {
+            Ok(total_tile.with_horizontal(parse(hori)?))
+          } else {
+            Err(invalid())
+          }
+        })
+        .map(Tile::Total);
+    }
+
+    let token = node.as_leaf().ok_or_else(invalid)?.to_ascii_uppercase();
+    match token.as_str() {
+      "X" => return Ok(Tile::Empty),
+      "O" => return Ok(Tile::Unknown(UnknownTile::Blank)),
+      _ => {}
+    }
+    let mut chars = token.chars();
+    match (chars.next(), chars.next()) {
+      (Some(digit @ '1'..='9'), None) => Ok(Tile::Unknown(UnknownTile::Given {
+        digit: digit.to_digit(10).unwrap(),
+      })),
+      (Some(hint @ 'A'..='J'), None) => Ok(Tile::Unknown(UnknownTile::Prefilled { hint })),
+      _ => Err(invalid()),
+    }
+  }
+
+  /// The token for this tile in the p424 line format.
+  fn to_token(&self) -> String {
+    match self {
+      Tile::Empty => "X".to_string(),
+      Tile::Unknown(UnknownTile::Blank) => "O".to_string(),
+      Tile::Unknown(UnknownTile::Prefilled { hint }) => hint.to_string(),
+      Tile::Unknown(UnknownTile::Given { digit }) => digit.to_string(),
+      Tile::Total(TotalTile {
+        horizontal,
+        vertical,
+      }) => {
+        let rules = horizontal
+          .iter()
+          .map(|clue| format!("h{clue}"))
+          .chain(vertical.iter().map(|clue| format!("v{clue}")))
+          .join(",");
+        format!("({rules})")
+      }
+    }
+  }
+
+  pub fn as_unknown(&self) -> Option<&UnknownTile> {
+    match self {
+      Tile::Unknown(unknown) => Some(unknown),
+      _ => None,
+    }
+  }
+
+  pub fn as_total(&self) -> Option<&TotalTile> {
+    match self {
+      Tile::Total(total) => Some(total),
+      _ => None,
     }
   }
 }
@@ -266,7 +872,7 @@ impl fmt::Display for Tile {
           Some(x) => x.to_string(),
           None => "".to_string(),
         };
-        [vertical_str, horizontal_str].join(",").fmt(f)
+        format!("{vertical_str}\\{horizontal_str}").fmt(f)
       }
     }
   }
@@ -280,585 +886,5472 @@ enum DlxItem {
   LetterValue { value: u32 },
 }
 
-pub struct LetterAssignment {
-  letters: [u32; 10],
-}
-
-impl LetterAssignment {
-  fn new() -> Self {
-    Self { letters: [10; 10] }
-  }
+/// Runs admitting more digit combinations than this only generate subsets for
+/// the combinations which survive propagation.
+const LOOSE_RUN_COMBINATIONS: u64 = 4;
 
-  fn letter_idx(letter: char) -> usize {
-    debug_assert!(('A'..='J').contains(&letter));
-    letter as usize - 'A' as usize
-  }
+/// The most letters a puzzle may use, one per digit.
+pub const MAX_ALPHABET_SIZE: usize = 10;
 
-  pub fn letter_value(&self, letter: char) -> u32 {
-    self.letters[Self::letter_idx(letter)]
-  }
+/// The most letters `Kakuro::solve_brute_force` will try the values of.
+pub const BRUTE_FORCE_MAX_LETTERS: usize = 7;
 
-  fn set_value(&mut self, letter: char, value: u32) {
-    debug_assert_eq!(self.letters[Self::letter_idx(letter)], 10);
-    self.letters[Self::letter_idx(letter)] = value;
-  }
+/// The value stored for letters which haven't been assigned yet.
+const UNASSIGNED: u32 = 10;
 
-  fn with_value(mut self, letter: char, value: u32) -> Self {
-    self.set_value(letter, value);
-    self
-  }
+/// `SUBSET_SUM_COUNTS[k][sum]` is the number of sets of `k` distinct digits
+/// 1-9 which add up to `sum`, so counting combinations is a table lookup.
+const SUBSET_SUM_COUNTS: [[u64; 46]; 10] = subset_sum_counts();
 
-  fn fill_remaining(&mut self) {
-    debug_assert!(self.letters.iter().filter(|&&count| count == 10).count() <= 1);
-    if let Some((idx, _)) = self
-      .letters
-      .iter()
-      .enumerate()
-      .find(|(_, &value)| value == 10)
-    {
-      self.letters[idx] = 55 - self.letters.iter().sum::<u32>();
+const fn subset_sum_counts() -> [[u64; 46]; 10] {
+  // Adds the digits one at a time, going down in `k` and `sum` so each digit
+  // is used at most once.
+  let mut counts = [[0u64; 46]; 10];
+  counts[0][0] = 1;
+  let mut digit = 1;
+  while digit <= 9 {
+    let mut k = 9;
+    while k >= 1 {
+      let mut sum = 45;
+      while sum >= digit {
+        counts[k][sum] += counts[k - 1][sum - digit];
+        sum -= 1;
+      }
+      k -= 1;
     }
+    digit += 1;
   }
+  counts
+}
 
-  fn with_filled_remaining(mut self) -> Self {
-    self.fill_remaining();
-    self
-  }
+/// Returned when reading the value of an assignment which leaves a letter
+/// unassigned.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct UnassignedLetter(pub char);
 
-  pub fn int_value(&self) -> u64 {
-    debug_assert!(self.letters.iter().all(|value| (0..=9).contains(value)));
-    self
-      .letters
-      .iter()
-      .fold(0, |acc, &value| 10 * acc + value as u64)
+impl Display for UnassignedLetter {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "Letter {} has no value", self.0)
   }
 }
 
-impl Display for LetterAssignment {
+impl Error for UnassignedLetter {}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LetterAssignmentError {
+  /// The letter is outside of the assignment's alphabet, or the value isn't a
+  /// digit.
+  OutOfRange { letter: char, value: u32 },
+  /// Two pairs give one letter different values, or two letters the same
+  /// value.
+  Conflict {
+    first: (char, u32),
+    second: (char, u32),
+  },
+}
+
+impl Display for LetterAssignmentError {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-    ('A'..='J').try_fold((), |_, letter| write!(f, "{letter} "))?;
-    writeln!(f)?;
-    ('A'..='J').try_fold((), |_, letter| write!(f, "{} ", self.letter_value(letter)))
+    match self {
+      LetterAssignmentError::OutOfRange { letter, value } => {
+        write!(f, "Can't assign {letter}={value}")
+      }
+      LetterAssignmentError::Conflict {
+        first: (first_letter, first_value),
+        second: (second_letter, second_value),
+      } => write!(
+        f,
+        "{first_letter}={first_value} conflicts with {second_letter}={second_value}"
+      ),
+    }
   }
 }
 
-pub struct Kakuro {
-  n: usize,
-  tiles: Vec<Tile>,
+impl Error for LetterAssignmentError {}
+
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct LetterAssignment {
+  letters: Vec<u32>,
 }
 
-impl Kakuro {
-  pub fn from_file(path: &str) -> io::Result<Vec<Kakuro>> {
-    let f = File::open(path)?;
-    let f = BufReader::new(f);
+impl LetterAssignment {
+  /// Constructs an assignment for the letters A-J with no letters assigned
+  /// yet.
+  pub fn new() -> Self {
+    Self::for_alphabet(MAX_ALPHABET_SIZE)
+  }
 
-    let mut grids: Vec<Kakuro> = Vec::new();
-    let mut sizes: Vec<usize> = Vec::new();
-    for line in f.lines() {
-      let line_str = line?;
-      let parts: Vec<&str> = line_str.split_paren().collect();
-      let n: usize = parts[0].parse::<usize>().unwrap();
-      sizes.push(n);
-      let mut grid = Vec::new();
-      for i in 0..n {
-        for j in 0..n {
-          let idx: usize = i * n + j + 1;
-          let part: &str = parts[idx];
-          if part == "X" {
-            grid.push(Tile::Empty);
-          } else if part == "O" {
-            grid.push(Tile::Unknown(UnknownTile::Blank));
-          } else if ("A"..="J").contains(&part) {
-            grid.push(Tile::Unknown(UnknownTile::Prefilled {
-              hint: part.chars().next().unwrap(),
-            }));
-          } else if let Some(line) = part
-            .strip_prefix('(')
-            .and_then(|line| line.strip_suffix(')'))
-          {
-            let total_tile = line.split(',').fold(
-              TotalTile {
-                vertical: None,
-                horizontal: None,
-              },
-              |total_tile, rule| {
-                if let Some(vert) = rule.strip_prefix('v') {
-                  TotalTile {
-                    vertical: Some(TotalClue::new(vert)),
-                    ..total_tile
-                  }
-                } else if let Some(hori) = rule.strip_prefix('h') {
-                  TotalTile {
-                    horizontal: Some(TotalClue::new(hori)),
-                    ..total_tile
-                  }
-                } else {
-                  total_tile
-                }
-              },
-            );
-            grid.push(Tile::Total(total_tile));
-          }
-        }
-      }
-      grids.push(Kakuro { tiles: grid, n });
+  /// Constructs an assignment for the first `size` letters of the alphabet,
+  /// with no letters assigned yet.
+  pub fn for_alphabet(size: usize) -> Self {
+    debug_assert!(size <= MAX_ALPHABET_SIZE);
+    Self {
+      letters: vec![UNASSIGNED; size],
     }
-    Ok(grids)
   }
 
-  fn get_idx(&self, row: usize, col: usize) -> usize {
-    row * self.n + col
+  /// Constructs an assignment for the letters A-J from `(letter, value)`
+  /// pairs. Pairs may be repeated, but may not give a letter two values or two
+  /// letters the same value.
+  pub fn from_pairs(pairs: &[(char, u32)]) -> Result<Self, LetterAssignmentError> {
+    Self::from_pairs_for_alphabet(MAX_ALPHABET_SIZE, pairs)
   }
 
-  fn take_unknowns(
-    &self,
-    row: usize,
-    col: usize,
-    vertical: bool,
-  ) -> impl Iterator<Item = DlxItem> + '_ {
-    let idx = if vertical { row } else { col };
-    let step = if vertical { self.n } else { 1 };
-    (1..(self.n - idx)).map_while(move |idx| {
-      let idx = self.get_idx(row, col) + idx * step;
-      match self.tiles.get(idx) {
-        Some(Tile::Unknown(UnknownTile::Blank)) => Some(DlxItem::Tile { idx: idx as u32 }),
-        Some(Tile::Unknown(UnknownTile::Prefilled { hint })) => {
-          Some(DlxItem::Letter { letter: *hint })
-        }
-        _ => None,
+  /// Like `from_pairs`, for an alphabet of the first `size` letters.
+  pub fn from_pairs_for_alphabet(
+    size: usize,
+    pairs: &[(char, u32)],
+  ) -> Result<Self, LetterAssignmentError> {
+    let mut assignment = Self::for_alphabet(size);
+    for (idx, &(letter, value)) in pairs.iter().enumerate() {
+      if !assignment.alphabet().contains(&letter) || value > 9 {
+        return Err(LetterAssignmentError::OutOfRange { letter, value });
+      }
+      if let Some(&first) = pairs[..idx]
+        .iter()
+        .find(|&&(prev_letter, prev_value)| (prev_letter == letter) != (prev_value == value))
+      {
+        return Err(LetterAssignmentError::Conflict {
+          first,
+          second: (letter, value),
+        });
+      }
+      if assignment.letter_value(letter).is_none() {
+        assignment.set_value(letter, value);
+      }
+    }
+    Ok(assignment)
+  }
+
+  pub fn alphabet_size(&self) -> usize {
+    self.letters.len()
+  }
+
+  /// Iterates over the assigned letters and their values, in alphabetical
+  /// order.
+  pub fn iter(&self) -> impl Iterator<Item = (char, u32)> + '_ {
+    self
+      .alphabet()
+      .filter_map(|letter| self.letter_value(letter).map(|value| (letter, value)))
+  }
+
+  pub fn to_map(&self) -> HashMap<char, u32> {
+    self.iter().collect()
+  }
+
+  /// The letters this assignment covers, in order.
+  pub fn alphabet(&self) -> impl Iterator<Item = char> {
+    ('A'..='J').take(self.alphabet_size())
+  }
+
+  /// The position of `letter` among the letters A-J, or None if it isn't
+  /// one of them. Every conversion of a letter to an index goes through this.
+  fn letter_idx(letter: char) -> Option<usize> {
+    ('A'..='J')
+      .contains(&letter)
+      .then(|| letter as usize - 'A' as usize)
+  }
+
+  /// The value assigned to `letter`, or None if it's unassigned or outside of
+  /// this assignment's alphabet.
+  pub fn letter_value(&self, letter: char) -> Option<u32> {
+    Self::letter_idx(letter)
+      .and_then(|idx| self.letters.get(idx))
+      .copied()
+      .filter(|&value| value != UNASSIGNED)
+  }
+
+  #[deprecated(note = "use `letter_value`, which now returns None for unassigned letters")]
+  pub fn known_value(&self, letter: char) -> Option<u32> {
+    self.letter_value(letter)
+  }
+
+  /// Panics if `letter` is outside of this assignment's alphabet.
+  pub fn set_value(&mut self, letter: char, value: u32) {
+    let idx = Self::letter_idx(letter)
+      .filter(|&idx| idx < self.letters.len())
+      .unwrap_or_else(|| panic!("Letter {letter} is outside of the alphabet"));
+    debug_assert_eq!(self.letters[idx], UNASSIGNED);
+    self.letters[idx] = value;
+  }
+
+  pub fn with_value(mut self, letter: char, value: u32) -> Self {
+    self.set_value(letter, value);
+    self
+  }
+
+  /// True if `letter` could take `value` without contradicting this
+  /// assignment, i.e. `letter` is either unassigned or already has `value`,
+  /// and no other letter has been assigned `value`.
+  fn admits(&self, letter: char, value: u32) -> bool {
+    let Some(letter_idx) = Self::letter_idx(letter) else {
+      return false;
+    };
+    self.letters.iter().enumerate().all(|(idx, &assigned)| {
+      if idx == letter_idx {
+        assigned == UNASSIGNED || assigned == value
+      } else {
+        assigned != value
       }
     })
   }
 
-  fn enumerate_lines(
-    &self,
-  ) -> impl Iterator<Item = ((DlxItem, TotalClue), impl Iterator<Item = DlxItem> + '_)> + '_ {
-    (0..self.n).flat_map(move |row| {
-      (0..self.n)
-        .filter_map(move |col| {
-          self
-            .tiles
-            .get(row * self.n + col)
-            .unwrap()
-            .map_total(|total| {
-              total
-                .map_horizontal(|horizontal_clue| {
-                  iter::once(Some((
-                    (
-                      DlxItem::Sum {
-                        idx: self.get_idx(row, col) as u32,
-                        vertical: false,
-                      },
-                      horizontal_clue,
-                    ),
-                    self.take_unknowns(row, col, false),
-                  )))
-                })
-                .unwrap_or(iter::once(None))
-                .flatten()
-                .chain(
-                  total
-                    .map_vertical(|vertical_clue| {
-                      iter::once(Some((
-                        (
-                          DlxItem::Sum {
-                            idx: self.get_idx(row, col) as u32,
-                            vertical: true,
-                          },
-                          vertical_clue,
-                        ),
-                        self.take_unknowns(row, col, true),
-                      )))
-                    })
-                    .unwrap_or(iter::once(None))
-                    .flatten(),
-                )
-            })
-        })
-        .flatten()
+  /// Every way to give the unassigned letters distinct digits not used by the
+  /// assigned letters, in increasing order, generated as the iterator is
+  /// advanced. Yields just this assignment if every letter is assigned.
+  pub fn completions(&self) -> impl Iterator<Item = LetterAssignment> {
+    let unassigned = self
+      .alphabet()
+      .filter(|&letter| self.letter_value(letter).is_none())
+      .collect_vec();
+    let used: DigitSet = self.iter().map(|(_, value)| value).collect();
+    let letters = self.clone();
+    (0..10)
+      .filter(move |&digit| !used.contains(digit))
+      .permutations(unassigned.len())
+      .map(move |values| {
+        unassigned
+          .iter()
+          .zip(values)
+          .fold(letters.clone(), |letters, (&letter, value)| {
+            letters.with_value(letter, value)
+          })
+      })
+  }
+
+  /// The number formed by concatenating the values of each letter, with A as
+  /// the most significant digit. Fails if any letter is unassigned.
+  pub fn try_int_value(&self) -> Result<u64, UnassignedLetter> {
+    self.alphabet().try_fold(0, |acc, letter| {
+      self
+        .letter_value(letter)
+        .map(|value| 10 * acc + value as u64)
+        .ok_or(UnassignedLetter(letter))
     })
   }
 
-  fn all_items(&self) -> impl Iterator<Item = (DlxItem, HeaderType)> + '_ {
+  /// Like `try_int_value`, but panics if any letter is unassigned.
+  pub fn int_value(&self) -> u64 {
     self
-      .tiles
-      .iter()
-      .enumerate()
-      .flat_map(|(idx, tile)| {
-        let idx = idx as u32;
-        match tile {
-          Tile::Total(TotalTile {
-            horizontal,
-            vertical,
-          }) => [
-            horizontal.as_ref().map(|_| {
-              (
-                DlxItem::Sum {
-                  idx,
-                  vertical: false,
-                },
-                HeaderType::Primary,
-              )
-            }),
-            vertical.as_ref().map(|_| {
-              (
-                DlxItem::Sum {
-                  idx,
-                  vertical: true,
-                },
-                HeaderType::Primary,
-              )
-            }),
-          ],
-          Tile::Unknown(UnknownTile::Blank) => {
-            [Some((DlxItem::Tile { idx }, HeaderType::Secondary)), None]
-          }
-          _ => [None, None],
-        }
-        .into_iter()
-        .flatten()
-      })
-      .chain(('A'..='J').enumerate().flat_map(|(value, letter)| {
-        [
-          (DlxItem::Letter { letter }, HeaderType::Secondary),
-          (
-            DlxItem::LetterValue {
-              value: value as u32,
-            },
-            HeaderType::Secondary,
-          ),
-        ]
-        .into_iter()
-      }))
+      .try_int_value()
+      .unwrap_or_else(|err| panic!("Can't take the int value of {self}: {err}"))
   }
+}
 
-  /// Constructs Dlx constraints from a list of assignments to letters or
-  /// tiles. Letter assignments may be repeated, and they will be deduplicated.
-  /// If any color assignments conflict among letters (i.e. A=1 and A=2, or A=1
-  /// and B=1), then None is returned.
-  fn construct_dlx(
-    clue_item: DlxItem,
-    items: Vec<(DlxItem, u32)>,
-  ) -> Option<impl Iterator<Item = Constraint<DlxItem>>> {
-    // println!("Checking: {clue_item:?}: {items:?}");
-    let (letters, values) = match items.iter().try_fold(
-      ([(); 10].map(|_| None), [(); 10].map(|_| None)),
-      |(mut letters_array, mut values_array), (item, value)| {
-        let value = *value;
-        match item {
-          DlxItem::Letter { letter } => {
-            if letters_array[*letter as usize - 'A' as usize]
-              .is_some_and(|prev_value| prev_value != value)
-              || values_array[value as usize].is_some_and(|prev_letter| prev_letter != *letter)
-            {
-              ControlFlow::Break(())
-            } else {
-              letters_array[*letter as usize - 'A' as usize] = Some(value);
-              values_array[value as usize] = Some(*letter);
-              ControlFlow::Continue((letters_array, values_array))
-            }
-          }
-          _ => ControlFlow::Continue((letters_array, values_array)),
-        }
-      },
-    ) {
-      ControlFlow::Break(_) => {
-        // println!("Filtered!");
-        return None;
+impl Default for LetterAssignment {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Display for LetterAssignment {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    self
+      .alphabet()
+      .try_fold((), |_, letter| write!(f, "{letter} "))?;
+    writeln!(f)?;
+    self.alphabet().try_fold((), |_, letter| {
+      write!(f, "{} ", self.letter_value(letter).unwrap_or(UNASSIGNED))
+    })
+  }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Direction {
+  Horizontal,
+  Vertical,
+}
+
+impl Display for Direction {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Direction::Horizontal => write!(f, "horizontal"),
+      Direction::Vertical => write!(f, "vertical"),
+    }
+  }
+}
+
+/// A violation of the structural rules of a kakuro grid, located by the
+/// coordinates of the offending tile.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum KakuroStructureError {
+  /// The clue at `(row, col)` covers more than 9 tiles.
+  RunTooLong {
+    row: usize,
+    col: usize,
+    direction: Direction,
+    len: usize,
+  },
+  /// The clue at `(row, col)` is followed by a tile which isn't an unknown.
+  EmptyRun {
+    row: usize,
+    col: usize,
+    direction: Direction,
+  },
+  /// The clue at `(row, col)` is on the edge of the grid, so its run would
+  /// start outside of it.
+  RunExitsGrid {
+    row: usize,
+    col: usize,
+    direction: Direction,
+  },
+  /// The unknown tile at `(row, col)` isn't part of any clue's run.
+  UncoveredTile { row: usize, col: usize },
+}
+
+impl Display for KakuroStructureError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      KakuroStructureError::RunTooLong {
+        row,
+        col,
+        direction,
+        len,
+      } => write!(
+        f,
+        "{direction} run of clue at ({row}, {col}) has {len} tiles, more than 9"
+      ),
+      KakuroStructureError::EmptyRun {
+        row,
+        col,
+        direction,
+      } => write!(f, "{direction} clue at ({row}, {col}) has no tiles"),
+      KakuroStructureError::RunExitsGrid {
+        row,
+        col,
+        direction,
+      } => write!(
+        f,
+        "{direction} clue at ({row}, {col}) points outside the grid"
+      ),
+      KakuroStructureError::UncoveredTile { row, col } => {
+        write!(f, "tile at ({row}, {col}) is not part of any run")
       }
-      ControlFlow::Continue(arrays) => arrays,
-    };
-    // println!("Kept");
+    }
+  }
+}
 
-    Some(
-      iter::once(clue_item.into())
-        .chain(
-          items
-            .into_iter()
-            .filter(|(item, _)| matches!(item, DlxItem::Tile { .. }))
-            .map(|(item, color)| ColorItem::new(item, color).into()),
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum KakuroError {
+  /// The puzzle grid breaks the structural rules of kakuro.
+  InvalidStructure(Vec<KakuroStructureError>),
+  /// A hint names a letter outside of the puzzle's alphabet, or a value which
+  /// isn't a digit.
+  InvalidHint { letter: char, value: u32 },
+  /// Two hints give one letter different values, or two letters the same
+  /// value.
+  ConflictingHints {
+    first: (char, u32),
+    second: (char, u32),
+  },
+  /// The solver gave up before finishing its search.
+  TimedOut(SolveTimeout),
+  /// The puzzle uses more letters than the brute force solver allows.
+  TooManyLetters { used: usize, limit: usize },
+  /// There's no clue at `(row, col)` for a run in `direction` to change.
+  NoClue {
+    row: usize,
+    col: usize,
+    direction: Direction,
+  },
+  /// A clue uses a letter outside of the puzzle's alphabet of `alphabet`
+  /// letters.
+  LetterOutsideAlphabet { letter: char, alphabet: usize },
+  /// The DLX model of the puzzle couldn't be updated after an edit.
+  Model(DlxError),
+  /// An alphabet size outside of 1 to `MAX_ALPHABET_SIZE`.
+  AlphabetSize(usize),
+}
+
+impl Display for KakuroError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      KakuroError::InvalidStructure(errors) => {
+        write!(f, "Invalid kakuro: {}", errors.iter().join("; "))
+      }
+      KakuroError::InvalidHint { letter, value } => {
+        write!(f, "Invalid hint {letter}={value}")
+      }
+      KakuroError::ConflictingHints {
+        first: (first_letter, first_value),
+        second: (second_letter, second_value),
+      } => write!(
+        f,
+        "Hint {first_letter}={first_value} conflicts with {second_letter}={second_value}"
+      ),
+      KakuroError::TimedOut(timeout) => write!(f, "{timeout}"),
+      KakuroError::TooManyLetters { used, limit } => write!(
+        f,
+        "The puzzle uses {used} letters, more than the {limit} allowed"
+      ),
+      KakuroError::NoClue {
+        row,
+        col,
+        direction,
+      } => write!(f, "No {direction} clue at ({row}, {col})"),
+      KakuroError::LetterOutsideAlphabet { letter, alphabet } => write!(
+        f,
+        "Letter {letter} is outside of the puzzle's {alphabet} letters"
+      ),
+      KakuroError::Model(err) => write!(f, "Couldn't update the model: {err}"),
+      KakuroError::AlphabetSize(alphabet) => write!(
+        f,
+        "Alphabet size must be between 1 and {MAX_ALPHABET_SIZE}, got {alphabet}"
+      ),
+    }
+  }
+}
+
+impl Error for KakuroError {}
+
+/// Why a `SolvedKakuro` isn't a solution to a puzzle, as found by
+/// `Kakuro::verify`, located by the coordinates of the offending tile.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SolutionError {
+  /// The solution's grid isn't the same size as the puzzle's.
+  WrongSize,
+  /// The solution gives two letters the same value.
+  SharedValue {
+    first: char,
+    second: char,
+    value: u32,
+  },
+  /// The unknown tile at `(row, col)` has no digit.
+  MissingDigit { row: usize, col: usize },
+  /// The tile at `(row, col)` has a digit, but isn't an unknown.
+  UnexpectedDigit { row: usize, col: usize },
+  /// The digit at `(row, col)` isn't 1-9, or isn't the digit given or
+  /// prefilled there.
+  WrongDigit { row: usize, col: usize, digit: u32 },
+  /// The run of the clue at `(row, col)` uses `digit` more than once.
+  RepeatedDigit {
+    row: usize,
+    col: usize,
+    direction: Direction,
+    digit: u32,
+  },
+  /// The run of the clue at `(row, col)` adds up to `total`, which isn't the
+  /// value of its clue.
+  WrongTotal {
+    row: usize,
+    col: usize,
+    direction: Direction,
+    total: u32,
+  },
+}
+
+impl Display for SolutionError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      SolutionError::WrongSize => write!(f, "solution is not the size of the grid"),
+      SolutionError::SharedValue {
+        first,
+        second,
+        value,
+      } => write!(f, "letters {first} and {second} are both {value}"),
+      SolutionError::MissingDigit { row, col } => {
+        write!(f, "tile at ({row}, {col}) has no digit")
+      }
+      SolutionError::UnexpectedDigit { row, col } => {
+        write!(
+          f,
+          "tile at ({row}, {col}) is not an unknown, but has a digit"
         )
-        .chain(letters.into_iter().enumerate().filter_map(|(idx, value)| {
-          value.map(|value| {
-            ColorItem::new(
-              DlxItem::Letter {
-                letter: (idx as u32 + 'A' as u32) as u8 as char,
-              },
-              value,
-            )
-            .into()
-          })
-        }))
-        .chain(values.into_iter().enumerate().filter_map(|(idx, letter)| {
-          letter.map(|letter| {
-            ColorItem::new(
-              DlxItem::LetterValue { value: idx as u32 },
-              letter as u32 - 'A' as u32,
+      }
+      SolutionError::WrongDigit { row, col, digit } => {
+        write!(f, "tile at ({row}, {col}) can't be {digit}")
+      }
+      SolutionError::RepeatedDigit {
+        row,
+        col,
+        direction,
+        digit,
+      } => write!(
+        f,
+        "{direction} run of clue at ({row}, {col}) repeats {digit}"
+      ),
+      SolutionError::WrongTotal {
+        row,
+        col,
+        direction,
+        total,
+      } => write!(
+        f,
+        "{direction} run of clue at ({row}, {col}) adds up to {total}, not its clue"
+      ),
+    }
+  }
+}
+
+impl Error for SolutionError {}
+
+/// Reported by `solve_p424` after it solves each puzzle.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ProgressEvent {
+  /// The index of the puzzle just solved, counting from 0.
+  pub index: usize,
+  /// The time since solving started.
+  pub elapsed: Duration,
+  /// The sum of the values of every puzzle solved so far.
+  pub sum: u64,
+}
+
+/// Why `solve_p424` failed, along with the index of the puzzle at fault.
+#[derive(Debug)]
+pub enum P424Error {
+  Io(io::Error),
+  Parse {
+    index: usize,
+    message: String,
+  },
+  Solve {
+    index: usize,
+    error: KakuroError,
+  },
+  NoSolution {
+    index: usize,
+  },
+  Unassigned {
+    index: usize,
+    error: UnassignedLetter,
+  },
+}
+
+impl Display for P424Error {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      P424Error::Io(err) => write!(f, "{err}"),
+      P424Error::Parse { index, message } => write!(f, "Puzzle {index}: {message}"),
+      P424Error::Solve { index, error } => write!(f, "Puzzle {index}: {error}"),
+      P424Error::NoSolution { index } => write!(f, "Puzzle {index} has no solution"),
+      P424Error::Unassigned { index, error } => write!(f, "Puzzle {index}: {error}"),
+    }
+  }
+}
+
+impl Error for P424Error {}
+
+impl From<io::Error> for P424Error {
+  fn from(err: io::Error) -> Self {
+    P424Error::Io(err)
+  }
+}
+
+/// Solves Project Euler problem 424: streams the puzzles in the file at
+/// `path`, and sums the values of the first solution to each. `progress` is
+/// called after every puzzle.
+pub fn solve_p424(path: &str, mut progress: impl FnMut(ProgressEvent)) -> Result<u64, P424Error> {
+  let start = Instant::now();
+  let mut cache = CombinationCache::new();
+  let mut sum = 0;
+  sum_puzzles(path, |index, kakuro| {
+    let value = first_solution_value(index, &kakuro, &mut cache)?;
+    sum += value;
+    progress(ProgressEvent {
+      index,
+      elapsed: start.elapsed(),
+      sum,
+    });
+    Ok(value)
+  })
+}
+
+/// Streams the puzzles in the file at `path`, and sums the values `value`
+/// gives each of them along with their index.
+fn sum_puzzles(
+  path: &str,
+  mut value: impl FnMut(usize, Kakuro) -> Result<u64, P424Error>,
+) -> Result<u64, P424Error> {
+  let mut sum = 0;
+  let mut index = 0;
+  for line in BufReader::new(File::open(path)?).lines() {
+    let Some(kakuro) =
+      Kakuro::parse_line(&line?).map_err(|message| P424Error::Parse { index, message })?
+    else {
+      continue;
+    };
+    sum += value(index, kakuro)?;
+    index += 1;
+  }
+  Ok(sum)
+}
+
+/// The value of the first solution found to `kakuro`, the puzzle at `index`.
+fn first_solution_value(
+  index: usize,
+  kakuro: &Kakuro,
+  cache: &mut CombinationCache,
+) -> Result<u64, P424Error> {
+  kakuro
+    .solve_first_with_cache(cache)
+    .map_err(|error| P424Error::Solve { index, error })?
+    .ok_or(P424Error::NoSolution { index })?
+    .try_int_value()
+    .map_err(|error| P424Error::Unassigned { index, error })
+}
+
+/// Solves a file of puzzles like `solve_p424`, but appends each puzzle's value
+/// to a results file as `index,value` lines as soon as it's found. Puzzles
+/// already in the results file are skipped, so an interrupted run can be
+/// resumed by running again.
+pub struct BatchRunner {
+  puzzles_path: String,
+  results_path: String,
+}
+
+impl BatchRunner {
+  pub fn new(puzzles_path: &str, results_path: &str) -> Self {
+    Self {
+      puzzles_path: puzzles_path.to_string(),
+      results_path: results_path.to_string(),
+    }
+  }
+
+  /// Solves every puzzle without a recorded result, and returns the sum over
+  /// all of them. `progress` is only called for the puzzles solved by this
+  /// run.
+  pub fn run(&self, mut progress: impl FnMut(ProgressEvent)) -> Result<u64, P424Error> {
+    let recorded = self.recorded()?;
+    let mut results = OpenOptions::new()
+      .create(true)
+      .append(true)
+      .open(&self.results_path)?;
+
+    let start = Instant::now();
+    let mut cache = CombinationCache::new();
+    let mut sum = 0;
+    sum_puzzles(&self.puzzles_path, |index, kakuro| {
+      let value = match recorded.get(&index) {
+        Some(&value) => value,
+        None => {
+          let value = first_solution_value(index, &kakuro, &mut cache)?;
+          writeln!(results, "{index},{value}")?;
+          results.flush()?;
+          progress(ProgressEvent {
+            index,
+            elapsed: start.elapsed(),
+            sum: sum + value,
+          });
+          value
+        }
+      };
+      sum += value;
+      Ok(value)
+    })
+  }
+
+  /// Reads the results recorded so far. A final line without a newline was
+  /// cut short by an interrupted write, so it's ignored and removed from the
+  /// file.
+  fn recorded(&self) -> io::Result<HashMap<usize, u64>> {
+    let contents = match std::fs::read_to_string(&self.results_path) {
+      Ok(contents) => contents,
+      Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+      Err(err) => return Err(err),
+    };
+    let complete = contents.rfind('\n').map_or(0, |idx| idx + 1);
+    if complete < contents.len() {
+      OpenOptions::new()
+        .write(true)
+        .open(&self.results_path)?
+        .set_len(complete as u64)?;
+    }
+
+    contents[..complete]
+      .lines()
+      .enumerate()
+      .map(|(line_idx, line)| {
+        line
+          .split_once(',')
+          .and_then(|(index, value)| Some((index.parse().ok()?, value.parse().ok()?)))
+          .ok_or_else(|| {
+            io::Error::new(
+              io::ErrorKind::InvalidData,
+              format!(
+                "{} line {}: Invalid result \"{line}\"",
+                self.results_path,
+                line_idx + 1
+              ),
             )
-            .into()
           })
-        })),
-    )
+      })
+      .collect()
+  }
+}
+
+/// A solve which was abandoned for taking longer than its time limit.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SolveTimeout {
+  pub limit: Duration,
+}
+
+impl Display for SolveTimeout {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "Gave up solving after {:?}", self.limit)
+  }
+}
+
+impl Error for SolveTimeout {}
+
+/// The result of solving one puzzle from a batch with `Kakuro::solve_all`.
+#[derive(Debug)]
+pub enum PuzzleOutcome {
+  Solved(Vec<LetterAssignment>),
+  TimedOut(SolveTimeout),
+  /// The puzzle's line couldn't be parsed.
+  ParseError(String),
+  /// The puzzle parsed, but isn't a well-formed kakuro.
+  InvalidStructure(Vec<KakuroStructureError>),
+  /// The solver failed for a reason other than running out of time.
+  SolveError(KakuroError),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Kakuro {
+  rows: usize,
+  cols: usize,
+  /// The number of letters, starting from A, which the puzzle's letters
+  /// stand for.
+  alphabet: usize,
+  tiles: Vec<Tile>,
+}
+
+impl Kakuro {
+  pub fn from_file(path: &str) -> io::Result<Vec<Kakuro>> {
+    let f = File::open(path)?;
+    Self::from_reader(BufReader::new(f))
+  }
+
+  /// Reads one puzzle per line. Tokens may be surrounded by whitespace and
+  /// written in either case, and blank lines and lines starting with `#` are
+  /// skipped.
+  pub fn from_reader<R: BufRead>(f: R) -> io::Result<Vec<Kakuro>> {
+    let mut grids: Vec<Kakuro> = Vec::new();
+    for (line_idx, line) in f.lines().enumerate() {
+      if let Some(grid) = Self::parse_line(&line?).map_err(|message| {
+        io::Error::new(
+          io::ErrorKind::InvalidData,
+          format!("Line {}: {message}", line_idx + 1),
+        )
+      })? {
+        grids.push(grid);
+      }
+    }
+    Ok(grids)
+  }
+
+  /// Parses a single line of a puzzle file, returning None for blank and
+  /// comment lines.
+  fn parse_line(line: &str) -> Result<Option<Kakuro>, String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      return Ok(None);
+    }
+    let parse_size = |part: &str| {
+      part
+        .parse::<usize>()
+        .map_err(|_| format!("Invalid grid size \"{part}\""))
+    };
+
+    // Unbalanced parentheses would garble the fields, so report them first.
+    line
+      .try_split_paren()
+      .try_for_each(|field| field.map(drop))
+      .map_err(|err| err.to_string())?;
+
+    // The header is either a single size for square grids, or `rows,cols`.
+    let (size, rest) = line.split_paren_once().unwrap_or((line, ""));
+    let rows = parse_size(size.trim())?;
+    let (cols, tiles) = if Some(rest.split_paren().count()) == rows.checked_mul(rows) {
+      (rows, rest)
+    } else {
+      // A second field which isn't a number means this was meant to be a square
+      // grid with the wrong number of tiles, which is reported below.
+      let (cols, tiles) = rest.split_paren_once().unwrap_or((rest, ""));
+      match cols.trim().parse::<usize>() {
+        Ok(cols) => (cols, tiles),
+        Err(_) => (rows, rest),
+      }
+    };
+    let num_tiles = tiles.split_paren().count();
+    let expected = rows
+      .checked_mul(cols)
+      .ok_or_else(|| format!("Grid size {rows}x{cols} is too large"))?;
+    if num_tiles != expected {
+      return Err(format!(
+        "Expected {expected} tiles for a {rows}x{cols} grid, found {num_tiles}"
+      ));
+    }
+
+    let grid = tiles
+      .split_paren()
+      .enumerate()
+      .map(|(idx, field)| {
+        let node = parse_node(field).map_err(|err| err.to_string())?;
+        Tile::from_node(&node, field.trim())
+          .map_err(|err| format!("Tile at ({}, {}): {err}", idx / cols, idx % cols))
+      })
+      .collect::<Result<_, _>>()?;
+    Ok(Some(Kakuro {
+      rows,
+      cols,
+      alphabet: MAX_ALPHABET_SIZE,
+      tiles: grid,
+    }))
+  }
+
+  /// Solves each puzzle read from `f`, giving up on any which take longer than
+  /// `limit`. Lines which fail to parse are reported in their puzzle's outcome
+  /// rather than stopping the batch, so only errors reading `f` are returned.
+  pub fn solve_all<R: BufRead>(f: R, limit: Duration) -> io::Result<Vec<PuzzleOutcome>> {
+    let mut cache = CombinationCache::new();
+    let mut outcomes = Vec::new();
+    for line in f.lines() {
+      let outcome = match Self::parse_line(&line?) {
+        Ok(None) => continue,
+        Ok(Some(kakuro)) => {
+          match kakuro.solve_full_within(&LetterAssignment::new(), &mut cache, Some(limit), true) {
+            Ok(solutions) => PuzzleOutcome::Solved(SolvedKakuro::distinct_letters(solutions)),
+            Err(KakuroError::TimedOut(timeout)) => PuzzleOutcome::TimedOut(timeout),
+            Err(KakuroError::InvalidStructure(errors)) => PuzzleOutcome::InvalidStructure(errors),
+            Err(err) => PuzzleOutcome::SolveError(err),
+          }
+        }
+        Err(message) => PuzzleOutcome::ParseError(message),
+      };
+      outcomes.push(outcome);
+    }
+    Ok(outcomes)
+  }
+
+  /// Serializes this puzzle as a single line in the format read by
+  /// `from_reader`, e.g. `3,X,(vA),(vI),(hBB),O,O,(hC),D,O`.
+  pub fn to_line(&self) -> String {
+    let header = if self.rows == self.cols {
+      self.rows.to_string()
+    } else {
+      format!("{},{}", self.rows, self.cols)
+    };
+    iter::once(header)
+      .chain(self.tiles.iter().map(Tile::to_token))
+      .join(",")
+  }
+
+  /// A key which is the same for two puzzles exactly when one is the other
+  /// with its letters renamed, its grid transposed, or both. This is the
+  /// smaller `to_line` of the puzzle and its transpose, with the letters
+  /// renamed A, B, C, ... in the order they first appear in the line.
+  pub fn canonical_key(&self) -> String {
+    let rename = |line: String| {
+      let mut names = HashMap::new();
+      line
+        .chars()
+        .map(|c| match c {
+          'A'..='J' => {
+            let next = (b'A' + names.len() as u8) as char;
+            *names.entry(c).or_insert(next)
+          }
+          _ => c,
+        })
+        .collect::<String>()
+    };
+    rename(self.to_line()).min(rename(self.transposed().to_line()))
+  }
+
+  /// This puzzle reflected across its main diagonal, which swaps the rows
+  /// with the columns and the horizontal clues with the vertical ones.
+  pub fn transposed(&self) -> Kakuro {
+    let tiles = (0..self.cols)
+      .cartesian_product(0..self.rows)
+      .map(|(col, row)| match &self[(row, col)] {
+        Tile::Total(TotalTile {
+          horizontal,
+          vertical,
+        }) => Tile::Total(TotalTile::new(vertical.clone(), horizontal.clone())),
+        tile => tile.clone(),
+      })
+      .collect();
+    Kakuro {
+      rows: self.cols,
+      cols: self.rows,
+      alphabet: self.alphabet,
+      tiles,
+    }
+  }
+
+  pub fn write_file(path: &str, puzzles: &[Kakuro]) -> io::Result<()> {
+    let f = File::create(path)?;
+    let mut writer = BufWriter::new(f);
+    Self::to_writer(&mut writer, puzzles)?;
+    writer.flush()
+  }
+
+  pub fn to_writer<W: Write>(mut w: W, puzzles: &[Kakuro]) -> io::Result<()> {
+    puzzles
+      .iter()
+      .try_for_each(|kakuro| writeln!(w, "{}", kakuro.to_line()))
+  }
+
+  fn get_idx(&self, row: usize, col: usize) -> usize {
+    row * self.cols + col
+  }
+
+  pub fn rows(&self) -> usize {
+    self.rows
+  }
+
+  pub fn cols(&self) -> usize {
+    self.cols
+  }
+
+  /// The number of letters the puzzle is encoded with. Puzzles read from a
+  /// file use all of A-J.
+  pub fn alphabet_size(&self) -> usize {
+    self.alphabet
+  }
+
+  /// Sets the number of letters, starting from A, that this puzzle is encoded
+  /// with. Each letter stands for a distinct digit. Panics if the size isn't
+  /// between 1 and `MAX_ALPHABET_SIZE`, or leaves out a letter the puzzle uses.
+  pub fn with_alphabet_size(self, alphabet: usize) -> Self {
+    self
+      .try_with_alphabet_size(alphabet)
+      .unwrap_or_else(|err| panic!("{err}"))
+  }
+
+  /// Like `with_alphabet_size`, but returns an error instead of panicking.
+  pub fn try_with_alphabet_size(self, alphabet: usize) -> Result<Self, KakuroError> {
+    if !(1..=MAX_ALPHABET_SIZE).contains(&alphabet) {
+      return Err(KakuroError::AlphabetSize(alphabet));
+    }
+    if let Some(letter) = self
+      .letters_used()
+      .find(|&letter| LetterAssignment::letter_idx(letter).is_none_or(|idx| idx >= alphabet))
+    {
+      return Err(KakuroError::LetterOutsideAlphabet { letter, alphabet });
+    }
+    Ok(Self { alphabet, ..self })
+  }
+
+  /// Shrinks the alphabet to end at the last letter the puzzle uses.
+  pub fn with_inferred_alphabet_size(self) -> Self {
+    let alphabet = self
+      .letters_used()
+      .filter_map(LetterAssignment::letter_idx)
+      .map(|idx| idx + 1)
+      .max()
+      .unwrap_or(MAX_ALPHABET_SIZE);
+    self.with_alphabet_size(alphabet)
+  }
+
+  /// All letters appearing in clues or prefilled tiles, possibly repeated.
+  fn letters_used(&self) -> impl Iterator<Item = char> + '_ {
+    self.tiles.iter().flat_map(|tile| match tile {
+      Tile::Unknown(UnknownTile::Prefilled { hint }) => vec![*hint],
+      Tile::Total(TotalTile {
+        horizontal,
+        vertical,
+      }) => [horizontal, vertical]
+        .into_iter()
+        .flatten()
+        .flat_map(TotalClue::letters)
+        .collect(),
+      _ => vec![],
+    })
+  }
+
+  /// The tile at `(row, col)`, or None if that's outside the grid.
+  pub fn tile(&self, row: usize, col: usize) -> Option<&Tile> {
+    if row < self.rows && col < self.cols {
+      self.tiles.get(self.get_idx(row, col))
+    } else {
+      None
+    }
+  }
+
+  /// Iterates over all tiles in row-major order, along with their
+  /// `(row, col)` coordinates.
+  pub fn tiles_with_coords(&self) -> impl Iterator<Item = ((usize, usize), &Tile)> + '_ {
+    self
+      .tiles
+      .iter()
+      .enumerate()
+      .map(|(idx, tile)| ((idx / self.cols, idx % self.cols), tile))
+  }
+
+  /// The coordinates of the unknown tiles in the run starting from the clue at
+  /// `(row, col)` in `direction`.
+  fn take_cells(
+    &self,
+    row: usize,
+    col: usize,
+    direction: Direction,
+  ) -> impl Iterator<Item = (usize, usize)> + '_ {
+    let (len, step) = match direction {
+      Direction::Vertical => (self.rows - row, (1, 0)),
+      Direction::Horizontal => (self.cols - col, (0, 1)),
+    };
+    (1..len)
+      .map(move |offset| (row + offset * step.0, col + offset * step.1))
+      .take_while(|&(row, col)| matches!(self[(row, col)], Tile::Unknown(_)))
+  }
+
+  /// The DLX item for the unknown tile at `(row, col)`: its own tile item, or
+  /// its letter if it's prefilled.
+  fn cell_item(&self, (row, col): (usize, usize)) -> DlxItem {
+    match &self[(row, col)] {
+      Tile::Unknown(UnknownTile::Prefilled { hint }) => DlxItem::Letter { letter: *hint },
+      _ => DlxItem::Tile {
+        idx: self.get_idx(row, col) as u32,
+      },
+    }
+  }
+
+  /// The DLX item for the clue of `run`.
+  fn run_item(&self, run: &Run) -> DlxItem {
+    DlxItem::Sum {
+      idx: self.get_idx(run.start.0, run.start.1) as u32,
+      vertical: run.direction == Direction::Vertical,
+    }
+  }
+
+  /// All runs in the puzzle, ordered by the position of their clues in
+  /// row-major order, with horizontal runs before vertical ones.
+  pub fn runs(&self) -> Vec<Run> {
+    self
+      .enumerate_lines()
+      .map(|(start, direction, clue, cells)| Run {
+        start,
+        direction,
+        cells: cells.collect(),
+        clue: clue.clone(),
+      })
+      .collect()
+  }
+
+  /// The digits each unknown tile could take, considering only which
+  /// combinations of digits each of its runs could add up to. Letter clues
+  /// allow any total they could stand for. No search is done, so tiles may
+  /// have candidates which appear in no solution.
+  pub fn candidates(&self) -> HashMap<(usize, usize), DigitSet> {
+    let mut cache = CombinationCache::new();
+    let partial = LetterAssignment::for_alphabet(self.alphabet);
+    let mut candidates: HashMap<_, _> = self
+      .tiles_with_coords()
+      .filter_map(|(coords, tile)| match tile {
+        Tile::Unknown(UnknownTile::Given { digit }) => Some((coords, DigitSet::new().with(*digit))),
+        Tile::Unknown(_) => Some((coords, DigitSet::from_range(1..=9))),
+        _ => None,
+      })
+      .collect();
+
+    for run in self.runs() {
+      let possible = self
+        .run_combinations(&run, &partial, &mut cache)
+        .into_iter()
+        .fold(DigitSet::new(), |possible, digits| possible.union(digits));
+      for cell in &run.cells {
+        if let Some(cell_candidates) = candidates.get_mut(cell) {
+          *cell_candidates = cell_candidates.intersection(possible);
+        }
+      }
+    }
+
+    candidates
+  }
+
+  /// The distinct sets of digits which could fill `run`, containing all of the
+  /// digits given in it.
+  fn run_combinations(
+    &self,
+    run: &Run,
+    partial: &LetterAssignment,
+    cache: &mut CombinationCache,
+  ) -> Vec<DigitSet> {
+    let required: DigitSet = run
+      .cells
+      .iter()
+      .filter_map(|&cell| self.given_digit(&self.cell_item(cell)))
+      .collect();
+    run
+      .clue
+      .all_combinations(run.cells.len() as u32, partial, cache)
+      .map(|(_, digits)| digits.into_iter().collect::<DigitSet>())
+      .filter(|digits| digits.intersection(required) == required)
+      .unique()
+      .collect()
+  }
+
+  /// Narrows the digit `combinations` of each of `runs` to those its tiles'
+  /// `candidates` could still hold, and the candidates to the digits in their
+  /// runs' combinations, until neither changes.
+  fn propagate(
+    runs: &[Run],
+    combinations: &mut [Vec<DigitSet>],
+    candidates: &mut HashMap<(usize, usize), DigitSet>,
+  ) {
+    loop {
+      let mut changed = false;
+      for (run, combinations) in runs.iter().zip(combinations.iter_mut()) {
+        let cell_candidates = run.cells.iter().map(|cell| candidates[cell]).collect_vec();
+        // A combination is only possible if its digits can be placed in the
+        // run's tiles, one per tile.
+        combinations.retain(|&digits| fits(&cell_candidates, digits));
+
+        for (idx, cell) in run.cells.iter().enumerate() {
+          let mut others = cell_candidates.clone();
+          others.remove(idx);
+          let possible = combinations
+            .iter()
+            .flat_map(|&digits| {
+              cell_candidates[idx]
+                .intersection(digits)
+                .iter()
+                .filter(|&digit| fits(&others, digits.without(digit)))
+                .collect_vec()
+            })
+            .collect::<DigitSet>();
+          let cell_candidates = candidates.get_mut(cell).unwrap();
+          let narrowed = cell_candidates.intersection(possible);
+          if narrowed != *cell_candidates {
+            *cell_candidates = narrowed;
+            changed = true;
+          }
+        }
+      }
+      if !changed {
+        break;
+      }
+    }
+  }
+
+  /// Explains why the puzzle has no solution, or returns None if it has one.
+  /// Cheap checks are tried first: a run with no combinations of digits at
+  /// all, then a tile left with no candidates after repeatedly narrowing each
+  /// run's combinations to those its tiles could still hold. Failing those,
+  /// the puzzle is searched, and the clue the search most often failed to
+  /// fill is reported.
+  pub fn diagnose(&self) -> Option<KakuroDiagnosis> {
+    if let Err(errors) = self.validate() {
+      return Some(KakuroDiagnosis::InvalidStructure(errors));
+    }
+
+    let mut cache = CombinationCache::new();
+    let partial = LetterAssignment::for_alphabet(self.alphabet);
+    let runs = self.runs();
+    let mut combinations = runs
+      .iter()
+      .map(|run| self.run_combinations(run, &partial, &mut cache))
+      .collect_vec();
+
+    if let Some((run, _)) = runs
+      .iter()
+      .zip(&combinations)
+      .find(|(_, combinations)| combinations.is_empty())
+    {
+      return Some(KakuroDiagnosis::ImpossibleRun {
+        start: run.start,
+        direction: run.direction,
+        clue: run.clue.to_string(),
+      });
+    }
+
+    let mut candidates = self.candidates();
+    Self::propagate(&runs, &mut combinations, &mut candidates);
+
+    if let Some((row, col)) = candidates
+      .iter()
+      .filter(|(_, candidates)| candidates.is_empty())
+      .map(|(&cell, _)| cell)
+      .min()
+    {
+      return Some(KakuroDiagnosis::EmptyCandidates { row, col });
+    }
+
+    let solved = solve_model(&self.model(&partial, &mut cache, true, Relaxed::Nothing));
+    if !solved.solutions.is_empty() {
+      return None;
+    }
+    let (item, dead_ends) = solved.stats.worst_dead_end()?;
+    runs
+      .into_iter()
+      .find(|run| self.run_item(run) == *item)
+      .map(|run| KakuroDiagnosis::SearchDeadEnd {
+        start: run.start,
+        direction: run.direction,
+        clue: run.clue.to_string(),
+        dead_ends,
+      })
+  }
+
+  /// Checks that the grid is a well-formed kakuro: every clue has between 1
+  /// and 9 unknown tiles in its run, and every unknown tile is part of some
+  /// run. Returns every violation found.
+  pub fn validate(&self) -> Result<(), Vec<KakuroStructureError>> {
+    let mut covered = vec![false; self.tiles.len()];
+    let mut errors = Vec::new();
+
+    for run in self.runs() {
+      let Run {
+        start: (row, col),
+        direction,
+        ..
+      } = run;
+      run
+        .cells
+        .iter()
+        .for_each(|&(row, col)| covered[self.get_idx(row, col)] = true);
+
+      let (pos, limit) = match direction {
+        Direction::Vertical => (row, self.rows),
+        Direction::Horizontal => (col, self.cols),
+      };
+      let len = run.cells.len();
+      if pos + 1 == limit {
+        errors.push(KakuroStructureError::RunExitsGrid {
+          row,
+          col,
+          direction,
+        });
+      } else if len == 0 {
+        errors.push(KakuroStructureError::EmptyRun {
+          row,
+          col,
+          direction,
+        });
+      } else if len > 9 {
+        errors.push(KakuroStructureError::RunTooLong {
+          row,
+          col,
+          direction,
+          len,
+        });
+      }
+    }
+
+    errors.extend(
+      self
+        .tiles
+        .iter()
+        .zip(covered)
+        .enumerate()
+        .filter(|(_, (tile, covered))| matches!(tile, Tile::Unknown(_)) && !covered)
+        .map(|(idx, _)| KakuroStructureError::UncoveredTile {
+          row: idx / self.cols,
+          col: idx % self.cols,
+        }),
+    );
+
+    if errors.is_empty() {
+      Ok(())
+    } else {
+      Err(errors)
+    }
+  }
+
+  /// True if any clue or tile in the puzzle is written with letters. Puzzles
+  /// which only use numbers need no letter items in the DLX model.
+  fn uses_letters(&self) -> bool {
+    self.tiles.iter().any(|tile| match tile {
+      Tile::Unknown(UnknownTile::Prefilled { .. }) => true,
+      Tile::Total(TotalTile {
+        horizontal,
+        vertical,
+      }) => [horizontal, vertical]
+        .into_iter()
+        .flatten()
+        .any(TotalClue::uses_letters),
+      _ => false,
+    })
+  }
+
+  /// The digit given outright for the tile `item`, if any.
+  fn given_digit(&self, item: &DlxItem) -> Option<u32> {
+    match item {
+      DlxItem::Tile { idx } => match self.tiles.get(*idx as usize) {
+        Some(Tile::Unknown(UnknownTile::Given { digit })) => Some(*digit),
+        _ => None,
+      },
+      _ => None,
+    }
+  }
+
+  fn enumerate_lines(
+    &self,
+  ) -> impl Iterator<
+    Item = (
+      (usize, usize),
+      Direction,
+      &TotalClue,
+      impl Iterator<Item = (usize, usize)> + '_,
+    ),
+  > + '_ {
+    self
+      .tiles_with_coords()
+      .filter_map(|(start, tile)| tile.as_total().map(|total| (start, total)))
+      .flat_map(move |((row, col), total)| {
+        [Direction::Horizontal, Direction::Vertical]
+          .into_iter()
+          .filter_map(move |direction| {
+            total.clue(direction).map(|clue| {
+              (
+                (row, col),
+                direction,
+                clue,
+                self.take_cells(row, col, direction),
+              )
+            })
+          })
+      })
+  }
+
+  fn all_items(&self) -> impl Iterator<Item = (DlxItem, HeaderType)> + '_ {
+    let uses_letters = self.uses_letters();
+    self
+      .tiles
+      .iter()
+      .enumerate()
+      .flat_map(|(idx, tile)| {
+        let idx = idx as u32;
+        match tile {
+          Tile::Total(TotalTile {
+            horizontal,
+            vertical,
+          }) => [
+            horizontal.as_ref().map(|_| {
+              (
+                DlxItem::Sum {
+                  idx,
+                  vertical: false,
+                },
+                HeaderType::Primary,
+              )
+            }),
+            vertical.as_ref().map(|_| {
+              (
+                DlxItem::Sum {
+                  idx,
+                  vertical: true,
+                },
+                HeaderType::Primary,
+              )
+            }),
+          ],
+          Tile::Unknown(UnknownTile::Blank | UnknownTile::Given { .. }) => {
+            [Some((DlxItem::Tile { idx }, HeaderType::Secondary)), None]
+          }
+          _ => [None, None],
+        }
+        .into_iter()
+        .flatten()
+      })
+      .chain(
+        ('A'..='J')
+          .take(self.alphabet)
+          .filter(move |_| uses_letters)
+          .map(|letter| (DlxItem::Letter { letter }, HeaderType::Secondary)),
+      )
+      .chain(
+        (0..10)
+          .filter(move |_| uses_letters)
+          .map(|value| (DlxItem::LetterValue { value }, HeaderType::Secondary)),
+      )
+  }
+
+  /// Constructs Dlx constraints from a list of assignments to letters or
+  /// tiles. Letter assignments may be repeated, and they will be deduplicated.
+  /// If any color assignments conflict among letters (i.e. A=1 and A=2, or A=1
+  /// and B=1), or with the letters already assigned in `partial`, then None is
+  /// returned. A clue's letters and the letters prefilled in its own run are
+  /// both in `items`, so a letter on both sides of the sum must take the same
+  /// value in the two places.
+  fn construct_dlx(
+    clue_item: DlxItem,
+    items: Vec<(DlxItem, u32)>,
+    partial: &LetterAssignment,
+  ) -> Option<impl Iterator<Item = Constraint<DlxItem>>> {
+    if log_enabled!(Level::Trace) {
+      trace!("Checking {clue_item:?}: {items:?}");
+    }
+    let (letters, values) = match items.iter().try_fold(
+      ([(); 10].map(|_| None), [(); 10].map(|_| None)),
+      |(mut letters_array, mut values_array), (item, value)| {
+        let value = *value;
+        match item {
+          DlxItem::Letter { letter } => {
+            // Letters outside of A-J and values which aren't digits can't be
+            // part of any solution.
+            let Some(idx) = LetterAssignment::letter_idx(*letter).filter(|_| value < 10) else {
+              return ControlFlow::Break(());
+            };
+            if letters_array[idx].is_some_and(|prev_value| prev_value != value)
+              || values_array[value as usize].is_some_and(|prev_idx| prev_idx != idx)
+              || !partial.admits(*letter, value)
+            {
+              ControlFlow::Break(())
+            } else {
+              letters_array[idx] = Some(value);
+              values_array[value as usize] = Some(idx);
+              ControlFlow::Continue((letters_array, values_array))
+            }
+          }
+          _ => ControlFlow::Continue((letters_array, values_array)),
+        }
+      },
+    ) {
+      ControlFlow::Break(_) => {
+        trace!("Filtered out {clue_item:?}");
+        return None;
+      }
+      ControlFlow::Continue(arrays) => arrays,
+    };
+
+    Some(
+      iter::once(clue_item.into())
+        .chain(
+          items
+            .into_iter()
+            .filter(|(item, _)| matches!(item, DlxItem::Tile { .. }))
+            .map(|(item, color)| ColorItem::new(item, color).into()),
+        )
+        .chain(letters.into_iter().enumerate().filter_map(|(idx, value)| {
+          value.map(|value| {
+            ColorItem::new(
+              DlxItem::Letter {
+                letter: (idx as u32 + 'A' as u32) as u8 as char,
+              },
+              value,
+            )
+            .into()
+          })
+        }))
+        .chain(values.into_iter().enumerate().filter_map(|(value, idx)| {
+          idx.map(|idx| {
+            ColorItem::new(
+              DlxItem::LetterValue {
+                value: value as u32,
+              },
+              idx as u32,
+            )
+            .into()
+          })
+        })),
+    )
+  }
+
+  /// The width of the widest of `cells`, one per tile, in each column.
+  fn column_widths(&self, cells: &[String]) -> Vec<usize> {
+    (0..self.cols)
+      .map(|col| {
+        (0..self.rows)
+          .map(|row| cells[self.get_idx(row, col)].chars().count())
+          .max()
+          .unwrap_or(0)
+      })
+      .collect()
+  }
+
+  /// Lays out one string per tile as a grid, right-aligning each column to
+  /// its widest cell.
+  fn layout(&self, cells: &[String]) -> String {
+    let widths = self.column_widths(cells);
+    cells
+      .chunks(self.cols)
+      .map(|row| {
+        row
+          .iter()
+          .zip(&widths)
+          .map(|(cell, &width)| format!("{cell:>width$}"))
+          .join(" ")
+      })
+      .join("\n")
+  }
+
+  /// Renders the grid with clues decoded under `letters` and unknown tiles
+  /// filled in by `digit`. Clues are drawn as `vertical\horizontal`, and
+  /// anything not yet known as `?`.
+  fn render_with(
+    &self,
+    letters: &LetterAssignment,
+    digit: impl Fn(usize, usize) -> Option<u32>,
+  ) -> String {
+    let clue_text = |clue: &Option<TotalClue>| match clue {
+      Some(clue) => clue
+        .known_total(letters)
+        .map_or("?".to_string(), |total| total.to_string()),
+      None => String::new(),
+    };
+    let cells = self
+      .tiles_with_coords()
+      .map(|((row, col), tile)| match tile {
+        Tile::Empty => "X".to_string(),
+        Tile::Unknown(_) => digit(row, col).map_or("?".to_string(), |digit| digit.to_string()),
+        Tile::Total(TotalTile {
+          horizontal,
+          vertical,
+        }) => format!("{}\\{}", clue_text(vertical), clue_text(horizontal)),
+      })
+      .collect_vec();
+    self.layout(&cells)
+  }
+
+  /// Renders `solution` as a grid of digits, with each clue decoded.
+  pub fn render_solution(&self, solution: &SolvedKakuro) -> String {
+    self.render_with(&solution.letters, |row, col| solution.digit(row, col))
+  }
+
+  /// Renders the grid as far as it's determined by `letters`, which may be
+  /// partial: clues with unassigned letters and tiles not given or prefilled
+  /// with an assigned letter are drawn as `?`.
+  pub fn render_letters(&self, letters: &LetterAssignment) -> String {
+    self.render_with(letters, |row, col| match &self[(row, col)] {
+      Tile::Unknown(UnknownTile::Given { digit }) => Some(*digit),
+      Tile::Unknown(UnknownTile::Prefilled { hint }) => letters.letter_value(*hint),
+      _ => None,
+    })
+  }
+
+  pub fn solve(&self) -> Result<Vec<LetterAssignment>, KakuroError> {
+    self.solve_with_cache(&mut CombinationCache::new())
+  }
+
+  /// Solves the puzzle, drawing clue combinations from `cache`. Sharing one
+  /// cache between puzzles avoids regenerating combinations for sum ranges and
+  /// run lengths that have already been seen.
+  pub fn solve_with_cache(
+    &self,
+    cache: &mut CombinationCache,
+  ) -> Result<Vec<LetterAssignment>, KakuroError> {
+    self.solve_with_partial(&LetterAssignment::new(), cache)
+  }
+
+  /// Solves the puzzle assuming each `(letter, value)` in `hints`. Returns an
+  /// error if the hints are malformed or contradict each other, and no
+  /// solutions if the puzzle can't be solved under them.
+  pub fn solve_with_hints(
+    &self,
+    hints: &[(char, u32)],
+  ) -> Result<Vec<LetterAssignment>, KakuroError> {
+    let partial =
+      LetterAssignment::from_pairs_for_alphabet(self.alphabet, hints).map_err(|err| match err {
+        LetterAssignmentError::OutOfRange { letter, value } => {
+          KakuroError::InvalidHint { letter, value }
+        }
+        LetterAssignmentError::Conflict { first, second } => {
+          KakuroError::ConflictingHints { first, second }
+        }
+      })?;
+
+    self.solve_with_partial(&partial, &mut CombinationCache::new())
+  }
+
+  /// Solves the puzzle assuming the letters assigned in `partial` take those
+  /// values. Clues whose letters are all known only enumerate combinations
+  /// for their exact total.
+  ///
+  /// Each distinct letter assignment is returned once, in increasing order of
+  /// `int_value`, even if several fillings of the grid share it.
+  pub fn solve_with_partial(
+    &self,
+    partial: &LetterAssignment,
+    cache: &mut CombinationCache,
+  ) -> Result<Vec<LetterAssignment>, KakuroError> {
+    Ok(SolvedKakuro::distinct_letters(
+      self.solve_full_within(partial, cache, None, true)?,
+    ))
+  }
+
+  /// Finds every solution to the puzzle, including the digit in every tile.
+  /// For puzzles which only use numbers, the letter assignments are empty.
+  pub fn solve_full(&self) -> Result<Vec<SolvedKakuro>, KakuroError> {
+    self.solve_full_with_partial(&LetterAssignment::new(), &mut CombinationCache::new())
+  }
+
+  /// Finds every solution to the puzzle like `solve_full`, along with the
+  /// digits the search placed in each run, decoded from the subsets it chose.
+  pub fn solve_explained(&self) -> Result<Vec<(SolvedKakuro, Vec<RunFill>)>, KakuroError> {
+    self.validate().map_err(KakuroError::InvalidStructure)?;
+    let partial = LetterAssignment::new();
+    let mut cache = CombinationCache::new();
+    let model = self.model(&partial, &mut cache, true, Relaxed::Nothing);
+    let mut dlx = build_dlx(&model);
+    let explained = dlx
+      .find_selections(None)
+      .flat_map(|selection| {
+        let fills = model.fills(&selection.names);
+        let solution = self.decode_fills(&fills, &selection.colors);
+        self
+          .complete_letters(vec![solution])
+          .map(move |solution| (solution, fills.clone()))
+      })
+      .collect();
+    Ok(explained)
+  }
+
+  pub fn solve_full_with_partial(
+    &self,
+    partial: &LetterAssignment,
+    cache: &mut CombinationCache,
+  ) -> Result<Vec<SolvedKakuro>, KakuroError> {
+    self.solve_full_within(partial, cache, None, false)
+  }
+
+  /// Solves the puzzle like `solve`, also measuring the work it took.
+  pub fn solve_instrumented(
+    &self,
+  ) -> Result<(Vec<LetterAssignment>, KakuroSolveStats), KakuroError> {
+    let start = Instant::now();
+    self.validate().map_err(KakuroError::InvalidStructure)?;
+    let partial = LetterAssignment::new();
+    let mut cache = CombinationCache::new();
+    let model = self.model(&partial, &mut cache, true, Relaxed::Nothing);
+    let solved = solve_model(&model);
+
+    let counts = &model.counts;
+    let stats = KakuroSolveStats {
+      runs: self.runs().len(),
+      combinations: counts.combinations.get(),
+      permutations: counts.permutations.get(),
+      subsets: counts.kept.get(),
+      nodes: solved.stats.nodes,
+      solutions: solved.stats.solutions,
+      seconds: start.elapsed().as_secs_f64(),
+    };
+    Ok((self.completed_letters(solved.solutions), stats))
+  }
+
+  /// Finds one solution to the puzzle, including the digit in every tile,
+  /// without checking for others.
+  pub fn solve_first(&self) -> Result<Option<SolvedKakuro>, KakuroError> {
+    self.solve_first_full_with_cache(&mut CombinationCache::new())
+  }
+
+  /// Finds one solution to the puzzle, without checking for others.
+  pub fn solve_first_with_cache(
+    &self,
+    cache: &mut CombinationCache,
+  ) -> Result<Option<LetterAssignment>, KakuroError> {
+    Ok(
+      self
+        .solve_first_full_with_cache(cache)?
+        .map(SolvedKakuro::into_letters),
+    )
+  }
+
+  fn solve_first_full_with_cache(
+    &self,
+    cache: &mut CombinationCache,
+  ) -> Result<Option<SolvedKakuro>, KakuroError> {
+    self.validate().map_err(KakuroError::InvalidStructure)?;
+    let partial = LetterAssignment::new();
+    let solved = solve_model_with(
+      &self.model(&partial, cache, true, Relaxed::Nothing),
+      SearchLimits::first(),
+    );
+    Ok(self.complete_letters(solved.solutions).next())
+  }
+
+  /// Checks that `solution` fills every unknown tile with a digit, agreeing
+  /// with the givens and prefilled letters, such that every run has distinct
+  /// digits adding up to its clue under the solution's letters. This doesn't
+  /// trust the solver, so it can check its answers.
+  pub fn verify(&self, solution: &SolvedKakuro) -> Result<(), SolutionError> {
+    if solution.cols != self.cols || solution.digits.len() != self.tiles.len() {
+      return Err(SolutionError::WrongSize);
+    }
+    if let Some(((first, value), (second, _))) = solution
+      .letters
+      .iter()
+      .collect_vec()
+      .into_iter()
+      .tuple_combinations()
+      .find(|((_, first), (_, second))| first == second)
+    {
+      return Err(SolutionError::SharedValue {
+        first,
+        second,
+        value,
+      });
+    }
+    let digit = |(row, col): (usize, usize)| solution.digits[row * self.cols + col];
+
+    for ((row, col), tile) in self.tiles_with_coords() {
+      match (tile.as_unknown(), digit((row, col))) {
+        (None, None) => {}
+        (None, Some(_)) => return Err(SolutionError::UnexpectedDigit { row, col }),
+        (Some(_), None) => return Err(SolutionError::MissingDigit { row, col }),
+        (Some(unknown), Some(digit)) => {
+          let allowed = match unknown {
+            UnknownTile::Blank => (1..=9).contains(&digit),
+            UnknownTile::Given { digit: given } => digit == *given,
+            UnknownTile::Prefilled { hint } => {
+              (1..=9).contains(&digit) && solution.letters.letter_value(*hint) == Some(digit)
+            }
+          };
+          if !allowed {
+            return Err(SolutionError::WrongDigit { row, col, digit });
+          }
+        }
+      }
+    }
+
+    for run in self.runs() {
+      let (row, col) = run.start;
+      let direction = run.direction;
+      // Every tile in a run is an unknown, so was checked to have a digit.
+      let digits = run
+        .cells
+        .iter()
+        .map(|&cell| digit(cell).unwrap())
+        .collect_vec();
+      if let Some(&digit) = digits.iter().duplicates().next() {
+        return Err(SolutionError::RepeatedDigit {
+          row,
+          col,
+          direction,
+          digit,
+        });
+      }
+      let total = digits.iter().sum();
+      // A two digit clue can't be written with a leading zero.
+      let leading_zero = matches!(
+        run.clue,
+        TotalClue::TwoDigit { .. } | TotalClue::Mixed { .. }
+      ) && total < 10;
+      if leading_zero || run.clue.known_total(&solution.letters) != Some(total) {
+        return Err(SolutionError::WrongTotal {
+          row,
+          col,
+          direction,
+          total,
+        });
+      }
+    }
+    Ok(())
+  }
+
+  /// Finds every letter assignment solving the puzzle without building a DLX
+  /// model, by trying each way of giving the letters the puzzle uses distinct
+  /// digits and searching for digits to fill its tiles under it, checked with
+  /// `verify`. Letters used nowhere are completed as in `solve`. Meant as a
+  /// reference to check the solver against, so fails with
+  /// `KakuroError::TooManyLetters` for puzzles using more than
+  /// `BRUTE_FORCE_MAX_LETTERS` letters.
+  pub fn solve_brute_force(&self) -> Result<Vec<LetterAssignment>, KakuroError> {
+    self.solve_brute_force_with_limit(BRUTE_FORCE_MAX_LETTERS)
+  }
+
+  /// Like `solve_brute_force`, allowing puzzles using up to `max_letters`
+  /// letters. Each extra letter multiplies the work by the number of digits
+  /// left for it.
+  pub fn solve_brute_force_with_limit(
+    &self,
+    max_letters: usize,
+  ) -> Result<Vec<LetterAssignment>, KakuroError> {
+    self.validate().map_err(KakuroError::InvalidStructure)?;
+    let letters = self.letters_used().unique().sorted().collect_vec();
+    if letters.len() > max_letters {
+      return Err(KakuroError::TooManyLetters {
+        used: letters.len(),
+        limit: max_letters,
+      });
+    }
+
+    let runs = self.runs();
+    let unknowns = self
+      .tiles
+      .iter()
+      .positions(|tile| matches!(tile, Tile::Unknown(_)))
+      .collect_vec();
+    let solutions = (0..10)
+      .permutations(letters.len())
+      .map(|values| {
+        let pairs = letters.iter().copied().zip(values).collect_vec();
+        LetterAssignment::from_pairs_for_alphabet(self.alphabet, &pairs).unwrap()
+      })
+      .filter(|assignment| {
+        // Only search for digits if every clue is a total its run could have.
+        runs.iter().all(|run| {
+          let (min, max) = TotalClue::attainable_sum_range(run.cells.len() as u32).unwrap();
+          run
+            .clue
+            .known_total(assignment)
+            .is_some_and(|total| (min..=max).contains(&total))
+        })
+      })
+      .filter(|assignment| {
+        let mut solution = SolvedKakuro {
+          cols: self.cols,
+          letters: assignment.clone(),
+          digits: vec![None; self.tiles.len()],
+        };
+        self.brute_force_fill(&runs, &unknowns, &mut solution)
+      })
+      .collect_vec();
+
+    Ok(if self.uses_letters() {
+      solutions
+        .iter()
+        .flat_map(LetterAssignment::completions)
+        .collect()
+    } else {
+      solutions
+    })
+  }
+
+  /// Tries every digit in each of the tiles `unknowns` of `solution` in turn,
+  /// returning true once it finds digits which `verify` accepts. Digits which
+  /// repeat in a run or overshoot its total are never extended.
+  fn brute_force_fill(
+    &self,
+    runs: &[Run],
+    unknowns: &[usize],
+    solution: &mut SolvedKakuro,
+  ) -> bool {
+    let Some((&idx, rest)) = unknowns.split_first() else {
+      return self.verify(solution).is_ok();
+    };
+    let candidates = match &self.tiles[idx] {
+      Tile::Unknown(UnknownTile::Given { digit }) => vec![*digit],
+      Tile::Unknown(UnknownTile::Prefilled { hint }) => {
+        solution.letters.letter_value(*hint).into_iter().collect()
+      }
+      _ => (1..=9).collect(),
+    };
+    let coords = (idx / self.cols, idx % self.cols);
+    for digit in candidates {
+      solution.digits[idx] = Some(digit);
+      let consistent = runs
+        .iter()
+        .filter(|run| run.cells.contains(&coords))
+        .all(|run| {
+          let digits = run
+            .cells
+            .iter()
+            .filter_map(|&(row, col)| solution.digits[self.get_idx(row, col)])
+            .collect_vec();
+          digits.iter().all_unique()
+            && run
+              .clue
+              .known_total(&solution.letters)
+              .is_some_and(|total| digits.iter().sum::<u32>() <= total)
+        });
+      if consistent && self.brute_force_fill(runs, rest, solution) {
+        solution.digits[idx] = None;
+        return true;
+      }
+    }
+    solution.digits[idx] = None;
+    false
+  }
+
+  /// Solves the puzzle, giving up with `KakuroError::TimedOut` if it takes
+  /// longer than `limit`. The clock starts before the DLX subsets are
+  /// generated, but is only checked during the search, so a puzzle which is
+  /// slow to generate can overrun `limit` before giving up.
+  pub fn solve_with_timeout(&self, limit: Duration) -> Result<Vec<LetterAssignment>, KakuroError> {
+    Ok(SolvedKakuro::distinct_letters(self.solve_full_within(
+      &LetterAssignment::new(),
+      &mut CombinationCache::new(),
+      Some(limit),
+      true,
+    )?))
+  }
+
+  /// Finds every solution consistent with `partial`, stopping the search once
+  /// `limit` has passed if one is given. With `letters_only`, only one
+  /// solution is found for each assignment of the letters, and the search
+  /// skips the other fillings of the grid for assignments it has already
+  /// found.
+  fn solve_full_within(
+    &self,
+    partial: &LetterAssignment,
+    cache: &mut CombinationCache,
+    limit: Option<Duration>,
+    letters_only: bool,
+  ) -> Result<Vec<SolvedKakuro>, KakuroError> {
+    // Only read the clock when there's a limit, as it isn't available on
+    // every target (e.g. wasm32-unknown-unknown).
+    let deadline = limit.map(|limit| Instant::now() + limit);
+    self.validate().map_err(KakuroError::InvalidStructure)?;
+    let model = self.model(partial, cache, true, Relaxed::Nothing);
+    let (solutions, interrupted) = if letters_only {
+      let mut dlx = build_dlx(&model);
+      dlx.set_deadline(deadline);
+      let solutions = self.distinct_letter_solutions(&model, &mut dlx);
+      (solutions, dlx.interrupted())
+    } else {
+      let limits = SearchLimits {
+        deadline,
+        ..SearchLimits::default()
+      };
+      let solved = solve_model_with(&model, limits);
+      (solved.solutions, solved.interrupted)
+    };
+    if let Some(limit) = limit.filter(|_| interrupted) {
+      return Err(KakuroError::TimedOut(SolveTimeout { limit }));
+    }
+
+    Ok(
+      self
+        .complete_letters(solutions)
+        .filter(|solution| {
+          partial.alphabet().all(|letter| {
+            partial
+              .letter_value(letter)
+              .is_none_or(|value| Some(value) == solution.letters.letter_value(letter))
+          })
+        })
+        .collect_vec(),
+    )
+  }
+
+  /// Searches `dlx`, built from `model`, for one solution for each distinct
+  /// assignment of the letters.
+  fn distinct_letter_solutions(
+    &self,
+    model: &KakuroModel,
+    dlx: &mut Dlx<DlxItem, ChoiceId>,
+  ) -> Vec<SolvedKakuro> {
+    let letters = self
+      .all_items()
+      .filter_map(|(item, _)| matches!(item, DlxItem::Letter { .. }).then_some(item))
+      .collect_vec();
+    dlx
+      .find_all_distinct_colorings(&letters)
+      .map(|selection| model.decode(selection))
+      .collect()
+  }
+
+  /// Solves the puzzle like `solve`, reading the subsets of its DLX model
+  /// from its entry in `dir` if one was saved by an earlier solve, and
+  /// generating and saving them there otherwise. Entries which are corrupted
+  /// or were written by another version are regenerated rather than trusted.
+  pub fn solve_with_cache_dir(
+    &self,
+    dir: Option<&CacheDir>,
+  ) -> Result<Vec<LetterAssignment>, KakuroError> {
+    let Some(dir) = dir else {
+      return self.solve();
+    };
+    self.validate().map_err(KakuroError::InvalidStructure)?;
+    let partial = LetterAssignment::new();
+    let mut combinations = CombinationCache::new();
+    let model = self.model(&partial, &mut combinations, true, Relaxed::Nothing);
+    let choices = cache::load_or_generate(dir, &self.to_line(), &model);
+    let mut dlx = Dlx::new(model.items(), choices.into_subsets());
+    model.prepare(&mut dlx);
+    let solutions = self.distinct_letter_solutions(&model, &mut dlx);
+    Ok(self.completed_letters(solutions))
+  }
+
+  /// For each letter the puzzle determines, the runs it depends on: those
+  /// whose clue, if removed, would leave the letter's value ambiguous. Runs
+  /// are listed in the order of `runs`.
+  ///
+  /// This solves the puzzle once for every run, finding all solutions each
+  /// time, so can be slow on large puzzles.
+  pub fn letter_dependencies(&self) -> Result<HashMap<char, Vec<RunId>>, KakuroError> {
+    self.validate().map_err(KakuroError::InvalidStructure)?;
+    let mut cache = CombinationCache::new();
+    let partial = LetterAssignment::for_alphabet(self.alphabet);
+    let solve = |relaxed: Option<RunId>, cache: &mut CombinationCache| {
+      let solved = solve_model(&self.model(
+        &partial,
+        cache,
+        relaxed.is_none(),
+        relaxed.map_or(Relaxed::Nothing, Relaxed::Run),
+      ));
+      self.completed_letters(solved.solutions)
+    };
+
+    // The value of `letter` if it's the same in every one of `solutions`.
+    let determined = |solutions: &[LetterAssignment], letter| {
+      solutions
+        .iter()
+        .map(|letters| letters.letter_value(letter))
+        .all_equal_value()
+        .ok()
+        .flatten()
+    };
+    let solutions = solve(None, &mut cache);
+    let mut dependencies: HashMap<char, Vec<RunId>> = partial
+      .alphabet()
+      .filter(|&letter| determined(&solutions, letter).is_some())
+      .map(|letter| (letter, Vec::new()))
+      .collect();
+
+    for run in self.runs() {
+      let relaxed = solve(Some(run.id()), &mut cache);
+      for (&letter, runs) in dependencies.iter_mut() {
+        if determined(&relaxed, letter).is_none() {
+          runs.push(run.id());
+        }
+      }
+    }
+    Ok(dependencies)
+  }
+
+  /// Solves the puzzle like `solve`, but with the subsets of each run
+  /// generated from the solutions of its clue's equation, found by an
+  /// `EquationSystem`, rather than from the digit combinations of its total.
+  /// This is slower, and serves to cross-check the two formulations.
+  pub fn solve_via_linear(&self) -> Result<Vec<LetterAssignment>, KakuroError> {
+    self.validate().map_err(KakuroError::InvalidStructure)?;
+    if let Some(letter) = self
+      .letters_used()
+      .find(|&letter| LetterAssignment::letter_idx(letter).is_none_or(|idx| idx >= self.alphabet))
+    {
+      return Err(KakuroError::LetterOutsideAlphabet {
+        letter,
+        alphabet: self.alphabet,
+      });
+    }
+    let model = LinearKakuroModel {
+      kakuro: self,
+      runs: self
+        .runs()
+        .iter()
+        .map(|run| (self.run_item(run), self.run_equation(run)))
+        .collect(),
+    };
+    Ok(self.completed_letters(solve_model(&model).solutions))
+  }
+
+  /// The equation saying the digits of `run` add up to its clue, with its
+  /// tiles and letters as the variables, and the distinctness of both. None if
+  /// the run repeats a letter, which can't be filled.
+  fn run_equation(&self, run: &Run) -> Option<EquationSystem<DlxItem>> {
+    let cells = run
+      .cells
+      .iter()
+      .map(|&cell| self.cell_item(cell))
+      .collect_vec();
+    if !cells.iter().all_unique() {
+      return None;
+    }
+    let domains = cells
+      .iter()
+      .map(|item| match self.given_digit(item) {
+        Some(digit) => digit as i32..=digit as i32,
+        None => 1..=9,
+      })
+      .collect_vec();
+
+    let mut sum = LinearSolver::new();
+    for (cell, domain) in cells.iter().zip(&domains) {
+      sum.add_with_domain(cell.clone(), 1, domain.clone());
+    }
+    let letter = |letter| DlxItem::Letter { letter };
+    // The tens digit of a two-digit clue can't be a leading zero.
+    let clue_digits = match run.clue {
+      TotalClue::OneDigit(ones) => vec![(ClueDigit::Letter(ones), 1)],
+      TotalClue::TwoDigit { ones, tens } => {
+        vec![(ClueDigit::Letter(tens), 10), (ClueDigit::Letter(ones), 1)]
+      }
+      TotalClue::Mixed { tens, ones } => vec![(tens, 10), (ones, 1)],
+      TotalClue::Numeric(total) => vec![(ClueDigit::Literal(total), 1)],
+    };
+    for &(digit, factor) in &clue_digits {
+      match digit {
+        ClueDigit::Literal(value) => sum.add_constant(-factor * value as i32),
+        ClueDigit::Letter(l) if factor == 10 => sum.add_with_domain(letter(l), -factor, 1..=9),
+        ClueDigit::Letter(l) => sum.add(letter(l), -factor),
+      }
+    }
+
+    let mut system = EquationSystem::new();
+    system.add_equation(sum);
+    // A letter in both the clue and the run may cancel out of the sum, but
+    // still needs a digit.
+    for (cell, domain) in cells.iter().zip(domains) {
+      system.add_variable(cell.clone(), domain);
+    }
+    system.require_all_distinct(&cells);
+    let letters = cells
+      .iter()
+      .cloned()
+      .chain(
+        clue_digits
+          .iter()
+          .filter_map(|(digit, _)| digit.letter().map(letter)),
+      )
+      .filter(|item| matches!(item, DlxItem::Letter { .. }))
+      .unique()
+      .collect_vec();
+    system.require_all_distinct(&letters);
+    Some(system)
+  }
+
+  /// The puzzle's runs in the order their subsets are generated when solving,
+  /// along with the number of digit combinations each admits. Runs with the
+  /// fewest combinations come first.
+  pub fn solve_order(&self) -> Vec<(Run, u64)> {
+    self.runs_by_constrainedness(&LetterAssignment::for_alphabet(self.alphabet))
+  }
+
+  fn runs_by_constrainedness(&self, partial: &LetterAssignment) -> Vec<(Run, u64)> {
+    self
+      .runs()
+      .into_iter()
+      .map(|run| {
+        let count = run.clue.combination_count(run.cells.len() as u32, partial);
+        (run, count)
+      })
+      .sorted_by_key(|(_, count)| *count)
+      .collect()
+  }
+
+  /// The exact cover model of this puzzle, with letters assigned in
+  /// `partial` fixed to their values. The grid must be valid.
+  ///
+  /// The `relaxed` runs only need distinct digits, and their clues don't
+  /// constrain their letters. Pruning would still narrow them by their clues,
+  /// so the two shouldn't be combined.
+  fn model<'a>(
+    &'a self,
+    partial: &'a LetterAssignment,
+    cache: &'a mut CombinationCache,
+    prune_loose_runs: bool,
+    relaxed: Relaxed,
+  ) -> KakuroModel<'a> {
+    debug_assert!(!(prune_loose_runs && relaxed != Relaxed::Nothing));
+    KakuroModel {
+      kakuro: self,
+      partial,
+      cache: RefCell::new(cache),
+      prune_loose_runs,
+      relaxed,
+      runs: self.runs_by_constrainedness(partial),
+      counts: BuildCounts::default(),
+    }
+  }
+
+  /// Reads the letter values out of the colors assigned in a DLX solution.
+  fn decode_letters(&self, soln: &HashMap<DlxItem, u32>) -> LetterAssignment {
+    soln
+      .iter()
+      .filter_map(|(item, &color)| match item {
+        DlxItem::Letter { letter } => Some((*letter, color)),
+        _ => None,
+      })
+      .fold(
+        LetterAssignment::for_alphabet(self.alphabet),
+        |la, (letter, color)| la.with_value(letter, color),
+      )
+  }
+
+  /// Places the digits of each of `fills` in the grid, with the letter values
+  /// read out of the colors assigned in a DLX solution.
+  fn decode_fills(&self, fills: &[RunFill], soln: &HashMap<DlxItem, u32>) -> SolvedKakuro {
+    let mut digits = vec![None; self.tiles.len()];
+    for fill in fills {
+      for (&(row, col), &digit) in fill.run.cells.iter().zip(&fill.digits) {
+        digits[self.get_idx(row, col)] = Some(digit);
+      }
+    }
+    SolvedKakuro {
+      cols: self.cols,
+      letters: self.decode_letters(soln),
+      digits,
+    }
+  }
+
+  /// Reads the letter values and tile digits out of the colors assigned in a
+  /// DLX solution.
+  fn decode_solution(&self, soln: &HashMap<DlxItem, u32>) -> SolvedKakuro {
+    let letters = self.decode_letters(soln);
+
+    let digits = self
+      .tiles
+      .iter()
+      .enumerate()
+      .map(|(idx, tile)| match tile {
+        Tile::Unknown(UnknownTile::Blank | UnknownTile::Given { .. }) => {
+          soln.get(&DlxItem::Tile { idx: idx as u32 }).copied()
+        }
+        Tile::Unknown(UnknownTile::Prefilled { hint }) => letters.letter_value(*hint),
+        _ => None,
+      })
+      .collect();
+
+    SolvedKakuro {
+      cols: self.cols,
+      letters,
+      digits,
+    }
+  }
+
+  /// Expands each of `solutions` into one solution for every way of giving
+  /// the letters it leaves unassigned the digits left over. A letter which
+  /// appears in no clue or tile is only constrained by having to differ from
+  /// the other letters, so each of those ways solves the puzzle. Puzzles
+  /// written only with numbers have no letters to complete.
+  ///
+  /// There can be thousands of completions of each solution, so they're only
+  /// built as the iterator is advanced.
+  fn complete_letters(&self, solutions: Vec<SolvedKakuro>) -> impl Iterator<Item = SolvedKakuro> {
+    let uses_letters = self.uses_letters();
+    solutions.into_iter().flat_map(move |solution| {
+      if !uses_letters {
+        return Either::Left(iter::once(solution));
+      }
+      Either::Right(
+        solution
+          .letters
+          .completions()
+          .map(move |letters| SolvedKakuro {
+            cols: solution.cols,
+            letters,
+            digits: solution.digits.clone(),
+          }),
+      )
+    })
+  }
+
+  /// The distinct letter assignments among the completions of `solutions`,
+  /// ordered like `SolvedKakuro::distinct_letters`. This only completes the
+  /// letters, without copying each solution's grid for every completion.
+  fn completed_letters(&self, solutions: Vec<SolvedKakuro>) -> Vec<LetterAssignment> {
+    let letters = solutions.into_iter().map(SolvedKakuro::into_letters);
+    if self.uses_letters() {
+      distinct_letters(letters.flat_map(|letters| letters.completions()))
+    } else {
+      distinct_letters(letters)
+    }
+  }
+}
+
+/// One combination of digits for a run, with the letters of its clue assigned
+/// to make up their total.
+type ClueCombination = (Vec<(DlxItem, u32)>, Vec<u32>);
+
+/// Tallies of the subsets generated while building a DLX model.
+#[derive(Default)]
+struct BuildCounts {
+  combinations: Cell<u64>,
+  permutations: Cell<u64>,
+  kept: Cell<u64>,
+}
+
+/// Which runs of a `KakuroModel` ignore their clues, only needing distinct
+/// digits.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Relaxed {
+  Nothing,
+  Run(RunId),
+  AllRuns,
+}
+
+impl Relaxed {
+  fn contains(self, run: RunId) -> bool {
+    match self {
+      Relaxed::Nothing => false,
+      Relaxed::Run(relaxed) => relaxed == run,
+      Relaxed::AllRuns => true,
+    }
+  }
+}
+
+/// The exact cover model of a `Kakuro`, from `Kakuro::model`. Each subset is
+/// one placement of digits in a run, named by the `ChoiceId` it was generated
+/// from.
+struct KakuroModel<'a> {
+  kakuro: &'a Kakuro,
+  partial: &'a LetterAssignment,
+  cache: RefCell<&'a mut CombinationCache>,
+  prune_loose_runs: bool,
+  relaxed: Relaxed,
+  /// The runs in the order their subsets are generated, indexed by
+  /// `ChoiceId::run_index`, with the number of combinations each admits.
+  runs: Vec<(Run, u64)>,
+  /// Tallies of the subsets generated so far.
+  counts: BuildCounts,
+}
+
+impl ExactCoverModel for KakuroModel<'_> {
+  type Item = DlxItem;
+  type Choice = ChoiceId;
+  type Solution = SolvedKakuro;
+
+  fn items(&self) -> impl Iterator<Item = (DlxItem, HeaderType)> + '_ {
+    self.kakuro.all_items()
+  }
+
+  fn choices(
+    &self,
+  ) -> impl Iterator<Item = (ChoiceId, impl IntoIterator<Item = Constraint<DlxItem>>)> + '_ {
+    let kakuro = self.kakuro;
+    let partial = self.partial;
+
+    // Runs with many combinations produce the bulk of the subsets, so only
+    // generate the combinations for those which survive propagation.
+    let allowed = if self.prune_loose_runs {
+      let runs = self.runs.iter().map(|(run, _)| run.clone()).collect_vec();
+      let mut combinations = runs
+        .iter()
+        .map(|run| kakuro.run_combinations(run, partial, &mut self.cache.borrow_mut()))
+        .collect_vec();
+      Kakuro::propagate(&runs, &mut combinations, &mut kakuro.candidates());
+      combinations
+        .into_iter()
+        .zip(&self.runs)
+        .map(|(combinations, &(_, count))| {
+          (count > LOOSE_RUN_COMBINATIONS).then(|| combinations.into_iter().collect::<HashSet<_>>())
+        })
+        .collect_vec()
+    } else {
+      vec![None; self.runs.len()]
+    };
+
+    // The runs are already ordered with the most constrained first, which is
+    // the order their subsets are generated in.
+    allowed
+      .into_iter()
+      .enumerate()
+      .flat_map(|(run_idx, allowed)| self.run_choices(run_idx, allowed))
+  }
+
+  fn prepare(&self, dlx: &mut Dlx<DlxItem, ChoiceId>) {
+    let kakuro = self.kakuro;
+    if kakuro.uses_letters() {
+      for (letter, value, idx) in self
+        .partial
+        .alphabet()
+        .take(kakuro.alphabet)
+        .enumerate()
+        .filter_map(|(idx, letter)| {
+          self
+            .partial
+            .letter_value(letter)
+            .map(|value| (letter, value, idx))
+        })
+      {
+        dlx.require_color(&DlxItem::Letter { letter }, value);
+        dlx.require_color(&DlxItem::LetterValue { value }, idx as u32);
+      }
+    }
+  }
+
+  fn decode(&self, selection: Selection<DlxItem, ChoiceId>) -> SolvedKakuro {
+    self
+      .kakuro
+      .decode_fills(&self.fills(&selection.names), &selection.colors)
+  }
+}
+
+impl KakuroModel<'_> {
+  /// The subsets placing digits in the run at `run_idx`, only from the sets of
+  /// digits in `allowed` if given.
+  fn run_choices(
+    &self,
+    run_idx: usize,
+    allowed: Option<HashSet<DigitSet>>,
+  ) -> impl Iterator<Item = (ChoiceId, impl Iterator<Item = Constraint<DlxItem>>)> + '_ {
+    let kakuro = self.kakuro;
+    let partial = self.partial;
+    let counts = &self.counts;
+    let (run, count) = &self.runs[run_idx];
+    debug!(
+      "Generating subsets for the {} run at {:?} with clue {}, of {count} combinations",
+      run.direction, run.start, run.clue
+    );
+    let item = kakuro.run_item(run);
+    let items = run
+      .cells
+      .iter()
+      .map(|&cell| kakuro.cell_item(cell))
+      .collect_vec();
+
+    let items_len = items.len();
+    // Tiles with given digits must take exactly that digit, so only
+    // combinations containing all of them, placed in the right positions, are
+    // kept.
+    let givens = items
+      .iter()
+      .map(|item| kakuro.given_digit(item))
+      .collect_vec();
+    let required: DigitSet = givens.iter().flatten().copied().collect();
+    self
+      .combinations(run_idx)
+      .inspect(move |_| counts.combinations.set(counts.combinations.get() + 1))
+      .filter(move |(_, (_, digits))| required.iter().all(|digit| digits.contains(&digit)))
+      .filter(move |(_, (_, digits))| {
+        allowed
+          .as_ref()
+          .is_none_or(|allowed| allowed.contains(&digits.iter().copied().collect()))
+      })
+      .flat_map(move |(combination_idx, (total, choices))| {
+        choices.into_iter().permutations(items_len).enumerate().map(
+          move |(permutation_idx, choices)| {
+            (
+              ChoiceId::new(run_idx, combination_idx, permutation_idx),
+              total.clone(),
+              choices,
+            )
+          },
+        )
+      })
+      .inspect(move |_| counts.permutations.set(counts.permutations.get() + 1))
+      .filter(move |(_, _, choices)| {
+        givens
+          .iter()
+          .zip(choices)
+          .all(|(given, digit)| given.is_none_or(|given| given == *digit))
+      })
+      .filter_map(move |(choice, total, choices)| {
+        Kakuro::construct_dlx(
+          item.clone(),
+          total
+            .iter()
+            .map(Clone::clone)
+            .chain(items.iter().map(Clone::clone).zip(choices))
+            .collect(),
+          partial,
+        )
+        .map(|constraints| (choice, constraints))
+      })
+      .inspect(move |_| counts.kept.set(counts.kept.get() + 1))
+  }
+
+  /// The combinations of digits for the run at `run_idx`, with the letters
+  /// they assign, each numbered as in `ChoiceId::combination_index`.
+  fn combinations(&self, run_idx: usize) -> impl Iterator<Item = (usize, ClueCombination)> {
+    let run = &self.runs[run_idx].0;
+    let num_tiles = run.cells.len() as u32;
+    let mut cache = self.cache.borrow_mut();
+    if self.relaxed.contains(run.id()) {
+      Either::Left(TotalClue::unconstrained_combinations(num_tiles, &mut cache))
+    } else {
+      Either::Right(
+        run
+          .clue
+          .all_combinations(num_tiles, self.partial, &mut cache),
+      )
+    }
+    .enumerate()
+  }
+
+  /// Decodes the digits each of `choices` places in its run, ordered like
+  /// `Kakuro::runs`.
+  fn fills(&self, choices: &[ChoiceId]) -> Vec<RunFill> {
+    choices
+      .iter()
+      .map(|&choice| {
+        let (_, (_, combination)) = self
+          .combinations(choice.run_index())
+          .nth(choice.combination_index())
+          .expect("Chosen subsets name a combination of their run");
+        RunFill {
+          run: self.runs[choice.run_index()].0.clone(),
+          choice,
+          digits: nth_permutation(&combination, choice.permutation_index()),
+        }
+      })
+      .sorted_by_key(|fill| (fill.run.start, fill.run.direction == Direction::Vertical))
+      .collect()
+  }
+}
+
+/// The exact cover model of a `Kakuro` built from the equation of each run,
+/// from `Kakuro::solve_via_linear`. Each subset is one solution of a run's
+/// equation, named by the run's clue item and the values of its variables.
+struct LinearKakuroModel<'a> {
+  kakuro: &'a Kakuro,
+  /// The clue item of each run, with its equation from `Kakuro::run_equation`.
+  runs: Vec<(DlxItem, Option<EquationSystem<DlxItem>>)>,
+}
+
+impl ExactCoverModel for LinearKakuroModel<'_> {
+  type Item = DlxItem;
+  type Choice = (DlxItem, Vec<i32>);
+  type Solution = SolvedKakuro;
+
+  fn items(&self) -> impl Iterator<Item = (DlxItem, HeaderType)> + '_ {
+    self.kakuro.all_items()
+  }
+
+  fn choices(
+    &self,
+  ) -> impl Iterator<
+    Item = (
+      (DlxItem, Vec<i32>),
+      impl IntoIterator<Item = Constraint<DlxItem>>,
+    ),
+  > + '_ {
+    self.runs.iter().flat_map(|(run_item, system)| {
+      system.iter().flat_map(move |system| {
+        to_dlx_choices(system, |assignment| {
+          let letters = assignment
+            .iter()
+            .filter_map(|&(item, value)| match item {
+              DlxItem::Letter { letter } => Some((*letter, value as u32)),
+              _ => None,
+            })
+            .collect_vec();
+          let values = assignment.iter().map(|&(_, value)| value).collect_vec();
+          (letters, values)
+        })
+        .map(move |((letters, values), mut constraints)| {
+          constraints.push(Constraint::Primary(run_item.clone()));
+          // Colors each value with the letter taking it, so no two letters
+          // share a value.
+          constraints.extend(letters.into_iter().map(|(letter, value)| {
+            let idx = LetterAssignment::letter_idx(letter).unwrap_or_else(|| {
+              unreachable!("solve_via_linear rejects letters outside of the alphabet")
+            });
+            ColorItem::new(DlxItem::LetterValue { value }, idx as u32).into()
+          }));
+          ((run_item.clone(), values), constraints)
+        })
+      })
+    })
+  }
+
+  fn decode(&self, selection: Selection<DlxItem, (DlxItem, Vec<i32>)>) -> SolvedKakuro {
+    self.kakuro.decode_solution(&selection.colors)
+  }
+}
+
+/// Measurements of one solve, from `Kakuro::solve_instrumented`.
+#[derive(Clone, Debug, Serialize)]
+pub struct KakuroSolveStats {
+  /// The number of runs in the puzzle.
+  pub runs: usize,
+  /// The digit combinations generated for every run's clue.
+  pub combinations: u64,
+  /// The orderings of the combinations which survived pruning.
+  pub permutations: u64,
+  /// The permutations which were consistent with the puzzle's givens and
+  /// letters, and so became subsets in the DLX model.
+  pub subsets: u64,
+  /// The subsets tried by the search.
+  pub nodes: u64,
+  /// The solutions found by the search, before collapsing any with the same
+  /// letter assignment.
+  pub solutions: u64,
+  /// The wall time of the whole solve, in seconds.
+  pub seconds: f64,
+}
+
+/// A run of unknown tiles to the right of or below a clue, which must add up
+/// to the clue's total.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Run {
+  /// The clue tile the run starts from.
+  pub start: (usize, usize),
+  pub direction: Direction,
+  /// The unknown tiles in the run, in order away from the clue.
+  pub cells: Vec<(usize, usize)>,
+  pub clue: TotalClue,
+}
+
+impl Run {
+  pub fn id(&self) -> RunId {
+    RunId {
+      start: self.start,
+      direction: self.direction,
+    }
+  }
+}
+
+/// Identifies a run of a puzzle by its clue tile and direction.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct RunId {
+  pub start: (usize, usize),
+  pub direction: Direction,
+}
+
+/// The name of a subset in the DLX model of a `Kakuro`: the run it fills, by
+/// its position in the order subsets are generated, the combination of digits
+/// it takes from those of the run's clue, and the order it places them in as
+/// an index into their permutations. The three are packed into one u64.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct ChoiceId(u64);
+
+impl ChoiceId {
+  const RUN_BITS: u32 = 16;
+  const INDEX_BITS: u32 = 24;
+  const INDEX_MASK: u64 = (1 << Self::INDEX_BITS) - 1;
+
+  /// Panics if `run_index` doesn't fit in 16 bits, or either of the other
+  /// indices in 24 bits. A run of 9 tiles has fewer than 2^19 permutations.
+  pub fn new(run_index: usize, combination_index: usize, permutation_index: usize) -> Self {
+    assert!(
+      run_index < 1 << Self::RUN_BITS,
+      "Run index {run_index} out of range"
+    );
+    assert!(
+      combination_index < 1 << Self::INDEX_BITS && permutation_index < 1 << Self::INDEX_BITS,
+      "Combination index {combination_index} or permutation index {permutation_index} out of \
+       range"
+    );
+    Self(
+      ((run_index as u64) << (2 * Self::INDEX_BITS))
+        | ((combination_index as u64) << Self::INDEX_BITS)
+        | permutation_index as u64,
+    )
+  }
+
+  pub fn run_index(&self) -> usize {
+    (self.0 >> (2 * Self::INDEX_BITS)) as usize
+  }
+
+  pub fn combination_index(&self) -> usize {
+    ((self.0 >> Self::INDEX_BITS) & Self::INDEX_MASK) as usize
+  }
+
+  pub fn permutation_index(&self) -> usize {
+    (self.0 & Self::INDEX_MASK) as usize
+  }
+}
+
+/// The digits placed in one run of a solution, decoded from the `ChoiceId` of
+/// the subset the search chose for it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RunFill {
+  pub run: Run,
+  pub choice: ChoiceId,
+  /// The digit in each of the run's cells, in order away from the clue.
+  pub digits: Vec<u32>,
+}
+
+impl Display for RunFill {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "run at {:?} {} used digits {} in that order",
+      self.run.start,
+      self.run.direction,
+      self.digits.iter().join(",")
+    )
+  }
+}
+
+/// The reason a `Kakuro` has no solution, as found by `Kakuro::diagnose`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum KakuroDiagnosis {
+  /// The grid isn't a well-formed kakuro.
+  InvalidStructure(Vec<KakuroStructureError>),
+  /// No combination of distinct digits across the run starting from `start`
+  /// adds up to its clue.
+  ImpossibleRun {
+    start: (usize, usize),
+    direction: Direction,
+    clue: String,
+  },
+  /// The tile at `(row, col)` can't take any digit allowed by its runs.
+  EmptyCandidates { row: usize, col: usize },
+  /// The search most often failed to fill the run starting from `start`,
+  /// doing so `dead_ends` times.
+  SearchDeadEnd {
+    start: (usize, usize),
+    direction: Direction,
+    clue: String,
+    dead_ends: u64,
+  },
+}
+
+/// The distinct assignments among `letters`, ordered by their `int_value`.
+/// Assignments with unassigned letters come first.
+fn distinct_letters(letters: impl IntoIterator<Item = LetterAssignment>) -> Vec<LetterAssignment> {
+  letters
+    .into_iter()
+    .unique()
+    .sorted_by_cached_key(|letters| (letters.try_int_value().ok(), letters.clone()))
+    .collect()
+}
+
+/// A complete solution to a `Kakuro`: the value of every letter, and the digit
+/// in every unknown tile.
+pub struct SolvedKakuro {
+  cols: usize,
+  letters: LetterAssignment,
+  /// The digit in each tile, in row-major order, or None for tiles which
+  /// aren't unknowns.
+  digits: Vec<Option<u32>>,
+}
+
+impl SolvedKakuro {
+  pub fn letters(&self) -> &LetterAssignment {
+    &self.letters
+  }
+
+  pub fn into_letters(self) -> LetterAssignment {
+    self.letters
+  }
+
+  /// The distinct letter assignments among `solutions`, ordered by their
+  /// `int_value`. Assignments with unassigned letters come first.
+  fn distinct_letters(solutions: Vec<SolvedKakuro>) -> Vec<LetterAssignment> {
+    distinct_letters(solutions.into_iter().map(SolvedKakuro::into_letters))
+  }
+
+  /// The digit in the tile at `(row, col)`, or None if that tile isn't an
+  /// unknown.
+  pub fn digit(&self, row: usize, col: usize) -> Option<u32> {
+    if col >= self.cols {
+      return None;
+    }
+    self.digits.get(row * self.cols + col).copied().flatten()
+  }
+
+  /// The digits of the solution as rows, with None for non-unknown tiles.
+  pub fn digit_grid(&self) -> Vec<Vec<Option<u32>>> {
+    self.digits.chunks(self.cols).map(<[_]>::to_vec).collect()
+  }
+}
+
+/// Why a puzzle line couldn't be parsed by `Kakuro::from_str`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ParseKakuroError(pub String);
+
+impl Display for ParseKakuroError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
+
+impl Error for ParseKakuroError {}
+
+/// Parses one puzzle in the line format read by `Kakuro::from_reader`.
+impl FromStr for Kakuro {
+  type Err = ParseKakuroError;
+
+  fn from_str(line: &str) -> Result<Self, Self::Err> {
+    Self::parse_line(line)
+      .map_err(ParseKakuroError)?
+      .ok_or_else(|| ParseKakuroError("No puzzle on the line".to_string()))
+  }
+}
+
+impl Index<(usize, usize)> for Kakuro {
+  type Output = Tile;
+
+  fn index(&self, (row, col): (usize, usize)) -> &Tile {
+    self.tile(row, col).unwrap_or_else(|| {
+      panic!(
+        "Tile ({row}, {col}) out of bounds for {}x{} kakuro",
+        self.rows, self.cols
+      )
+    })
+  }
+}
+
+/// The distinct puzzles of `puzzles` up to renaming letters and transposing,
+/// see `Kakuro::canonical_key`, each with the number of times it appeared.
+/// The first of each is kept, in the order they first appeared.
+pub fn dedup(puzzles: impl IntoIterator<Item = Kakuro>) -> Vec<(Kakuro, usize)> {
+  let mut indices: HashMap<String, usize> = HashMap::new();
+  let mut distinct: Vec<(Kakuro, usize)> = Vec::new();
+  for kakuro in puzzles {
+    match indices.entry(kakuro.canonical_key()) {
+      Entry::Occupied(entry) => distinct[*entry.get()].1 += 1,
+      Entry::Vacant(entry) => {
+        entry.insert(distinct.len());
+        distinct.push((kakuro, 1));
+      }
+    }
+  }
+  distinct
+}
+
+impl Kakuro {
+  /// Writes the grid with lines drawn around every run, and between tiles
+  /// which aren't part of the same run.
+  fn fmt_boxed(&self, f: &mut fmt::Formatter, cells: &[String]) -> fmt::Result {
+    let widths = self.column_widths(cells);
+    let is_unknown = |row: usize, col: usize| matches!(self[(row, col)], Tile::Unknown(_));
+    // Whether a line separates the tile at (row, col) from the one to its
+    // left, or above it. Lines are always drawn along the border.
+    let left_wall = |row: usize, col: usize| {
+      col == 0 || col == self.cols || !(is_unknown(row, col - 1) && is_unknown(row, col))
+    };
+    let top_wall = |row: usize, col: usize| {
+      row == 0 || row == self.rows || !(is_unknown(row - 1, col) && is_unknown(row, col))
+    };
+
+    for row in 0..=self.rows {
+      for col in 0..=self.cols {
+        let up = row > 0 && left_wall(row - 1, col);
+        let down = row < self.rows && left_wall(row, col);
+        let left = col > 0 && top_wall(row, col - 1);
+        let right = col < self.cols && top_wall(row, col);
+        write!(f, "{}", box_junction(up, down, left, right))?;
+        if let Some(&width) = widths.get(col) {
+          let line = if right { "─" } else { " " };
+          write!(f, "{}", line.repeat(width))?;
+        }
+      }
+      writeln!(f)?;
+
+      if row < self.rows {
+        for col in 0..=self.cols {
+          write!(f, "{}", if left_wall(row, col) { '│' } else { ' ' })?;
+          if let Some(&width) = widths.get(col) {
+            write!(f, "{:>width$}", cells[self.get_idx(row, col)])?;
+          }
+        }
+        writeln!(f)?;
+      }
+    }
+    Ok(())
+  }
+}
+
+/// The box-drawing character joining lines in the given directions.
+fn box_junction(up: bool, down: bool, left: bool, right: bool) -> char {
+  match (up, down, left, right) {
+    (false, false, false, false) => ' ',
+    (true, false, false, false) => '╵',
+    (false, true, false, false) => '╷',
+    (false, false, true, false) => '╴',
+    (false, false, false, true) => '╶',
+    (true, true, false, false) => '│',
+    (false, false, true, true) => '─',
+    (false, true, false, true) => '┌',
+    (false, true, true, false) => '┐',
+    (true, false, false, true) => '└',
+    (true, false, true, false) => '┘',
+    (true, true, false, true) => '├',
+    (true, true, true, false) => '┤',
+    (false, true, true, true) => '┬',
+    (true, false, true, true) => '┴',
+    (true, true, true, true) => '┼',
+  }
+}
+
+/// Draws the grid with each column aligned to its widest tile, and clues as
+/// `vertical\horizontal`. The alternate flag (`{:#}`) also draws lines around
+/// each run.
+impl fmt::Display for Kakuro {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let cells = self.tiles.iter().map(Tile::to_string).collect_vec();
+    if f.alternate() {
+      self.fmt_boxed(f, &cells)
+    } else {
+      writeln!(f, "{}", self.layout(&cells))
+    }
+  }
+}
+
+/// Controls the appearance of `Kakuro::to_svg`.
+#[derive(Clone, Debug)]
+pub struct SvgOptions {
+  /// The width and height of each tile, in pixels.
+  pub cell_size: f64,
+  pub font_family: String,
+  /// The font size of the clues, in pixels.
+  pub clue_font_size: f64,
+  /// The font size of the digits and letters in unknown tiles, in pixels.
+  pub cell_font_size: f64,
+}
+
+impl Default for SvgOptions {
+  fn default() -> Self {
+    Self {
+      cell_size: 40.,
+      font_family: "sans-serif".to_string(),
+      clue_font_size: 12.,
+      cell_font_size: 24.,
+    }
+  }
+}
+
+/// Escapes the characters which can't appear literally in SVG text or
+/// attribute values.
+fn escape_xml(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+impl Kakuro {
+  /// Draws the puzzle as an SVG image. Clue tiles are split by a diagonal,
+  /// with the vertical clue below it and the horizontal clue above, and
+  /// unknown tiles show their given digit or hint letter. With a `solution`,
+  /// every unknown tile also shows its digit, moving any hint letter to the
+  /// corner.
+  pub fn to_svg(&self, solution: Option<&SolvedKakuro>, opts: SvgOptions) -> String {
+    let size = opts.cell_size;
+    let (width, height) = (self.cols as f64 * size, self.rows as f64 * size);
+    let text = |x: f64, y: f64, font_size: f64, class: &str, content: &str| {
+      format!(
+        "<text class=\"{class}\" x=\"{x}\" y=\"{y}\" font-size=\"{font_size}\">{}</text>",
+        escape_xml(content)
+      )
+    };
+
+    let mut svg = vec![
+      format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+         viewBox=\"0 0 {width} {height}\" font-family=\"{}\" text-anchor=\"middle\" \
+         dominant-baseline=\"central\">",
+        escape_xml(&opts.font_family)
+      ),
+      "<style>.solution { fill: #1f5fbf; }</style>".to_string(),
+    ];
+    for ((row, col), tile) in self.tiles_with_coords() {
+      let (x, y) = (col as f64 * size, row as f64 * size);
+      let fill = match tile {
+        Tile::Empty => "#333333",
+        Tile::Total(_) => "#cccccc",
+        Tile::Unknown(_) => "#ffffff",
+      };
+      svg.push(format!(
+        "<rect x=\"{x}\" y=\"{y}\" width=\"{size}\" height=\"{size}\" fill=\"{fill}\" \
+         stroke=\"#000000\"/>"
+      ));
+
+      match tile {
+        Tile::Empty => {}
+        Tile::Total(total_tile) => {
+          svg.push(format!(
+            "<line x1=\"{x}\" y1=\"{y}\" x2=\"{}\" y2=\"{}\" stroke=\"#000000\"/>",
+            x + size,
+            y + size
+          ));
+          if let Some(clue) = total_tile.vertical() {
+            svg.push(text(
+              x + size * 0.3,
+              y + size * 0.75,
+              opts.clue_font_size,
+              "clue",
+              &clue.to_string(),
+            ));
+          }
+          if let Some(clue) = total_tile.horizontal() {
+            svg.push(text(
+              x + size * 0.7,
+              y + size * 0.25,
+              opts.clue_font_size,
+              "clue",
+              &clue.to_string(),
+            ));
+          }
+        }
+        Tile::Unknown(unknown) => {
+          let (cx, cy) = (x + size / 2., y + size / 2.);
+          let solved = solution.and_then(|solution| solution.digit(row, col));
+          match (unknown, solved) {
+            (UnknownTile::Given { digit }, _) => svg.push(text(
+              cx,
+              cy,
+              opts.cell_font_size,
+              "given",
+              &digit.to_string(),
+            )),
+            (UnknownTile::Prefilled { hint }, Some(digit)) => {
+              svg.push(text(
+                x + size * 0.2,
+                y + size * 0.2,
+                opts.clue_font_size,
+                "hint",
+                &hint.to_string(),
+              ));
+              svg.push(text(
+                cx,
+                cy,
+                opts.cell_font_size,
+                "solution",
+                &digit.to_string(),
+              ));
+            }
+            (UnknownTile::Prefilled { hint }, None) => {
+              svg.push(text(cx, cy, opts.cell_font_size, "hint", &hint.to_string()))
+            }
+            (UnknownTile::Blank, Some(digit)) => svg.push(text(
+              cx,
+              cy,
+              opts.cell_font_size,
+              "solution",
+              &digit.to_string(),
+            )),
+            (UnknownTile::Blank, None) => {}
+          }
+        }
+      }
+    }
+    svg.push("</svg>".to_string());
+    svg.join("\n") + "\n"
+  }
+}
+
+/// A small deterministic pseudo-random number generator (SplitMix64), so that
+/// generated puzzles are reproducible from their seed.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+  fn next_u64(&mut self) -> u64 {
+    self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = self.0;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+  }
+
+  /// A uniformly random index below `n`.
+  fn below(&mut self, n: usize) -> usize {
+    (self.next_u64() % n as u64) as usize
+  }
+
+  /// True with probability `p`.
+  fn chance(&mut self, p: f64) -> bool {
+    ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < p
+  }
+
+  fn shuffle<T>(&mut self, items: &mut [T]) {
+    for i in (1..items.len()).rev() {
+      items.swap(i, self.below(i + 1));
+    }
+  }
+}
+
+/// The number of random layouts `generate` tries before giving up.
+const GENERATE_ATTEMPTS: usize = 1000;
+
+/// Generates a random letter-encoded kakuro with a unique solution. Roughly
+/// `density` of the tiles off the top row and left column are unknowns, and
+/// the same `seed` always produces the same puzzle.
+///
+/// Returns None if none of `GENERATE_ATTEMPTS` layouts could be encoded
+/// uniquely, which happens on grids too small to use 9 of the letters.
+///
+/// Panics if the grid is smaller than 3x3 or `density` isn't in `(0, 1]`.
+pub fn generate(rows: usize, cols: usize, density: f64, seed: u64) -> Option<Kakuro> {
+  generate_with_letters(rows, cols, density, seed).map(|(puzzle, _)| puzzle)
+}
+
+/// Generates a puzzle as in `generate`, along with the letter assignment it
+/// was encoded with.
+fn generate_with_letters(
+  rows: usize,
+  cols: usize,
+  density: f64,
+  seed: u64,
+) -> Option<(Kakuro, LetterAssignment)> {
+  assert!(
+    rows >= 3 && cols >= 3,
+    "Can't generate a {rows}x{cols} kakuro"
+  );
+  assert!(
+    density > 0. && density <= 1.,
+    "Density must be in (0, 1], got {density}"
+  );
+
+  let mut rng = SplitMix64(seed);
+  for _ in 0..GENERATE_ATTEMPTS {
+    let blanks = random_layout(rows, cols, density, &mut rng);
+    if !blanks.contains(&true) {
+      continue;
+    }
+    let puzzle = Kakuro {
+      rows,
+      cols,
+      alphabet: MAX_ALPHABET_SIZE,
+      tiles: blanks
+        .iter()
+        .enumerate()
+        .map(|(idx, &blank)| {
+          let (row, col) = (idx / cols, idx % cols);
+          let starts_run = |neighbor: Option<usize>| {
+            (!blank && neighbor.is_some_and(|idx| blanks[idx])).then_some(TotalClue::Numeric(0))
+          };
+          if blank {
+            Tile::Unknown(UnknownTile::Blank)
+          } else {
+            match (
+              starts_run((col + 1 < cols).then_some(idx + 1)),
+              starts_run((row + 1 < rows).then_some(idx + cols)),
+            ) {
+              (None, None) => Tile::Empty,
+              (horizontal, vertical) => Tile::Total(TotalTile::new(horizontal, vertical)),
+            }
+          }
+        })
+        .collect(),
+    };
+
+    let Some(digits) = random_fill(&puzzle, &mut rng) else {
+      continue;
+    };
+    let mut letter_for_digit: Vec<char> = ('A'..='J').collect();
+    rng.shuffle(&mut letter_for_digit);
+    if let Some(puzzle) = encode_unique(puzzle, &digits, &letter_for_digit, &mut rng) {
+      let letters = letter_for_digit
+        .iter()
+        .zip(0..)
+        .fold(LetterAssignment::new(), |letters, (&letter, digit)| {
+          letters.with_value(letter, digit)
+        });
+      return Some((puzzle, letters));
+    }
+  }
+  None
+}
+
+/// Picks which tiles of a `rows`x`cols` grid are unknowns, returned in
+/// row-major order. Every run of unknowns ends up between 2 and 9 tiles long.
+fn random_layout(rows: usize, cols: usize, density: f64, rng: &mut SplitMix64) -> Vec<bool> {
+  let mut blanks: Vec<bool> = (0..rows * cols)
+    .map(|idx| idx / cols != 0 && idx % cols != 0 && rng.chance(density))
+    .collect();
+
+  let lines = (0..rows)
+    .map(|row| (0..cols).map(|col| row * cols + col).collect_vec())
+    .chain((0..cols).map(|col| (0..rows).map(|row| row * cols + col).collect_vec()))
+    .collect_vec();
+  // Walling off tiles only ever shortens runs, so this reaches a fixpoint.
+  let mut changed = true;
+  while changed {
+    changed = false;
+    for line in &lines {
+      let runs = line
+        .iter()
+        .group_by(|&&idx| blanks[idx])
+        .into_iter()
+        .filter(|(is_blank, _)| *is_blank)
+        .map(|(_, run)| run.copied().collect_vec())
+        .collect_vec();
+      for run in runs {
+        if run.len() == 1 {
+          blanks[run[0]] = false;
+          changed = true;
+        } else if run.len() > 9 {
+          blanks[run[rng.below(run.len())]] = false;
+          changed = true;
+        }
+      }
+    }
+  }
+  blanks
+}
+
+/// Fills the unknowns of `puzzle` with random digits which are distinct
+/// within every run, returning the digit for each tile in row-major order, or
+/// None if the runs can't be filled. The fill is the first solution of the
+/// puzzle's model with every clue relaxed, searched in a random order.
+fn random_fill(puzzle: &Kakuro, rng: &mut SplitMix64) -> Option<Vec<u32>> {
+  let partial = LetterAssignment::for_alphabet(puzzle.alphabet);
+  let mut cache = CombinationCache::new();
+  let model = ShuffledModel {
+    model: puzzle.model(&partial, &mut cache, false, Relaxed::AllRuns),
+    rng: RefCell::new(rng),
+  };
+  let solved = solve_model_with(&model, SearchLimits::first())
+    .solutions
+    .into_iter()
+    .next()?;
+  Some(
+    solved
+      .digits
+      .into_iter()
+      .map(|digit| digit.unwrap_or(0))
+      .collect(),
+  )
+}
+
+/// An exact cover model with the subsets of `model` listed in a random order,
+/// so the first solution found depends on the seed of `rng`.
+struct ShuffledModel<'a, M> {
+  model: M,
+  rng: RefCell<&'a mut SplitMix64>,
+}
+
+impl<M: ExactCoverModel> ExactCoverModel for ShuffledModel<'_, M> {
+  type Item = M::Item;
+  type Choice = M::Choice;
+  type Solution = M::Solution;
+
+  fn items(&self) -> impl Iterator<Item = (M::Item, HeaderType)> + '_ {
+    self.model.items()
+  }
+
+  fn choices(
+    &self,
+  ) -> impl Iterator<Item = (M::Choice, impl IntoIterator<Item = Constraint<M::Item>>)> + '_ {
+    let mut choices = self
+      .model
+      .choices()
+      .map(|(choice, constraints)| (choice, constraints.into_iter().collect_vec()))
+      .collect_vec();
+    self.rng.borrow_mut().shuffle(&mut choices);
+    choices.into_iter()
+  }
+
+  fn prepare(&self, dlx: &mut Dlx<M::Item, M::Choice>) {
+    self.model.prepare(dlx);
+  }
+
+  fn decode(&self, selection: Selection<M::Item, M::Choice>) -> M::Solution {
+    self.model.decode(selection)
+  }
+}
+
+/// Writes the run totals of the filled `puzzle` with `letter_for_digit`, and
+/// chooses which tiles to leave as letter hints so the puzzle has a unique
+/// solution. Returns None if a total can't be written as a clue, or if even
+/// hinting every tile leaves it ambiguous.
+fn encode_unique(
+  mut puzzle: Kakuro,
+  digits: &[u32],
+  letter_for_digit: &[char],
+  rng: &mut SplitMix64,
+) -> Option<Kakuro> {
+  let encode = |total: u32| {
+    TotalClue::parse(
+      &total
+        .to_string()
+        .chars()
+        .map(|digit| letter_for_digit[digit.to_digit(10).unwrap() as usize])
+        .collect::<String>(),
+    )
+    .ok()
+  };
+  for run in puzzle.runs() {
+    let total = run
+      .cells
+      .iter()
+      .map(|&(row, col)| digits[puzzle.get_idx(row, col)])
+      .sum();
+    let idx = puzzle.get_idx(run.start.0, run.start.1);
+    if let Tile::Total(total_tile) = &mut puzzle.tiles[idx] {
+      match run.direction {
+        Direction::Horizontal => total_tile.horizontal = Some(encode(total)?),
+        Direction::Vertical => total_tile.vertical = Some(encode(total)?),
+      }
+    }
+  }
+
+  // Start with every tile hinted, which pins down every digit, then drop
+  // hints in a random order as long as the solution stays unique.
+  let mut unknowns = (0..puzzle.tiles.len())
+    .filter(|&idx| digits[idx] != 0)
+    .collect_vec();
+  for &idx in &unknowns {
+    puzzle.tiles[idx] = Tile::Unknown(UnknownTile::Prefilled {
+      hint: letter_for_digit[digits[idx] as usize],
+    });
+  }
+  // Letters which appear nowhere in the puzzle could take any value, and only
+  // one can be left for the solver to infer from the others.
+  if puzzle.letters_used().collect::<HashSet<_>>().len() < MAX_ALPHABET_SIZE - 1 {
+    return None;
+  }
+  let is_unique = |puzzle: &Kakuro| {
+    puzzle
+      .solve_full()
+      .is_ok_and(|solutions| solutions.len() == 1)
+  };
+  if !is_unique(&puzzle) {
+    return None;
+  }
+
+  rng.shuffle(&mut unknowns);
+  for idx in unknowns {
+    let hint = std::mem::replace(&mut puzzle.tiles[idx], Tile::Unknown(UnknownTile::Blank));
+    if puzzle.letters_used().collect::<HashSet<_>>().len() < MAX_ALPHABET_SIZE - 1
+      || !is_unique(&puzzle)
+    {
+      puzzle.tiles[idx] = hint;
+    }
+  }
+  Some(puzzle)
+}
+
+#[cfg(test)]
+mod test {
+  use std::{
+    collections::{HashMap, HashSet},
+    iter,
+    sync::Arc,
+    time::Duration,
+    vec,
+  };
+
+  use itertools::Itertools;
+
+  use crate::exact_cover::build_dlx;
+
+  use super::{
+    dedup, generate, generate_with_letters, nth_permutation, solve_p424, BatchRunner, CacheStats,
+    ChoiceId, ClueDigit, ClueParseError, CombinationCache, DigitSet, Direction, DlxItem, Kakuro,
+    KakuroDiagnosis, KakuroError, KakuroStructureError, LetterAssignment, LetterAssignmentError,
+    ParseKakuroError, PuzzleOutcome, Relaxed, Run, RunId, SolutionError, SolveTimeout,
+    SolvedKakuro, SvgOptions, Tile, TotalClue, TotalTile, UnassignedLetter, UnknownTile,
+    MAX_ALPHABET_SIZE, SUBSET_SUM_COUNTS,
+  };
+
+  const PUZZLE_A: &str = "6,X,(vIJ),(vIE),X,X,X,(hF),O,C,(vIA),X,X,(hCA),O,O,D,(vIH),X,X,(hIB),E,O,O,(vF),X,X,(hD),O,A,O,X,X,X,(hID),O,G";
+  const PUZZLE_B: &str = "6,X,X,X,X,(vEH),(vEC),X,X,X,(hEB,vEJ),O,O,X,X,(hEC,vEF),O,O,B,X,(hDD,vEI),O,B,C,X,(hB),O,D,A,X,X,(hEC),O,O,X,X,X";
+
+  fn parse(line: &str) -> Kakuro {
+    Kakuro::from_reader(line.as_bytes()).unwrap().pop().unwrap()
+  }
+
+  fn all_combinations(range: (u32, u32), num_tiles: u32) -> Vec<Vec<u32>> {
+    TotalClue::all_combinations_for_range(range, num_tiles)
+      .map(|(total, nums)| {
+        assert_eq!(nums.iter().sum::<u32>(), total);
+        nums
+      })
+      .collect()
+  }
+
+  #[test]
+  fn test_combinations_one() {
+    assert_eq!(
+      all_combinations((2, 5), 1),
+      vec![vec![2], vec![3], vec![4], vec![5]]
+    );
+  }
+
+  #[test]
+  fn test_combinations_wide_range() {
+    assert_eq!(
+      all_combinations((0, 12), 1),
+      vec![
+        vec![1],
+        vec![2],
+        vec![3],
+        vec![4],
+        vec![5],
+        vec![6],
+        vec![7],
+        vec![8],
+        vec![9]
+      ]
+    );
+  }
+
+  #[test]
+  fn test_combinations_two() {
+    assert_eq!(
+      all_combinations((2, 5), 2),
+      vec![vec![1, 2], vec![1, 3], vec![1, 4], vec![2, 3]]
+    );
+  }
+
+  #[test]
+  fn test_combinations_large_range() {
+    assert_eq!(
+      all_combinations((10, 20), 2),
+      vec![
+        vec![1, 9],
+        vec![2, 8],
+        vec![2, 9],
+        vec![3, 7],
+        vec![3, 8],
+        vec![3, 9],
+        vec![4, 6],
+        vec![4, 7],
+        vec![4, 8],
+        vec![4, 9],
+        vec![5, 6],
+        vec![5, 7],
+        vec![5, 8],
+        vec![5, 9],
+        vec![6, 7],
+        vec![6, 8],
+        vec![6, 9],
+        vec![7, 8],
+        vec![7, 9],
+        vec![8, 9],
+      ]
+    );
+  }
+
+  #[test]
+  fn test_combinations_large_range_three() {
+    assert_eq!(
+      all_combinations((10, 20), 3),
+      vec![
+        vec![1, 2, 7],
+        vec![1, 2, 8],
+        vec![1, 2, 9],
+        vec![1, 3, 6],
+        vec![1, 3, 7],
+        vec![1, 3, 8],
+        vec![1, 3, 9],
+        vec![1, 4, 5],
+        vec![1, 4, 6],
+        vec![1, 4, 7],
+        vec![1, 4, 8],
+        vec![1, 4, 9],
+        vec![1, 5, 6],
+        vec![1, 5, 7],
+        vec![1, 5, 8],
+        vec![1, 5, 9],
+        vec![1, 6, 7],
+        vec![1, 6, 8],
+        vec![1, 6, 9],
+        vec![1, 7, 8],
+        vec![1, 7, 9],
+        vec![1, 8, 9],
+        vec![2, 3, 5],
+        vec![2, 3, 6],
+        vec![2, 3, 7],
+        vec![2, 3, 8],
+        vec![2, 3, 9],
+        vec![2, 4, 5],
+        vec![2, 4, 6],
+        vec![2, 4, 7],
+        vec![2, 4, 8],
+        vec![2, 4, 9],
+        vec![2, 5, 6],
+        vec![2, 5, 7],
+        vec![2, 5, 8],
+        vec![2, 5, 9],
+        vec![2, 6, 7],
+        vec![2, 6, 8],
+        vec![2, 6, 9],
+        vec![2, 7, 8],
+        vec![2, 7, 9],
+        vec![2, 8, 9],
+        vec![3, 4, 5],
+        vec![3, 4, 6],
+        vec![3, 4, 7],
+        vec![3, 4, 8],
+        vec![3, 4, 9],
+        vec![3, 5, 6],
+        vec![3, 5, 7],
+        vec![3, 5, 8],
+        vec![3, 5, 9],
+        vec![3, 6, 7],
+        vec![3, 6, 8],
+        vec![3, 6, 9],
+        vec![3, 7, 8],
+        vec![3, 7, 9],
+        vec![3, 8, 9],
+        vec![4, 5, 6],
+        vec![4, 5, 7],
+        vec![4, 5, 8],
+        vec![4, 5, 9],
+        vec![4, 6, 7],
+        vec![4, 6, 8],
+        vec![4, 6, 9],
+        vec![4, 7, 8],
+        vec![4, 7, 9],
+        vec![5, 6, 7],
+        vec![5, 6, 8],
+        vec![5, 6, 9],
+        vec![5, 7, 8],
+      ]
+    );
+  }
+
+  #[test]
+  fn test_cache_shared_across_puzzles() {
+    let mut cache = CombinationCache::new();
+
+    let letters = parse(PUZZLE_A).solve_with_cache(&mut cache).unwrap();
+    assert_eq!(letters.first().unwrap().int_value(), 4027398516);
+    let first_stats = cache.stats();
+    assert_eq!(first_stats.misses, cache.len() as u64);
+
+    // Solving the same puzzle again should be served entirely from the cache.
+    parse(PUZZLE_A).solve_with_cache(&mut cache).unwrap();
+    assert_eq!(cache.stats().misses, first_stats.misses);
+    assert!(cache.stats().hits > first_stats.hits);
+
+    // Every miss for the second puzzle must be for a key not seen before.
+    let letters = parse(PUZZLE_B).solve_with_cache(&mut cache).unwrap();
+    assert_eq!(letters.first().unwrap().int_value(), 4762183509);
+    assert_eq!(cache.stats().misses, cache.len() as u64);
+  }
+
+  #[test]
+  fn test_cache_total_shares_range() {
+    let mut cache = CombinationCache::new();
+    let total = cache.combinations_for_total(15, 3);
+    assert_eq!(
+      total.iter().map(|(_, digits)| digits.clone()).collect_vec(),
+      TotalClue::all_combinations_for_total(15, 3).collect_vec()
+    );
+    assert!(Arc::ptr_eq(
+      &total,
+      &cache.combinations_for_range((15, 15), 3)
+    ));
+    assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 1 });
+  }
+
+  #[test]
+  fn test_combinations_infeasible_ranges() {
+    // Smallest sum of 3 digits is 6.
+    assert!(all_combinations((3, 4), 3).is_empty());
+    // Largest sum of 5 digits is 35.
+    assert!(all_combinations((50, 60), 5).is_empty());
+    assert!(all_combinations((10, 45), 1).is_empty());
+    assert!(all_combinations((31, 45), 3).is_empty());
+    assert!(all_combinations((41, 45), 4).is_empty());
+  }
+
+  #[test]
+  fn test_combinations_empty_range() {
+    assert!(all_combinations((10, 5), 2).is_empty());
+    assert!(all_combinations((45, 0), 9).is_empty());
+  }
+
+  #[test]
+  fn test_combinations_invalid_num_tiles() {
+    assert!(all_combinations((0, 45), 0).is_empty());
+    assert!(all_combinations((0, 100), 10).is_empty());
+    assert!(all_combinations((0, 100), u32::MAX).is_empty());
+  }
+
+  #[test]
+  fn test_combinations_match_brute_force() {
+    for num_tiles in 0..=10 {
+      for min in 0..=50 {
+        for max in 0..=50 {
+          assert_eq!(
+            TotalClue::all_combinations_for_range((min, max), num_tiles).collect_vec(),
+            TotalClue::brute_force_combinations_for_range((min, max), num_tiles),
+            "Mismatch for range ({min}, {max}) with {num_tiles} tiles"
+          );
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn test_filtered_combinations() {
+    assert_eq!(
+      TotalClue::all_combinations_for_range_filtered(
+        (10, 10),
+        3,
+        DigitSet::new().with(5),
+        DigitSet::new().with(1)
+      )
+      .collect_vec(),
+      vec![(10, vec![2, 3, 5])]
+    );
+  }
+
+  #[test]
+  fn test_filtered_combinations_match_post_filtering() {
+    let ranges = [(0, 45), (3, 9), (10, 20), (15, 15), (30, 45)];
+    for num_tiles in 0..=10 {
+      for &range in &ranges {
+        for required_mask in (0u16..(1 << 10)).step_by(29) {
+          for forbidden_mask in (0u16..(1 << 10)).step_by(31) {
+            let from_mask = |mask: u16| (0..=9).filter(move |digit| mask & (1 << digit) != 0);
+            let required: DigitSet = from_mask(required_mask).collect();
+            let forbidden: DigitSet = from_mask(forbidden_mask).collect();
+            assert_eq!(
+              TotalClue::all_combinations_for_range_filtered(range, num_tiles, required, forbidden)
+                .collect_vec(),
+              TotalClue::all_combinations_for_range(range, num_tiles)
+                .filter(|(_, digits)| {
+                  required.iter().all(|digit| digits.contains(&digit))
+                    && !digits.iter().any(|&digit| forbidden.contains(digit))
+                })
+                .collect_vec(),
+              "Mismatch for {range:?} with {num_tiles} tiles, required {required}, \
+               forbidden {forbidden}"
+            );
+          }
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn test_combinations_for_total_match_range() {
+    for num_tiles in 1..=9 {
+      for total in 3..=45 {
+        assert_eq!(
+          TotalClue::all_combinations_for_total(total, num_tiles).collect_vec(),
+          TotalClue::all_combinations_for_range((0, 45), num_tiles)
+            .filter(|&(sum, _)| sum == total)
+            .map(|(_, digits)| digits)
+            .collect_vec(),
+          "Mismatch for total {total} with {num_tiles} tiles"
+        );
+      }
+    }
+  }
+
+  #[test]
+  fn test_solve_with_partial() {
+    let kakuro = parse(PUZZLE_A);
+    let mut cache = CombinationCache::new();
+
+    // I and J form the two-digit clue "IJ", whose total becomes known.
+    let partial = LetterAssignment::new()
+      .with_value('I', 1)
+      .with_value('J', 6);
+    assert_eq!(
+      TotalClue::parse("IJ").unwrap().known_total(&partial),
+      Some(16)
+    );
+    let letters = kakuro.solve_with_partial(&partial, &mut cache).unwrap();
+    assert_eq!(letters.len(), 1);
+    assert_eq!(letters.first().unwrap().int_value(), 4027398516);
+    assert!(cache.stats().misses > 0);
+  }
+
+  #[test]
+  fn test_solve_with_wrong_partial() {
+    let kakuro = parse(PUZZLE_A);
+    let partial = LetterAssignment::new().with_value('I', 2);
+    assert!(kakuro
+      .solve_with_partial(&partial, &mut CombinationCache::new())
+      .unwrap()
+      .is_empty());
+  }
+
+  #[test]
+  fn test_combination_count_matches_enumeration() {
+    for num_tiles in 0..=10 {
+      for min in 0..=50 {
+        for max in min..=50 {
+          let count = TotalClue::combination_count_for_range((min, max), num_tiles) as usize;
+          let combinations = TotalClue::all_combinations_for_range((min, max), num_tiles);
+          assert_eq!(combinations.len(), count);
+          assert_eq!(
+            combinations.count(),
+            count,
+            "Mismatch for range ({min}, {max}) with {num_tiles} tiles"
+          );
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn test_subset_sum_counts() {
+    // Each of the 9 choose k sets of digits has exactly one sum.
+    for (k, counts) in SUBSET_SUM_COUNTS.iter().enumerate() {
+      let sets = (0..9u32).combinations(k).count() as u64;
+      assert_eq!(counts.iter().sum::<u64>(), sets, "{k} digits");
+    }
+    assert_eq!(SUBSET_SUM_COUNTS[9][45], 1);
+    assert_eq!(SUBSET_SUM_COUNTS[2][10], 4);
+  }
+
+  #[test]
+  fn test_combinations_size_hint() {
+    let mut combinations = TotalClue::all_combinations_for_range((2, 5), 2);
+    assert_eq!(combinations.size_hint(), (4, Some(4)));
+    combinations.next();
+    assert_eq!(combinations.len(), 3);
+    assert_eq!(combinations.by_ref().count(), 3);
+    assert_eq!(combinations.len(), 0);
+  }
+
+  /// The distinct totals `clue` admits over a run of `num_tiles`, along with
+  /// the letter values each total implies.
+  fn clue_totals(
+    clue: &str,
+    num_tiles: u32,
+    partial: &LetterAssignment,
+  ) -> Vec<(u32, Vec<(char, u32)>)> {
+    TotalClue::parse(clue)
+      .unwrap()
+      .all_combinations(num_tiles, partial, &mut CombinationCache::new())
+      .map(|(letters, digits)| {
+        (
+          digits.iter().sum(),
+          letters
+            .into_iter()
+            .map(|(item, value)| match item {
+              DlxItem::Letter { letter } => (letter, value),
+              _ => unreachable!(),
+            })
+            .collect_vec(),
+        )
+      })
+      .dedup()
+      .collect()
+  }
+
+  #[test]
+  fn test_two_digit_clue_totals() {
+    let totals = clue_totals("AB", 3, &LetterAssignment::new());
+    assert!(totals.contains(&(10, vec![('B', 0), ('A', 1)])));
+    assert!(totals.contains(&(20, vec![('B', 0), ('A', 2)])));
+    assert!(totals.iter().all(|&(total, _)| total != 11 && total != 22));
+
+    let totals = clue_totals("BA", 9, &LetterAssignment::new());
+    assert_eq!(totals, vec![(45, vec![('A', 5), ('B', 4)])]);
+  }
+
+  #[test]
+  fn test_repeated_letter_clue_totals() {
+    let totals = clue_totals("AA", 3, &LetterAssignment::new());
+    assert_eq!(
+      totals.iter().map(|&(total, _)| total).collect_vec(),
+      vec![11, 22]
+    );
+    assert!(totals.contains(&(22, vec![('A', 2), ('A', 2)])));
+
+    // 45 is not a multiple of 11, so no repeated-letter clue can total it.
+    assert!(clue_totals("AA", 9, &LetterAssignment::new()).is_empty());
+  }
+
+  #[test]
+  fn test_two_digit_clue_never_zero_tens() {
+    for num_tiles in 1..=9 {
+      assert!(clue_totals("AB", num_tiles, &LetterAssignment::new())
+        .iter()
+        .all(|(_, letters)| !letters.contains(&('A', 0))));
+    }
+
+    // If the tens letter is known to be 0, the clue admits nothing.
+    let partial = LetterAssignment::new()
+      .with_value('A', 0)
+      .with_value('B', 5);
+    assert!(clue_totals("AB", 2, &partial).is_empty());
+  }
+
+  #[test]
+  fn test_given_digits() {
+    // PUZZLE_A with the C, D, and E hints blanked out is ambiguous.
+    let ambiguous = "6,X,(vIJ),(vIE),X,X,X,(hF),O,O,(vIA),X,X,(hCA),O,O,O,(vIH),X,X,(hIB),O,O,O,(vF),X,X,(hD),O,A,O,X,X,X,(hID),O,G";
+    assert!(parse(ambiguous)
+      .solve()
+      .unwrap()
+      .iter()
+      .any(|letters| letters.int_value() != 4027398516));
+
+    // Giving the digits of those tiles instead pins down the solution again.
+    let given = "6,X,(vIJ),(vIE),X,X,X,(hF),O,2,(vIA),X,X,(hCA),O,O,7,(vIH),X,X,(hIB),3,O,O,(vF),X,X,(hD),O,A,O,X,X,X,(hID),O,G";
+    let letters = parse(given).solve().unwrap();
+    assert_eq!(
+      letters
+        .iter()
+        .map(|letters| letters.int_value())
+        .collect_vec(),
+      vec![4027398516]
+    );
+
+    // A wrong given digit leaves no solutions.
+    let wrong = "6,X,(vIJ),(vIE),X,X,X,(hF),O,2,(vIA),X,X,(hCA),O,O,6,(vIH),X,X,(hIB),3,O,O,(vF),X,X,(hD),O,A,O,X,X,X,(hID),O,G";
+    assert!(parse(wrong).solve().unwrap().is_empty());
+  }
+
+  #[test]
+  fn test_solve_via_linear() {
+    for line in [
+      PUZZLE_A,
+      PUZZLE_B,
+      RECT_PUZZLE,
+      // Ambiguous, with given digits, and with mixed clues.
+      "6,X,(vIJ),(vIE),X,X,X,(hF),O,O,(vIA),X,X,(hCA),O,O,O,(vIH),X,X,(hIB),O,O,O,(vF),X,X,(hD),O,A,O,X,X,X,(hID),O,G",
+      "6,X,(vIJ),(vIE),X,X,X,(hF),O,2,(vIA),X,X,(hCA),O,O,7,(vIH),X,X,(hIB),3,O,O,(vF),X,X,(hD),O,A,O,X,X,X,(hID),O,G",
+      "3,X,(v1A),(vA5),(h17),O,O,(hB),O,O",
+    ] {
+      let kakuro = parse(line);
+      let expected = kakuro.solve().unwrap();
+      assert!(!expected.is_empty());
+      assert_eq!(kakuro.solve_via_linear().unwrap(), expected, "{line}");
+    }
+  }
+
+  #[test]
+  fn test_given_digit_display() {
+    let kakuro = parse("3,X,(vA),(vI),(hBB),O,5,(hC),D,O");
+    assert_eq!(kakuro.tiles[5].to_string(), "5");
+  }
+
+  #[test]
+  fn test_numeric_clues() {
+    let kakuro = parse("4,X,(v16),(v11),X,(h17),O,O,(v11),(h11),O,O,O,X,(h10),O,O");
+    assert!(!kakuro.uses_letters());
+
+    let solutions = kakuro.solve_full().unwrap();
+    assert_eq!(solutions.len(), 1);
+    assert_eq!(
+      solutions.first().unwrap().digit_grid(),
+      vec![
+        vec![None, None, None, None],
+        vec![None, Some(9), Some(8), None],
+        vec![None, Some(7), Some(1), Some(3)],
+        vec![None, None, Some(2), Some(8)],
+      ]
+    );
+  }
+
+  #[test]
+  fn test_numeric_clue_display() {
+    let kakuro = parse("4,X,(v16),(v11),X,(h17),O,O,(v11),(h11),O,O,O,X,(h10),O,O");
+    assert_eq!(kakuro.tiles[1].to_string(), "16\\");
+    assert_eq!(kakuro.tiles[4].to_string(), "\\17");
+  }
+
+  #[test]
+  fn test_validate_ok() {
+    assert_eq!(parse(PUZZLE_A).validate(), Ok(()));
+  }
+
+  #[test]
+  fn test_validate_run_too_long() {
+    let kakuro = parse(&format!("11,(h45){}{}", ",O".repeat(10), ",X".repeat(110)));
+    assert_eq!(
+      kakuro.validate(),
+      Err(vec![KakuroStructureError::RunTooLong {
+        row: 0,
+        col: 0,
+        direction: Direction::Horizontal,
+        len: 10,
+      }])
+    );
+    assert_eq!(
+      kakuro.solve().err(),
+      Some(KakuroError::InvalidStructure(vec![
+        KakuroStructureError::RunTooLong {
+          row: 0,
+          col: 0,
+          direction: Direction::Horizontal,
+          len: 10,
+        }
+      ]))
+    );
+  }
+
+  #[test]
+  fn test_validate_uncovered_tile() {
+    let kakuro = parse("3,X,X,X,X,O,X,X,X,X");
+    assert_eq!(
+      kakuro.validate(),
+      Err(vec![KakuroStructureError::UncoveredTile { row: 1, col: 1 }])
+    );
+  }
+
+  #[test]
+  fn test_validate_empty_run() {
+    let kakuro = parse("3,X,(vA),X,X,O,X,(hB),X,X");
+    assert_eq!(
+      kakuro.validate(),
+      Err(vec![KakuroStructureError::EmptyRun {
+        row: 2,
+        col: 0,
+        direction: Direction::Horizontal,
+      }])
+    );
+  }
+
+  #[test]
+  fn test_validate_run_exits_grid() {
+    let kakuro = parse("3,X,X,(hA,vB),X,X,O,X,X,X");
+    assert_eq!(
+      kakuro.validate(),
+      Err(vec![KakuroStructureError::RunExitsGrid {
+        row: 0,
+        col: 2,
+        direction: Direction::Horizontal,
+      }])
+    );
+  }
+
+  #[test]
+  fn test_tile_accessors() {
+    let kakuro = parse("3,X,(vA),(vI),(hBB),O,5,(hC),D,O");
+    assert_eq!(kakuro.rows(), 3);
+    assert_eq!(kakuro.cols(), 3);
+
+    assert_eq!(kakuro[(0, 0)], Tile::Empty);
+    let clue = kakuro[(0, 1)].as_total().unwrap();
+    assert_eq!(clue.vertical(), Some(&TotalClue::OneDigit('A')));
+    assert_eq!(clue.horizontal(), None);
+    let clue = kakuro[(1, 0)].as_total().unwrap();
+    assert_eq!(
+      clue.clue(Direction::Horizontal),
+      Some(&TotalClue::TwoDigit {
+        ones: 'B',
+        tens: 'B'
+      })
+    );
+    assert_eq!(
+      clue.clue(Direction::Horizontal).unwrap().letters(),
+      vec!['B', 'B']
+    );
+    assert_eq!(kakuro[(1, 1)], Tile::Unknown(UnknownTile::Blank));
+    assert_eq!(kakuro[(1, 2)].as_unknown().unwrap().given_digit(), Some(5));
+    assert_eq!(kakuro[(2, 1)].as_unknown().unwrap().hint(), Some('D'));
+
+    assert_eq!(kakuro.tile(2, 2), Some(&Tile::Unknown(UnknownTile::Blank)));
+    assert_eq!(kakuro.tile(3, 0), None);
+    assert_eq!(kakuro.tile(0, 3), None);
+  }
+
+  #[test]
+  fn test_tiles_with_coords() {
+    let kakuro = parse("3,X,(vA),(vI),(hBB),O,5,(hC),D,O");
+    let unknowns = kakuro
+      .tiles_with_coords()
+      .filter(|(_, tile)| tile.as_unknown().is_some())
+      .map(|(coords, _)| coords)
+      .collect_vec();
+    assert_eq!(unknowns, vec![(1, 1), (1, 2), (2, 1), (2, 2)]);
+  }
+
+  #[test]
+  #[should_panic]
+  fn test_index_out_of_bounds() {
+    let kakuro = parse("3,X,(vA),(vI),(hBB),O,5,(hC),D,O");
+    let _ = &kakuro[(3, 3)];
+  }
+
+  #[test]
+  fn test_to_line() {
+    for line in [
+      "3,X,(vA),(vI),(hBB),O,5,(hC),D,O",
+      "4,X,(v16),(v11),X,(h17),O,O,(v11),(h11),O,O,O,X,(h10),O,O",
+      PUZZLE_A,
+      PUZZLE_B,
+    ] {
+      assert_eq!(parse(line).to_line(), line);
+    }
+  }
+
+  /// `line` with each letter A-J replaced by the one `shift` letters later,
+  /// wrapping around.
+  fn shift_letters(line: &str, shift: u8) -> String {
+    line
+      .chars()
+      .map(|c| match c {
+        'A'..='J' => (b'A' + (c as u8 - b'A' + shift) % 10) as char,
+        _ => c,
+      })
+      .collect()
+  }
+
+  #[test]
+  fn test_canonical_key() {
+    let kakuro = parse(PUZZLE_A);
+    let renamed = parse(&shift_letters(PUZZLE_A, 3));
+    assert_ne!(renamed, kakuro);
+    assert_eq!(renamed.canonical_key(), kakuro.canonical_key());
+    assert_eq!(
+      parse(&PUZZLE_A.to_lowercase()).canonical_key(),
+      kakuro.canonical_key()
+    );
+
+    assert_ne!(parse(PUZZLE_B).canonical_key(), kakuro.canonical_key());
+    // Only the letters are renamed, so a different given digit still counts.
+    assert_ne!(
+      parse("3,X,(vA),(vI),(hBB),O,5,(hC),D,O").canonical_key(),
+      parse("3,X,(vA),(vI),(hBB),O,6,(hC),D,O").canonical_key()
+    );
+  }
+
+  #[test]
+  fn test_canonical_key_transposed() {
+    let kakuro = parse(RECT_PUZZLE);
+    let transposed = kakuro.transposed();
+    assert_eq!((transposed.rows(), transposed.cols()), (6, 4));
+    assert_eq!(transposed.transposed(), kakuro);
+    assert_eq!(
+      transposed[(1, 0)],
+      Tile::Total(TotalTile::new(Some(TotalClue::Numeric(24)), None))
+    );
+    assert_eq!(transposed.canonical_key(), kakuro.canonical_key());
+
+    // Transposing doesn't change which digits the letters stand for.
+    let kakuro = parse(PUZZLE_A);
+    let transposed = parse(&shift_letters(&kakuro.transposed().to_line(), 7));
+    assert_eq!(transposed.canonical_key(), kakuro.canonical_key());
+    assert_eq!(
+      transposed.solve().unwrap().len(),
+      kakuro.solve().unwrap().len()
+    );
+  }
+
+  #[test]
+  fn test_dedup() {
+    let a = parse(PUZZLE_A);
+    let b = parse(PUZZLE_B);
+    let deduped = dedup([
+      a.clone(),
+      parse(&shift_letters(PUZZLE_A, 1)),
+      b.clone(),
+      a.transposed(),
+      b.clone(),
+      a.clone(),
+    ]);
+    assert_eq!(deduped, vec![(a, 4), (b, 2)]);
+    assert!(dedup([]).is_empty());
+  }
+
+  #[test]
+  fn test_verify() {
+    let puzzle = parse(PUZZLE_A);
+    let solved = puzzle.solve_first().unwrap().unwrap();
+    assert_eq!(puzzle.verify(&solved), Ok(()));
+    assert_eq!(solved.letters().int_value(), 4027398516);
+
+    let tampered = |change: &dyn Fn(&mut Vec<Option<u32>>, &mut LetterAssignment)| {
+      let mut digits = solved.digits.clone();
+      let mut letters = solved.letters.clone();
+      change(&mut digits, &mut letters);
+      puzzle.verify(&SolvedKakuro {
+        cols: solved.cols,
+        letters,
+        digits,
+      })
+    };
+    assert_eq!(
+      tampered(&|digits, _| digits.truncate(6)),
+      Err(SolutionError::WrongSize)
+    );
+    assert_eq!(
+      tampered(&|digits, _| digits[0] = Some(1)),
+      Err(SolutionError::UnexpectedDigit { row: 0, col: 0 })
+    );
+    assert_eq!(
+      tampered(&|digits, _| digits[7] = None),
+      Err(SolutionError::MissingDigit { row: 1, col: 1 })
+    );
+    assert_eq!(
+      tampered(&|digits, _| digits[7] = Some(0)),
+      Err(SolutionError::WrongDigit {
+        row: 1,
+        col: 1,
+        digit: 0
+      })
+    );
+    assert!(matches!(
+      tampered(&|digits, _| digits[7] = digits[7].map(|digit| digit % 9 + 1)),
+      Err(SolutionError::RepeatedDigit { .. } | SolutionError::WrongTotal { .. })
+    ));
+    assert_eq!(
+      tampered(&|_, letters| *letters = LetterAssignment::new()
+        .with_value('A', 1)
+        .with_value('B', 1)),
+      Err(SolutionError::SharedValue {
+        first: 'A',
+        second: 'B',
+        value: 1
+      })
+    );
+
+    // A prefilled letter is a digit like any other, so may not be zero.
+    let puzzle = parse("3,X,(vC),(vAC),(hAB),A,O,(hD),O,O");
+    let letters = LetterAssignment::from_pairs(&[('A', 0), ('B', 3), ('C', 4), ('D', 5)]).unwrap();
+    let digits = vec![
+      None,
+      None,
+      None,
+      None,
+      Some(0),
+      Some(3),
+      None,
+      Some(4),
+      Some(1),
+    ];
+    assert_eq!(
+      puzzle.verify(&SolvedKakuro {
+        cols: 3,
+        letters,
+        digits,
+      }),
+      Err(SolutionError::WrongDigit {
+        row: 1,
+        col: 1,
+        digit: 0
+      })
+    );
+    // With A=0, the clue AB would be the two digit total 03.
+    let puzzle = parse("3,X,(vC),(vD),(hAB),O,O,(hE),O,O");
+    let letters =
+      LetterAssignment::from_pairs(&[('A', 0), ('B', 3), ('C', 4), ('D', 6), ('E', 7)]).unwrap();
+    let digits = vec![
+      None,
+      None,
+      None,
+      None,
+      Some(1),
+      Some(2),
+      None,
+      Some(3),
+      Some(4),
+    ];
+    assert_eq!(
+      puzzle.verify(&SolvedKakuro {
+        cols: 3,
+        letters,
+        digits,
+      }),
+      Err(SolutionError::WrongTotal {
+        row: 1,
+        col: 0,
+        direction: Direction::Horizontal,
+        total: 3
+      })
+    );
+  }
+
+  #[test]
+  fn test_from_str() {
+    let kakuro: Kakuro = " 3,X,(vA),(vI),(hBB),O,5,(hC),D,O ".parse().unwrap();
+    assert_eq!(kakuro, parse("3,X,(vA),(vI),(hBB),O,5,(hC),D,O"));
+    assert!("# comment".parse::<Kakuro>().is_err());
+    assert!("3,X".parse::<Kakuro>().is_err());
+  }
+
+  #[test]
+  fn test_round_trip_file() {
+    for path in ["kakuro_test.txt", "p424_kakuro200.txt"] {
+      let kakuros = Kakuro::from_file(path).unwrap();
+      let mut out = Vec::new();
+      Kakuro::to_writer(&mut out, &kakuros).unwrap();
+      assert_eq!(Kakuro::from_reader(out.as_slice()).unwrap(), kakuros);
+      assert_eq!(
+        String::from_utf8(out).unwrap(),
+        std::fs::read_to_string(path).unwrap()
+      );
+    }
+  }
+
+  const RECT_PUZZLE: &str = "4,6,X,(v24),(v18),X,(v7),(v12),\
+    (h16),O,O,(h4),O,O,\
+    (h14),O,O,(h6),O,O,\
+    (h12),O,O,(h9),O,O";
+
+  #[test]
+  fn test_parse_rectangular() {
+    let kakuro = parse(RECT_PUZZLE);
+    assert_eq!((kakuro.rows(), kakuro.cols()), (4, 6));
+    assert_eq!(kakuro.tile(3, 5), Some(&Tile::Unknown(UnknownTile::Blank)));
+    assert_eq!(kakuro.tile(3, 6), None);
+    assert_eq!(kakuro.tile(4, 0), None);
+    assert_eq!(kakuro.to_line(), RECT_PUZZLE);
+    assert_eq!(kakuro.validate(), Ok(()));
+  }
+
+  #[test]
+  fn test_solve_rectangular() {
+    let wide = parse(RECT_PUZZLE);
+    // The same puzzle transposed, so vertical runs are longer than the grid
+    // is wide.
+    let tall = parse(
+      "6,4,X,(v16),(v14),(v12),(h24),O,O,O,(h18),O,O,O,\
+       X,(v4),(v6),(v9),(h7),O,O,O,(h12),O,O,O",
+    );
+    assert_eq!((tall.rows(), tall.cols()), (6, 4));
+
+    let wide_grids = wide
+      .solve_full()
+      .unwrap()
+      .iter()
+      .map(|solution| solution.digit_grid())
+      .sorted()
+      .collect_vec();
+    let tall_grids = tall
+      .solve_full()
+      .unwrap()
+      .iter()
+      .map(|solution| {
+        // Transpose back into the wide orientation.
+        let grid = solution.digit_grid();
+        (0..4)
+          .map(|col| (0..6).map(|row| grid[row][col]).collect_vec())
+          .collect_vec()
+      })
+      .sorted()
+      .collect_vec();
+    assert_eq!(wide_grids.len(), 6);
+    assert_eq!(wide_grids, tall_grids);
+
+    for grid in &wide_grids {
+      // Every run, including those ending on the bottom and right edges, adds
+      // up to its clue.
+      let rows = grid[1..]
+        .iter()
+        .map(|row| {
+          [
+            row[1].unwrap() + row[2].unwrap(),
+            row[4].unwrap() + row[5].unwrap(),
+          ]
+        })
+        .collect_vec();
+      assert_eq!(rows, vec![[16, 4], [14, 6], [12, 9]]);
+      let cols = [1, 2, 4, 5].map(|col| (1..4).map(|row| grid[row][col].unwrap()).sum::<u32>());
+      assert_eq!(cols, [24, 18, 7, 12]);
+    }
+  }
+
+  #[test]
+  fn test_validate_rectangular_edges() {
+    let kakuro = parse("2,3,X,(v3),(h3),(h3),O,(v3)");
+    assert_eq!(
+      kakuro.validate(),
+      Err(vec![
+        KakuroStructureError::RunExitsGrid {
+          row: 0,
+          col: 2,
+          direction: Direction::Horizontal,
+        },
+        KakuroStructureError::RunExitsGrid {
+          row: 1,
+          col: 2,
+          direction: Direction::Vertical,
+        },
+      ])
+    );
+  }
+
+  #[test]
+  fn test_small_alphabet() {
+    let kakuro = parse("4,X,(v16),(vAA),X,(h17),E,O,(v11),(hAA),D,A,C,X,(h10),B,O")
+      .with_inferred_alphabet_size();
+    assert_eq!(kakuro.alphabet_size(), 5);
+
+    let solutions = kakuro.solve().unwrap();
+    assert_eq!(solutions.len(), 1);
+    let letters = &solutions[0];
+    assert_eq!(letters.alphabet_size(), 5);
+    assert_eq!(letters.alphabet().collect::<String>(), "ABCDE");
+    assert_eq!(letters.int_value(), 12379);
+    assert_eq!(letters.int_value().to_string().len(), 5);
+  }
+
+  #[test]
+  fn test_unconstrained_letters() {
+    // Only A to G appear, so H, I and J can take the three leftover digits in
+    // any order.
+    let kakuro = parse("4,X,(vAG),(vAA),X,(h17),E,F,(v11),(hAA),D,A,C,X,(h10),B,F");
+    let solutions = kakuro.solve().unwrap();
+    assert_eq!(
+      solutions
+        .iter()
+        .map(|letters| letters.int_value())
+        .collect_vec(),
+      vec![1237986045, 1237986054, 1237986405, 1237986450, 1237986504, 1237986540]
+    );
+    assert_eq!(kakuro.solve_full().unwrap().len(), 6);
+  }
+
+  /// The values of the letters a puzzle uses, and the digit in each tile.
+  type LettersAndDigits = (Vec<(char, u32)>, Vec<Option<u32>>);
+
+  /// Every solution of `kakuro`, found by trying every value of the letters
+  /// it uses and every digit in each blank tile. Only practical for tiny
+  /// puzzles using few letters.
+  fn brute_force_solutions(kakuro: &Kakuro) -> HashSet<LettersAndDigits> {
+    let letters = kakuro.letters_used().unique().sorted().collect_vec();
+    let runs = kakuro.runs();
+    (0..10)
+      .permutations(letters.len())
+      .map(|values| letters.iter().copied().zip(values).collect_vec())
+      .filter(|pairs| {
+        // Skip trying the digits for clues which no run of their length could
+        // add up to.
+        let assignment = LetterAssignment::from_pairs(pairs).unwrap();
+        runs.iter().all(|run| {
+          let (min, max) = TotalClue::attainable_sum_range(run.cells.len() as u32).unwrap();
+          run
+            .clue
+            .known_total(&assignment)
+            .is_some_and(|total| (min..=max).contains(&total))
+        })
+      })
+      .flat_map(|pairs| {
+        let assignment = LetterAssignment::from_pairs(&pairs).unwrap();
+        let tile_digits = kakuro
+          .tiles
+          .iter()
+          .map(|tile| match tile {
+            Tile::Unknown(UnknownTile::Blank) => (1..=9).map(Some).collect_vec(),
+            Tile::Unknown(UnknownTile::Given { digit }) => vec![Some(*digit)],
+            Tile::Unknown(UnknownTile::Prefilled { hint }) => assignment
+              .letter_value(*hint)
+              .filter(|&digit| digit != 0)
+              .map(Some)
+              .into_iter()
+              .collect_vec(),
+            _ => vec![None],
+          })
+          .collect_vec();
+        let runs = &runs;
+        tile_digits
+          .into_iter()
+          .multi_cartesian_product()
+          .filter(move |digits| {
+            runs.iter().all(|run| {
+              let run_digits = run
+                .cells
+                .iter()
+                .map(|&(row, col)| digits[kakuro.get_idx(row, col)].unwrap())
+                .collect_vec();
+              run_digits.iter().all_unique()
+                && run.clue.known_total(&assignment) == Some(run_digits.iter().sum())
+            })
+          })
+          .map(move |digits| (pairs.clone(), digits))
+      })
+      .collect()
+  }
+
+  /// The solutions of `kakuro` found by the solver, in the same form as
+  /// `brute_force_solutions`.
+  fn solver_solutions(kakuro: &Kakuro) -> HashSet<LettersAndDigits> {
+    let letters = kakuro.letters_used().unique().sorted().collect_vec();
+    kakuro
+      .solve_full()
+      .unwrap()
+      .into_iter()
+      .map(|solution| {
+        let pairs = letters
+          .iter()
+          .map(|&letter| (letter, solution.letters.letter_value(letter).unwrap()))
+          .collect_vec();
+        (pairs, solution.digits)
+      })
+      .collect()
+  }
+
+  #[test]
+  fn test_clue_letter_in_own_run() {
+    // The horizontal run at (1, 0) adds up to AB and contains A, which only
+    // works with A=1, B=0, and the other tile 9.
+    for line in [
+      "3,X,(vC),(vAC),(hAB),A,O,(hD),O,O",
+      // The same, transposed.
+      "3,X,(vAB),(vD),(hC),A,O,(hAC),O,O",
+    ] {
+      let kakuro = parse(line);
+      let solutions = solver_solutions(&kakuro);
+      assert!(!solutions.is_empty(), "{line}");
+      assert!(solutions
+        .iter()
+        .all(|(letters, _)| letters[..2] == [('A', 1), ('B', 0)]));
+      assert_eq!(solutions, brute_force_solutions(&kakuro), "{line}");
+    }
+  }
+
+  #[test]
+  fn test_impossible_clue_letter_in_own_run() {
+    for line in [
+      // A run can't add up to one of its own digits.
+      "3,X,(vB),(vC),(hA),A,O,(hD),O,O",
+      // Nor to 10 + a digit in it, as the other digit would have to be 10.
+      "3,X,(vC),(vD),(hAB),B,O,(hE),O,O",
+      // The letter can't be in the run twice, as its digits are distinct.
+      "3,X,(vBA),(vCA),(hD),A,A,(hE),O,O",
+    ] {
+      let kakuro = parse(line);
+      assert_eq!(solver_solutions(&kakuro), HashSet::new(), "{line}");
+      assert_eq!(brute_force_solutions(&kakuro), HashSet::new(), "{line}");
+    }
+  }
+
+  #[test]
+  fn test_brute_force() {
+    for kakuro in [
+      parse("4,X,(v16),(v11),X,(h17),O,O,(v11),(h11),O,O,O,X,(h10),O,O"),
+      parse("4,X,(v16),(vAA),X,(h17),E,O,(v11),(hAA),D,A,C,X,(h10),B,O")
+        .with_inferred_alphabet_size(),
+      parse("4,X,(vAB),(vBA),X,(hAC),O,A,(vAA),(hBB),O,O,O,X,(hB),C,2"),
+      parse("3,X,(vC),(vAC),(hAB),A,O,(hD),O,O"),
+      parse("3,X,(vAB),(vD),(hC),A,O,(hAC),O,O"),
+      parse("3,X,(vB),(vC),(hA),A,O,(hD),O,O"),
+      parse("4,X,(vAG),(vAA),X,(h17),E,F,(v11),(hAA),D,A,C,X,(h10),B,F"),
+    ] {
+      let sorted = |solutions: Vec<LetterAssignment>| {
+        solutions
+          .into_iter()
+          .map(|letters| letters.iter().collect_vec())
+          .sorted()
+          .collect_vec()
+      };
+      assert_eq!(
+        sorted(kakuro.solve_brute_force().unwrap()),
+        sorted(kakuro.solve().unwrap()),
+        "{}",
+        kakuro.to_line()
+      );
+    }
+  }
+
+  #[test]
+  fn test_brute_force_letter_limit() {
+    assert_eq!(
+      parse(PUZZLE_A).solve_brute_force(),
+      Err(KakuroError::TooManyLetters { used: 10, limit: 7 })
+    );
+    let kakuro = parse("3,X,(vC),(vAC),(hAB),A,O,(hD),O,O");
+    assert_eq!(
+      kakuro
+        .solve_brute_force_with_limit(3)
+        .unwrap_err()
+        .to_string(),
+      "The puzzle uses 4 letters, more than the 3 allowed"
+    );
+    assert_eq!(
+      kakuro.solve_brute_force_with_limit(4).unwrap(),
+      kakuro.solve_brute_force().unwrap()
+    );
+  }
+
+  #[test]
+  fn test_solve_distinct_letters() {
+    for kakuro in [
+      parse("4,X,(v16),(v11),X,(h17),O,O,(v11),(h11),O,O,O,X,(h10),O,O"),
+      parse("4,X,(vAG),(vAA),X,(h17),E,F,(v11),(hAA),D,A,C,X,(h10),B,F"),
+      parse("3,X,(vB),(vC),(hA),A,O,(hD),O,O"),
+      parse(PUZZLE_A),
+    ] {
+      assert_eq!(
+        kakuro.solve().unwrap(),
+        SolvedKakuro::distinct_letters(kakuro.solve_full().unwrap()),
+        "{}",
+        kakuro.to_line()
+      );
+    }
+  }
+
+  #[test]
+  fn test_choice_id() {
+    for (run, combination, permutation) in [
+      (0, 0, 0),
+      (3, 17, 5),
+      (65_535, 0, 362_879),
+      (1, (1 << 24) - 1, (1 << 24) - 1),
+    ] {
+      let choice = ChoiceId::new(run, combination, permutation);
+      assert_eq!(
+        (
+          choice.run_index(),
+          choice.combination_index(),
+          choice.permutation_index()
+        ),
+        (run, combination, permutation)
+      );
+    }
+  }
+
+  #[test]
+  #[should_panic]
+  fn test_choice_id_out_of_range() {
+    ChoiceId::new(0, 1 << 24, 0);
+  }
+
+  #[test]
+  fn test_nth_permutation() {
+    for len in 0..=5 {
+      let items = (1..=len).collect_vec();
+      let permutations = items.iter().copied().permutations(len).collect_vec();
+      assert_eq!(
+        (0..permutations.len())
+          .map(|idx| nth_permutation(&items, idx))
+          .collect_vec(),
+        permutations
+      );
+    }
+  }
+
+  #[test]
+  fn test_solve_explained() {
+    for kakuro in [
+      parse("4,X,(v16),(v11),X,(h17),O,O,(v11),(h11),O,O,O,X,(h10),O,O"),
+      parse("3,X,(vC),(vAC),(hAB),A,O,(hD),O,O"),
+      parse(PUZZLE_A),
+    ] {
+      let explained = kakuro.solve_explained().unwrap();
+      let solutions = kakuro.solve_full().unwrap();
+      assert_eq!(explained.len(), solutions.len(), "{}", kakuro.to_line());
+      for ((solution, fills), expected) in explained.iter().zip(&solutions) {
+        assert_eq!(solution.digit_grid(), expected.digit_grid());
+        assert_eq!(solution.letters(), expected.letters());
+        assert_eq!(
+          fills.iter().map(|fill| fill.run.clone()).collect_vec(),
+          kakuro.runs()
+        );
+        for fill in fills {
+          assert_eq!(
+            fill.digits,
+            fill
+              .run
+              .cells
+              .iter()
+              .map(|&(row, col)| solution.digit(row, col).unwrap())
+              .collect_vec()
+          );
+          assert_eq!(
+            fill.run.clue.known_total(solution.letters()),
+            Some(fill.digits.iter().sum())
+          );
+        }
+      }
+    }
+
+    let explained = parse("4,X,(v16),(v11),X,(h17),O,O,(v11),(h11),O,O,O,X,(h10),O,O")
+      .solve_explained()
+      .unwrap();
+    assert_eq!(
+      explained[0].1.iter().map(ToString::to_string).collect_vec(),
+      vec![
+        "run at (0, 1) vertical used digits 9,7 in that order",
+        "run at (0, 2) vertical used digits 8,1,2 in that order",
+        "run at (1, 0) horizontal used digits 9,8 in that order",
+        "run at (1, 3) vertical used digits 3,8 in that order",
+        "run at (2, 0) horizontal used digits 7,1,3 in that order",
+        "run at (3, 1) horizontal used digits 2,8 in that order",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_completions() {
+    let letters =
+      LetterAssignment::from_pairs_for_alphabet(4, &[('A', 3), ('C', 0), ('D', 1)]).unwrap();
+    assert_eq!(
+      letters
+        .completions()
+        .map(|letters| letters.int_value())
+        .collect_vec(),
+      vec![3201, 3401, 3501, 3601, 3701, 3801, 3901]
+    );
+    let full = LetterAssignment::from_pairs_for_alphabet(2, &[('A', 3), ('B', 2)]).unwrap();
+    assert_eq!(full.completions().collect_vec(), vec![full]);
+  }
+
+  #[test]
+  fn test_explicit_alphabet() {
+    let kakuro = parse(PUZZLE_A);
+    assert_eq!(kakuro.alphabet_size(), 10);
+    assert_eq!(
+      kakuro.clone().with_inferred_alphabet_size().alphabet_size(),
+      10
+    );
+    assert_eq!(
+      kakuro.with_alphabet_size(10).solve().unwrap()[0].int_value(),
+      4027398516
+    );
+  }
+
+  #[test]
+  #[should_panic(expected = "Letter I is outside of the puzzle's 5 letters")]
+  fn test_alphabet_too_small() {
+    parse(PUZZLE_A).with_alphabet_size(5);
+  }
+
+  #[test]
+  fn test_try_with_alphabet_size() {
+    assert_eq!(
+      parse(PUZZLE_A).try_with_alphabet_size(5),
+      Err(KakuroError::LetterOutsideAlphabet {
+        letter: 'I',
+        alphabet: 5
+      })
+    );
+    for alphabet in [0, MAX_ALPHABET_SIZE + 1] {
+      assert_eq!(
+        parse(PUZZLE_A).try_with_alphabet_size(alphabet),
+        Err(KakuroError::AlphabetSize(alphabet))
+      );
+    }
+    assert_eq!(
+      parse(PUZZLE_A)
+        .try_with_alphabet_size(10)
+        .map(|kakuro| kakuro.alphabet_size()),
+      Ok(10)
+    );
+  }
+
+  #[test]
+  fn test_solve_with_hints() {
+    let kakuro = parse(PUZZLE_A);
+    let solutions = kakuro
+      .solve_with_hints(&[('A', 4), ('J', 6), ('A', 4)])
+      .unwrap();
+    assert_eq!(
+      solutions
+        .iter()
+        .map(LetterAssignment::int_value)
+        .collect_vec(),
+      vec![4027398516]
+    );
+  }
+
+  #[test]
+  fn test_solve_with_unsatisfiable_hints() {
+    let kakuro = parse(PUZZLE_A);
+    assert!(kakuro.solve_with_hints(&[('A', 3)]).unwrap().is_empty());
+    // B=0 is consistent with the only solution on its own, but J=6 there.
+    assert!(kakuro
+      .solve_with_hints(&[('B', 0), ('J', 5)])
+      .unwrap()
+      .is_empty());
+  }
+
+  #[test]
+  fn test_solve_with_invalid_hints() {
+    let kakuro = parse(PUZZLE_A);
+    assert_eq!(
+      kakuro.solve_with_hints(&[('A', 3), ('B', 3)]).err(),
+      Some(KakuroError::ConflictingHints {
+        first: ('A', 3),
+        second: ('B', 3),
+      })
+    );
+    assert_eq!(
+      kakuro
+        .solve_with_hints(&[('A', 3), ('C', 1), ('A', 4)])
+        .err(),
+      Some(KakuroError::ConflictingHints {
+        first: ('A', 3),
+        second: ('A', 4),
+      })
+    );
+    assert_eq!(
+      kakuro.solve_with_hints(&[('K', 3)]).err(),
+      Some(KakuroError::InvalidHint {
+        letter: 'K',
+        value: 3
+      })
+    );
+    assert_eq!(
+      kakuro.solve_with_hints(&[('A', 10)]).err(),
+      Some(KakuroError::InvalidHint {
+        letter: 'A',
+        value: 10
+      })
+    );
+  }
+
+  #[test]
+  fn test_runs() {
+    let kakuro = parse("3,X,(vA),(vI),(hBB),O,5,(hC),D,O");
+    assert_eq!(
+      kakuro.runs(),
+      vec![
+        Run {
+          start: (0, 1),
+          direction: Direction::Vertical,
+          cells: vec![(1, 1), (2, 1)],
+          clue: TotalClue::OneDigit('A'),
+        },
+        Run {
+          start: (0, 2),
+          direction: Direction::Vertical,
+          cells: vec![(1, 2), (2, 2)],
+          clue: TotalClue::OneDigit('I'),
+        },
+        Run {
+          start: (1, 0),
+          direction: Direction::Horizontal,
+          cells: vec![(1, 1), (1, 2)],
+          clue: TotalClue::TwoDigit {
+            ones: 'B',
+            tens: 'B'
+          },
+        },
+        Run {
+          start: (2, 0),
+          direction: Direction::Horizontal,
+          cells: vec![(2, 1), (2, 2)],
+          clue: TotalClue::OneDigit('C'),
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn test_total_tile_builder() {
+    let tile = TotalTile::new(None, None)
+      .with_vertical(TotalClue::Numeric(4))
+      .with_horizontal(TotalClue::OneDigit('A'));
+    assert_eq!(
+      tile,
+      TotalTile::new(Some(TotalClue::OneDigit('A')), Some(TotalClue::Numeric(4)))
+    );
+    assert_eq!(tile.horizontal(), Some(&TotalClue::OneDigit('A')));
+    assert_eq!(tile.clue(Direction::Vertical), Some(&TotalClue::Numeric(4)));
+    // Setting a clue again replaces it.
+    assert_eq!(
+      tile.with_vertical(TotalClue::Numeric(6)).vertical(),
+      Some(&TotalClue::Numeric(6))
+    );
+
+    let kakuro = parse("4,X,(v3),(v4),X,(h3,v4),O,O,X,O,O,O,X,O,X,X,X");
+    assert_eq!(
+      kakuro.tiles[4],
+      Tile::Total(TotalTile::new(
+        Some(TotalClue::Numeric(3)),
+        Some(TotalClue::Numeric(4))
+      ))
+    );
+    assert_eq!(
+      kakuro.tiles[1],
+      Tile::Total(TotalTile::new(None, Some(TotalClue::Numeric(3))))
+    );
+  }
+
+  #[test]
+  fn test_runs_stop_at_blocks() {
+    // The clue at (1, 0) has both a horizontal and a vertical run, and the
+    // horizontal run is cut short by the empty tile at (1, 3).
+    let kakuro = parse("4,X,(v3),(v4),X,(h3,v4),O,O,X,O,O,O,X,O,X,X,X");
+    let runs = kakuro.runs();
+    assert_eq!(runs.len(), 4);
+    assert_eq!(
+      runs[2],
+      Run {
+        start: (1, 0),
+        direction: Direction::Horizontal,
+        cells: vec![(1, 1), (1, 2)],
+        clue: TotalClue::Numeric(3),
+      }
+    );
+    assert_eq!(
+      runs[3],
+      Run {
+        start: (1, 0),
+        direction: Direction::Vertical,
+        cells: vec![(2, 0), (3, 0)],
+        clue: TotalClue::Numeric(4),
+      }
+    );
+  }
+
+  #[test]
+  fn test_candidates() {
+    let kakuro = parse("3,X,(v4),(v3),(h3),O,O,(h4),O,O");
+    let candidates = kakuro.candidates();
+    assert_eq!(candidates.len(), 4);
+    assert_eq!(candidates[&(1, 1)], DigitSet::new().with(1));
+    assert_eq!(candidates[&(1, 2)], DigitSet::new().with(1).with(2));
+    assert_eq!(candidates[&(2, 1)], DigitSet::new().with(1).with(3));
+    assert_eq!(candidates[&(2, 2)], DigitSet::new().with(1));
+  }
+
+  #[test]
+  fn test_candidates_with_givens_and_letters() {
+    // A can stand for any total up to 9, so its runs can hold any digit up to
+    // 8, while the given 3 restricts the runs it's part of.
+    let kakuro = parse("3,X,(vA),(v12),(hA),O,3,(h17),O,O");
+    let candidates = kakuro.candidates();
+    assert_eq!(candidates[&(1, 1)], (1..=6).collect());
+    assert_eq!(candidates[&(1, 2)], DigitSet::new().with(3));
+    assert_eq!(candidates[&(2, 1)], DigitSet::new().with(8));
+    assert_eq!(candidates[&(2, 2)], DigitSet::new().with(9));
+  }
+
+  #[test]
+  fn test_diagnose_impossible_run() {
+    let kakuro = parse("2,4,X,(v1),(v2),(v3),(h45),O,O,O");
+    assert_eq!(
+      kakuro.diagnose(),
+      Some(KakuroDiagnosis::ImpossibleRun {
+        start: (1, 0),
+        direction: Direction::Horizontal,
+        clue: "45".to_string(),
+      })
+    );
+  }
+
+  #[test]
+  fn test_diagnose_empty_candidates() {
+    // 17 needs an 8 and a 9, but the vertical 3 needs a 1 and a 2.
+    let kakuro = parse("3,X,(v3),(v16),(h17),O,O,(h3),O,O");
+    assert_eq!(
+      kakuro.diagnose(),
+      Some(KakuroDiagnosis::EmptyCandidates { row: 1, col: 1 })
+    );
+
+    // Every tile has candidates on its own, but the 4s need a 1 and a 3 and
+    // the 3 needs a 1 and a 2, so both tiles of the 3 can only be 1 and
+    // neither can take its 2.
+    let kakuro = parse("3,X,(v4),(v4),(h3),O,O,(h5),O,O");
+    assert!(matches!(
+      kakuro.diagnose(),
+      Some(KakuroDiagnosis::EmptyCandidates { .. })
+    ));
+  }
+
+  #[test]
+  fn test_diagnose_dead_end() {
+    // Every run has combinations its tiles could take, but no grid satisfies
+    // all of them at once.
+    let kakuro = parse("4,X,(v23),(v22),(v22),(h22),O,O,O,(h21),O,O,O,(h24),O,O,O");
+    assert!(kakuro.solve().unwrap().is_empty());
+    assert!(matches!(
+      kakuro.diagnose(),
+      Some(KakuroDiagnosis::SearchDeadEnd { dead_ends, .. }) if dead_ends > 0
+    ));
+  }
+
+  #[test]
+  fn test_diagnose_solvable() {
+    assert_eq!(parse(PUZZLE_A).diagnose(), None);
+  }
+
+  #[test]
+  fn test_solve_order() {
+    let kakuro = parse(PUZZLE_A);
+    let order = kakuro.solve_order();
+    assert_eq!(order.len(), kakuro.runs().len());
+    assert!(order.iter().tuple_windows().all(|((_, a), (_, b))| a <= b));
+    assert!(order.iter().all(|(run, count)| *count
+      == run
+        .clue
+        .combination_count(run.cells.len() as u32, &LetterAssignment::new())));
+  }
+
+  #[test]
+  #[ignore = "prints construction sizes for comparison"]
+  fn test_pruned_construction_size() {
+    let kakuros = Kakuro::from_file("p424_kakuro200.txt").unwrap();
+    let kakuro = kakuros
+      .iter()
+      .max_by_key(|kakuro| kakuro.runs().len())
+      .unwrap();
+    let letters = kakuro.solve().unwrap().pop().unwrap();
+
+    // The same puzzle with its clues decoded, which propagation can narrow
+    // much further than the letter clues.
+    let decoded = Kakuro {
+      tiles: kakuro
+        .tiles
+        .iter()
+        .map(|tile| match tile {
+          Tile::Unknown(UnknownTile::Prefilled { .. }) => Tile::Unknown(UnknownTile::Blank),
+          Tile::Total(TotalTile {
+            horizontal,
+            vertical,
+          }) => Tile::Total(TotalTile {
+            horizontal: horizontal
+              .as_ref()
+              .map(|clue| TotalClue::Numeric(clue.known_total(&letters).unwrap())),
+            vertical: vertical
+              .as_ref()
+              .map(|clue| TotalClue::Numeric(clue.known_total(&letters).unwrap())),
+          }),
+          tile => tile.clone(),
+        })
+        .collect(),
+      ..kakuro.clone()
+    };
+
+    for (name, kakuro) in [("letters", kakuro), ("decoded", &decoded)] {
+      let partial = LetterAssignment::new();
+      let mut cache = CombinationCache::new();
+      let unpruned =
+        build_dlx(&kakuro.model(&partial, &mut cache, false, Relaxed::Nothing)).num_nodes();
+      let pruned =
+        build_dlx(&kakuro.model(&partial, &mut cache, true, Relaxed::Nothing)).num_nodes();
+      println!("{name}: {unpruned} DLX nodes unpruned, {pruned} pruned");
+      assert!(pruned <= unpruned);
+    }
+  }
+
+  #[test]
+  fn test_letter_assignment_from_pairs() {
+    let assignment = LetterAssignment::from_pairs(&[('C', 2), ('A', 7), ('C', 2)]).unwrap();
+    assert_eq!(assignment.iter().collect_vec(), vec![('A', 7), ('C', 2)]);
+    assert_eq!(
+      assignment.to_map(),
+      [('A', 7), ('C', 2)].into_iter().collect()
+    );
+    assert_eq!(
+      assignment,
+      LetterAssignment::new()
+        .with_value('A', 7)
+        .with_value('C', 2)
+    );
+
+    assert_eq!(
+      LetterAssignment::from_pairs(&[('A', 1), ('B', 1)]),
+      Err(LetterAssignmentError::Conflict {
+        first: ('A', 1),
+        second: ('B', 1)
+      })
+    );
+    assert_eq!(
+      LetterAssignment::from_pairs(&[('A', 1), ('A', 2)]),
+      Err(LetterAssignmentError::Conflict {
+        first: ('A', 1),
+        second: ('A', 2)
+      })
+    );
+    assert_eq!(
+      LetterAssignment::from_pairs_for_alphabet(3, &[('D', 1)]),
+      Err(LetterAssignmentError::OutOfRange {
+        letter: 'D',
+        value: 1
+      })
+    );
+  }
+
+  #[test]
+  fn test_letter_value_out_of_range() {
+    let assignment = LetterAssignment::from_pairs_for_alphabet(3, &[('A', 5)]).unwrap();
+    assert_eq!(assignment.letter_value('A'), Some(5));
+    assert_eq!(assignment.letter_value('B'), None);
+    // Outside of the assignment's alphabet, or of A-J altogether.
+    for letter in ['D', 'J', 'K', 'Z', 'a', '@', '0'] {
+      assert_eq!(assignment.letter_value(letter), None, "{letter}");
+    }
+    assert!(!assignment.admits('Z', 1));
+    assert!(assignment.admits('B', 1));
+  }
+
+  #[test]
+  fn test_solve_via_linear_letter_outside_alphabet() {
+    let mut kakuro = parse(PUZZLE_A);
+    let idx = kakuro.get_idx(1, 0);
+    let total = kakuro.tiles[idx].as_total().unwrap().clone();
+    kakuro.tiles[idx] = Tile::Total(total.with_horizontal(TotalClue::OneDigit('Z')));
+    assert_eq!(
+      kakuro.solve_via_linear(),
+      Err(KakuroError::LetterOutsideAlphabet {
+        letter: 'Z',
+        alphabet: 10
+      })
+    );
+  }
+
+  #[test]
+  #[should_panic(expected = "Letter Z is outside of the alphabet")]
+  fn test_set_value_out_of_range() {
+    LetterAssignment::new().set_value('Z', 1);
+  }
+
+  #[test]
+  fn test_construct_dlx_out_of_range() {
+    let clue_item = DlxItem::Sum {
+      idx: 0,
+      vertical: false,
+    };
+    let partial = LetterAssignment::new();
+    let construct = |letter, value| {
+      Kakuro::construct_dlx(
+        clue_item.clone(),
+        vec![
+          (DlxItem::Tile { idx: 1 }, 3),
+          (DlxItem::Letter { letter }, value),
+        ],
+        &partial,
+      )
+      .map(Iterator::count)
+    };
+    // The clue, the tile, the letter and its value.
+    assert_eq!(construct('B', 3), Some(4));
+    assert_eq!(construct('Z', 3), None);
+    assert_eq!(construct('a', 3), None);
+    assert_eq!(construct('B', 12), None);
+  }
+
+  #[test]
+  fn test_letter_assignment_int_value() {
+    let pairs = ('A'..='J')
+      .zip([4, 0, 2, 7, 3, 9, 8, 5, 1, 6])
+      .collect_vec();
+    let assignment = LetterAssignment::from_pairs(&pairs).unwrap();
+    assert_eq!(assignment.try_int_value(), Ok(4027398516));
+    assert_eq!(assignment.int_value(), 4027398516);
+
+    let partial = LetterAssignment::from_pairs(&pairs[..9]).unwrap();
+    assert_eq!(partial.try_int_value(), Err(UnassignedLetter('J')));
+
+    let smaller = LetterAssignment::from_pairs(&[('A', 3), ('B', 9)]).unwrap();
+    let larger = LetterAssignment::from_pairs(&[('A', 4), ('B', 0)]).unwrap();
+    assert!(smaller < larger);
+  }
+
+  #[test]
+  #[should_panic(expected = "Letter B has no value")]
+  fn test_letter_assignment_int_value_unassigned() {
+    LetterAssignment::from_pairs(&[('A', 1)])
+      .unwrap()
+      .int_value();
+  }
+
+  #[test]
+  fn test_render_solution() {
+    let kakuro = parse("4,X,(v16),(v11),X,(h17),O,O,(v11),(h11),O,O,O,X,(h10),O,O");
+    let solution = kakuro.solve_full().unwrap().pop().unwrap();
+    assert_eq!(
+      kakuro.render_solution(&solution),
+      [
+        "  X 16\\ 11\\   X",
+        "\\17   9   8 11\\",
+        "\\11   7   1   3",
+        "  X \\10   2   8",
+      ]
+      .join("\n")
+    );
+  }
+
+  #[test]
+  fn test_render_letters() {
+    let kakuro = parse("4,X,(vAB),(vBA),X,(hAC),O,A,(vAA),(hBB),O,O,O,X,(hB),C,2");
+    assert_eq!(
+      kakuro.render_letters(&LetterAssignment::new().with_value('A', 1)),
+      [
+        " X ?\\ ?\\   X",
+        "\\?  ?  1 11\\",
+        "\\?  ?  ?   ?",
+        " X \\?  ?   2",
+      ]
+      .join("\n")
+    );
+  }
+
+  #[test]
+  fn test_display() {
+    let kakuro = parse("4,X,(v16),(vAB),X,(hA),O,O,X,X,X,X,X,X,(h3,v4),O,O");
+    assert_eq!(
+      kakuro.to_string(),
+      [
+        " X 16\\ AB\\ X",
+        "\\A   _   _ X",
+        " X   X   X X",
+        " X 4\\3   _ _",
+        "",
+      ]
+      .join("\n")
+    );
+  }
+
+  #[test]
+  fn test_display_boxed() {
+    let kakuro = parse("3,X,(v4),(v3),(h3),O,O,(h4),O,O");
+    assert_eq!(
+      format!("{kakuro:#}"),
+      [
+        "┌──┬──┬──┐",
+        "│ X│4\\│3\\│",
+        "├──┼──┴──┤",
+        "│\\3│ _  _│",
+        "├──┤     │",
+        "│\\4│ _  _│",
+        "└──┴─────┘",
+        "",
+      ]
+      .join("\n")
+    );
+  }
+
+  #[test]
+  fn test_tolerant_parsing() {
+    let messy = "# A comment, followed by a blank line\r\n\
+                 \r\n\
+                 \t3, x ,( vA , hB ),(vi),(hbb), o ,5,(hC),d,O \r\n\
+                 \n\
+                 #4,X,X\n\
+                 4,X,(V16),(v11),X,(H17),O,O,(v11),(h11),O,O,O,X,(h10),O,O";
+    let clean = "3,X,(vA,hB),(vI),(hBB),O,5,(hC),D,O\n\
+                 4,X,(v16),(v11),X,(h17),O,O,(v11),(h11),O,O,O,X,(h10),O,O\n";
+    assert_eq!(
+      Kakuro::from_reader(messy.as_bytes()).unwrap(),
+      Kakuro::from_reader(clean.as_bytes()).unwrap()
+    );
+  }
+
+  #[test]
+  fn test_parse_errors() {
+    for line in [
+      "3,X,(vA),(vI),(hBB),O,Q,(hC),D,O",
+      "3,X,(vA),(vI),(hBB),O,0,(hC),D,O",
+      "3,X,(vA),(vI),(hBBB),O,O,(hC),D,O",
+      "3,X,(vA),(vK),(hBB),O,O,(hC),D,O",
+      "3,X,(vA),(xI),(hBB),O,O,(hC),D,O",
+      "3,X,(vA),(vI),(hBB),O,O,(hC),D",
+      "three,X",
+      "3,X,(vA,(vI),(hBB),O,O,(hC),D,O",
+      "3,X,(vA)),(vI),(hBB),O,O,(hC),D,O",
+    ] {
+      let err = Kakuro::from_reader(line.as_bytes()).unwrap_err();
+      assert_eq!(err.kind(), std::io::ErrorKind::InvalidData, "{line}");
+    }
   }
 
-  #[allow(unused)]
-  fn print_solution(&self, soln: &HashMap<DlxItem, u32>) {
-    self.tiles.iter().enumerate().for_each(|(idx, tile)| {
-      let out = match tile {
-        Tile::Unknown(UnknownTile::Blank) => {
-          format!("{}", soln.get(&DlxItem::Tile { idx: idx as u32 }).unwrap())
-        }
-        Tile::Unknown(UnknownTile::Prefilled { hint }) => {
-          format!("{}", soln.get(&DlxItem::Letter { letter: *hint }).unwrap())
-        }
-        Tile::Total(TotalTile {
-          horizontal,
-          vertical,
-        }) => format!(
-          "{},{}",
-          match vertical {
-            Some(TotalClue::OneDigit(digit)) => {
-              format!("{}", soln.get(&DlxItem::Letter { letter: *digit }).unwrap())
-            }
-            Some(TotalClue::TwoDigit { ones, tens }) => format!(
-              "{}{}",
-              soln.get(&DlxItem::Letter { letter: *tens }).unwrap(),
-              soln.get(&DlxItem::Letter { letter: *ones }).unwrap()
-            ),
-            None => "".to_string(),
-          },
-          match horizontal {
-            Some(TotalClue::OneDigit(digit)) => {
-              format!("{}", soln.get(&DlxItem::Letter { letter: *digit }).unwrap())
-            }
-            Some(TotalClue::TwoDigit { ones, tens }) => format!(
-              "{}{}",
-              soln.get(&DlxItem::Letter { letter: *tens }).unwrap(),
-              soln.get(&DlxItem::Letter { letter: *ones }).unwrap()
-            ),
-            None => "".to_string(),
-          },
-        ),
-        Tile::Empty => "X".to_owned(),
-      };
-      println!("{:10}", out);
-    });
+  #[test]
+  fn test_wrong_tile_count_error() {
+    assert_eq!(
+      "3,X,(vA),(vI),(hBB),O,5,(hC),D".parse::<Kakuro>(),
+      Err(ParseKakuroError(
+        "Expected 9 tiles for a 3x3 grid, found 8".to_string()
+      ))
+    );
+    assert_eq!(
+      "three,X".parse::<Kakuro>(),
+      Err(ParseKakuroError("Invalid grid size \"three\"".to_string()))
+    );
   }
 
-  pub fn solve(&self) -> Vec<LetterAssignment> {
-    let items = self.all_items();
+  #[test]
+  fn test_unbalanced_parentheses_error() {
+    let err =
+      Kakuro::from_reader("# comment\n3,X,(vA,(vI),(hBB),O,O,(hC),D,O".as_bytes()).unwrap_err();
+    assert_eq!(err.to_string(), "Line 2: Unclosed bracket at byte 4");
+  }
 
-    let choices = self.enumerate_lines().flat_map(|((item, clue), items)| {
-      let items = items.collect_vec();
-      let items_len = items.len();
-      clue
-        .all_combinations(items.len() as u32)
-        .flat_map(move |(total, choices)| {
-          choices
-            .into_iter()
-            .permutations(items_len)
-            .map(move |choices| (total.clone(), choices))
-        })
-        .filter_map(move |(total, choices)| {
-          Self::construct_dlx(
-            item.clone(),
-            total
-              .iter()
-              .map(Clone::clone)
-              .chain(items.iter().map(Clone::clone).zip(choices))
-              .collect(),
-          )
-        })
-    });
-    let choices = (0u64..).zip(choices);
+  /// Found by `fuzz_kakuro_parse`: the number of tiles in a huge grid
+  /// overflowed.
+  #[test]
+  fn test_huge_grid_size() {
+    assert!("5438954600631462710,v".parse::<Kakuro>().is_err());
+    assert_eq!(
+      "4294967296,4294967296,X".parse::<Kakuro>(),
+      Err(ParseKakuroError(
+        "Grid size 4294967296x4294967296 is too large".to_string()
+      ))
+    );
+  }
 
-    let mut dlx = Dlx::new(items, choices);
-    // println!("{dlx:?}");
+  #[test]
+  fn test_generate() {
+    let (puzzle, letters) = generate_with_letters(5, 5, 0.7, 424).unwrap();
+    assert_eq!(puzzle.rows(), 5);
+    assert_eq!(puzzle.cols(), 5);
+    assert_eq!(puzzle.solve().unwrap(), vec![letters]);
 
-    dlx
-      .find_all_solution_colors()
-      .map(|soln| {
-        // self.print_test(&soln);
-        soln
-          .into_iter()
-          .filter_map(|(item, color)| match item {
-            DlxItem::Letter { letter } => Some((letter, color)),
-            _ => None,
-          })
-          .fold(LetterAssignment::new(), |la, (letter, color)| {
-            la.with_value(letter, color)
-          })
-          .with_filled_remaining()
-      })
-      .collect_vec()
+    // A 3x3 grid has at most 4 unknowns, too few to use 9 letters.
+    assert_eq!(generate(3, 3, 0.9, 0), None);
   }
-}
 
-impl fmt::Display for Kakuro {
-  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-    self.tiles.iter().enumerate().try_for_each(|(idx, tile)| {
-      write!(f, "{:10}", tile)?;
-      if (idx + 1) % self.n == 0 {
-        writeln!(f)?;
-      }
-      Ok(())
-    })
+  #[test]
+  fn test_generate_is_deterministic() {
+    assert_eq!(generate(5, 5, 0.6, 1), generate(5, 5, 0.6, 1));
   }
-}
 
-#[cfg(test)]
-mod test {
-  use std::vec;
+  #[test]
+  fn test_solve_with_timeout() {
+    let puzzle = parse(PUZZLE_A);
+    assert_eq!(
+      puzzle.solve_with_timeout(Duration::ZERO),
+      Err(KakuroError::TimedOut(SolveTimeout {
+        limit: Duration::ZERO
+      }))
+    );
+    let letters = puzzle.solve_with_timeout(Duration::from_secs(60)).unwrap();
+    assert_eq!(letters.len(), 1);
+    assert_eq!(letters[0].int_value(), 4027398516);
 
-  use super::TotalClue;
+    assert!(matches!(
+      parse("2,X,(hA),X,X").solve_with_timeout(Duration::from_secs(60)),
+      Err(KakuroError::InvalidStructure(_))
+    ));
+  }
 
-  fn all_combinations(range: (u32, u32), num_tiles: u32) -> Vec<Vec<u32>> {
-    TotalClue::all_combinations_for_range(range, num_tiles)
-      .map(|(total, nums)| {
-        assert_eq!(nums.iter().sum::<u32>(), total);
-        nums
-      })
-      .collect()
+  #[test]
+  fn test_solve_all() {
+    let batch = format!(
+      "{PUZZLE_A}\n# Skipped\n3,X,(vA),(vI),(hBB),O,Q,(hC),D,O\n{PUZZLE_B}\n2,X,(hA),X,X\n"
+    );
+    let outcomes = Kakuro::solve_all(batch.as_bytes(), Duration::from_secs(60)).unwrap();
+    assert_eq!(outcomes.len(), 4);
+    assert!(
+      matches!(&outcomes[0], PuzzleOutcome::Solved(letters) if letters.len() == 1 && letters[0].int_value() == 4027398516)
+    );
+    assert!(
+      matches!(&outcomes[1], PuzzleOutcome::ParseError(message) if message.contains("\"Q\""))
+    );
+    assert!(
+      matches!(&outcomes[2], PuzzleOutcome::Solved(letters) if letters.len() == 1 && letters[0].int_value() == 4762183509)
+    );
+    assert!(matches!(&outcomes[3], PuzzleOutcome::InvalidStructure(errors) if !errors.is_empty()));
+
+    let outcomes = Kakuro::solve_all(batch.as_bytes(), Duration::ZERO).unwrap();
+    assert!(matches!(outcomes[0], PuzzleOutcome::TimedOut(_)));
+    assert!(matches!(outcomes[1], PuzzleOutcome::ParseError(_)));
+    assert!(matches!(outcomes[2], PuzzleOutcome::TimedOut(_)));
+    assert!(matches!(outcomes[3], PuzzleOutcome::InvalidStructure(_)));
   }
 
   #[test]
-  fn test_combinations_one() {
+  fn test_solve_dedups_and_sorts() {
+    // A lone run of two tiles summing to A: every digit from 3 to 9 works,
+    // most in several ways.
+    let kakuro = parse("2,3,X,X,X,(hA),O,O").with_alphabet_size(1);
+    assert_eq!(kakuro.solve_full().unwrap().len(), 32);
     assert_eq!(
-      all_combinations((2, 5), 1),
-      vec![vec![2], vec![3], vec![4], vec![5]]
+      kakuro
+        .solve()
+        .unwrap()
+        .iter()
+        .map(LetterAssignment::int_value)
+        .collect_vec(),
+      (3..=9).collect_vec()
     );
   }
 
   #[test]
-  fn test_combinations_wide_range() {
+  fn test_mixed_clues() {
+    let line = "3,X,(v1A),(vA5),(h17),O,O,(hB),O,O";
+    let kakuro = parse(line);
+    assert_eq!(kakuro.to_line(), line);
+    let clue = |row, col, direction| {
+      kakuro[(row, col)]
+        .as_total()
+        .unwrap()
+        .clue(direction)
+        .unwrap()
+        .clone()
+    };
     assert_eq!(
-      all_combinations((0, 12), 1),
-      vec![
-        vec![1],
-        vec![2],
-        vec![3],
-        vec![4],
-        vec![5],
-        vec![6],
-        vec![7],
-        vec![8],
-        vec![9]
-      ]
+      clue(0, 1, Direction::Vertical),
+      TotalClue::Mixed {
+        tens: ClueDigit::Literal(1),
+        ones: ClueDigit::Letter('A'),
+      }
+    );
+    assert_eq!(
+      clue(0, 2, Direction::Vertical),
+      TotalClue::Mixed {
+        tens: ClueDigit::Letter('A'),
+        ones: ClueDigit::Literal(5),
+      }
+    );
+    assert_eq!(clue(1, 0, Direction::Horizontal), TotalClue::Numeric(17));
+    assert_eq!(clue(0, 2, Direction::Vertical).letters(), vec!['A']);
+    assert_eq!(
+      clue(0, 1, Direction::Vertical)
+        .known_total(&LetterAssignment::from_pairs(&[('A', 3)]).unwrap()),
+      Some(13)
     );
+
+    // The 17 forces {8, 9} on the top row, so A5 can only be 15, and either
+    // way the bottom row adds up to 9.
+    let letters = kakuro.with_alphabet_size(2).solve().unwrap();
+    assert_eq!(letters.len(), 1);
+    assert_eq!(letters[0].int_value(), 19);
   }
 
   #[test]
-  fn test_combinations_two() {
+  fn test_total_clue_parse() {
+    assert_eq!(TotalClue::parse("A"), Ok(TotalClue::OneDigit('A')));
     assert_eq!(
-      all_combinations((2, 5), 2),
-      vec![vec![1, 2], vec![1, 3], vec![1, 4], vec![2, 3]]
+      TotalClue::parse("IJ"),
+      Ok(TotalClue::TwoDigit {
+        tens: 'I',
+        ones: 'J'
+      })
+    );
+    assert_eq!(
+      TotalClue::parse("1A"),
+      Ok(TotalClue::Mixed {
+        tens: ClueDigit::Literal(1),
+        ones: ClueDigit::Letter('A')
+      })
+    );
+    assert_eq!("17".parse(), Ok(TotalClue::Numeric(17)));
+
+    assert_eq!(TotalClue::parse(""), Err(ClueParseError::Empty));
+    assert_eq!(
+      TotalClue::parse("?%"),
+      Err(ClueParseError::InvalidCharacter {
+        clue: "?%".to_string(),
+        character: '?'
+      })
+    );
+    assert_eq!(
+      TotalClue::parse("aB"),
+      Err(ClueParseError::InvalidCharacter {
+        clue: "aB".to_string(),
+        character: 'a'
+      })
+    );
+    assert_eq!(
+      TotalClue::parse("K"),
+      Err(ClueParseError::InvalidCharacter {
+        clue: "K".to_string(),
+        character: 'K'
+      })
+    );
+    assert_eq!(
+      TotalClue::parse("ABC"),
+      Err(ClueParseError::TooLong("ABC".to_string()))
+    );
+    assert_eq!(
+      TotalClue::parse("0A"),
+      Err(ClueParseError::LeadingZero("0A".to_string()))
+    );
+    assert_eq!(
+      TotalClue::parse("07"),
+      Err(ClueParseError::LeadingZero("07".to_string()))
+    );
+    assert_eq!(
+      TotalClue::parse("99999999999"),
+      Err(ClueParseError::TooLarge("99999999999".to_string()))
+    );
+    assert_eq!(
+      TotalClue::parse("?%").unwrap_err().to_string(),
+      "Invalid character '?' in clue \"?%\""
     );
   }
 
   #[test]
-  fn test_combinations_large_range() {
+  fn test_total_clue_round_trip() {
+    let letters = ('A'..='J').collect_vec();
+    let digits = ('0'..='9').collect_vec();
+    // Every clue of letters, or of a letter and a digit, is valid.
+    for &letter in &letters {
+      let clue = letter.to_string();
+      assert_eq!(TotalClue::parse(&clue).unwrap().to_string(), clue);
+    }
+    for (&tens, &ones) in letters
+      .iter()
+      .chain(&digits[1..])
+      .cartesian_product(letters.iter().chain(&digits))
+    {
+      let clue = format!("{tens}{ones}");
+      assert_eq!(TotalClue::parse(&clue).unwrap().to_string(), clue);
+    }
+    for total in 0..=100 {
+      let clue = total.to_string();
+      assert_eq!(TotalClue::parse(&clue).unwrap().to_string(), clue);
+    }
+
+    // Whatever else is accepted also prints as it was written.
+    let chars = letters
+      .iter()
+      .chain(&digits)
+      .chain(&['K', 'Z', 'a', 'j', '?', '%', ' ', '-'])
+      .copied()
+      .collect_vec();
+    for len in 1..=3 {
+      for clue in iter::repeat_n(&chars, len).multi_cartesian_product() {
+        let clue = clue.into_iter().collect::<String>();
+        if let Ok(parsed) = TotalClue::parse(&clue) {
+          assert_eq!(parsed.to_string(), clue);
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn test_invalid_clue_error() {
+    let err = Kakuro::from_reader("3,X,(vA),(vI),(hB?),O,O,(hC),D,O".as_bytes()).unwrap_err();
     assert_eq!(
-      all_combinations((10, 20), 2),
-      vec![
-        vec![1, 9],
-        vec![2, 8],
-        vec![2, 9],
-        vec![3, 7],
-        vec![3, 8],
-        vec![3, 9],
-        vec![4, 6],
-        vec![4, 7],
-        vec![4, 8],
-        vec![4, 9],
-        vec![5, 6],
-        vec![5, 7],
-        vec![5, 8],
-        vec![5, 9],
-        vec![6, 7],
-        vec![6, 8],
-        vec![6, 9],
-        vec![7, 8],
-        vec![7, 9],
-        vec![8, 9],
-      ]
+      err.to_string(),
+      "Line 1: Tile at (1, 0): Invalid character '?' in clue \"B?\" in tile \"(hB?)\""
     );
+    let err = "3,X,(vA),(vI),(hBB),O,O,(hC),D,Q"
+      .parse::<Kakuro>()
+      .unwrap_err();
+    assert_eq!(err.to_string(), "Tile at (2, 2): Invalid tile \"Q\"");
   }
 
   #[test]
-  fn test_combinations_large_range_three() {
+  fn test_mixed_clue_leading_zero() {
+    assert!(Kakuro::from_reader("3,X,(v0A),(vA5),(h17),O,O,(hB),O,O".as_bytes()).is_err());
+  }
+
+  #[test]
+  fn test_letter_dependencies() {
+    // The given 1 fixes the left column and top row without their clues, and
+    // A is only written in the bottom row's clue.
+    let kakuro = parse("3,X,(v3),(v4),(h3),O,O,(hA),1,O").with_alphabet_size(1);
+    assert_eq!(kakuro.solve().unwrap()[0].int_value(), 4);
+    let bottom = RunId {
+      start: (2, 0),
+      direction: Direction::Horizontal,
+    };
+    let right = RunId {
+      start: (0, 2),
+      direction: Direction::Vertical,
+    };
     assert_eq!(
-      all_combinations((10, 20), 3),
+      kakuro.letter_dependencies().unwrap(),
+      HashMap::from([('A', vec![right, bottom])])
+    );
+  }
+
+  #[test]
+  fn test_batch_runner_resumes() {
+    let results_path =
+      std::env::temp_dir().join(format!("p424_results_{}.txt", std::process::id()));
+    let results_path = results_path.to_str().unwrap();
+    // Puzzle 0 was recorded, and the run died while writing puzzle 2.
+    std::fs::write(results_path, "0,8426039571\n2,30196").unwrap();
+
+    let runner = BatchRunner::new("testdata/p424_sample.txt", results_path);
+    let mut solved = Vec::new();
+    assert_eq!(
+      runner.run(|event| solved.push(event.index)).unwrap(),
+      19965339657
+    );
+    assert_eq!(solved, vec![1, 2]);
+    assert_eq!(
+      std::fs::read_to_string(results_path).unwrap(),
+      "0,8426039571\n1,8519647302\n2,3019652784\n"
+    );
+
+    // Everything is recorded now, so nothing is solved again.
+    assert_eq!(
+      runner.run(|_| panic!("Nothing left to solve")).unwrap(),
+      solve_p424("testdata/p424_sample.txt", |_| {}).unwrap()
+    );
+    std::fs::remove_file(results_path).unwrap();
+  }
+
+  #[test]
+  fn test_solve_instrumented() {
+    let kakuro = parse(PUZZLE_A);
+    let (letters, stats) = kakuro.solve_instrumented().unwrap();
+    assert_eq!(letters, kakuro.solve().unwrap());
+    assert_eq!(stats.runs, kakuro.runs().len());
+    assert!(stats.subsets <= stats.permutations);
+    assert!(stats.subsets > 0);
+    assert!(stats.nodes > 0);
+    assert_eq!(stats.solutions, 1);
+  }
+
+  /// Checks that every tag in `svg` is closed in order, returning the text
+  /// inside each `<text>` element.
+  fn svg_texts(svg: &str) -> Vec<String> {
+    let mut open = Vec::new();
+    let mut texts = Vec::new();
+    let mut rest = svg;
+    while let Some(start) = rest.find('<') {
+      let end = start + rest[start..].find('>').unwrap();
+      let tag = &rest[start + 1..end];
+      if let Some(name) = tag.strip_prefix('/') {
+        let (open_name, content_start) = open.pop().unwrap();
+        assert_eq!(name, open_name);
+        if name == "text" {
+          texts.push(svg[content_start..svg.len() - rest.len() + start].to_string());
+        }
+      } else if !tag.ends_with('/') {
+        let name = tag.split_whitespace().next().unwrap();
+        open.push((name, svg.len() - rest.len() + end + 1));
+      }
+      rest = &rest[end + 1..];
+    }
+    assert!(open.is_empty());
+    texts
+  }
+
+  #[test]
+  fn test_svg() {
+    let kakuro = parse("3,X,(vA),(vI),(hBB),O,O,(hC),D,O");
+    let svg = kakuro.to_svg(None, SvgOptions::default());
+    assert!(svg.starts_with("<svg "));
+    assert_eq!(svg.matches("<rect ").count(), 9);
+    assert_eq!(svg.matches("<line ").count(), 4);
+    assert_eq!(svg_texts(&svg), vec!["A", "I", "BB", "C", "D"]);
+
+    let solution = kakuro.solve_full().unwrap().pop().unwrap();
+    let svg = kakuro.to_svg(
+      Some(&solution),
+      SvgOptions {
+        font_family: "\"Fira Sans\"".to_string(),
+        ..SvgOptions::default()
+      },
+    );
+    assert!(svg.contains("font-family=\"&quot;Fira Sans&quot;\""));
+    let digit = |row, col| solution.digit(row, col).unwrap().to_string();
+    assert_eq!(
+      svg_texts(&svg),
       vec![
-        vec![1, 2, 7],
-        vec![1, 2, 8],
-        vec![1, 2, 9],
-        vec![1, 3, 6],
-        vec![1, 3, 7],
-        vec![1, 3, 8],
-        vec![1, 3, 9],
-        vec![1, 4, 5],
-        vec![1, 4, 6],
-        vec![1, 4, 7],
-        vec![1, 4, 8],
-        vec![1, 4, 9],
-        vec![1, 5, 6],
-        vec![1, 5, 7],
-        vec![1, 5, 8],
-        vec![1, 5, 9],
-        vec![1, 6, 7],
-        vec![1, 6, 8],
-        vec![1, 6, 9],
-        vec![1, 7, 8],
-        vec![1, 7, 9],
-        vec![1, 8, 9],
-        vec![2, 3, 5],
-        vec![2, 3, 6],
-        vec![2, 3, 7],
-        vec![2, 3, 8],
-        vec![2, 3, 9],
-        vec![2, 4, 5],
-        vec![2, 4, 6],
-        vec![2, 4, 7],
-        vec![2, 4, 8],
-        vec![2, 4, 9],
-        vec![2, 5, 6],
-        vec![2, 5, 7],
-        vec![2, 5, 8],
-        vec![2, 5, 9],
-        vec![2, 6, 7],
-        vec![2, 6, 8],
-        vec![2, 6, 9],
-        vec![2, 7, 8],
-        vec![2, 7, 9],
-        vec![2, 8, 9],
-        vec![3, 4, 5],
-        vec![3, 4, 6],
-        vec![3, 4, 7],
-        vec![3, 4, 8],
-        vec![3, 4, 9],
-        vec![3, 5, 6],
-        vec![3, 5, 7],
-        vec![3, 5, 8],
-        vec![3, 5, 9],
-        vec![3, 6, 7],
-        vec![3, 6, 8],
-        vec![3, 6, 9],
-        vec![3, 7, 8],
-        vec![3, 7, 9],
-        vec![3, 8, 9],
-        vec![4, 5, 6],
-        vec![4, 5, 7],
-        vec![4, 5, 8],
-        vec![4, 5, 9],
-        vec![4, 6, 7],
-        vec![4, 6, 8],
-        vec![4, 6, 9],
-        vec![4, 7, 8],
-        vec![4, 7, 9],
-        vec![5, 6, 7],
-        vec![5, 6, 8],
-        vec![5, 6, 9],
-        vec![5, 7, 8],
+        "A".to_string(),
+        "I".to_string(),
+        "BB".to_string(),
+        digit(1, 1),
+        digit(1, 2),
+        "C".to_string(),
+        "D".to_string(),
+        digit(2, 1),
+        digit(2, 2),
       ]
     );
   }
@@ -0,0 +1,357 @@
+//! A disk cache of the DLX subsets generated for kakuro puzzles, so puzzles
+//! solved again don't have to regenerate the combinations and permutations of
+//! their runs.
+//!
+//! Each entry is a binary file: a magic number, the generator version, the
+//! puzzle's hash, the subsets, and a checksum of everything before it. Every
+//! integer is little-endian, and each subset is its `ChoiceId` followed by the
+//! number of its constraints and the constraints themselves.
+
+use std::{
+  collections::HashSet,
+  fs, io,
+  path::{Path, PathBuf},
+};
+
+use log::debug;
+
+use super::{ChoiceId, DlxItem, KakuroModel};
+use crate::{
+  dlx::{ColorItem, Constraint},
+  exact_cover::ExactCoverModel,
+};
+
+/// Identifies the file format, and the way subsets are generated. Bump this
+/// whenever either changes, so entries written before aren't read back.
+pub const GENERATOR_VERSION: u32 = 1;
+
+const MAGIC: &[u8; 8] = b"p424dlx\0";
+
+/// The subsets of a kakuro's DLX model, as generated by `Kakuro::solve`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Choices {
+  subsets: Vec<(ChoiceId, Vec<Constraint<DlxItem>>)>,
+}
+
+impl Choices {
+  pub(super) fn generate(model: &KakuroModel) -> Self {
+    Self {
+      subsets: model
+        .choices()
+        .map(|(choice, constraints)| (choice, constraints.into_iter().collect()))
+        .collect(),
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    self.subsets.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.subsets.is_empty()
+  }
+
+  /// True if every subset only uses items of `model` and names one of its
+  /// runs, so could have been generated for it.
+  fn fits(&self, model: &KakuroModel) -> bool {
+    let items: HashSet<_> = model.items().map(|(item, _)| item).collect();
+    self.subsets.iter().all(|(choice, constraints)| {
+      choice.run_index() < model.runs.len()
+        && constraints
+          .iter()
+          .all(|constraint| items.contains(constraint.item()))
+    })
+  }
+
+  pub(super) fn into_subsets(self) -> Vec<(ChoiceId, Vec<Constraint<DlxItem>>)> {
+    self.subsets
+  }
+}
+
+/// A directory of cached subsets, holding one entry per puzzle.
+#[derive(Clone, Debug)]
+pub struct CacheDir {
+  path: PathBuf,
+}
+
+impl CacheDir {
+  /// Caches entries in the directory at `path`, creating it if it doesn't
+  /// exist.
+  pub fn new(path: impl Into<PathBuf>) -> io::Result<Self> {
+    let path = path.into();
+    fs::create_dir_all(&path)?;
+    Ok(Self { path })
+  }
+
+  /// The file holding the entry for the puzzle with hash `puzzle_hash`.
+  pub fn entry(&self, puzzle_hash: u64) -> PathBuf {
+    self.path.join(format!("{puzzle_hash:016x}.dlx"))
+  }
+}
+
+/// The FNV-1a hash of `bytes`, which unlike `DefaultHasher` is the same on
+/// every platform and Rust version.
+fn fnv1a(bytes: &[u8]) -> u64 {
+  bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+    (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+  })
+}
+
+/// The hash keying the entry for the puzzle written as `line`, as from
+/// `Kakuro::to_line`, under the current `GENERATOR_VERSION`.
+pub fn puzzle_hash(line: &str) -> u64 {
+  fnv1a(
+    &GENERATOR_VERSION
+      .to_le_bytes()
+      .into_iter()
+      .chain(line.bytes())
+      .collect::<Vec<_>>(),
+  )
+}
+
+fn encode_item(item: &DlxItem) -> (u8, u32) {
+  match *item {
+    DlxItem::Sum { idx, vertical } => (vertical as u8, idx),
+    DlxItem::Tile { idx } => (2, idx),
+    DlxItem::Letter { letter } => (3, letter as u32),
+    DlxItem::LetterValue { value } => (4, value),
+  }
+}
+
+fn decode_item(tag: u8, value: u32) -> Option<DlxItem> {
+  match tag {
+    0 | 1 => Some(DlxItem::Sum {
+      idx: value,
+      vertical: tag == 1,
+    }),
+    2 => Some(DlxItem::Tile { idx: value }),
+    3 => char::from_u32(value)
+      .filter(char::is_ascii_uppercase)
+      .map(|letter| DlxItem::Letter { letter }),
+    4 => Some(DlxItem::LetterValue { value }),
+    _ => None,
+  }
+}
+
+fn encode(puzzle_hash: u64, choices: &Choices) -> Vec<u8> {
+  let mut bytes = MAGIC.to_vec();
+  bytes.extend(GENERATOR_VERSION.to_le_bytes());
+  bytes.extend(puzzle_hash.to_le_bytes());
+  bytes.extend((choices.subsets.len() as u32).to_le_bytes());
+  for (choice, constraints) in &choices.subsets {
+    bytes.extend(choice.0.to_le_bytes());
+    bytes.extend((constraints.len() as u16).to_le_bytes());
+    for constraint in constraints {
+      let kind = match constraint {
+        Constraint::Primary(_) => 0,
+        Constraint::Secondary(_) => 1,
+        Constraint::Uncolored(_) => 2,
+      };
+      let (tag, value) = encode_item(constraint.item());
+      bytes.extend([kind, tag]);
+      bytes.extend(value.to_le_bytes());
+      bytes.extend(
+        constraint
+          .color()
+          .map(u32::to_le_bytes)
+          .into_iter()
+          .flatten(),
+      );
+    }
+  }
+  bytes.extend(fnv1a(&bytes).to_le_bytes());
+  bytes
+}
+
+/// Reads fixed-size values off the front of a byte slice, failing once it
+/// runs out.
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+  fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+    let (bytes, rest) = self.0.split_first_chunk()?;
+    self.0 = rest;
+    Some(*bytes)
+  }
+
+  fn u8(&mut self) -> Option<u8> {
+    self.take().map(u8::from_le_bytes)
+  }
+
+  fn u16(&mut self) -> Option<u16> {
+    self.take().map(u16::from_le_bytes)
+  }
+
+  fn u32(&mut self) -> Option<u32> {
+    self.take().map(u32::from_le_bytes)
+  }
+
+  fn u64(&mut self) -> Option<u64> {
+    self.take().map(u64::from_le_bytes)
+  }
+}
+
+/// Decodes an entry written by `encode`, or None if it's malformed, was
+/// written for another puzzle or generator version, or fails its checksum.
+fn decode(puzzle_hash: u64, bytes: &[u8]) -> Option<Choices> {
+  let (body, checksum) = bytes.split_last_chunk::<8>()?;
+  if fnv1a(body) != u64::from_le_bytes(*checksum) {
+    return None;
+  }
+
+  let mut reader = Reader(body);
+  if reader.take::<8>()? != *MAGIC
+    || reader.u32()? != GENERATOR_VERSION
+    || reader.u64()? != puzzle_hash
+  {
+    return None;
+  }
+  let subsets = (0..reader.u32()?)
+    .map(|_| {
+      let choice = ChoiceId(reader.u64()?);
+      let constraints = (0..reader.u16()?)
+        .map(|_| {
+          let kind = reader.u8()?;
+          let item = decode_item(reader.u8()?, reader.u32()?)?;
+          match kind {
+            0 => Some(Constraint::Primary(item)),
+            1 => Some(ColorItem::new(item, reader.u32()?).into()),
+            2 => Some(Constraint::Uncolored(item)),
+            _ => None,
+          }
+        })
+        .collect::<Option<_>>()?;
+      Some((choice, constraints))
+    })
+    .collect::<Option<_>>()?;
+  reader.0.is_empty().then_some(Choices { subsets })
+}
+
+/// Writes `choices` to the file at `path`, as the entry for the puzzle with
+/// hash `puzzle_hash`.
+pub fn save(path: &Path, puzzle_hash: u64, choices: &Choices) -> io::Result<()> {
+  fs::write(path, encode(puzzle_hash, choices))
+}
+
+/// Reads the entry for the puzzle with hash `puzzle_hash` from the file at
+/// `path`. Returns None if the file can't be read, or holds anything other
+/// than an intact entry for that puzzle written by this `GENERATOR_VERSION`.
+pub fn load(path: &Path, puzzle_hash: u64) -> Option<Choices> {
+  decode(puzzle_hash, &fs::read(path).ok()?)
+}
+
+/// The subsets of `model`, the model of the puzzle written as `line`, read
+/// from its entry in `dir` if that can be trusted, and otherwise generated
+/// and saved there.
+pub(super) fn load_or_generate(dir: &CacheDir, line: &str, model: &KakuroModel) -> Choices {
+  let hash = puzzle_hash(line);
+  let path = dir.entry(hash);
+  if let Some(choices) = load(&path, hash).filter(|choices| choices.fits(model)) {
+    debug!("Loaded {} subsets from {}", choices.len(), path.display());
+    return choices;
+  }
+
+  let choices = Choices::generate(model);
+  if let Err(err) = save(&path, hash, &choices) {
+    debug!("Failed to cache subsets at {}: {err}", path.display());
+  }
+  choices
+}
+
+#[cfg(test)]
+mod test {
+  use std::{fs, path::PathBuf};
+
+  use super::{load, puzzle_hash, save, CacheDir, Choices, GENERATOR_VERSION};
+  use crate::kakuro::{CombinationCache, Kakuro, LetterAssignment, Relaxed};
+
+  const PUZZLE: &str = "4,X,(vAG),(vAA),X,(h17),E,F,(v11),(hAA),D,A,C,X,(h10),B,F";
+
+  fn parse(line: &str) -> Kakuro {
+    Kakuro::from_reader(line.as_bytes()).unwrap().pop().unwrap()
+  }
+
+  fn temp_dir(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("p424_cache_{name}_{}", std::process::id()))
+  }
+
+  fn choices(kakuro: &Kakuro) -> Choices {
+    let partial = LetterAssignment::new();
+    let mut cache = CombinationCache::new();
+    Choices::generate(&kakuro.model(&partial, &mut cache, true, Relaxed::Nothing))
+  }
+
+  #[test]
+  fn test_round_trip() {
+    let dir = CacheDir::new(temp_dir("round_trip")).unwrap();
+    let kakuro = parse(PUZZLE);
+    let choices = choices(&kakuro);
+    assert!(!choices.is_empty());
+
+    let hash = puzzle_hash(&kakuro.to_line());
+    let path = dir.entry(hash);
+    save(&path, hash, &choices).unwrap();
+    assert_eq!(load(&path, hash), Some(choices));
+    // The entry is only for its own puzzle.
+    assert_eq!(load(&path, hash + 1), None);
+    assert_eq!(load(&dir.entry(hash + 1), hash + 1), None);
+
+    fs::remove_dir_all(&dir.path).unwrap();
+  }
+
+  #[test]
+  fn test_poisoned_entry() {
+    let dir = CacheDir::new(temp_dir("poisoned")).unwrap();
+    let kakuro = parse(PUZZLE);
+    let expected = kakuro.solve().unwrap();
+    let hash = puzzle_hash(&kakuro.to_line());
+    let path = dir.entry(hash);
+
+    assert_eq!(kakuro.solve_with_cache_dir(Some(&dir)).unwrap(), expected);
+    let written = fs::read(&path).unwrap();
+    assert!(load(&path, hash).is_some());
+
+    let mut flipped = written.clone();
+    flipped[written.len() / 2] ^= 1;
+    let mut version = written.clone();
+    version[8..12].copy_from_slice(&(GENERATOR_VERSION + 1).to_le_bytes());
+    for poisoned in [
+      flipped,
+      version,
+      written[..written.len() - 1].to_vec(),
+      b"not an entry".to_vec(),
+      vec![],
+    ] {
+      fs::write(&path, &poisoned).unwrap();
+      assert_eq!(load(&path, hash), None);
+      // The solve falls back to generating the subsets, and replaces the
+      // entry with them.
+      assert_eq!(kakuro.solve_with_cache_dir(Some(&dir)).unwrap(), expected);
+      assert_eq!(fs::read(&path).unwrap(), written);
+    }
+
+    fs::remove_dir_all(&dir.path).unwrap();
+  }
+
+  #[test]
+  fn test_entry_for_other_puzzle() {
+    let dir = CacheDir::new(temp_dir("other_puzzle")).unwrap();
+    let kakuro = parse(PUZZLE);
+    let other = parse("4,X,(v16),(v11),X,(h17),O,O,(v11),(h11),O,O,O,X,(h10),O,O");
+    let hash = puzzle_hash(&kakuro.to_line());
+
+    // An intact entry with subsets of the wrong puzzle isn't trusted either.
+    save(&dir.entry(hash), hash, &choices(&other)).unwrap();
+    assert_eq!(
+      kakuro.solve_with_cache_dir(Some(&dir)).unwrap(),
+      kakuro.solve().unwrap()
+    );
+    assert_eq!(load(&dir.entry(hash), hash), Some(choices(&kakuro)));
+
+    assert_eq!(
+      other.solve_with_cache_dir(None).unwrap(),
+      other.solve().unwrap()
+    );
+    fs::remove_dir_all(&dir.path).unwrap();
+  }
+}
@@ -0,0 +1,293 @@
+//! Re-solving a kakuro after editing its clues, without regenerating the
+//! subsets of every run.
+//!
+//! A `KakuroSession` keeps the DLX model of its puzzle between solves. Editing
+//! a clue only replaces the subsets of that clue's run, so the runs left alone
+//! keep the subsets generated for them the first time.
+//!
+//! Replaced subsets leave their nodes behind in the grid, unreachable, so the
+//! grid grows with every edit. Once it's twice the size it was last built at,
+//! the session builds it again from scratch to drop them.
+
+use itertools::Itertools;
+
+use super::{
+  ChoiceId, CombinationCache, Direction, DlxItem, Kakuro, KakuroError, KakuroModel,
+  LetterAssignment, Relaxed, Run, Tile, TotalClue,
+};
+use crate::{dlx::Dlx, exact_cover::ExactCoverModel};
+
+/// A puzzle along with the DLX model built for it, which can be solved
+/// repeatedly as its clues are changed with `set_clue`.
+///
+/// Subsets are generated for every combination of each run rather than only
+/// those which survive propagation, as propagation would tie the subsets of
+/// each run to the clues of the others.
+pub struct KakuroSession {
+  kakuro: Kakuro,
+  cache: CombinationCache,
+  /// The runs in the order their subsets were first generated, indexed by
+  /// `ChoiceId::run_index`, each with the number of combinations it admits.
+  runs: Vec<(Run, u64)>,
+  /// The names of the subsets generated for each run, indexed like `runs`.
+  choices: Vec<Vec<ChoiceId>>,
+  dlx: Dlx<DlxItem, ChoiceId>,
+  /// The number of nodes in `dlx` when it was built, before any were left
+  /// behind by edits.
+  built_nodes: usize,
+}
+
+impl KakuroSession {
+  /// Builds the model of `kakuro`, generating the subsets of all of its runs.
+  pub fn new(kakuro: Kakuro) -> Result<Self, KakuroError> {
+    kakuro.validate().map_err(KakuroError::InvalidStructure)?;
+    let mut cache = CombinationCache::new();
+    let partial = LetterAssignment::new();
+    let model = kakuro.model(&partial, &mut cache, false, Relaxed::Nothing);
+    let runs = model.runs.clone();
+
+    let mut choices = vec![Vec::new(); runs.len()];
+    let subsets = model
+      .choices()
+      .inspect(|(choice, _)| choices[choice.run_index()].push(*choice))
+      .map(|(choice, constraints)| (choice, constraints.into_iter().collect_vec()))
+      .collect_vec();
+    let mut dlx = Dlx::new(model.items(), subsets);
+    model.prepare(&mut dlx);
+
+    Ok(Self {
+      kakuro,
+      cache,
+      runs,
+      choices,
+      built_nodes: dlx.num_nodes(),
+      dlx,
+    })
+  }
+
+  /// The puzzle with every edit made so far.
+  pub fn kakuro(&self) -> &Kakuro {
+    &self.kakuro
+  }
+
+  /// Replaces the clue at `(row, col)` for the run in `direction` with `clue`,
+  /// regenerating the subsets of that run alone. Changing whether the puzzle
+  /// uses letters at all changes the items of the model, so rebuilds it from
+  /// scratch, as does an edit which grows the grid to twice its built size.
+  /// Fails without changing anything if there's no such clue, or `clue` uses
+  /// letters outside of the puzzle's alphabet.
+  pub fn set_clue(
+    &mut self,
+    row: usize,
+    col: usize,
+    direction: Direction,
+    clue: TotalClue,
+  ) -> Result<(), KakuroError> {
+    let no_clue = KakuroError::NoClue {
+      row,
+      col,
+      direction,
+    };
+    let Some(Tile::Total(total_tile)) = self.kakuro.tile(row, col) else {
+      return Err(no_clue);
+    };
+    if total_tile.clue(direction).is_none() {
+      return Err(no_clue);
+    }
+    let alphabet = self.kakuro.alphabet;
+    if let Some(letter) = clue
+      .letters()
+      .into_iter()
+      .find(|&letter| LetterAssignment::letter_idx(letter).is_none_or(|idx| idx >= alphabet))
+    {
+      return Err(KakuroError::LetterOutsideAlphabet { letter, alphabet });
+    }
+
+    let total_tile = match direction {
+      Direction::Horizontal => total_tile.clone().with_horizontal(clue.clone()),
+      Direction::Vertical => total_tile.clone().with_vertical(clue.clone()),
+    };
+    let mut kakuro = self.kakuro.clone();
+    let idx = kakuro.get_idx(row, col);
+    kakuro.tiles[idx] = Tile::Total(total_tile);
+    if kakuro.uses_letters() != self.kakuro.uses_letters() {
+      *self = Self::new(kakuro)?;
+      return Ok(());
+    }
+    self.kakuro = kakuro;
+
+    let run_idx = self
+      .runs
+      .iter()
+      .position(|(run, _)| run.start == (row, col) && run.direction == direction)
+      .expect("Every clue has a run");
+    let partial = LetterAssignment::new();
+    let (run, count) = &mut self.runs[run_idx];
+    *count = clue.combination_count(run.cells.len() as u32, &partial);
+    run.clue = clue;
+
+    for choice in self.choices[run_idx].drain(..) {
+      self.dlx.remove_subset(&choice);
+    }
+    let model = model(&self.kakuro, &partial, &mut self.cache, &self.runs);
+    for (choice, constraints) in model.run_choices(run_idx, None) {
+      self
+        .dlx
+        .add_subset(choice, constraints)
+        .map_err(KakuroError::Model)?;
+      self.choices[run_idx].push(choice);
+    }
+
+    if self.dlx.num_nodes() >= 2 * self.built_nodes {
+      *self = Self::new(self.kakuro.clone())?;
+    }
+    Ok(())
+  }
+
+  /// Solves the puzzle as it stands, like `Kakuro::solve`.
+  pub fn solve(&mut self) -> Vec<LetterAssignment> {
+    let partial = LetterAssignment::new();
+    let model = model(&self.kakuro, &partial, &mut self.cache, &self.runs);
+    let solutions = self.kakuro.distinct_letter_solutions(&model, &mut self.dlx);
+    self.kakuro.completed_letters(solutions)
+  }
+}
+
+/// The model of `kakuro` with its runs in the order `runs` first generated
+/// them in, which may no longer be sorted by how constrained they are.
+fn model<'a>(
+  kakuro: &'a Kakuro,
+  partial: &'a LetterAssignment,
+  cache: &'a mut CombinationCache,
+  runs: &[(Run, u64)],
+) -> KakuroModel<'a> {
+  KakuroModel {
+    runs: runs.to_vec(),
+    ..kakuro.model(partial, cache, false, Relaxed::Nothing)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::KakuroSession;
+  use crate::kakuro::{Direction, Kakuro, KakuroError, Tile, TotalClue};
+
+  const PUZZLE_A: &str = "6,X,(vIJ),(vIE),X,X,X,(hF),O,C,(vIA),X,X,(hCA),O,O,D,(vIH),X,X,(hIB),E,O,O,(vF),X,X,(hD),O,A,O,X,X,X,(hID),O,G";
+  const NUMERIC: &str = "4,X,(v16),(v11),X,(h17),O,O,(v11),(h11),O,O,O,X,(h10),O,O";
+
+  fn parse(line: &str) -> Kakuro {
+    line.parse().unwrap()
+  }
+
+  /// Edits the clue at `(row, col)` in both `session` and `kakuro`, and checks
+  /// the session solves to the same as a fresh solve of the edited puzzle.
+  fn edit_and_compare(
+    session: &mut KakuroSession,
+    kakuro: &mut Kakuro,
+    (row, col): (usize, usize),
+    direction: Direction,
+    clue: &str,
+  ) {
+    let clue = TotalClue::parse(clue).unwrap();
+    session.set_clue(row, col, direction, clue.clone()).unwrap();
+    let idx = kakuro.get_idx(row, col);
+    let total = kakuro.tiles[idx].as_total().unwrap().clone();
+    kakuro.tiles[idx] = Tile::Total(match direction {
+      Direction::Horizontal => total.with_horizontal(clue),
+      Direction::Vertical => total.with_vertical(clue),
+    });
+
+    assert_eq!(session.kakuro(), kakuro);
+    assert_eq!(session.solve(), kakuro.solve().unwrap());
+  }
+
+  #[test]
+  fn test_set_clue() {
+    let mut kakuro = parse(PUZZLE_A);
+    let mut session = KakuroSession::new(kakuro.clone()).unwrap();
+    assert_eq!(session.solve(), kakuro.solve().unwrap());
+
+    edit_and_compare(
+      &mut session,
+      &mut kakuro,
+      (2, 0),
+      Direction::Horizontal,
+      "CB",
+    );
+    edit_and_compare(&mut session, &mut kakuro, (0, 2), Direction::Vertical, "IF");
+    edit_and_compare(
+      &mut session,
+      &mut kakuro,
+      (2, 0),
+      Direction::Horizontal,
+      "CA",
+    );
+    edit_and_compare(&mut session, &mut kakuro, (0, 2), Direction::Vertical, "IE");
+    assert_eq!(kakuro, parse(PUZZLE_A));
+    assert_eq!(session.solve(), kakuro.solve().unwrap());
+
+    // Edits leave dead nodes behind, but the grid is rebuilt before they
+    // outnumber the live ones.
+    for clue in ["CB", "CA"].into_iter().cycle().take(20) {
+      session
+        .set_clue(2, 0, Direction::Horizontal, TotalClue::parse(clue).unwrap())
+        .unwrap();
+      assert!(session.dlx.num_nodes() < 2 * session.built_nodes);
+    }
+    assert_eq!(session.solve(), kakuro.solve().unwrap());
+  }
+
+  #[test]
+  fn test_set_numeric_clue() {
+    let mut kakuro = parse(NUMERIC);
+    let mut session = KakuroSession::new(kakuro.clone()).unwrap();
+    let mut found = 0;
+    for total in 3..=17 {
+      edit_and_compare(
+        &mut session,
+        &mut kakuro,
+        (1, 0),
+        Direction::Horizontal,
+        &total.to_string(),
+      );
+      found += session.solve().len();
+    }
+    assert!(found > 0);
+
+    // Switching to a letter clue adds the letters to the model.
+    edit_and_compare(&mut session, &mut kakuro, (0, 1), Direction::Vertical, "AB");
+    edit_and_compare(&mut session, &mut kakuro, (0, 1), Direction::Vertical, "16");
+  }
+
+  #[test]
+  fn test_set_clue_errors() {
+    let mut session = KakuroSession::new(parse(PUZZLE_A)).unwrap();
+    assert_eq!(
+      session.set_clue(0, 0, Direction::Horizontal, TotalClue::Numeric(3)),
+      Err(KakuroError::NoClue {
+        row: 0,
+        col: 0,
+        direction: Direction::Horizontal
+      })
+    );
+    assert_eq!(
+      session.set_clue(1, 0, Direction::Vertical, TotalClue::Numeric(3)),
+      Err(KakuroError::NoClue {
+        row: 1,
+        col: 0,
+        direction: Direction::Vertical
+      })
+    );
+
+    let kakuro = parse("3,X,(vA),(vB),(hBA),O,O,(hC),O,O").with_alphabet_size(3);
+    let mut session = KakuroSession::new(kakuro.clone()).unwrap();
+    assert_eq!(
+      session.set_clue(1, 0, Direction::Horizontal, TotalClue::OneDigit('D')),
+      Err(KakuroError::LetterOutsideAlphabet {
+        letter: 'D',
+        alphabet: 3
+      })
+    );
+    assert_eq!(session.kakuro(), &kakuro);
+  }
+}
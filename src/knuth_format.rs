@@ -0,0 +1,326 @@
+use std::{
+  collections::{HashMap, HashSet},
+  error::Error,
+  fmt::{self, Display},
+  io::{self, BufRead},
+};
+
+use itertools::Itertools;
+
+use crate::dlx::{ColorItem, Constraint, Dlx, HeaderType};
+
+/// Why a problem in Knuth's DLX format couldn't be read, along with the line
+/// it was found on, counting from 1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KnuthFormatError {
+  /// The input has no line listing the items.
+  MissingItems,
+  /// The items line lists `item` more than once.
+  DuplicateItem { line: usize, item: String },
+  /// The items line has more than one `|` separating primary from secondary
+  /// items.
+  ExtraSeparator { line: usize },
+  /// An option names an item which isn't in the items line.
+  UnknownItem { line: usize, item: String },
+  /// An option names `item` more than once.
+  RepeatedItem { line: usize, item: String },
+  /// An option gives the primary item `item` a color.
+  ColoredPrimary { line: usize, item: String },
+  /// The secondary item `item` is given a color in some options but not in
+  /// others.
+  MixedColors { line: usize, item: String },
+}
+
+impl Display for KnuthFormatError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      KnuthFormatError::MissingItems => write!(f, "Missing the line of items"),
+      KnuthFormatError::DuplicateItem { line, item } => {
+        write!(f, "Line {line}: Item \"{item}\" is listed twice")
+      }
+      KnuthFormatError::ExtraSeparator { line } => {
+        write!(f, "Line {line}: More than one '|' in the items")
+      }
+      KnuthFormatError::UnknownItem { line, item } => {
+        write!(f, "Line {line}: Unknown item \"{item}\"")
+      }
+      KnuthFormatError::RepeatedItem { line, item } => {
+        write!(
+          f,
+          "Line {line}: Item \"{item}\" appears twice in one option"
+        )
+      }
+      KnuthFormatError::ColoredPrimary { line, item } => {
+        write!(f, "Line {line}: Primary item \"{item}\" can't have a color")
+      }
+      KnuthFormatError::MixedColors { line, item } => write!(
+        f,
+        "Line {line}: Secondary item \"{item}\" is used both with and without colors"
+      ),
+    }
+  }
+}
+
+impl Error for KnuthFormatError {}
+
+/// An exact cover problem written in the text format read by Knuth's DLX
+/// programs. The first line lists the primary items, then `|`, then the
+/// secondary items. Every later line is an option, listing the items it
+/// covers, where secondary items may be given a color as `item:color`. Lines
+/// starting with `|` are comments.
+///
+/// ```
+/// use p424::knuth_format::KnuthProblem;
+///
+/// let problem = KnuthProblem::parse("a b | x\na x:red\nb x:red\nb").unwrap();
+/// let mut dlx = problem.to_dlx();
+/// assert_eq!(dlx.find_all_solution_names().count(), 2);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KnuthProblem {
+  items: Vec<(String, HeaderType)>,
+  /// The items of each option, with the index of their color in `colors`.
+  options: Vec<Vec<(String, Option<u32>)>>,
+  /// The name of each color, in the order they first appear.
+  colors: Vec<String>,
+}
+
+impl KnuthProblem {
+  pub fn parse(text: &str) -> Result<Self, KnuthFormatError> {
+    Self::from_lines(text.lines().map(str::to_string))
+  }
+
+  pub fn from_reader<R: BufRead>(f: R) -> io::Result<Self> {
+    Self::from_lines(f.lines().collect::<io::Result<Vec<_>>>()?)
+      .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+  }
+
+  fn from_lines(lines: impl IntoIterator<Item = String>) -> Result<Self, KnuthFormatError> {
+    let mut lines = lines
+      .into_iter()
+      .enumerate()
+      .map(|(idx, line)| (idx + 1, line))
+      .filter(|(_, line)| {
+        let line = line.trim();
+        !line.is_empty() && !line.starts_with('|')
+      });
+
+    let (items_line, items) = lines.next().ok_or(KnuthFormatError::MissingItems)?;
+    let mut sections = items.split('|');
+    let primary = sections.next().unwrap_or_default();
+    let secondary = sections.next().unwrap_or_default();
+    if sections.next().is_some() {
+      return Err(KnuthFormatError::ExtraSeparator { line: items_line });
+    }
+    let items = primary
+      .split_whitespace()
+      .map(|item| (item.to_string(), HeaderType::Primary))
+      .chain(
+        secondary
+          .split_whitespace()
+          .map(|item| (item.to_string(), HeaderType::Secondary)),
+      )
+      .collect_vec();
+    if let Some((item, _)) = items.iter().duplicates_by(|(item, _)| item).next() {
+      return Err(KnuthFormatError::DuplicateItem {
+        line: items_line,
+        item: item.clone(),
+      });
+    }
+
+    let header_types: HashMap<_, _> = items
+      .iter()
+      .map(|(item, header_type)| (item.clone(), matches!(header_type, HeaderType::Primary)))
+      .collect();
+    // Whether each secondary item has been seen with a color, or without.
+    let mut colored: HashMap<String, bool> = HashMap::new();
+    let mut color_indices: HashMap<String, u32> = HashMap::new();
+    let mut colors = Vec::new();
+    let mut options = Vec::new();
+    for (line, option) in lines {
+      let mut seen = HashSet::new();
+      let option = option
+        .split_whitespace()
+        .map(|token| {
+          let (item, color) = match token.split_once(':') {
+            Some((item, color)) => (item, Some(color)),
+            None => (token, None),
+          };
+          let is_primary =
+            *header_types
+              .get(item)
+              .ok_or_else(|| KnuthFormatError::UnknownItem {
+                line,
+                item: item.to_string(),
+              })?;
+          if !seen.insert(item) {
+            return Err(KnuthFormatError::RepeatedItem {
+              line,
+              item: item.to_string(),
+            });
+          }
+          if is_primary && color.is_some() {
+            return Err(KnuthFormatError::ColoredPrimary {
+              line,
+              item: item.to_string(),
+            });
+          }
+          if !is_primary
+            && *colored.entry(item.to_string()).or_insert(color.is_some()) != color.is_some()
+          {
+            return Err(KnuthFormatError::MixedColors {
+              line,
+              item: item.to_string(),
+            });
+          }
+
+          let color = color.map(|color| {
+            *color_indices.entry(color.to_string()).or_insert_with(|| {
+              colors.push(color.to_string());
+              colors.len() as u32 - 1
+            })
+          });
+          Ok((item.to_string(), color))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+      options.push(option);
+    }
+
+    Ok(Self {
+      items,
+      options,
+      colors,
+    })
+  }
+
+  pub fn num_options(&self) -> usize {
+    self.options.len()
+  }
+
+  /// The name of the color with index `color`, as given in `Dlx` solutions.
+  pub fn color_name(&self, color: u32) -> Option<&str> {
+    self.colors.get(color as usize).map(String::as_str)
+  }
+
+  /// The option at `idx` as it would be written in the input.
+  pub fn option_line(&self, idx: usize) -> Option<String> {
+    self.options.get(idx).map(|option| {
+      option
+        .iter()
+        .map(|(item, color)| match color {
+          Some(color) => format!("{item}:{}", self.colors[*color as usize]),
+          None => item.clone(),
+        })
+        .join(" ")
+    })
+  }
+
+  /// Builds the `Dlx` for this problem, naming each subset by the index of its
+  /// option.
+  pub fn to_dlx(&self) -> Dlx<String, usize> {
+    let is_primary: HashSet<_> = self
+      .items
+      .iter()
+      .filter(|(_, header_type)| matches!(header_type, HeaderType::Primary))
+      .map(|(item, _)| item.as_str())
+      .collect();
+    let subsets = self.options.iter().enumerate().map(|(idx, option)| {
+      let constraints = option
+        .iter()
+        .map(|(item, color)| match color {
+          Some(color) => ColorItem::new(item.clone(), *color).into(),
+          None if is_primary.contains(item.as_str()) => Constraint::Primary(item.clone()),
+          None => Constraint::Uncolored(item.clone()),
+        })
+        .collect_vec();
+      (idx, constraints)
+    });
+    Dlx::new(self.items.iter().cloned(), subsets)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use itertools::Itertools;
+
+  use super::{KnuthFormatError, KnuthProblem};
+
+  #[test]
+  fn test_parse() {
+    let problem = KnuthProblem::parse(
+      "| A comment\n\
+       p q r | x\n\
+       \n\
+       p q x:A\n\
+       p r x:B\n\
+       p\n\
+       q\n\
+       | Another comment\n\
+       r x:A",
+    )
+    .unwrap();
+    assert_eq!(problem.num_options(), 5);
+    assert_eq!(problem.option_line(1), Some("p r x:B".to_string()));
+    assert_eq!(problem.color_name(0), Some("A"));
+    assert_eq!(problem.color_name(2), None);
+
+    let solutions = problem
+      .to_dlx()
+      .find_all_solution_names()
+      .map(|names| names.into_iter().sorted().collect_vec())
+      .sorted()
+      .collect_vec();
+    assert_eq!(solutions, vec![vec![0, 4], vec![1, 3], vec![2, 3, 4]]);
+  }
+
+  #[test]
+  fn test_uncolored_secondary() {
+    let problem = KnuthProblem::parse("p q | x\np x\nq x\np\nq").unwrap();
+    assert_eq!(problem.to_dlx().find_all_solution_names().count(), 3);
+  }
+
+  #[test]
+  fn test_parse_errors() {
+    for (text, expected) in [
+      ("| Only a comment", KnuthFormatError::MissingItems),
+      (
+        "p p",
+        KnuthFormatError::DuplicateItem {
+          line: 1,
+          item: "p".to_string(),
+        },
+      ),
+      ("p | x | y", KnuthFormatError::ExtraSeparator { line: 1 }),
+      (
+        "p\n\np z",
+        KnuthFormatError::UnknownItem {
+          line: 3,
+          item: "z".to_string(),
+        },
+      ),
+      (
+        "p\np p",
+        KnuthFormatError::RepeatedItem {
+          line: 2,
+          item: "p".to_string(),
+        },
+      ),
+      (
+        "p\np:A",
+        KnuthFormatError::ColoredPrimary {
+          line: 2,
+          item: "p".to_string(),
+        },
+      ),
+      (
+        "p | x\np x:A\np x",
+        KnuthFormatError::MixedColors {
+          line: 3,
+          item: "x".to_string(),
+        },
+      ),
+    ] {
+      assert_eq!(KnuthProblem::parse(text), Err(expected), "{text}");
+    }
+  }
+}
@@ -0,0 +1,126 @@
+use std::fmt::{self, Display};
+
+use itertools::Itertools;
+
+use crate::dlx::{Constraint, Dlx, HeaderType};
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+enum LangfordItem {
+  Value(u32),
+  Position(usize),
+}
+
+/// Each value 1..=n is placed exactly once as a pair, and each of the `2n`
+/// positions holds exactly one value. A subset `(k, i)` puts `k` at positions
+/// `i` and `i + k + 1`.
+///
+/// Every pairing reversed is also a pairing, so the pair of 1s is kept in the
+/// first half of the sequence to count each pairing only once. The 1s are
+/// never placed symmetrically about the middle, so this drops exactly one of
+/// each reversed pair.
+fn build_dlx(n: u32) -> Dlx<LangfordItem, (u32, usize)> {
+  let len = 2 * n as usize;
+  let items = (1..=n)
+    .map(LangfordItem::Value)
+    .chain((0..len).map(LangfordItem::Position))
+    .map(|item| (item, HeaderType::Primary));
+
+  let subsets = (1..=n).flat_map(move |value| {
+    let gap = value as usize + 1;
+    (0..len.saturating_sub(gap))
+      .filter(move |&i| value != 1 || i + gap < len - i - 1)
+      .map(move |i| {
+        (
+          (value, i),
+          vec![
+            Constraint::Primary(LangfordItem::Value(value)),
+            Constraint::Primary(LangfordItem::Position(i)),
+            Constraint::Primary(LangfordItem::Position(i + gap)),
+          ],
+        )
+      })
+  });
+
+  Dlx::new(items, subsets)
+}
+
+/// Finds a Langford pairing of the values 1..=n, a sequence of length `2n`
+/// containing each value twice with `k` values between the two `k`s.
+pub fn solve(n: u32) -> Option<Vec<u32>> {
+  let pairs = build_dlx(n).find_first_solution_names()?;
+  let mut sequence = vec![0; 2 * n as usize];
+  for (value, i) in pairs {
+    sequence[i] = value;
+    sequence[i + value as usize + 1] = value;
+  }
+  Some(sequence)
+}
+
+/// Counts the Langford pairings of 1..=n, not counting a pairing and its
+/// reversal separately.
+pub fn count(n: u32) -> u64 {
+  build_dlx(n).count_solutions(usize::MAX) as u64
+}
+
+/// A Langford sequence, displayed as space-separated values.
+pub struct Sequence(pub Vec<u32>);
+
+impl Display for Sequence {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.0.iter().join(" "))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use itertools::Itertools;
+
+  use super::{count, solve, Sequence};
+
+  fn is_langford(sequence: &[u32]) -> bool {
+    let n = sequence.len() as u32 / 2;
+    (1..=n).all(|value| {
+      let positions = sequence.iter().positions(|&v| v == value).collect_vec();
+      positions.len() == 2 && positions[1] - positions[0] == value as usize + 1
+    })
+  }
+
+  #[test]
+  fn test_solve() {
+    let sequence = solve(3).unwrap();
+    assert!(
+      sequence == [2, 3, 1, 2, 1, 3] || sequence == [3, 1, 2, 1, 3, 2],
+      "{}",
+      Sequence(sequence)
+    );
+
+    for n in [4, 7, 8, 11] {
+      let sequence = solve(n).unwrap();
+      assert!(is_langford(&sequence), "{}", Sequence(sequence));
+    }
+  }
+
+  #[test]
+  fn test_no_solution() {
+    for n in [1, 2, 5, 6, 9] {
+      assert_eq!(solve(n), None);
+      assert_eq!(count(n), 0);
+    }
+  }
+
+  #[test]
+  fn test_count() {
+    assert_eq!(count(3), 1);
+    assert_eq!(count(4), 1);
+    assert_eq!(count(7), 26);
+    assert_eq!(count(8), 150);
+  }
+
+  #[test]
+  fn test_display() {
+    assert_eq!(
+      Sequence(vec![4, 1, 3, 1, 2, 4, 3, 2]).to_string(),
+      "4 1 3 1 2 4 3 2"
+    );
+  }
+}
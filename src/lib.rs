@@ -0,0 +1,21 @@
+pub mod bench_support;
+pub mod dlx;
+pub mod exact_cover;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod futoshiki;
+pub mod kakuro;
+pub mod knuth_format;
+pub mod langford;
+pub mod linear_solver;
+pub mod magic_square;
+pub mod nonogram;
+pub mod nqueens;
+pub mod paren_tree;
+pub mod parenthesis_split;
+pub mod polyomino;
+pub mod sudoku;
+pub mod util;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod wordfill;
@@ -0,0 +1,1038 @@
+use std::{mem, ops::RangeInclusive};
+
+use crate::dlx::{ColorItem, Constraint};
+
+/// The values a variable takes when no domain is given.
+const DIGITS: RangeInclusive<i32> = 0..=9;
+
+#[derive(Clone, Debug)]
+struct Variable<V> {
+  var: V,
+  domain: RangeInclusive<i32>,
+}
+
+/// One equation of an `EquationSystem`: a weighted sum of the variables plus a
+/// constant, which must be zero.
+#[derive(Clone, Debug, Default)]
+struct Equation {
+  /// The factor of each variable, by its index in the system. Variables past
+  /// the end have a factor of zero.
+  factors: Vec<i32>,
+  constant: i64,
+}
+
+impl Equation {
+  fn factor(&self, var_idx: usize) -> i32 {
+    self.factors.get(var_idx).copied().unwrap_or(0)
+  }
+}
+
+/// Finds the assignments of values to variables, each from its own domain,
+/// satisfying several linear equations at once, where variables in the same
+/// distinctness group take different values.
+pub struct EquationSystem<V> {
+  vars: Vec<Variable<V>>,
+  equations: Vec<Equation>,
+  distinct: Vec<Vec<V>>,
+}
+
+impl<V: Eq + Clone> EquationSystem<V> {
+  pub fn new() -> Self {
+    Self {
+      vars: Vec::new(),
+      equations: Vec::new(),
+      distinct: Vec::new(),
+    }
+  }
+
+  /// Adds the equation, domains and distinctness groups of `equation` to the
+  /// system. Variables already in the system are shared, with their domains
+  /// narrowed to values allowed by both.
+  pub fn add_equation(&mut self, equation: LinearSolver<V>) {
+    let EquationSystem {
+      vars,
+      equations,
+      distinct,
+    } = equation.system;
+    for equation in equations {
+      let eq_idx = self.equations.len();
+      self.equations.push(Equation {
+        factors: Vec::new(),
+        constant: equation.constant,
+      });
+      for (var_idx, Variable { var, domain }) in vars.iter().enumerate() {
+        self.add_term(
+          eq_idx,
+          var.clone(),
+          equation.factor(var_idx),
+          domain.clone(),
+        );
+      }
+    }
+    self.distinct.extend(distinct);
+  }
+
+  /// Adds `var` to the system without adding it to any equation, so every
+  /// value in `domain` is tried for it. If it's already in the system, its
+  /// domain is narrowed to values in both.
+  pub fn add_variable(&mut self, var: V, domain: RangeInclusive<i32>) {
+    if self.equations.is_empty() {
+      self.equations.push(Equation::default());
+    }
+    self.add_term(0, var, 0, domain);
+  }
+
+  /// Requires the variables in `vars` to take pairwise different values.
+  /// Variables which aren't in the system are ignored.
+  pub fn require_all_distinct(&mut self, vars: &[V]) {
+    self.distinct.push(vars.to_vec());
+  }
+
+  /// Every assignment of values to variables satisfying all of the equations.
+  pub fn find_all_solutions(&self) -> BorrowedSolutions<'_, V> {
+    BorrowedSolutions {
+      vars: &self.vars,
+      search: Search::new(self),
+    }
+  }
+
+  /// Like `find_all_solutions`, but yielding the variables themselves.
+  pub fn find_all_solutions_owned(self) -> Solutions<V> {
+    let search = Search::new(&self);
+    Solutions {
+      vars: self.vars.into_iter().map(|variable| variable.var).collect(),
+      search,
+    }
+  }
+
+  /// Adds `factor * var` to equation `eq_idx`, where `var` takes values in
+  /// `domain`, returning the index of `var`.
+  fn add_term(&mut self, eq_idx: usize, var: V, factor: i32, domain: RangeInclusive<i32>) -> usize {
+    let var_idx = match self.vars.iter().position(|variable| variable.var == var) {
+      Some(var_idx) => {
+        let current = &mut self.vars[var_idx].domain;
+        *current = *current.start().max(domain.start())..=*current.end().min(domain.end());
+        var_idx
+      }
+      None => {
+        self.vars.push(Variable { var, domain });
+        self.vars.len() - 1
+      }
+    };
+
+    let factors = &mut self.equations[eq_idx].factors;
+    if factors.len() <= var_idx {
+      factors.resize(var_idx + 1, 0);
+    }
+    factors[var_idx] += factor;
+    var_idx
+  }
+
+  /// Removes the variable at `var_idx` from every equation.
+  fn remove_var(&mut self, var_idx: usize) {
+    self.vars.remove(var_idx);
+    for equation in &mut self.equations {
+      if var_idx < equation.factors.len() {
+        equation.factors.remove(var_idx);
+      }
+    }
+  }
+}
+
+impl<V: Eq + Clone> Default for EquationSystem<V> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+/// Finds the assignments of values to variables, each from its own domain,
+/// for which a weighted sum of the variables plus a constant is zero, and
+/// variables in the same distinctness group take different values.
+pub struct LinearSolver<V> {
+  /// A system of exactly one equation.
+  system: EquationSystem<V>,
+}
+
+impl<V: Eq + Clone> LinearSolver<V> {
+  pub fn new() -> Self {
+    Self {
+      system: EquationSystem {
+        equations: vec![Equation::default()],
+        ..EquationSystem::new()
+      },
+    }
+  }
+
+  /// Adds `factor * var` to the sum, where `var` is a digit 0-9.
+  pub fn add(&mut self, var: V, factor: i32) {
+    self.add_with_domain(var, factor, DIGITS);
+  }
+
+  /// Adds `factor * var` to the sum, where `var` takes values in `domain`.
+  /// Adding a variable twice adds the factors, and narrows its domain to
+  /// values in both. Once the factors cancel out, the variable is dropped
+  /// from the sum.
+  pub fn add_with_domain(&mut self, var: V, factor: i32, domain: RangeInclusive<i32>) {
+    let var_idx = self.system.add_term(0, var, factor, domain);
+    if self.system.equations[0].factor(var_idx) == 0 {
+      self.system.remove_var(var_idx);
+    }
+  }
+
+  /// Drops `var` from the sum, if it's there.
+  pub fn remove(&mut self, var: &V) {
+    if let Some(var_idx) = self
+      .system
+      .vars
+      .iter()
+      .position(|variable| variable.var == *var)
+    {
+      self.system.remove_var(var_idx);
+    }
+  }
+
+  /// The number of variables in the sum.
+  pub fn len(&self) -> usize {
+    self.system.vars.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.system.vars.is_empty()
+  }
+
+  /// Adds the constant `c` to the sum.
+  pub fn add_constant(&mut self, c: i32) {
+    self.system.equations[0].constant += c as i64;
+  }
+
+  /// Requires the variables in `vars` to take pairwise different values.
+  /// Variables which aren't part of the sum are ignored.
+  pub fn require_all_distinct(&mut self, vars: &[V]) {
+    self.system.require_all_distinct(vars);
+  }
+
+  /// Every assignment of values to variables for which the sum is zero, as
+  /// `(&var, value)` pairs in the order the variables were added.
+  pub fn find_all_solutions(&self) -> BorrowedSolutions<'_, V> {
+    self.system.find_all_solutions()
+  }
+
+  /// Like `find_all_solutions`, but yielding the variables themselves.
+  pub fn find_all_solutions_owned(self) -> Solutions<V> {
+    self.find_solutions_for_target(0)
+  }
+
+  /// Every assignment for which the sum is `target` rather than zero.
+  pub fn find_solutions_for_target(mut self, target: i32) -> Solutions<V> {
+    self.add_constant(-target);
+    self.system.find_all_solutions_owned()
+  }
+
+  /// The number of assignments for which the sum is `target`.
+  ///
+  /// This counts the ways to reach each partial sum one variable at a time,
+  /// which takes time proportional to the number of variables times the
+  /// range of possible sums rather than the number of assignments. That
+  /// doesn't work with distinctness groups, so with any of those the
+  /// solutions are enumerated instead.
+  pub fn count_solutions(&self, target: i32) -> u64 {
+    let equation = &self.system.equations[0];
+    let target = target as i64 - equation.constant;
+    if !self.system.distinct.is_empty() {
+      let mut search = Search::new(&self.system);
+      search.targets[0] = target;
+      return search.count() as u64;
+    }
+
+    // `counts[s - min]` is the number of assignments to the variables so far
+    // summing to `s`, where `min` is the smallest sum they can reach.
+    let mut counts = vec![1u64];
+    let mut min = 0;
+    for (var_idx, variable) in self.system.vars.iter().enumerate() {
+      let factor = equation.factor(var_idx) as i64;
+      let (start, end) = (
+        *variable.domain.start() as i64,
+        *variable.domain.end() as i64,
+      );
+      if start > end {
+        return 0;
+      }
+      let low = (factor * start).min(factor * end);
+      let mut next = vec![0u64; counts.len() + (factor * (end - start)).unsigned_abs() as usize];
+      for (idx, &count) in counts.iter().enumerate().filter(|(_, &count)| count != 0) {
+        for value in start..=end {
+          next[(idx as i64 + factor * value - low) as usize] += count;
+        }
+      }
+      counts = next;
+      min += low;
+    }
+    usize::try_from(target - min)
+      .ok()
+      .and_then(|idx| counts.get(idx))
+      .copied()
+      .unwrap_or(0)
+  }
+}
+
+impl<V: Eq + Clone> Default for LinearSolver<V> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+/// A depth-first search over the variables of an `EquationSystem`, which cuts
+/// off any partial assignment for which some equation's sum can no longer
+/// reach zero. Only values are tracked, so the variables themselves are never
+/// cloned while searching.
+struct Search {
+  /// The domain of each variable, in the order they are assigned, with those
+  /// with the largest factors first since they narrow the sums the most.
+  domains: Vec<RangeInclusive<i32>>,
+  /// For each variable, its position in the order the variables were added.
+  positions: Vec<usize>,
+  /// For each variable, the earlier variables whose values it must differ
+  /// from.
+  distinct_from: Vec<Vec<usize>>,
+  /// `factors[i][e]` is the factor of the `i`th variable in equation `e`.
+  factors: Vec<Vec<i64>>,
+  /// `bounds[i][e]` is the range of sums the variables from `i` on can add up
+  /// to in equation `e`.
+  bounds: Vec<Vec<(i64, i64)>>,
+  /// The value each equation's variables must add up to.
+  targets: Vec<i64>,
+  /// The values assigned so far, to a prefix of the variables.
+  values: Vec<i32>,
+  /// The sums of each equation over the first `i` assigned variables, for
+  /// each `i` in turn.
+  sums: Vec<i64>,
+  /// Whether the next step extends the current partial assignment, rather
+  /// than moving on from it.
+  descend: bool,
+}
+
+impl Search {
+  fn new<V: Eq>(system: &EquationSystem<V>) -> Self {
+    let EquationSystem {
+      vars,
+      equations,
+      distinct: groups,
+    } = system;
+    let largest_factor = |var_idx| {
+      equations
+        .iter()
+        .map(|equation| equation.factor(var_idx).unsigned_abs())
+        .max()
+        .unwrap_or(0)
+    };
+    let mut order = (0..vars.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&var_idx| std::cmp::Reverse(largest_factor(var_idx)));
+
+    let factors = order
+      .iter()
+      .map(|&var_idx| {
+        equations
+          .iter()
+          .map(|equation| equation.factor(var_idx) as i64)
+          .collect::<Vec<_>>()
+      })
+      .collect::<Vec<_>>();
+    let domains = order
+      .iter()
+      .map(|&var_idx| vars[var_idx].domain.clone())
+      .collect::<Vec<_>>();
+
+    // The variables sharing a group with each variable, which are assigned
+    // before it.
+    let distinct_from = order
+      .iter()
+      .enumerate()
+      .map(|(depth, &var_idx)| {
+        let shares_group = |other: usize| {
+          groups
+            .iter()
+            .any(|group| group.contains(&vars[var_idx].var) && group.contains(&vars[other].var))
+        };
+        (0..depth)
+          .filter(|&earlier| shares_group(order[earlier]))
+          .collect()
+      })
+      .collect();
+
+    let mut bounds = vec![vec![(0, 0); equations.len()]; domains.len() + 1];
+    for (depth, domain) in domains.iter().enumerate().rev() {
+      for (eq_idx, &factor) in factors[depth].iter().enumerate() {
+        let (min, max) = bounds[depth + 1][eq_idx];
+        let low = factor * *domain.start() as i64;
+        let high = factor * *domain.end() as i64;
+        bounds[depth][eq_idx] = (min + low.min(high), max + low.max(high));
+      }
+    }
+
+    Self {
+      domains,
+      positions: order,
+      distinct_from,
+      factors,
+      bounds,
+      targets: equations
+        .iter()
+        .map(|equation| -equation.constant)
+        .collect(),
+      values: Vec::new(),
+      sums: vec![0; equations.len()],
+      descend: true,
+    }
+  }
+
+  /// The sums of each equation over the first `depth` assigned variables.
+  fn sums(&self, depth: usize) -> &[i64] {
+    let num_equations = self.targets.len();
+    &self.sums[depth * num_equations..(depth + 1) * num_equations]
+  }
+
+  /// The smallest value from `start` on which the variable at `depth` can
+  /// take while keeping every equation's target within reach, and differing
+  /// from the values of the variables in a group with it.
+  fn first_feasible(&self, depth: usize, start: i32) -> Option<i32> {
+    let sums = self.sums(depth);
+    let bounds = &self.bounds[depth + 1];
+    (start..=*self.domains[depth].end()).find(|&value| {
+      self.factors[depth]
+        .iter()
+        .zip(sums)
+        .zip(bounds)
+        .zip(&self.targets)
+        .all(|(((&factor, &sum), &(min, max)), target)| {
+          let sum = sum + factor * value as i64;
+          (sum + min..=sum + max).contains(target)
+        })
+        && self.distinct_from[depth]
+          .iter()
+          .all(|&earlier| self.values[earlier] != value)
+    })
+  }
+
+  fn push(&mut self, value: i32) {
+    let depth = self.values.len();
+    let num_equations = self.targets.len();
+    for eq_idx in 0..num_equations {
+      let sum =
+        self.sums[depth * num_equations + eq_idx] + self.factors[depth][eq_idx] * value as i64;
+      self.sums.push(sum);
+    }
+    self.values.push(value);
+  }
+
+  fn pop(&mut self) -> Option<i32> {
+    let value = self.values.pop()?;
+    self.sums.truncate(self.sums.len() - self.targets.len());
+    Some(value)
+  }
+
+  /// The current assignment, in the order the variables were added.
+  fn solution(&self) -> Vec<i32> {
+    let mut solution = vec![0; self.values.len()];
+    for (depth, &value) in self.values.iter().enumerate() {
+      solution[self.positions[depth]] = value;
+    }
+    solution
+  }
+}
+
+impl Iterator for Search {
+  type Item = Vec<i32>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    loop {
+      let depth = self.values.len();
+      if mem::replace(&mut self.descend, false) {
+        if depth == self.domains.len() {
+          // Every value was chosen to keep the targets reachable, so with no
+          // variables left every sum is exactly its target, unless there were
+          // no variables to begin with.
+          if self.sums(depth) == self.targets {
+            return Some(self.solution());
+          }
+          continue;
+        }
+        let start = *self.domains[depth].start();
+        if let Some(value) = self.first_feasible(depth, start) {
+          self.push(value);
+          self.descend = true;
+        }
+      } else {
+        // Move on to the next value of the deepest variable, backtracking
+        // further once it has none left.
+        let value = self.pop()?;
+        if let Some(value) = self.first_feasible(depth - 1, value + 1) {
+          self.push(value);
+          self.descend = true;
+        }
+      }
+    }
+  }
+}
+
+/// The solutions of a consumed `EquationSystem`, as `(var, value)` pairs in
+/// the order the variables were added.
+pub struct Solutions<V> {
+  vars: Vec<V>,
+  search: Search,
+}
+
+impl<V: Clone> Iterator for Solutions<V> {
+  type Item = Vec<(V, i32)>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let values = self.search.next()?;
+    Some(self.vars.iter().cloned().zip(values).collect())
+  }
+}
+
+/// The solutions of a borrowed `EquationSystem`, as `(&var, value)` pairs in
+/// the order the variables were added.
+pub struct BorrowedSolutions<'a, V> {
+  vars: &'a [Variable<V>],
+  search: Search,
+}
+
+impl<'a, V> Iterator for BorrowedSolutions<'a, V> {
+  type Item = Vec<(&'a V, i32)>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let values = self.search.next()?;
+    Some(
+      self
+        .vars
+        .iter()
+        .map(|variable| &variable.var)
+        .zip(values)
+        .collect(),
+    )
+  }
+}
+
+/// Turns each solution of `system` into a DLX subset named by `name`, which
+/// colors the secondary item of every variable with its value. Subsets from
+/// different systems sharing a variable can then only be chosen together if
+/// they agree on its value.
+///
+/// Colors can't be negative, so neither can any variable's domain.
+pub fn to_dlx_choices<'a, V: Eq + Clone, N>(
+  system: &'a EquationSystem<V>,
+  mut name: impl FnMut(&[(&V, i32)]) -> N + 'a,
+) -> impl Iterator<Item = (N, Vec<Constraint<V>>)> + 'a {
+  system.find_all_solutions().map(move |assignment| {
+    let constraints = assignment
+      .iter()
+      .map(|&(var, value)| {
+        let color = u32::try_from(value).expect("Variables used as DLX colors can't be negative");
+        ColorItem::new(var.clone(), color).into()
+      })
+      .collect();
+    (name(&assignment), constraints)
+  })
+}
+
+#[cfg(test)]
+mod test {
+  use itertools::Itertools;
+
+  use crate::{
+    dlx::{Constraint, Dlx, HeaderType},
+    kakuro::TotalClue,
+  };
+
+  use super::{to_dlx_choices, EquationSystem, LinearSolver};
+
+  /// Tries every combination of values, like an odometer.
+  fn brute_force(vars: &[(usize, i32)]) -> Vec<Vec<(usize, i32)>> {
+    // Variables with a factor of zero are dropped from the sum.
+    let vars = vars
+      .iter()
+      .copied()
+      .filter(|&(_, factor)| factor != 0)
+      .collect_vec();
+    (0..10u32.pow(vars.len() as u32))
+      .map(|mut state| {
+        vars
+          .iter()
+          .map(|&(var, _)| {
+            let value = (state % 10) as i32;
+            state /= 10;
+            (var, value)
+          })
+          .collect_vec()
+      })
+      .filter(|assignment| {
+        assignment
+          .iter()
+          .zip(&vars)
+          .map(|(&(_, value), &(_, factor))| value * factor)
+          .sum::<i32>()
+          == 0
+      })
+      .sorted()
+      .collect()
+  }
+
+  fn solve(vars: &[(usize, i32)]) -> Vec<Vec<(usize, i32)>> {
+    let mut solver = LinearSolver::new();
+    for &(var, factor) in vars {
+      solver.add(var, factor);
+    }
+    solver.find_all_solutions_owned().sorted().collect()
+  }
+
+  #[test]
+  fn test_simple() {
+    // x + y = 2z
+    assert_eq!(
+      solve(&[(0, 1), (1, 1), (2, -2)]),
+      brute_force(&[(0, 1), (1, 1), (2, -2)])
+    );
+    assert_eq!(solve(&[]), vec![vec![]]);
+    assert_eq!(solve(&[(0, 3), (1, 4)]), vec![vec![(0, 0), (1, 0)]]);
+  }
+
+  #[test]
+  fn test_repeated_var() {
+    let mut solver = LinearSolver::new();
+    solver.add('x', 2);
+    solver.add('y', -3);
+    solver.add('x', 1);
+    assert_eq!(
+      solver.find_all_solutions_owned().sorted().collect_vec(),
+      vec![
+        vec![('x', 0), ('y', 0)],
+        vec![('x', 1), ('y', 1)],
+        vec![('x', 2), ('y', 2)],
+        vec![('x', 3), ('y', 3)],
+        vec![('x', 4), ('y', 4)],
+        vec![('x', 5), ('y', 5)],
+        vec![('x', 6), ('y', 6)],
+        vec![('x', 7), ('y', 7)],
+        vec![('x', 8), ('y', 8)],
+        vec![('x', 9), ('y', 9)],
+      ]
+    );
+  }
+
+  #[test]
+  fn test_matches_brute_force() {
+    // A fixed linear congruential generator, so failures are reproducible.
+    let mut state = 12345u64;
+    let mut random = |bound: u64| {
+      state = state
+        .wrapping_mul(6364136223846793005)
+        .wrapping_add(1442695040888963407);
+      (state >> 33) % bound
+    };
+
+    for _ in 0..200 {
+      let num_vars = random(6) as usize;
+      let vars = (0..num_vars)
+        .map(|var| (var, random(21) as i32 - 10))
+        .collect_vec();
+      assert_eq!(solve(&vars), brute_force(&vars), "{vars:?}");
+    }
+  }
+
+  #[test]
+  fn test_target() {
+    // 2x + 3y = 17
+    let mut solver = LinearSolver::new();
+    solver.add('x', 2);
+    solver.add('y', 3);
+    assert_eq!(
+      solver.find_solutions_for_target(17).sorted().collect_vec(),
+      vec![
+        vec![('x', 1), ('y', 5)],
+        vec![('x', 4), ('y', 3)],
+        vec![('x', 7), ('y', 1)],
+      ]
+    );
+
+    // The same equation, written as 2x + 3y - 17 = 0.
+    let mut solver = LinearSolver::new();
+    solver.add('x', 2);
+    solver.add('y', 3);
+    solver.add_constant(-17);
+    assert_eq!(solver.find_all_solutions_owned().count(), 3);
+  }
+
+  #[test]
+  fn test_target_no_solutions() {
+    // 2x + 4y is always even.
+    let mut solver = LinearSolver::new();
+    solver.add('x', 2);
+    solver.add('y', 4);
+    assert_eq!(solver.find_solutions_for_target(7).count(), 0);
+
+    let mut solver = LinearSolver::new();
+    solver.add('x', 1);
+    assert_eq!(solver.find_solutions_for_target(10).count(), 0);
+  }
+
+  #[test]
+  fn test_target_max_sum() {
+    let mut solver = LinearSolver::new();
+    solver.add('x', 1);
+    solver.add('y', 1);
+    solver.add('z', 1);
+    solver.add_constant(3);
+    assert_eq!(
+      solver.find_solutions_for_target(30).collect_vec(),
+      vec![vec![('x', 9), ('y', 9), ('z', 9)]]
+    );
+  }
+
+  #[test]
+  fn test_domains() {
+    // 2x + 3y = 5, with x in 1..=9 and y in -3..=3.
+    let mut solver = LinearSolver::new();
+    solver.add_with_domain('x', 2, 1..=9);
+    solver.add_with_domain('y', 3, -3..=3);
+    let expected = (1..=9)
+      .cartesian_product(-3..=3)
+      .filter(|(x, y)| 2 * x + 3 * y == 5)
+      .map(|(x, y)| vec![('x', x), ('y', y)])
+      .collect_vec();
+    assert_eq!(
+      solver.find_solutions_for_target(5).sorted().collect_vec(),
+      expected
+    );
+    assert_eq!(expected.len(), 3);
+  }
+
+  #[test]
+  fn test_domain_narrowing() {
+    let mut solver = LinearSolver::new();
+    solver.add('x', 1);
+    solver.add_with_domain('x', 0, 3..=12);
+    solver.add_with_domain('y', -1, 5..=5);
+    assert_eq!(
+      solver.find_all_solutions_owned().collect_vec(),
+      vec![vec![('x', 5), ('y', 5)]]
+    );
+
+    let mut solver = LinearSolver::new();
+    solver.add_with_domain('x', 1, 0..=2);
+    solver.add_with_domain('x', 0, 4..=6);
+    assert_eq!(solver.find_all_solutions_owned().count(), 0);
+  }
+
+  #[test]
+  fn test_distinct() {
+    let mut solver = LinearSolver::new();
+    for var in ['a', 'b', 'c'] {
+      solver.add_with_domain(var, 1, 1..=9);
+    }
+    solver.require_all_distinct(&['a', 'b', 'c']);
+    let solutions = solver.find_solutions_for_target(15).collect_vec();
+
+    // Each combination appears once for every ordering of its digits.
+    let combinations = TotalClue::all_combinations_for_total(15, 3).collect_vec();
+    assert_eq!(solutions.len(), 6 * combinations.len());
+    assert_eq!(
+      solutions
+        .into_iter()
+        .map(|solution| solution
+          .into_iter()
+          .map(|(_, value)| value as u32)
+          .sorted()
+          .collect_vec())
+        .unique()
+        .sorted()
+        .collect_vec(),
+      combinations
+    );
+  }
+
+  #[test]
+  fn test_overlapping_distinct() {
+    // a + b + c = 6 where a != b and b != c, but a may equal c.
+    let mut solver = LinearSolver::new();
+    for var in ['a', 'b', 'c'] {
+      solver.add_with_domain(var, 1, 1..=9);
+    }
+    solver.require_all_distinct(&['a', 'b']);
+    solver.require_all_distinct(&['b', 'c', 'z']);
+    let expected = (1..=9)
+      .cartesian_product(1..=9)
+      .cartesian_product(1..=9)
+      .filter(|&((a, b), c)| a + b + c == 6 && a != b && b != c)
+      .map(|((a, b), c)| vec![('a', a), ('b', b), ('c', c)])
+      .collect_vec();
+    assert_eq!(
+      solver.find_solutions_for_target(6).sorted().collect_vec(),
+      expected
+    );
+    assert!(expected.contains(&vec![('a', 1), ('b', 4), ('c', 1)]));
+  }
+
+  #[test]
+  fn test_cancelled_terms() {
+    let mut solver = LinearSolver::new();
+    solver.add('x', 2);
+    solver.add('x', -2);
+    assert_eq!(solver.len(), 0);
+    assert!(solver.is_empty());
+    assert_eq!(solver.find_all_solutions().collect_vec(), vec![vec![]]);
+    solver.add_constant(1);
+    assert_eq!(solver.find_all_solutions().count(), 0);
+    solver.add_constant(-1);
+
+    solver.add('x', 1);
+    solver.add('y', 1);
+    solver.add('z', 3);
+    solver.remove(&'y');
+    solver.remove(&'w');
+    assert_eq!(solver.len(), 2);
+    solver.add_constant(-7);
+    assert_eq!(
+      solver.find_all_solutions().sorted().collect_vec(),
+      vec![
+        vec![(&'x', 1), (&'z', 2)],
+        vec![(&'x', 4), (&'z', 1)],
+        vec![(&'x', 7), (&'z', 0)]
+      ]
+    );
+  }
+
+  #[test]
+  fn test_borrowed_matches_owned() {
+    let mut solver = LinearSolver::new();
+    solver.add_with_domain('a', 3, 1..=9);
+    solver.add('b', -2);
+    solver.add('c', 5);
+    solver.add_constant(-20);
+    solver.require_all_distinct(&['a', 'b', 'c']);
+
+    let borrowed = solver
+      .find_all_solutions()
+      .map(|solution| {
+        solution
+          .into_iter()
+          .map(|(&var, value)| (var, value))
+          .collect_vec()
+      })
+      .collect_vec();
+    assert!(!borrowed.is_empty());
+    assert_eq!(borrowed, solver.find_all_solutions_owned().collect_vec());
+  }
+
+  #[test]
+  fn test_count_solutions() {
+    let mut state = 54321u64;
+    let mut random = |bound: u64| {
+      state = state
+        .wrapping_mul(6364136223846793005)
+        .wrapping_add(1442695040888963407);
+      (state >> 33) % bound
+    };
+
+    for _ in 0..100 {
+      let mut solver = LinearSolver::new();
+      for var in 0..random(5) {
+        let low = random(7) as i32 - 3;
+        solver.add_with_domain(var, random(15) as i32 - 7, low..=low + random(6) as i32);
+      }
+      solver.add_constant(random(11) as i32 - 5);
+      let target = random(21) as i32 - 10;
+      assert_eq!(
+        solver.count_solutions(target),
+        solver.find_solutions_for_target(target).count() as u64
+      );
+    }
+  }
+
+  #[test]
+  fn test_count_solutions_distinct() {
+    let mut solver = LinearSolver::new();
+    for var in 0..3 {
+      solver.add_with_domain(var, 1, 1..=9);
+    }
+    assert_eq!(solver.count_solutions(15), 61);
+    solver.require_all_distinct(&[0, 1, 2]);
+    assert_eq!(solver.count_solutions(15), 48);
+  }
+
+  #[test]
+  fn test_count_solutions_many_vars() {
+    let mut solver = LinearSolver::new();
+    for var in 0..15 {
+      solver.add(var, 1);
+    }
+    assert_eq!(solver.count_solutions(67), 35460394945125);
+    assert_eq!(solver.count_solutions(68), 35460394945125);
+    assert_eq!(solver.count_solutions(136), 0);
+
+    let mut solver = LinearSolver::new();
+    for (var, factor) in [3, -5, 7, -2, 1, 4, -6, 8, 2, -1, 5, -3, 9, -7, 6]
+      .into_iter()
+      .enumerate()
+    {
+      solver.add(var, factor);
+    }
+    assert_eq!(solver.count_solutions(0), 1899155917218);
+  }
+
+  #[test]
+  fn test_system() {
+    // x + y + z = 26 and x = z.
+    let mut sum = LinearSolver::new();
+    for var in ['x', 'y', 'z'] {
+      sum.add(var, 1);
+    }
+    sum.add_constant(-26);
+    let mut equal = LinearSolver::new();
+    equal.add('x', 1);
+    equal.add('z', -1);
+
+    let mut system = EquationSystem::new();
+    system.add_equation(sum);
+    system.add_equation(equal);
+    assert_eq!(
+      system.find_all_solutions_owned().collect_vec(),
+      vec![vec![('x', 9), ('y', 8), ('z', 9)]]
+    );
+  }
+
+  #[test]
+  fn test_system_matches_brute_force() {
+    // 3a - b + 2c = 4 and b + c - 2d = 1, with d in 1..=4.
+    let mut first = LinearSolver::new();
+    first.add('a', 3);
+    first.add('b', -1);
+    first.add('c', 2);
+    first.add_constant(-4);
+    let mut second = LinearSolver::new();
+    second.add('b', 1);
+    second.add('c', 1);
+    second.add_with_domain('d', -2, 1..=4);
+    second.add_constant(-1);
+
+    let mut system = EquationSystem::new();
+    system.add_equation(first);
+    system.add_equation(second);
+    let expected = (0..10)
+      .cartesian_product(0..10)
+      .cartesian_product(0..10)
+      .cartesian_product(1..=4)
+      .filter(|&(((a, b), c), d)| 3 * a - b + 2 * c == 4 && b + c - 2 * d == 1)
+      .map(|(((a, b), c), d)| vec![('a', a), ('b', b), ('c', c), ('d', d)])
+      .collect_vec();
+    assert!(!expected.is_empty());
+    assert_eq!(
+      system.find_all_solutions_owned().sorted().collect_vec(),
+      expected
+    );
+  }
+
+  #[test]
+  fn test_inconsistent_system() {
+    let mut three = LinearSolver::new();
+    three.add('x', 1);
+    three.add('y', 1);
+    let mut four = LinearSolver::new();
+    four.add('y', 1);
+    four.add('x', 1);
+    four.add_constant(-1);
+
+    let mut system = EquationSystem::new();
+    system.add_equation(three);
+    system.add_equation(four);
+    assert_eq!(system.find_all_solutions_owned().count(), 0);
+  }
+
+  #[test]
+  fn test_add_variable() {
+    // x + y = 3 says nothing about z, but z must differ from both.
+    let mut sum = LinearSolver::new();
+    sum.add('x', 1);
+    sum.add('y', 1);
+    sum.add_constant(-3);
+    let mut system = EquationSystem::new();
+    system.add_equation(sum);
+    system.add_variable('z', 0..=3);
+    system.add_variable('x', 1..=9);
+    system.require_all_distinct(&['x', 'y', 'z']);
+    assert_eq!(
+      system.find_all_solutions_owned().sorted().collect_vec(),
+      vec![
+        vec![('x', 1), ('y', 2), ('z', 0)],
+        vec![('x', 1), ('y', 2), ('z', 3)],
+        vec![('x', 2), ('y', 1), ('z', 0)],
+        vec![('x', 2), ('y', 1), ('z', 3)],
+        vec![('x', 3), ('y', 0), ('z', 1)],
+        vec![('x', 3), ('y', 0), ('z', 2)],
+      ]
+    );
+  }
+
+  #[test]
+  fn test_to_dlx_choices() {
+    // x + y = 10 and y + z = 3, as separate subsets which must agree on y.
+    let system = |vars: [char; 2], total: i32| {
+      let mut sum = LinearSolver::new();
+      sum.add(vars[0], 1);
+      sum.add(vars[1], 1);
+      sum.add_constant(-total);
+      let mut system = EquationSystem::new();
+      system.add_equation(sum);
+      system
+    };
+    let first = system(['x', 'y'], 10);
+    let second = system(['y', 'z'], 3);
+    let choices = [('1', &first), ('2', &second)]
+      .into_iter()
+      .flat_map(|(equation, system)| {
+        to_dlx_choices(system, move |assignment| {
+          let values = assignment.iter().map(|&(_, value)| value).collect_vec();
+          (equation, values)
+        })
+        .map(move |(name, mut constraints)| {
+          constraints.push(Constraint::Primary(equation));
+          (name, constraints)
+        })
+      })
+      .collect_vec();
+    assert_eq!(choices.len(), 9 + 4);
+
+    let items = [('1', HeaderType::Primary), ('2', HeaderType::Primary)]
+      .into_iter()
+      .chain("xyz".chars().map(|var| (var, HeaderType::Secondary)));
+    let mut dlx = Dlx::new(items, choices);
+    let solutions = dlx
+      .find_all_solution_names()
+      .map(|names| names.into_iter().sorted().collect_vec())
+      .sorted()
+      .collect_vec();
+    assert_eq!(
+      solutions,
+      vec![
+        vec![('1', vec![7, 3]), ('2', vec![3, 0])],
+        vec![('1', vec![8, 2]), ('2', vec![2, 1])],
+        vec![('1', vec![9, 1]), ('2', vec![1, 2])],
+      ]
+    );
+  }
+
+  #[test]
+  fn test_ten_vars() {
+    // The nine digits x_0..x_8 read as a number are a multiple y of 123456789.
+    let mut vars = (0..9).map(|i| (i, 10i32.pow(i as u32))).collect_vec();
+    vars.push((9, -123456789));
+    let solutions = solve(&vars);
+    assert_eq!(solutions.len(), 9);
+    assert!(solutions.iter().all(|solution| {
+      let number = solution[..9]
+        .iter()
+        .rev()
+        .fold(0, |acc, &(_, digit)| 10 * acc + digit as i64);
+      number == 123456789 * solution[9].1 as i64
+    }));
+  }
+}
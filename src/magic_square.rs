@@ -0,0 +1,130 @@
+use itertools::Itertools;
+
+use crate::linear_solver::{EquationSystem, LinearSolver};
+
+/// Finds an `n`x`n` magic square: distinct values whose rows, columns and
+/// both diagonals all add up to the magic constant. Without a constant, the
+/// square holds each of 1 to `n^2` once, so the constant is `n(n^2 + 1)/2`.
+/// With one, the values may be any distinct numbers from 1 up to the
+/// constant. Only practical for small `n`.
+pub fn solve(n: usize, magic_constant: Option<i32>) -> Option<Vec<Vec<i32>>> {
+  solve_with_fixed(n, magic_constant, &[])
+}
+
+/// Like `solve`, with the value at each `(row, col)` in `fixed` given
+/// beforehand.
+pub fn solve_with_fixed(
+  n: usize,
+  magic_constant: Option<i32>,
+  fixed: &[((usize, usize), i32)],
+) -> Option<Vec<Vec<i32>>> {
+  let size = n as i32;
+  let (constant, max_value) = match magic_constant {
+    Some(constant) => (constant, constant),
+    None => (size * (size * size + 1) / 2, size * size),
+  };
+  let domain = |cell| match fixed.iter().find(|&&(fixed_cell, _)| fixed_cell == cell) {
+    Some(&(_, value)) => value..=value,
+    None => 1..=max_value,
+  };
+
+  let rows = (0..n).map(|row| (0..n).map(|col| (row, col)).collect_vec());
+  let cols = (0..n).map(|col| (0..n).map(|row| (row, col)).collect_vec());
+  let diagonals = [
+    (0..n).map(|i| (i, i)).collect_vec(),
+    (0..n).map(|i| (i, n - 1 - i)).collect_vec(),
+  ];
+  let mut system = EquationSystem::new();
+  for line in rows.chain(cols).chain(diagonals) {
+    let mut sum = LinearSolver::new();
+    for cell in line {
+      sum.add_with_domain(cell, 1, domain(cell));
+    }
+    sum.add_constant(-constant);
+    system.add_equation(sum);
+  }
+  let cells = (0..n).cartesian_product(0..n).collect_vec();
+  system.require_all_distinct(&cells);
+
+  let solution = system.find_all_solutions_owned().next()?;
+  let mut square = vec![vec![0; n]; n];
+  for ((row, col), value) in solution {
+    square[row][col] = value;
+  }
+  Some(square)
+}
+
+#[cfg(test)]
+mod test {
+  use itertools::Itertools;
+
+  use super::{solve, solve_with_fixed};
+
+  fn is_magic(square: &[Vec<i32>], constant: i32) -> bool {
+    let n = square.len();
+    let rows = (0..n).map(|row| (0..n).map(|col| square[row][col]).sum::<i32>());
+    let cols = (0..n).map(|col| (0..n).map(|row| square[row][col]).sum::<i32>());
+    let diagonals = [
+      (0..n).map(|i| square[i][i]).sum::<i32>(),
+      (0..n).map(|i| square[i][n - 1 - i]).sum::<i32>(),
+    ];
+    square.iter().flatten().all_unique()
+      && rows.chain(cols).chain(diagonals).all(|sum| sum == constant)
+  }
+
+  /// The rotations and reflections of `square`.
+  fn symmetries(square: &[Vec<i32>]) -> Vec<Vec<Vec<i32>>> {
+    let n = square.len();
+    let rotate = |square: &Vec<Vec<i32>>| {
+      (0..n)
+        .map(|row| (0..n).map(|col| square[n - 1 - col][row]).collect_vec())
+        .collect_vec()
+    };
+    let reflect = |square: &Vec<Vec<i32>>| {
+      square
+        .iter()
+        .map(|row| row.iter().rev().copied().collect_vec())
+        .collect_vec()
+    };
+    let mut squares = vec![square.to_vec()];
+    for _ in 0..3 {
+      squares.push(rotate(squares.last().unwrap()));
+    }
+    squares.extend(squares.iter().map(reflect).collect_vec());
+    squares
+  }
+
+  #[test]
+  fn test_lo_shu() {
+    let square = solve(3, None).unwrap();
+    assert!(is_magic(&square, 15), "{square:?}");
+    let lo_shu = vec![vec![2, 7, 6], vec![9, 5, 1], vec![4, 3, 8]];
+    assert!(symmetries(&lo_shu).contains(&square), "{square:?}");
+  }
+
+  #[test]
+  fn test_wrong_center() {
+    // The center of a 3x3 magic square is always 5.
+    assert_eq!(solve_with_fixed(3, None, &[((1, 1), 4)]), None);
+    let square = solve_with_fixed(3, None, &[((0, 0), 8)]).unwrap();
+    assert_eq!(square[0][0], 8);
+    assert!(is_magic(&square, 15));
+  }
+
+  #[test]
+  fn test_constant() {
+    let square = solve(3, Some(18)).unwrap();
+    assert!(is_magic(&square, 18), "{square:?}");
+    // Every 3x3 magic square's constant is 3 times its center, and the
+    // values must be distinct.
+    assert_eq!(solve(3, Some(16)), None);
+    assert_eq!(solve(3, Some(6)), None);
+  }
+
+  #[test]
+  fn test_four() {
+    let square = solve_with_fixed(4, None, &[((0, 0), 16), ((0, 3), 13)]).unwrap();
+    assert!(is_magic(&square, 34), "{square:?}");
+    assert_eq!((square[0][0], square[0][3]), (16, 13));
+  }
+}
@@ -1,25 +1,358 @@
-use std::io;
+use std::{
+  fmt::Display,
+  fs::File,
+  io::{self, BufReader, IsTerminal},
+  ops::Range,
+  process::ExitCode,
+  time::Instant,
+};
 
-use kakuro::Kakuro;
+use clap::{ArgAction, Args, Parser, Subcommand};
+use itertools::Itertools;
+use p424::{
+  kakuro::{CombinationCache, Kakuro},
+  knuth_format::KnuthProblem,
+  sudoku::Sudoku,
+};
+use serde::Serialize;
 
-pub mod dlx;
-mod kakuro;
-mod parenthesis_split;
-#[cfg(test)]
-mod sudoku;
-
-fn main() -> io::Result<()> {
-  let kakuros = Kakuro::from_file("p424_kakuro200.txt")?;
-  let sums: u64 = kakuros
-    .iter()
-    .map(|kakuro| {
-      let letters = kakuro.solve();
-      debug_assert_eq!(letters.len(), 1);
-      letters.first().unwrap().int_value()
+mod repl;
+
+#[derive(Parser)]
+#[command(about = "Exact cover solvers for kakuro, sudoku and general DLX problems")]
+struct Cli {
+  /// Print results as JSON instead of text.
+  #[arg(long, global = true)]
+  json: bool,
+  /// Log what the solvers are doing to stderr: once for debug messages, twice
+  /// for traces of every step of the search. `RUST_LOG` overrides this.
+  #[arg(short, long, global = true, action = ArgAction::Count)]
+  verbose: u8,
+  #[command(subcommand)]
+  command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+  /// Solve letter-encoded kakuros in the Project Euler 424 format.
+  Kakuro(KakuroArgs),
+  /// Solve sudokus.
+  Sudoku(SudokuArgs),
+  /// Solve an exact cover problem written in Knuth's DLX format.
+  Dlx(DlxArgs),
+  /// Read commands from stdin to load, explore and solve puzzles. Type
+  /// `help` for the list of commands.
+  Repl,
+}
+
+#[derive(Args)]
+struct KakuroArgs {
+  /// File with one puzzle per line.
+  #[arg(long)]
+  file: String,
+  /// Only solve the puzzles with indices in this range, e.g. `0..10`, `5..`
+  /// or `3`.
+  #[arg(long, value_parser = parse_range)]
+  range: Option<Range<usize>>,
+  /// Also print the sum of the puzzles' values.
+  #[arg(long)]
+  sum: bool,
+}
+
+#[derive(Args)]
+#[group(required = true, multiple = false)]
+struct SudokuArgs {
+  /// File of grids in the Project Euler 96 format. Prints the sum of the
+  /// three-digit numbers in the top left corner of each solved grid.
+  #[arg(long)]
+  p096: Option<String>,
+  /// A single grid of 81 characters, with `.` or `0` for blanks. A grid with
+  /// several solutions is solved to one of them.
+  #[arg(long)]
+  grid: Option<String>,
+}
+
+#[derive(Args)]
+struct DlxArgs {
+  /// File with the problem, see `KnuthProblem`.
+  #[arg(long)]
+  knuth_format: String,
+  /// Only print the number of solutions.
+  #[arg(long)]
+  count: bool,
+}
+
+/// Why a command failed. Bad input exits with 2, like clap's usage errors,
+/// and unsolvable puzzles with 1.
+enum CliError {
+  Input(String),
+  Unsolved(String),
+}
+
+impl CliError {
+  fn input(err: impl Display) -> Self {
+    CliError::Input(err.to_string())
+  }
+
+  fn exit_code(&self) -> ExitCode {
+    match self {
+      CliError::Input(_) => ExitCode::from(2),
+      CliError::Unsolved(_) => ExitCode::FAILURE,
+    }
+  }
+}
+
+impl Display for CliError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      CliError::Input(message) | CliError::Unsolved(message) => write!(f, "{message}"),
+    }
+  }
+}
+
+/// Parses a range of puzzle indices: `a..b`, with either end optional, or a
+/// single index.
+fn parse_range(range: &str) -> Result<Range<usize>, String> {
+  let index = |bound: &str| {
+    bound
+      .trim()
+      .parse::<usize>()
+      .map_err(|_| format!("Invalid index \"{bound}\""))
+  };
+  match range.split_once("..") {
+    Some((start, end)) => {
+      let start = if start.is_empty() { 0 } else { index(start)? };
+      let end = if end.is_empty() {
+        usize::MAX
+      } else {
+        index(end)?
+      };
+      Ok(start..end)
+    }
+    None => {
+      let idx = index(range)?;
+      let end = idx
+        .checked_add(1)
+        .ok_or_else(|| format!("Index {idx} is too large"))?;
+      Ok(idx..end)
+    }
+  }
+}
+
+fn print_json(value: &impl Serialize) {
+  println!("{}", serde_json::to_string_pretty(value).unwrap());
+}
+
+#[derive(Serialize)]
+struct KakuroOutput {
+  puzzles: Vec<PuzzleValue>,
+  sum: u64,
+}
+
+#[derive(Serialize)]
+struct PuzzleValue {
+  index: usize,
+  value: u64,
+}
+
+fn run_kakuro(args: &KakuroArgs, json: bool) -> Result<(), CliError> {
+  let puzzles = Kakuro::from_file(&args.file).map_err(CliError::input)?;
+  let range = args.range.clone().unwrap_or(0..usize::MAX);
+  let show_progress = !json && io::stderr().is_terminal();
+
+  let start = Instant::now();
+  let mut cache = CombinationCache::new();
+  let mut output = KakuroOutput {
+    puzzles: Vec::new(),
+    sum: 0,
+  };
+  for (index, kakuro) in puzzles.iter().enumerate() {
+    if !range.contains(&index) {
+      continue;
+    }
+    let value = kakuro
+      .solve_first_with_cache(&mut cache)
+      .map_err(|err| CliError::Input(format!("Puzzle {index}: {err}")))?
+      .ok_or_else(|| CliError::Unsolved(format!("Puzzle {index} has no solution")))?
+      .try_int_value()
+      .map_err(|err| CliError::Unsolved(format!("Puzzle {index}: {err}")))?;
+    output.sum += value;
+    output.puzzles.push(PuzzleValue { index, value });
+    if show_progress {
+      eprint!(
+        "\rSolved {} puzzles in {:.1?}",
+        output.puzzles.len(),
+        start.elapsed()
+      );
+    }
+  }
+  if show_progress {
+    eprintln!();
+  }
+
+  if json {
+    print_json(&output);
+  } else {
+    for PuzzleValue { index, value } in &output.puzzles {
+      println!("Puzzle {index}: {value}");
+    }
+    if args.sum {
+      println!("Sum: {}", output.sum);
+    }
+  }
+  Ok(())
+}
+
+#[derive(Serialize)]
+struct SudokuOutput {
+  grids: Vec<SolvedGrid>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  sum: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct SolvedGrid {
+  name: String,
+  solution: String,
+}
+
+fn run_sudoku(args: &SudokuArgs, json: bool) -> Result<(), CliError> {
+  let puzzles = match (&args.p096, &args.grid) {
+    (Some(path), _) => File::open(path)
+      .and_then(|f| Sudoku::from_p096_reader(BufReader::new(f)))
+      .map_err(CliError::input)?,
+    (None, Some(grid)) => vec![(
+      "Grid".to_string(),
+      grid.parse::<Sudoku>().map_err(CliError::input)?,
+    )],
+    (None, None) => unreachable!("clap requires one of --p096 and --grid"),
+  };
+
+  let grids = puzzles
+    .into_iter()
+    .map(|(name, mut sudoku)| {
+      sudoku
+        .solve()
+        .map_err(|err| CliError::Unsolved(format!("{name}: {err}")))?;
+      Ok(SolvedGrid {
+        name,
+        solution: sudoku.to_line(),
+      })
     })
-    .sum();
+    .collect::<Result<Vec<_>, CliError>>()?;
+  let sum = args.p096.is_some().then(|| {
+    grids
+      .iter()
+      .map(|grid| grid.solution[..3].parse::<u32>().unwrap())
+      .sum()
+  });
+  let output = SudokuOutput { grids, sum };
 
-  println!("Sum: {sums}");
+  if json {
+    print_json(&output);
+  } else {
+    for SolvedGrid { name, solution } in &output.grids {
+      println!("{name}: {solution}");
+    }
+    if let Some(sum) = output.sum {
+      println!("Sum: {sum}");
+    }
+  }
+  Ok(())
+}
+
+#[derive(Serialize)]
+struct DlxOutput {
+  count: usize,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  solutions: Option<Vec<Vec<String>>>,
+}
 
+fn run_dlx(args: &DlxArgs, json: bool) -> Result<(), CliError> {
+  let problem = File::open(&args.knuth_format)
+    .and_then(|f| KnuthProblem::from_reader(BufReader::new(f)))
+    .map_err(CliError::input)?;
+  let mut dlx = problem.to_dlx();
+  let output = if args.count {
+    DlxOutput {
+      count: dlx.count_solutions(usize::MAX),
+      solutions: None,
+    }
+  } else {
+    let solutions = dlx
+      .find_all_solution_names()
+      .map(|names| {
+        names
+          .into_iter()
+          .sorted()
+          .map(|idx| problem.option_line(idx).unwrap())
+          .collect_vec()
+      })
+      .collect_vec();
+    if solutions.is_empty() {
+      return Err(CliError::Unsolved("No solution".to_string()));
+    }
+    DlxOutput {
+      count: solutions.len(),
+      solutions: Some(solutions),
+    }
+  };
+  if json {
+    print_json(&output);
+  } else if let Some(solutions) = &output.solutions {
+    for (idx, options) in solutions.iter().enumerate() {
+      println!("Solution {}:", idx + 1);
+      for option in options {
+        println!("  {option}");
+      }
+    }
+  } else {
+    println!("{}", output.count);
+  }
   Ok(())
 }
+
+fn run_repl() -> Result<(), CliError> {
+  let stdin = io::stdin();
+  let prompt = stdin.is_terminal();
+  repl::Repl::new()
+    .run(stdin.lock(), io::stdout().lock(), prompt)
+    .map_err(CliError::input)
+}
+
+fn main() -> ExitCode {
+  let cli = Cli::parse();
+  if cli.verbose > 0 {
+    let level = if cli.verbose == 1 { "debug" } else { "trace" };
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(level)).init();
+  }
+  let result = match &cli.command {
+    Command::Kakuro(args) => run_kakuro(args, cli.json),
+    Command::Sudoku(args) => run_sudoku(args, cli.json),
+    Command::Dlx(args) => run_dlx(args, cli.json),
+    Command::Repl => run_repl(),
+  };
+  match result {
+    Ok(()) => ExitCode::SUCCESS,
+    Err(err) => {
+      eprintln!("Error: {err}");
+      err.exit_code()
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::parse_range;
+
+  #[test]
+  fn test_parse_range() {
+    assert_eq!(parse_range("0..10"), Ok(0..10));
+    assert_eq!(parse_range("5.."), Ok(5..usize::MAX));
+    assert_eq!(parse_range("..3"), Ok(0..3));
+    assert_eq!(parse_range("7"), Ok(7..8));
+    assert!(parse_range("a..3").is_err());
+    let max = usize::MAX.to_string();
+    assert_eq!(parse_range(&max), Err(format!("Index {max} is too large")));
+  }
+}
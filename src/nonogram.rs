@@ -0,0 +1,292 @@
+use std::{
+  fmt::{self, Display},
+  iter,
+};
+
+use crate::{
+  dlx::{ColorItem, Constraint, HeaderType, Selection},
+  exact_cover::{build_dlx, solve_model_with, ExactCoverModel, SearchLimits},
+};
+
+/// The items of a nonogram's exact cover model.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum NonogramItem {
+  Row(usize),
+  Col(usize),
+  Cell { row: usize, col: usize },
+}
+
+impl NonogramItem {
+  /// The `i`th cell along this row or column.
+  fn line_cell(self, i: usize) -> Self {
+    match self {
+      Self::Row(row) => Self::Cell { row, col: i },
+      Self::Col(col) => Self::Cell { row: i, col },
+      Self::Cell { .. } => unreachable!("A cell isn't a line"),
+    }
+  }
+}
+
+/// A filled-in nonogram, where `true` cells are shaded.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Grid(pub Vec<Vec<bool>>);
+
+impl Grid {
+  pub fn filled(&self, row: usize, col: usize) -> bool {
+    self.0[row][col]
+  }
+}
+
+impl Display for Grid {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for row in &self.0 {
+      let row: String = row
+        .iter()
+        .map(|&filled| if filled { '#' } else { '.' })
+        .collect();
+      writeln!(f, "{row}")?;
+    }
+    Ok(())
+  }
+}
+
+/// Every way to shade a line of `len` cells so its runs of shaded cells have
+/// the lengths in `clue`, in order, separated by at least one blank cell.
+/// Patterns are produced with the first run as far left as possible first.
+pub fn line_patterns(clue: &[usize], len: usize) -> Vec<Vec<bool>> {
+  fn place(clue: &[usize], pattern: &mut Vec<bool>, len: usize, patterns: &mut Vec<Vec<bool>>) {
+    let Some((&run, rest)) = clue.split_first() else {
+      pattern.resize(len, false);
+      patterns.push(pattern.clone());
+      return;
+    };
+
+    let start = pattern.len();
+    // The remaining runs need their lengths plus a gap before each.
+    let needed = rest.iter().map(|run| run + 1).sum::<usize>() + run;
+    for offset in 0..=(len - start - needed) {
+      pattern.extend(iter::repeat_n(false, offset));
+      pattern.extend(iter::repeat_n(true, run));
+      if !rest.is_empty() {
+        pattern.push(false);
+      }
+      place(rest, pattern, len, patterns);
+      pattern.truncate(start);
+    }
+  }
+
+  let needed = clue.iter().sum::<usize>() + clue.len().saturating_sub(1);
+  if needed > len {
+    return Vec::new();
+  }
+  let mut patterns = Vec::new();
+  place(clue, &mut Vec::with_capacity(len), len, &mut patterns);
+  patterns
+}
+
+/// A nonogram, given by the lengths of the runs of shaded cells in each row
+/// and column.
+///
+/// Each row and each column is a primary item, whose subsets are the
+/// patterns its clue allows. The cells are secondary items colored by whether
+/// they are shaded, so the row and column through a cell must agree on it.
+pub struct Nonogram {
+  row_clues: Vec<Vec<usize>>,
+  col_clues: Vec<Vec<usize>>,
+}
+
+impl Nonogram {
+  /// Clues may be written as `[0]` for an empty line.
+  pub fn new(row_clues: Vec<Vec<usize>>, col_clues: Vec<Vec<usize>>) -> Self {
+    let strip_zeros = |clues: Vec<Vec<usize>>| {
+      clues
+        .into_iter()
+        .map(|clue| clue.into_iter().filter(|&run| run != 0).collect())
+        .collect()
+    };
+    Self {
+      row_clues: strip_zeros(row_clues),
+      col_clues: strip_zeros(col_clues),
+    }
+  }
+
+  pub fn width(&self) -> usize {
+    self.col_clues.len()
+  }
+
+  pub fn height(&self) -> usize {
+    self.row_clues.len()
+  }
+
+  pub fn solve(&self) -> Option<Grid> {
+    solve_model_with(self, SearchLimits::first())
+      .solutions
+      .pop()
+  }
+
+  /// Counts the solutions, stopping once `limit` have been found.
+  pub fn count_solutions(&self, limit: u64) -> u64 {
+    build_dlx(self).count_solutions(limit as usize) as u64
+  }
+
+  /// Whether the clues determine exactly one picture.
+  pub fn is_unique(&self) -> bool {
+    self.count_solutions(2) == 1
+  }
+}
+
+impl ExactCoverModel for Nonogram {
+  type Item = NonogramItem;
+  type Choice = (NonogramItem, usize);
+  type Solution = Grid;
+
+  fn items(&self) -> impl Iterator<Item = (NonogramItem, HeaderType)> + '_ {
+    let lines = (0..self.height())
+      .map(NonogramItem::Row)
+      .chain((0..self.width()).map(NonogramItem::Col))
+      .map(|item| (item, HeaderType::Primary));
+    let cells = (0..self.height())
+      .flat_map(|row| (0..self.width()).map(move |col| NonogramItem::Cell { row, col }))
+      .map(|item| (item, HeaderType::Secondary));
+    lines.chain(cells)
+  }
+
+  fn choices(
+    &self,
+  ) -> impl Iterator<
+    Item = (
+      (NonogramItem, usize),
+      impl IntoIterator<Item = Constraint<NonogramItem>>,
+    ),
+  > + '_ {
+    let rows = self
+      .row_clues
+      .iter()
+      .enumerate()
+      .map(|(row, clue)| (NonogramItem::Row(row), clue, self.width()));
+    let cols = self
+      .col_clues
+      .iter()
+      .enumerate()
+      .map(|(col, clue)| (NonogramItem::Col(col), clue, self.height()));
+
+    rows.chain(cols).flat_map(|(line, clue, len)| {
+      line_patterns(clue, len)
+        .into_iter()
+        .enumerate()
+        .map(move |(idx, pattern)| {
+          let cells = pattern
+            .into_iter()
+            .enumerate()
+            .map(move |(i, filled)| ColorItem::new(line.line_cell(i), filled as u32).into());
+          (
+            (line, idx),
+            iter::once(Constraint::Primary(line)).chain(cells),
+          )
+        })
+    })
+  }
+
+  fn decode(&self, selection: Selection<NonogramItem, (NonogramItem, usize)>) -> Grid {
+    let mut grid = vec![vec![false; self.width()]; self.height()];
+    for (item, color) in selection.colors {
+      if let NonogramItem::Cell { row, col } = item {
+        grid[row][col] = color == 1;
+      }
+    }
+    Grid(grid)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{line_patterns, Grid, Nonogram};
+
+  const FACE: &str = "\
+..######..
+.########.
+##.####.##
+##########
+#.######.#
+##.#..#.##
+.##....##.
+..######..
+...#..#...
+..##..##..
+";
+
+  fn face() -> Nonogram {
+    Nonogram::new(
+      vec![
+        vec![6],
+        vec![8],
+        vec![2, 4, 2],
+        vec![10],
+        vec![1, 6, 1],
+        vec![2, 1, 1, 2],
+        vec![2, 2],
+        vec![6],
+        vec![1, 1],
+        vec![2, 2],
+      ],
+      vec![
+        vec![4],
+        vec![3, 2],
+        vec![2, 2, 2, 1],
+        vec![6, 3],
+        vec![5, 1],
+        vec![5, 1],
+        vec![6, 3],
+        vec![2, 2, 2, 1],
+        vec![3, 2],
+        vec![4],
+      ],
+    )
+  }
+
+  fn pattern(s: &str) -> Vec<bool> {
+    s.chars().map(|c| c == '#').collect()
+  }
+
+  #[test]
+  fn test_line_patterns() {
+    assert_eq!(
+      line_patterns(&[2, 1], 5),
+      vec![pattern("##.#."), pattern("##..#"), pattern(".##.#")]
+    );
+    assert_eq!(line_patterns(&[], 3), vec![pattern("...")]);
+    assert_eq!(line_patterns(&[3], 3), vec![pattern("###")]);
+    assert!(line_patterns(&[2, 2], 4).is_empty());
+  }
+
+  #[test]
+  fn test_solve() {
+    let nonogram = face();
+    let grid = nonogram.solve().unwrap();
+    assert_eq!(grid.to_string(), FACE);
+    assert!(grid.filled(0, 2));
+    assert!(!grid.filled(0, 1));
+    assert!(nonogram.is_unique());
+  }
+
+  #[test]
+  fn test_ambiguous() {
+    // Either diagonal of a 2x2 grid fits.
+    let nonogram = Nonogram::new(vec![vec![1], vec![1]], vec![vec![1], vec![1]]);
+    assert_eq!(nonogram.count_solutions(u64::MAX), 2);
+    assert!(!nonogram.is_unique());
+  }
+
+  #[test]
+  fn test_no_solution() {
+    let nonogram = Nonogram::new(vec![vec![2], vec![0]], vec![vec![0], vec![1]]);
+    assert_eq!(nonogram.solve(), None);
+    assert_eq!(nonogram.count_solutions(2), 0);
+  }
+
+  #[test]
+  fn test_display() {
+    let grid = Grid(vec![vec![true, false], vec![false, true]]);
+    assert_eq!(grid.to_string(), "#.\n.#\n");
+  }
+}
@@ -0,0 +1,129 @@
+use std::fmt::{self, Display};
+
+use itertools::Itertools;
+
+use crate::dlx::{Constraint, Dlx, HeaderType};
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum QueenItem {
+  Rank(usize),
+  File(usize),
+  /// The diagonal with `row + col` equal to the index.
+  Diag(usize),
+  /// The anti-diagonal with `row + n - 1 - col` equal to the index.
+  AntiDiag(usize),
+}
+
+/// The `n` queens problem. Every rank and file holds exactly one queen, so
+/// those are primary items, while each diagonal may hold at most one queen and
+/// is an uncolored secondary item. Subsets are named by the `(row, col)` of
+/// their queen.
+pub fn build_dlx(n: usize) -> Dlx<QueenItem, (usize, usize)> {
+  let lines = 2 * n.max(1) - 1;
+  let items = (0..n)
+    .flat_map(|i| [QueenItem::Rank(i), QueenItem::File(i)])
+    .map(|item| (item, HeaderType::Primary))
+    .chain(
+      (0..lines)
+        .flat_map(|i| [QueenItem::Diag(i), QueenItem::AntiDiag(i)])
+        .map(|item| (item, HeaderType::Secondary)),
+    );
+
+  let subsets = (0..n).cartesian_product(0..n).map(|(row, col)| {
+    (
+      (row, col),
+      vec![
+        QueenItem::Rank(row).into(),
+        QueenItem::File(col).into(),
+        Constraint::Uncolored(QueenItem::Diag(row + col)),
+        Constraint::Uncolored(QueenItem::AntiDiag(row + n - 1 - col)),
+      ],
+    )
+  });
+
+  Dlx::new(items, subsets)
+}
+
+/// Finds a placement of `n` non-attacking queens on an `n`x`n` board,
+/// returning the column of the queen in each row.
+pub fn solve(n: usize) -> Option<Vec<usize>> {
+  let placements = build_dlx(n).find_first_solution_names()?;
+  let mut cols = vec![0; n];
+  for (row, col) in placements {
+    cols[row] = col;
+  }
+  Some(cols)
+}
+
+/// Counts the placements of `n` non-attacking queens on an `n`x`n` board.
+pub fn count_solutions(n: usize) -> u64 {
+  build_dlx(n).count_solutions(usize::MAX) as u64
+}
+
+/// A board with one queen per row, at the given column.
+pub struct Board(pub Vec<usize>);
+
+impl Display for Board {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let n = self.0.len();
+    for &col in &self.0 {
+      let row = (0..n).map(|c| if c == col { 'Q' } else { '.' }).join(" ");
+      writeln!(f, "{row}")?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use itertools::Itertools;
+
+  use super::{count_solutions, solve, Board};
+
+  fn is_valid(cols: &[usize]) -> bool {
+    cols.iter().all_unique()
+      && cols
+        .iter()
+        .enumerate()
+        .tuple_combinations()
+        .all(|((r1, &c1), (r2, &c2))| r1.abs_diff(r2) != c1.abs_diff(c2))
+  }
+
+  #[test]
+  fn test_solve() {
+    for n in [1, 4, 5, 8, 12] {
+      let cols = solve(n).unwrap();
+      assert_eq!(cols.len(), n);
+      assert!(is_valid(&cols), "{}", Board(cols));
+    }
+  }
+
+  #[test]
+  fn test_unsolvable() {
+    assert_eq!(solve(2), None);
+    assert_eq!(solve(3), None);
+  }
+
+  #[test]
+  fn test_count_solutions() {
+    assert_eq!(count_solutions(1), 1);
+    assert_eq!(count_solutions(4), 2);
+    assert_eq!(count_solutions(6), 4);
+    assert_eq!(count_solutions(8), 92);
+    assert_eq!(count_solutions(10), 724);
+  }
+
+  #[test]
+  #[ignore]
+  fn test_count_solutions_13() {
+    assert_eq!(count_solutions(13), 73712);
+  }
+
+  #[test]
+  fn test_display() {
+    assert_eq!(
+      Board(vec![1, 3, 0, 2]).to_string(),
+      ". Q . .\n. . . Q\nQ . . .\n. . Q .\n"
+    );
+  }
+}
@@ -0,0 +1,202 @@
+use std::ops::Range;
+
+use crate::parenthesis_split::{ParenError, ParenthesesAwareSplit};
+
+/// A comma-separated list, parsed with each parenthesized field as a nested
+/// list. Every node records the byte range of the source it was parsed from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Node<'a> {
+  /// A field without parentheses around it, trimmed of whitespace.
+  Leaf { text: &'a str, span: Range<usize> },
+  /// A parenthesized list of fields, whose span includes the parentheses.
+  Group {
+    children: Vec<Node<'a>>,
+    span: Range<usize>,
+  },
+}
+
+impl<'a> Node<'a> {
+  pub fn span(&self) -> Range<usize> {
+    match self {
+      Node::Leaf { span, .. } | Node::Group { span, .. } => span.clone(),
+    }
+  }
+
+  pub fn as_leaf(&self) -> Option<&'a str> {
+    match self {
+      Node::Leaf { text, .. } => Some(text),
+      Node::Group { .. } => None,
+    }
+  }
+
+  pub fn children(&self) -> Option<&[Node<'a>]> {
+    match self {
+      Node::Leaf { .. } => None,
+      Node::Group { children, .. } => Some(children),
+    }
+  }
+}
+
+/// Parses `source` as a comma-separated list of fields, returned as a group
+/// spanning all of `source`. Fields wrapped in parentheses are parsed as
+/// lists in turn, to any depth, while any other field is a leaf, even if it
+/// contains parentheses elsewhere.
+pub fn parse_tree(source: &str) -> Result<Node<'_>, ParenError> {
+  // Checking the whole string up front means every group within it is
+  // balanced too.
+  source
+    .try_split_paren()
+    .try_for_each(|field| field.map(drop))?;
+  Ok(Node::Group {
+    children: parse_list(source, 0),
+    span: 0..source.len(),
+  })
+}
+
+/// Parses `source` as a single field: a group if it's wrapped in
+/// parentheses, or otherwise a leaf.
+pub fn parse_node(source: &str) -> Result<Node<'_>, ParenError> {
+  source
+    .try_split_paren()
+    .try_for_each(|field| field.map(drop))?;
+  Ok(parse_field(source, 0))
+}
+
+/// The fields of `list`, which starts at byte `offset` of the source.
+fn parse_list(list: &str, offset: usize) -> Vec<Node<'_>> {
+  list
+    .split_paren_indices()
+    .map(|(range, field)| parse_field(field, offset + range.start))
+    .collect()
+}
+
+/// The node for `field`, which starts at byte `offset` of the source.
+fn parse_field(field: &str, offset: usize) -> Node<'_> {
+  let start = offset + (field.len() - field.trim_start().len());
+  let field = field.trim();
+  let span = start..start + field.len();
+  match field
+    .strip_prefix('(')
+    .and_then(|inner| inner.strip_suffix(')'))
+    .filter(|inner| encloses(inner))
+  {
+    Some(inner) => Node::Group {
+      children: parse_list(inner, start + 1),
+      span,
+    },
+    None => Node::Leaf { text: field, span },
+  }
+}
+
+/// Whether `inner`, the text between an opening and closing parenthesis, is
+/// balanced, so that the two parentheses match each other.
+fn encloses(inner: &str) -> bool {
+  inner
+    .chars()
+    .try_fold(0u32, |depth, c| match c {
+      '(' => Some(depth + 1),
+      ')' => depth.checked_sub(1),
+      _ => Some(depth),
+    })
+    .is_some()
+}
+
+#[cfg(test)]
+mod test {
+  use crate::parenthesis_split::ParenError;
+
+  use super::{parse_node, parse_tree, Node};
+
+  fn leaf(text: &str, start: usize) -> Node<'_> {
+    Node::Leaf {
+      text,
+      span: start..start + text.len(),
+    }
+  }
+
+  #[test]
+  fn test_flat() {
+    assert_eq!(
+      parse_tree("6, X ,O").unwrap(),
+      Node::Group {
+        children: vec![leaf("6", 0), leaf("X", 3), leaf("O", 6)],
+        span: 0..7,
+      }
+    );
+  }
+
+  #[test]
+  fn test_nested() {
+    let source = "a,(b,(c,(d,e)),f),g";
+    let tree = parse_tree(source).unwrap();
+    assert_eq!(
+      tree,
+      Node::Group {
+        children: vec![
+          leaf("a", 0),
+          Node::Group {
+            children: vec![
+              leaf("b", 3),
+              Node::Group {
+                children: vec![
+                  leaf("c", 6),
+                  Node::Group {
+                    children: vec![leaf("d", 9), leaf("e", 11)],
+                    span: 8..13,
+                  },
+                ],
+                span: 5..14,
+              },
+              leaf("f", 15),
+            ],
+            span: 2..17,
+          },
+          leaf("g", 18),
+        ],
+        span: 0..19,
+      }
+    );
+
+    let middle = &tree.children().unwrap()[1];
+    assert_eq!(&source[middle.span()], "(b,(c,(d,e)),f)");
+    assert_eq!(middle.children().unwrap()[2].as_leaf(), Some("f"));
+  }
+
+  #[test]
+  fn test_partial_parentheses() {
+    // Parentheses which don't wrap the whole field leave it a leaf.
+    let tree = parse_tree("f(x),(a)(b),()").unwrap();
+    let children = tree.children().unwrap();
+    assert_eq!(children[0].as_leaf(), Some("f(x)"));
+    assert_eq!(children[1].as_leaf(), Some("(a)(b)"));
+    assert_eq!(children[2].children(), Some(&[][..]));
+  }
+
+  #[test]
+  fn test_parse_node() {
+    assert_eq!(parse_node(" a,b ").unwrap(), leaf("a,b", 1));
+    assert_eq!(
+      parse_node("(a,(b))").unwrap(),
+      Node::Group {
+        children: vec![
+          leaf("a", 1),
+          Node::Group {
+            children: vec![leaf("b", 4)],
+            span: 3..6,
+          },
+        ],
+        span: 0..7,
+      }
+    );
+    assert_eq!(parse_node("(a,b"), Err(ParenError::UnmatchedOpen(0)));
+  }
+
+  #[test]
+  fn test_errors() {
+    assert_eq!(
+      parse_tree("a,(b,(c,d),e"),
+      Err(ParenError::UnmatchedOpen(2))
+    );
+    assert_eq!(parse_tree("a,(b,c)),d"), Err(ParenError::UnmatchedClose(7)));
+  }
+}
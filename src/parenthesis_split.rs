@@ -1,51 +1,812 @@
-use itertools::{FoldWhile, Itertools};
+use std::{error::Error, fmt::Display, iter::FusedIterator, mem, ops::Range};
 
-pub struct ParenthesesAwareSplitIter<'a> {
-  inner: &'a str,
+/// The grouping pairs `split_paren` respects.
+pub const PARENTHESES: &[(char, char)] = &[('(', ')')];
+
+/// How a `GroupedSplitIter` splits: on `delim`, except inside groups opened
+/// and closed by one of the `(open, close)` bracket `pairs`, and optionally
+/// inside quotes or right after an escape character. Fields are yielded as
+/// written, with their brackets, quotes and escapes left in.
+#[derive(Clone, Copy, Debug)]
+pub struct SplitOptions<'p> {
+  delim: char,
+  pairs: &'p [(char, char)],
+  quote: Option<char>,
+  escape: Option<char>,
 }
 
-impl<'a> Iterator for ParenthesesAwareSplitIter<'a> {
-  type Item = &'a str;
+impl<'p> SplitOptions<'p> {
+  pub fn new(delim: char, pairs: &'p [(char, char)]) -> Self {
+    Self {
+      delim,
+      pairs,
+      quote: None,
+      escape: None,
+    }
+  }
 
-  fn next(&mut self) -> Option<Self::Item> {
-    match self
-      .inner
-      .chars()
-      .enumerate()
-      .fold_while(0, |depth, (idx, c)| match c {
-        '(' => FoldWhile::Continue(depth + 1),
-        ')' => FoldWhile::Continue(depth - 1),
-        ',' => {
-          if depth == 0 {
-            FoldWhile::Done(idx)
-          } else {
-            FoldWhile::Continue(depth)
-          }
+  /// Treats everything between a pair of `quote` characters as part of the
+  /// field, including delimiters and brackets.
+  pub fn quotes(self, quote: char) -> Self {
+    Self {
+      quote: Some(quote),
+      ..self
+    }
+  }
+
+  /// Treats the character following `escape` as an ordinary character, even
+  /// if it's a delimiter, bracket or quote.
+  pub fn escape(self, escape: char) -> Self {
+    Self {
+      escape: Some(escape),
+      ..self
+    }
+  }
+
+  fn is_open(&self, c: char) -> bool {
+    self.pairs.iter().any(|&(open, _)| open == c)
+  }
+
+  fn is_close(&self, c: char) -> bool {
+    self.pairs.iter().any(|&(_, close)| close == c)
+  }
+
+  /// Whether the character at byte `idx` of `text` is preceded by an odd
+  /// number of escape characters.
+  fn is_escaped(&self, text: &str, idx: usize) -> bool {
+    self.escape.is_some_and(|escape| {
+      text[..idx]
+        .chars()
+        .rev()
+        .take_while(|&c| c == escape)
+        .count()
+        % 2
+        == 1
+    })
+  }
+}
+
+/// Follows quotes and escapes through a string from the front, to pick out
+/// the characters which may delimit fields or open and close groups.
+#[derive(Default)]
+struct Scanner {
+  escaped: bool,
+  /// The byte offset of the open quote, if inside quotes.
+  quote_start: Option<usize>,
+}
+
+impl Scanner {
+  /// Whether `c`, at byte `idx`, is neither quoted nor escaped, nor itself a
+  /// quote or escape character.
+  fn is_structural(&mut self, options: &SplitOptions, idx: usize, c: char) -> bool {
+    if mem::take(&mut self.escaped) {
+      false
+    } else if Some(c) == options.escape {
+      self.escaped = true;
+      false
+    } else if Some(c) == options.quote {
+      self.quote_start = match self.quote_start {
+        Some(_) => None,
+        None => Some(idx),
+      };
+      false
+    } else {
+      self.quote_start.is_none()
+    }
+  }
+}
+
+/// Splits a string on a delimiter, except where it appears inside a group
+/// opened and closed by one of the bracket pairs. Groups are only counted, so
+/// unbalanced input splits unpredictably, and may split differently from each
+/// end; `CheckedGroupedSplitIter` reports it instead.
+///
+/// A final empty field, as in `"a,b,"`, is dropped unless `keep_empty` is set.
+#[derive(Clone, Debug)]
+pub struct GroupedSplitIter<'a, 'p> {
+  source: &'a str,
+  /// The byte range of `source` not yet split off.
+  start: usize,
+  end: usize,
+  options: SplitOptions<'p>,
+  keep_empty: bool,
+  finished: bool,
+}
+
+/// The splitter used for puzzle lines, on commas outside parentheses.
+pub type ParenthesesAwareSplitIter<'a> = GroupedSplitIter<'a, 'static>;
+
+impl<'a, 'p> GroupedSplitIter<'a, 'p> {
+  fn new(source: &'a str, options: SplitOptions<'p>) -> Self {
+    Self {
+      source,
+      start: 0,
+      end: source.len(),
+      options,
+      keep_empty: false,
+      finished: false,
+    }
+  }
+
+  /// Also yields the empty field after a trailing delimiter, and a single
+  /// empty field for an empty string.
+  pub fn keep_empty(mut self) -> Self {
+    self.keep_empty = true;
+    self
+  }
+
+  /// Yields the byte range of each field in the split string along with it.
+  pub fn indices(self) -> GroupedSplitIndices<'a, 'p> {
+    GroupedSplitIndices { inner: self }
+  }
+
+  /// The range of the last field, once no delimiters are left.
+  fn last_range(&mut self) -> Option<Range<usize>> {
+    if self.finished {
+      return None;
+    }
+    self.finished = true;
+    (self.keep_empty || self.end > self.start).then_some(self.start..self.end)
+  }
+
+  fn next_range(&mut self) -> Option<Range<usize>> {
+    if self.finished {
+      return None;
+    }
+    let options = &self.options;
+    let mut scanner = Scanner::default();
+    let mut depth = 0i32;
+    let delim = self.source[self.start..self.end]
+      .char_indices()
+      .filter(|&(idx, c)| scanner.is_structural(options, idx, c))
+      .find_map(|(idx, c)| {
+        if options.is_open(c) {
+          depth += 1;
+        } else if options.is_close(c) {
+          depth -= 1;
+        } else if c == options.delim && depth == 0 {
+          return Some(self.start + idx);
         }
-        _ => FoldWhile::Continue(depth),
-      }) {
-      FoldWhile::Done(end) => {
-        let tmp = self.inner;
-        self.inner = &self.inner[(end + 1)..];
-        Some(&tmp[..end])
-      }
-      FoldWhile::Continue(_) => {
-        let tmp = self.inner;
-        self.inner = &self.inner[self.inner.len()..];
-        if !tmp.is_empty() {
-          Some(tmp)
+        None
+      });
+
+    match delim {
+      Some(delim) => {
+        let range = self.start..delim;
+        self.start = delim + self.options.delim.len_utf8();
+        Some(range)
+      }
+      None => self.last_range(),
+    }
+  }
+
+  fn next_back_range(&mut self) -> Option<Range<usize>> {
+    if self.finished {
+      return None;
+    }
+    if !self.keep_empty {
+      // Only the last field may be dropped for being empty, so check for it
+      // once and then keep every field.
+      self.keep_empty = true;
+      match self.next_back_range() {
+        Some(range) if !range.is_empty() => return Some(range),
+        _ if self.finished => return None,
+        _ => {}
+      }
+    }
+
+    let options = &self.options;
+    let text = &self.source[self.start..self.end];
+    let mut in_quotes = false;
+    let mut depth = 0i32;
+    let delim = text
+      .char_indices()
+      .rev()
+      .filter(|&(idx, c)| {
+        if Some(c) == options.escape || options.is_escaped(text, idx) {
+          false
+        } else if Some(c) == options.quote {
+          in_quotes = !in_quotes;
+          false
         } else {
-          None
+          !in_quotes
         }
+      })
+      .find_map(|(idx, c)| {
+        if options.is_close(c) {
+          depth += 1;
+        } else if options.is_open(c) {
+          depth -= 1;
+        } else if c == options.delim && depth == 0 {
+          return Some(self.start + idx);
+        }
+        None
+      });
+
+    match delim {
+      Some(delim) => {
+        let range = delim + self.options.delim.len_utf8()..self.end;
+        self.end = delim;
+        Some(range)
+      }
+      None => {
+        self.finished = true;
+        Some(self.start..self.end)
+      }
+    }
+  }
+}
+
+impl<'a> Iterator for GroupedSplitIter<'a, '_> {
+  type Item = &'a str;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    self.next_range().map(|range| &self.source[range])
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    if self.finished {
+      (0, Some(0))
+    } else {
+      // Every field but the last is followed by a delimiter.
+      let remaining = self.end - self.start;
+      (0, Some(remaining / self.options.delim.len_utf8() + 1))
+    }
+  }
+
+  fn last(mut self) -> Option<Self::Item> {
+    self.next_back()
+  }
+}
+
+impl DoubleEndedIterator for GroupedSplitIter<'_, '_> {
+  fn next_back(&mut self) -> Option<Self::Item> {
+    self.next_back_range().map(|range| &self.source[range])
+  }
+}
+
+impl FusedIterator for GroupedSplitIter<'_, '_> {}
+
+/// Splits like `GroupedSplitIter`, yielding the byte range of each field in
+/// the split string along with the field.
+#[derive(Clone, Debug)]
+pub struct GroupedSplitIndices<'a, 'p> {
+  inner: GroupedSplitIter<'a, 'p>,
+}
+
+impl<'a> Iterator for GroupedSplitIndices<'a, '_> {
+  type Item = (Range<usize>, &'a str);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let range = self.inner.next_range()?;
+    Some((range.clone(), &self.inner.source[range]))
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    self.inner.size_hint()
+  }
+
+  fn last(mut self) -> Option<Self::Item> {
+    self.next_back()
+  }
+}
+
+impl DoubleEndedIterator for GroupedSplitIndices<'_, '_> {
+  fn next_back(&mut self) -> Option<Self::Item> {
+    let range = self.inner.next_back_range()?;
+    Some((range.clone(), &self.inner.source[range]))
+  }
+}
+
+impl FusedIterator for GroupedSplitIndices<'_, '_> {}
+
+/// Splits like `GroupedSplitIter` into at most `n` fields, the last of which
+/// is the rest of the string, unsplit.
+#[derive(Clone, Debug)]
+pub struct GroupedSplitN<'a, 'p> {
+  inner: GroupedSplitIter<'a, 'p>,
+  remaining: usize,
+}
+
+impl<'a> Iterator for GroupedSplitN<'a, '_> {
+  type Item = &'a str;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let range = match self.remaining {
+      0 => return None,
+      1 => self.inner.last_range(),
+      _ => self.inner.next_range(),
+    }?;
+    self.remaining -= 1;
+    Some(&self.inner.source[range])
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    let (low, high) = self.inner.size_hint();
+    (
+      low.min(self.remaining),
+      Some(high.map_or(self.remaining, |high| high.min(self.remaining))),
+    )
+  }
+}
+
+impl FusedIterator for GroupedSplitN<'_, '_> {}
+
+/// A bracket without a partner, at a byte offset into the split string.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParenError {
+  UnmatchedOpen(usize),
+  UnmatchedClose(usize),
+  /// The bracket at `close` closes a different kind of bracket than the one
+  /// opened at `open`.
+  Mismatched {
+    open: usize,
+    close: usize,
+  },
+  /// The quote at this offset is never closed.
+  UnterminatedQuote(usize),
+}
+
+impl Display for ParenError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      ParenError::UnmatchedOpen(offset) => write!(f, "Unclosed bracket at byte {offset}"),
+      ParenError::UnmatchedClose(offset) => write!(f, "Unmatched bracket at byte {offset}"),
+      ParenError::Mismatched { open, close } => {
+        write!(
+          f,
+          "Bracket at byte {close} doesn't close the one at byte {open}"
+        )
       }
+      ParenError::UnterminatedQuote(offset) => write!(f, "Unterminated quote at byte {offset}"),
     }
   }
 }
 
+impl Error for ParenError {}
+
+/// Splits like `GroupedSplitIter`, but matches each closing bracket with the
+/// innermost open one, yielding an error in place of the field containing the
+/// first bracket or quote without a partner, and then stopping.
+pub struct CheckedGroupedSplitIter<'a, 'p> {
+  inner: &'a str,
+  options: SplitOptions<'p>,
+  /// The byte offset of `inner` in the string being split.
+  offset: usize,
+  failed: bool,
+}
+
+/// The checked splitter for puzzle lines, from `try_split_paren`.
+pub type CheckedParenthesesAwareSplitIter<'a> = CheckedGroupedSplitIter<'a, 'static>;
+
+impl<'a> Iterator for CheckedGroupedSplitIter<'a, '_> {
+  type Item = Result<&'a str, ParenError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.failed || self.inner.is_empty() {
+      return None;
+    }
+
+    let options = self.options;
+    let mut scanner = Scanner::default();
+    // The offset of each group still open, and the bracket closing it.
+    let mut open = Vec::new();
+    for (idx, c) in self.inner.char_indices() {
+      if !scanner.is_structural(&options, idx, c) {
+        continue;
+      }
+      if let Some(&(_, close)) = options.pairs.iter().find(|&&(open, _)| open == c) {
+        open.push((idx, close));
+      } else if options.is_close(c) {
+        let err = match open.pop() {
+          Some((_, close)) if close == c => continue,
+          Some((open, _)) => ParenError::Mismatched {
+            open: self.offset + open,
+            close: self.offset + idx,
+          },
+          None => ParenError::UnmatchedClose(self.offset + idx),
+        };
+        self.failed = true;
+        return Some(Err(err));
+      } else if c == options.delim && open.is_empty() {
+        let field = &self.inner[..idx];
+        let skip = idx + options.delim.len_utf8();
+        self.inner = &self.inner[skip..];
+        self.offset += skip;
+        return Some(Ok(field));
+      }
+    }
+
+    if let Some(quote) = scanner.quote_start {
+      self.failed = true;
+      return Some(Err(ParenError::UnterminatedQuote(self.offset + quote)));
+    }
+    if let Some(&(first, _)) = open.first() {
+      self.failed = true;
+      return Some(Err(ParenError::UnmatchedOpen(self.offset + first)));
+    }
+    let field = self.inner;
+    self.offset += field.len();
+    self.inner = &self.inner[field.len()..];
+    Some(Ok(field))
+  }
+}
+
 pub trait ParenthesesAwareSplit<'a>: Into<&'a str> {
   fn split_paren(self) -> ParenthesesAwareSplitIter<'a> {
-    ParenthesesAwareSplitIter { inner: self.into() }
+    self.split_grouped(',', PARENTHESES)
+  }
+
+  /// Splits like `split_paren`, yielding the byte range of each field along
+  /// with it.
+  fn split_paren_indices(self) -> GroupedSplitIndices<'a, 'static> {
+    self.split_paren().indices()
+  }
+
+  /// Splits like `split_paren` into at most `n` fields, leaving the rest of
+  /// the string unsplit in the last.
+  fn splitn_paren(self, n: usize) -> GroupedSplitN<'a, 'static> {
+    GroupedSplitN {
+      inner: self.split_paren(),
+      remaining: n,
+    }
+  }
+
+  /// Splits off the first field like `split_paren`, returning it and the rest
+  /// of the string, or None if there's only one field.
+  fn split_paren_once(self) -> Option<(&'a str, &'a str)> {
+    let mut split = self.split_paren();
+    let first = split.next_range()?;
+    if split.finished {
+      return None;
+    }
+    Some((&split.source[first], &split.source[split.start..split.end]))
+  }
+
+  /// Splits like `split_paren`, reporting the first unbalanced parenthesis.
+  fn try_split_paren(self) -> CheckedParenthesesAwareSplitIter<'a> {
+    self.try_split_grouped(',', PARENTHESES)
+  }
+
+  /// Splits on `delim`, except inside groups opened and closed by one of the
+  /// `(open, close)` bracket `pairs`.
+  fn split_grouped<'p>(self, delim: char, pairs: &'p [(char, char)]) -> GroupedSplitIter<'a, 'p> {
+    self.split_with(SplitOptions::new(delim, pairs))
+  }
+
+  /// Splits like `split_grouped`, reporting the first bracket without a
+  /// partner.
+  fn try_split_grouped<'p>(
+    self,
+    delim: char,
+    pairs: &'p [(char, char)],
+  ) -> CheckedGroupedSplitIter<'a, 'p> {
+    self.try_split_with(SplitOptions::new(delim, pairs))
+  }
+
+  /// Splits as described by `options`.
+  fn split_with(self, options: SplitOptions<'_>) -> GroupedSplitIter<'a, '_> {
+    GroupedSplitIter::new(self.into(), options)
+  }
+
+  /// Splits like `split_with`, reporting the first bracket or quote without
+  /// a partner.
+  fn try_split_with(self, options: SplitOptions<'_>) -> CheckedGroupedSplitIter<'a, '_> {
+    CheckedGroupedSplitIter {
+      inner: self.into(),
+      options,
+      offset: 0,
+      failed: false,
+    }
   }
 }
 
 impl<'a, T> ParenthesesAwareSplit<'a> for T where T: Into<&'a str> {}
+
+#[cfg(test)]
+mod test {
+  use itertools::Itertools;
+
+  use super::{ParenError, ParenthesesAwareSplit, SplitOptions, PARENTHESES};
+
+  fn try_split(s: &str) -> Vec<Result<&str, ParenError>> {
+    s.try_split_paren().collect_vec()
+  }
+
+  #[test]
+  fn test_split_paren() {
+    assert_eq!(
+      "a,(b,c),d".split_paren().collect_vec(),
+      vec!["a", "(b,c)", "d"]
+    );
+    assert_eq!("a,,b,".split_paren().collect_vec(), vec!["a", "", "b"]);
+    assert!("".split_paren().next().is_none());
+  }
+
+  #[test]
+  fn test_balanced() {
+    assert_eq!(
+      try_split("6,(v(1,2),h3),((x)),O"),
+      vec![Ok("6"), Ok("(v(1,2),h3)"), Ok("((x))"), Ok("O")]
+    );
+    assert_eq!(try_split("a,,b,"), vec![Ok("a"), Ok(""), Ok("b")]);
+    assert!(try_split("").is_empty());
+  }
+
+  #[test]
+  fn test_unclosed() {
+    assert_eq!(
+      try_split("X,(v12,h3"),
+      vec![Ok("X"), Err(ParenError::UnmatchedOpen(2))]
+    );
+    // The outermost unclosed parenthesis is reported.
+    assert_eq!(
+      try_split("((a),(b"),
+      vec![Err(ParenError::UnmatchedOpen(0))]
+    );
+  }
+
+  #[test]
+  fn test_over_closed() {
+    assert_eq!(
+      try_split("a)b,(c"),
+      vec![Err(ParenError::UnmatchedClose(1))]
+    );
+    assert_eq!(
+      try_split("x,(a)),y"),
+      vec![Ok("x"), Err(ParenError::UnmatchedClose(5))]
+    );
+    assert_eq!(
+      ParenError::UnmatchedClose(5).to_string(),
+      "Unmatched bracket at byte 5"
+    );
+  }
+
+  const BRACKETS: &[(char, char)] = &[('(', ')'), ('[', ']'), ('{', '}')];
+
+  #[test]
+  fn test_split_grouped() {
+    let row = "1;[a;b];{x;[y;z]};(p;q)";
+    assert_eq!(
+      row.split_grouped(';', BRACKETS).collect_vec(),
+      vec!["1", "[a;b]", "{x;[y;z]}", "(p;q)"]
+    );
+    assert_eq!(
+      row.try_split_grouped(';', BRACKETS).collect_vec(),
+      vec![Ok("1"), Ok("[a;b]"), Ok("{x;[y;z]}"), Ok("(p;q)")]
+    );
+    // Only the given pairs group.
+    assert_eq!(
+      "[a,b],(c,d)"
+        .split_grouped(',', &[('[', ']')])
+        .collect_vec(),
+      vec!["[a,b]", "(c", "d)"]
+    );
+  }
+
+  #[test]
+  fn test_mismatched_brackets() {
+    assert_eq!(
+      "x;([)];y".try_split_grouped(';', BRACKETS).collect_vec(),
+      vec![Ok("x"), Err(ParenError::Mismatched { open: 3, close: 4 })]
+    );
+    assert_eq!(
+      "{a;b)".try_split_grouped(';', BRACKETS).collect_vec(),
+      vec![Err(ParenError::Mismatched { open: 0, close: 4 })]
+    );
+    assert_eq!(
+      "[a];b]".try_split_grouped(';', BRACKETS).collect_vec(),
+      vec![Ok("[a]"), Err(ParenError::UnmatchedClose(5))]
+    );
+  }
+
+  #[test]
+  fn test_indices() {
+    let line = "6, (v1,h2),O";
+    assert_eq!(
+      line.split_paren_indices().collect_vec(),
+      vec![(0..1, "6"), (2..10, " (v1,h2)"), (11..12, "O")]
+    );
+    for (range, field) in line.split_paren_indices() {
+      assert_eq!(&line[range], field);
+    }
+    assert_eq!(
+      line.split_paren_indices().rev().collect_vec(),
+      vec![(11..12, "O"), (2..10, " (v1,h2)"), (0..1, "6")]
+    );
+  }
+
+  #[test]
+  fn test_keep_empty() {
+    assert_eq!("a,b,".split_paren().collect_vec(), vec!["a", "b"]);
+    assert_eq!(
+      "a,b,".split_paren().keep_empty().collect_vec(),
+      vec!["a", "b", ""]
+    );
+    assert_eq!(",a,,".split_paren().collect_vec(), vec!["", "a", ""]);
+    assert_eq!("".split_paren().keep_empty().collect_vec(), vec![""]);
+    assert_eq!(",".split_paren().collect_vec(), vec![""]);
+    assert_eq!(",".split_paren().keep_empty().collect_vec(), vec!["", ""]);
+  }
+
+  #[test]
+  fn test_double_ended() {
+    for line in ["a,(b,c),d", "a,b,", ",a,,", "", ",", "(x,(y)),z"] {
+      for keep_empty in [false, true] {
+        let split = || {
+          let split = line.split_paren();
+          if keep_empty {
+            split.keep_empty()
+          } else {
+            split
+          }
+        };
+        let forward = split().collect_vec();
+        let mut backward = split().rev().collect_vec();
+        backward.reverse();
+        assert_eq!(forward, backward, "{line:?}, keep_empty: {keep_empty}");
+        assert_eq!(split().last(), forward.last().copied());
+
+        // Taking from both ends meets in the middle.
+        let mut split = split();
+        let mut front = Vec::new();
+        let mut back = Vec::new();
+        while let Some(field) = split.next() {
+          front.push(field);
+          match split.next_back() {
+            Some(field) => back.push(field),
+            None => break,
+          }
+        }
+        back.reverse();
+        front.extend(back);
+        assert_eq!(front, forward, "{line:?}, keep_empty: {keep_empty}");
+      }
+    }
+  }
+
+  #[test]
+  fn test_fused_and_size_hint() {
+    let mut split = "a,(b,c),d".split_paren();
+    let mut remaining = 3;
+    loop {
+      let (low, high) = split.size_hint();
+      assert!(low <= remaining && remaining <= high.unwrap());
+      if split.next().is_none() {
+        break;
+      }
+      remaining -= 1;
+    }
+    assert_eq!(remaining, 0);
+    assert_eq!(split.size_hint(), (0, Some(0)));
+    assert_eq!(split.next(), None);
+    assert_eq!(split.next_back(), None);
+  }
+
+  #[test]
+  fn test_multi_byte() {
+    assert_eq!(
+      "é,(ü,ß),ñ".split_paren_indices().collect_vec(),
+      vec![(0..2, "é"), (3..10, "(ü,ß)"), (11..13, "ñ")]
+    );
+    let split = || "é·(a·ß)··ñ".split_grouped('·', PARENTHESES);
+    assert_eq!(split().collect_vec(), vec!["é", "(a·ß)", "", "ñ"]);
+    assert_eq!(split().rev().collect_vec(), vec!["ñ", "", "(a·ß)", "é"]);
+    assert_eq!(
+      "ü·"
+        .split_grouped('·', PARENTHESES)
+        .keep_empty()
+        .collect_vec(),
+      vec!["ü", ""]
+    );
+    assert_eq!(
+      "(é,ü".try_split_paren().collect_vec(),
+      vec![Err(ParenError::UnmatchedOpen(0))]
+    );
+    assert_eq!(
+      "é,ü)".try_split_paren().collect_vec(),
+      vec![Ok("é"), Err(ParenError::UnmatchedClose(5))]
+    );
+  }
+  #[test]
+  fn test_quotes() {
+    let options = SplitOptions::new(',', PARENTHESES).quotes('"');
+    let line = r#"(h12),name="4x4, easy",O"#;
+    assert_eq!(
+      line.split_with(options).collect_vec(),
+      vec!["(h12)", r#"name="4x4, easy""#, "O"]
+    );
+    assert_eq!(
+      line.split_with(options).rev().collect_vec(),
+      vec!["O", r#"name="4x4, easy""#, "(h12)"]
+    );
+    // Without quotes, the comma splits the name.
+    assert_eq!(line.split_paren().count(), 4);
+  }
+
+  #[test]
+  fn test_escapes() {
+    let options = SplitOptions::new(',', PARENTHESES).quotes('"').escape('\\');
+    let line = r#"a\,b,"say \"hi, there\"",c\\,d"#;
+    let fields = vec![r#"a\,b"#, r#""say \"hi, there\"""#, r#"c\\"#, "d"];
+    assert_eq!(line.split_with(options).collect_vec(), fields);
+    assert_eq!(
+      line.split_with(options).rev().collect_vec(),
+      fields.iter().rev().copied().collect_vec()
+    );
+    assert_eq!(
+      line.try_split_with(options).collect_vec(),
+      fields.into_iter().map(Ok).collect_vec()
+    );
+  }
+
+  #[test]
+  fn test_quotes_and_parentheses() {
+    let options = SplitOptions::new(',', PARENTHESES).quotes('"').escape('\\');
+    // Brackets inside quotes don't count, and quotes inside brackets still
+    // protect their delimiters.
+    let line = r#"(x,"y)"),"(",(\)),z"#;
+    let fields = vec![r#"(x,"y)")"#, r#""(""#, r#"(\))"#, "z"];
+    assert_eq!(line.split_with(options).collect_vec(), fields);
+    assert_eq!(
+      line.try_split_with(options).collect_vec(),
+      fields.iter().copied().map(Ok).collect_vec()
+    );
+    assert_eq!(
+      line.split_with(options).rev().collect_vec(),
+      fields.into_iter().rev().collect_vec()
+    );
+  }
+
+  #[test]
+  fn test_unterminated_quote() {
+    let options = SplitOptions::new(',', PARENTHESES).quotes('"').escape('\\');
+    assert_eq!(
+      r#"a,"b,c"#.try_split_with(options).collect_vec(),
+      vec![Ok("a"), Err(ParenError::UnterminatedQuote(2))]
+    );
+    assert_eq!(
+      r#"a,(b,"c)"#.try_split_with(options).collect_vec(),
+      vec![Ok("a"), Err(ParenError::UnterminatedQuote(5))]
+    );
+    // An escaped quote doesn't close the quotes.
+    assert_eq!(
+      r#""a\",b"#.try_split_with(options).collect_vec(),
+      vec![Err(ParenError::UnterminatedQuote(0))]
+    );
+  }
+
+  #[test]
+  fn test_splitn() {
+    let line = "4,(v1,h2),O,X";
+    assert_eq!(line.splitn_paren(0).collect_vec(), Vec::<&str>::new());
+    assert_eq!(line.splitn_paren(1).collect_vec(), vec![line]);
+    assert_eq!(line.splitn_paren(2).collect_vec(), vec!["4", "(v1,h2),O,X"]);
+    assert_eq!(
+      line.splitn_paren(3).collect_vec(),
+      vec!["4", "(v1,h2)", "O,X"]
+    );
+    assert_eq!(
+      line.splitn_paren(10).collect_vec(),
+      vec!["4", "(v1,h2)", "O", "X"]
+    );
+    assert_eq!("a,".splitn_paren(2).collect_vec(), vec!["a"]);
+    assert_eq!("".splitn_paren(2).next(), None);
+
+    let mut split = line.splitn_paren(2);
+    assert_eq!(split.size_hint().1, Some(2));
+    split.next();
+    split.next();
+    assert_eq!(split.next(), None);
+    assert_eq!(split.size_hint(), (0, Some(0)));
+  }
+
+  #[test]
+  fn test_split_once() {
+    assert_eq!("4,(v1,h2),O".split_paren_once(), Some(("4", "(v1,h2),O")));
+    assert_eq!("(a,b),c".split_paren_once(), Some(("(a,b)", "c")));
+    assert_eq!("a,".split_paren_once(), Some(("a", "")));
+    assert_eq!("(a,b)".split_paren_once(), None);
+    assert_eq!("".split_paren_once(), None);
+  }
+}
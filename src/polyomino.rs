@@ -0,0 +1,260 @@
+use std::{
+  fmt::{self, Display},
+  iter,
+};
+
+use itertools::Itertools;
+
+use crate::dlx::{Constraint, Dlx, HeaderType};
+
+/// The twelve free pentominoes, in Conway's lettering.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum Pentomino {
+  F,
+  I,
+  L,
+  N,
+  P,
+  T,
+  U,
+  V,
+  W,
+  X,
+  Y,
+  Z,
+}
+
+impl Pentomino {
+  pub const ALL: [Pentomino; 12] = [
+    Self::F,
+    Self::I,
+    Self::L,
+    Self::N,
+    Self::P,
+    Self::T,
+    Self::U,
+    Self::V,
+    Self::W,
+    Self::X,
+    Self::Y,
+    Self::Z,
+  ];
+
+  pub fn letter(&self) -> char {
+    match self {
+      Self::F => 'F',
+      Self::I => 'I',
+      Self::L => 'L',
+      Self::N => 'N',
+      Self::P => 'P',
+      Self::T => 'T',
+      Self::U => 'U',
+      Self::V => 'V',
+      Self::W => 'W',
+      Self::X => 'X',
+      Self::Y => 'Y',
+      Self::Z => 'Z',
+    }
+  }
+
+  /// The `(row, col)` cells of the piece in one fixed orientation.
+  fn cells(&self) -> [(usize, usize); 5] {
+    match self {
+      Self::F => [(0, 1), (0, 2), (1, 0), (1, 1), (2, 1)],
+      Self::I => [(0, 0), (0, 1), (0, 2), (0, 3), (0, 4)],
+      Self::L => [(0, 0), (1, 0), (2, 0), (3, 0), (3, 1)],
+      Self::N => [(0, 1), (1, 1), (2, 0), (2, 1), (3, 0)],
+      Self::P => [(0, 0), (0, 1), (1, 0), (1, 1), (2, 0)],
+      Self::T => [(0, 0), (0, 1), (0, 2), (1, 1), (2, 1)],
+      Self::U => [(0, 0), (0, 2), (1, 0), (1, 1), (1, 2)],
+      Self::V => [(0, 0), (1, 0), (2, 0), (2, 1), (2, 2)],
+      Self::W => [(0, 0), (1, 0), (1, 1), (2, 1), (2, 2)],
+      Self::X => [(0, 1), (1, 0), (1, 1), (1, 2), (2, 1)],
+      Self::Y => [(0, 1), (1, 0), (1, 1), (2, 1), (3, 1)],
+      Self::Z => [(0, 0), (0, 1), (1, 1), (2, 1), (2, 2)],
+    }
+  }
+
+  /// All distinct rotations and reflections of the piece, each shifted so its
+  /// cells touch row 0 and column 0, with cells in sorted order.
+  fn orientations(&self) -> Vec<Vec<(usize, usize)>> {
+    (0..8)
+      .map(|transform| {
+        let cells = self.cells().map(|(row, col)| {
+          let (row, col) = (row as isize, col as isize);
+          let (row, col) = if transform & 4 != 0 {
+            (col, row)
+          } else {
+            (row, col)
+          };
+          let row = if transform & 1 != 0 { -row } else { row };
+          let col = if transform & 2 != 0 { -col } else { col };
+          (row, col)
+        });
+        let min_row = cells.iter().map(|&(row, _)| row).min().unwrap();
+        let min_col = cells.iter().map(|&(_, col)| col).min().unwrap();
+        cells
+          .into_iter()
+          .map(|(row, col)| ((row - min_row) as usize, (col - min_col) as usize))
+          .sorted()
+          .collect_vec()
+      })
+      .unique()
+      .collect()
+  }
+}
+
+/// One piece of a tiling, covering the given `(row, col)` cells.
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+pub struct Placement {
+  pub piece: Pentomino,
+  pub cells: Vec<(usize, usize)>,
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+enum TileItem {
+  Cell(usize, usize),
+  Piece(Pentomino),
+}
+
+/// Every cell and every piece is used exactly once, so all items are
+/// primary. Each subset is one placement of one orientation of a piece.
+fn build_dlx(width: usize, height: usize, pieces: &[Pentomino]) -> Dlx<TileItem, Placement> {
+  let items = (0..height)
+    .cartesian_product(0..width)
+    .map(|(row, col)| TileItem::Cell(row, col))
+    .chain(pieces.iter().map(|&piece| TileItem::Piece(piece)))
+    .map(|item| (item, HeaderType::Primary));
+
+  let subsets = pieces.iter().flat_map(|&piece| {
+    piece.orientations().into_iter().flat_map(move |cells| {
+      let piece_height = cells.iter().map(|&(row, _)| row).max().unwrap() + 1;
+      let piece_width = cells.iter().map(|&(_, col)| col).max().unwrap() + 1;
+      (0..(height + 1).saturating_sub(piece_height))
+        .cartesian_product(0..(width + 1).saturating_sub(piece_width))
+        .map(move |(row, col)| {
+          let cells = cells.iter().map(|&(r, c)| (row + r, col + c)).collect_vec();
+          let constraints = cells
+            .iter()
+            .map(|&(r, c)| TileItem::Cell(r, c))
+            .chain(iter::once(TileItem::Piece(piece)))
+            .map(Constraint::Primary)
+            .collect_vec();
+          (Placement { piece, cells }, constraints)
+        })
+    })
+  });
+
+  Dlx::new(items, subsets)
+}
+
+/// Tiles a `width`x`height` rectangle using each of `pieces` exactly once.
+pub fn tile_with(width: usize, height: usize, pieces: &[Pentomino]) -> Option<Vec<Placement>> {
+  build_dlx(width, height, pieces).find_first_solution_names()
+}
+
+/// Counts the tilings of a `width`x`height` rectangle using each of `pieces`
+/// exactly once. Tilings which are rotations or reflections of each other are
+/// counted separately.
+pub fn count_tilings_with(width: usize, height: usize, pieces: &[Pentomino]) -> u64 {
+  build_dlx(width, height, pieces).count_solutions(usize::MAX) as u64
+}
+
+/// Tiles a `width`x`height` rectangle with all twelve pentominoes.
+pub fn tile(width: usize, height: usize) -> Option<Vec<Placement>> {
+  tile_with(width, height, &Pentomino::ALL)
+}
+
+/// Counts the tilings of a `width`x`height` rectangle with all twelve
+/// pentominoes, counting symmetric tilings separately. For rectangles, divide
+/// by 4 to count tilings up to rotation and reflection.
+pub fn count_tilings(width: usize, height: usize) -> u64 {
+  count_tilings_with(width, height, &Pentomino::ALL)
+}
+
+/// A tiling of a `width`x`height` rectangle, rendered with each cell showing
+/// the letter of the piece covering it.
+pub struct Tiling<'a> {
+  pub width: usize,
+  pub height: usize,
+  pub placements: &'a [Placement],
+}
+
+impl Display for Tiling<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let mut grid = vec![vec!['.'; self.width]; self.height];
+    for placement in self.placements {
+      for &(row, col) in &placement.cells {
+        grid[row][col] = placement.piece.letter();
+      }
+    }
+    for row in grid {
+      writeln!(f, "{}", row.into_iter().collect::<String>())?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use itertools::Itertools;
+
+  use super::{count_tilings, count_tilings_with, tile, tile_with, Pentomino, Tiling};
+
+  #[test]
+  fn test_orientations() {
+    let counts = Pentomino::ALL.map(|piece| piece.orientations().len());
+    assert_eq!(counts, [8, 2, 8, 8, 8, 4, 4, 4, 4, 1, 8, 4]);
+  }
+
+  #[test]
+  fn test_tile_subset() {
+    let pieces = [Pentomino::L, Pentomino::T, Pentomino::Y];
+    let placements = tile_with(5, 3, &pieces).unwrap();
+    assert_eq!(placements.len(), 3);
+    assert!(placements
+      .iter()
+      .flat_map(|placement| placement.cells.iter())
+      .all_unique());
+    assert_eq!(count_tilings_with(5, 3, &pieces), 4);
+  }
+
+  #[test]
+  fn test_untileable() {
+    let pieces = [Pentomino::I, Pentomino::X, Pentomino::Z];
+    assert_eq!(tile_with(5, 3, &pieces), None);
+    assert_eq!(count_tilings_with(5, 3, &pieces), 0);
+  }
+
+  #[test]
+  fn test_tile_3x20() {
+    let placements = tile(20, 3).unwrap();
+    let tiling = Tiling {
+      width: 20,
+      height: 3,
+      placements: &placements,
+    }
+    .to_string();
+    assert_eq!(tiling.lines().count(), 3);
+    assert!(!tiling.contains('.'));
+    assert_eq!(
+      tiling
+        .chars()
+        .filter(|c| c.is_alphabetic())
+        .unique()
+        .count(),
+      12
+    );
+  }
+
+  #[test]
+  fn test_count_tilings_3x20() {
+    assert_eq!(count_tilings(20, 3), 8);
+  }
+
+  #[test]
+  #[ignore]
+  fn test_count_tilings_6x10() {
+    assert_eq!(count_tilings(10, 6), 9356);
+  }
+}
@@ -0,0 +1,265 @@
+use std::io::{self, BufRead, Write};
+
+use itertools::Itertools;
+use p424::{
+  kakuro::{CombinationCache, Kakuro, LetterAssignment},
+  sudoku::Sudoku,
+};
+
+pub const USAGE: &str = "\
+Commands:
+  load kakuro <line>   Load a puzzle in the Project Euler 424 format
+  load sudoku <grid>   Load a grid of 81 characters, with . or 0 for blanks
+  show                 Print the puzzle, with any assumed letters filled in
+  candidates           Print the digits each blank tile could take
+  assume <letter>=<d>  Assume a kakuro letter stands for digit d
+  solve                Solve the puzzle under the assumptions made so far
+  hint                 Suggest the next step toward solving a sudoku
+  help                 Print this message
+  quit                 Leave the REPL";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+  LoadKakuro(String),
+  LoadSudoku(String),
+  Show,
+  Candidates,
+  Assume(char, u32),
+  Solve,
+  Hint,
+  Help,
+  Quit,
+}
+
+/// Parses one line of input, returning None for a blank line.
+pub fn parse_command(line: &str) -> Result<Option<Command>, String> {
+  let line = line.trim();
+  let (name, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+  let args = args.trim();
+  let no_args = |command| {
+    if args.is_empty() {
+      Ok(Some(command))
+    } else {
+      Err(format!("\"{name}\" takes no arguments"))
+    }
+  };
+
+  match name {
+    "" => Ok(None),
+    "load" => {
+      let (kind, puzzle) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+      let puzzle = puzzle.trim().to_string();
+      if puzzle.is_empty() {
+        return Err("Missing the puzzle to load".to_string());
+      }
+      match kind {
+        "kakuro" => Ok(Some(Command::LoadKakuro(puzzle))),
+        "sudoku" => Ok(Some(Command::LoadSudoku(puzzle))),
+        _ => Err(format!("Unknown puzzle type \"{kind}\"")),
+      }
+    }
+    "assume" => {
+      let invalid = || format!("Expected an assumption like A=3, found \"{args}\"");
+      let (letter, value) = args.split_once('=').ok_or_else(invalid)?;
+      let letter = letter
+        .trim()
+        .chars()
+        .exactly_one()
+        .map_err(|_| invalid())?
+        .to_ascii_uppercase();
+      let value = value.trim().parse().map_err(|_| invalid())?;
+      Ok(Some(Command::Assume(letter, value)))
+    }
+    "show" => no_args(Command::Show),
+    "candidates" => no_args(Command::Candidates),
+    "solve" => no_args(Command::Solve),
+    "hint" => no_args(Command::Hint),
+    "help" => no_args(Command::Help),
+    "quit" | "exit" => no_args(Command::Quit),
+    _ => Err(format!("Unknown command \"{name}\"")),
+  }
+}
+
+enum Puzzle {
+  Kakuro(Kakuro),
+  Sudoku(Sudoku),
+}
+
+/// The state kept between commands: the loaded puzzle, and the letters
+/// assumed so far for a kakuro.
+pub struct Repl {
+  puzzle: Option<Puzzle>,
+  letters: LetterAssignment,
+  cache: CombinationCache,
+}
+
+impl Repl {
+  pub fn new() -> Self {
+    Self {
+      puzzle: None,
+      letters: LetterAssignment::new(),
+      cache: CombinationCache::new(),
+    }
+  }
+
+  /// Reads and runs commands from `input` until it ends or a `quit` command,
+  /// writing their results to `out`. If `prompt` is set, a prompt is written
+  /// before each command.
+  pub fn run(&mut self, input: impl BufRead, mut out: impl Write, prompt: bool) -> io::Result<()> {
+    let mut lines = input.lines();
+    loop {
+      if prompt {
+        write!(out, "> ")?;
+        out.flush()?;
+      }
+      let Some(line) = lines.next() else {
+        return Ok(());
+      };
+      match parse_command(&line?) {
+        Ok(None) => {}
+        Ok(Some(Command::Quit)) => return Ok(()),
+        Ok(Some(command)) => writeln!(out, "{}", self.execute(command))?,
+        Err(message) => writeln!(out, "{message}\n{USAGE}")?,
+      }
+    }
+  }
+
+  /// Runs `command`, returning what to print.
+  fn execute(&mut self, command: Command) -> String {
+    match (command, &self.puzzle) {
+      (Command::Help, _) => USAGE.to_string(),
+      (Command::Quit, _) => String::new(),
+      (Command::LoadKakuro(line), _) => match line.parse::<Kakuro>() {
+        Ok(kakuro) => {
+          let message = format!("Loaded a {}x{} kakuro", kakuro.rows(), kakuro.cols());
+          self.load(Puzzle::Kakuro(kakuro));
+          message
+        }
+        Err(err) => format!("Invalid kakuro: {err}"),
+      },
+      (Command::LoadSudoku(grid), _) => match grid.parse::<Sudoku>() {
+        Ok(sudoku) => {
+          self.load(Puzzle::Sudoku(sudoku));
+          "Loaded a sudoku".to_string()
+        }
+        Err(err) => format!("Invalid sudoku: {err}"),
+      },
+      (_, None) => "No puzzle loaded, use \"load\" first".to_string(),
+
+      (Command::Show, Some(Puzzle::Kakuro(kakuro))) => kakuro.render_letters(&self.letters),
+      (Command::Candidates, Some(Puzzle::Kakuro(kakuro))) => kakuro
+        .candidates()
+        .into_iter()
+        .sorted_by_key(|&(coords, _)| coords)
+        .map(|((row, col), digits)| format!("({row}, {col}): {digits}"))
+        .join("\n"),
+      (Command::Assume(letter, value), Some(Puzzle::Kakuro(_))) => {
+        let pairs = self.letters.iter().chain([(letter, value)]).collect_vec();
+        match LetterAssignment::from_pairs(&pairs) {
+          Ok(letters) => {
+            self.letters = letters;
+            format!("Assuming {letter}={value}")
+          }
+          Err(err) => err.to_string(),
+        }
+      }
+      (Command::Solve, Some(Puzzle::Kakuro(kakuro))) => {
+        match kakuro.solve_full_with_partial(&self.letters, &mut self.cache) {
+          Ok(solutions) => match solutions.as_slice() {
+            [] => "No solution".to_string(),
+            [solution] => {
+              let value = match solution.letters().try_int_value() {
+                Ok(value) => value.to_string(),
+                Err(err) => err.to_string(),
+              };
+              format!("{}\nValue: {value}", kakuro.render_solution(solution))
+            }
+            [first, ..] => format!(
+              "{} solutions, the first of which is:\n{}",
+              solutions.len(),
+              kakuro.render_solution(first)
+            ),
+          },
+          Err(err) => err.to_string(),
+        }
+      }
+      (Command::Hint, Some(Puzzle::Kakuro(_))) => "Hints are only given for sudokus".to_string(),
+
+      (Command::Show, Some(Puzzle::Sudoku(sudoku))) => sudoku.to_string(),
+      (Command::Candidates, Some(Puzzle::Sudoku(sudoku))) => sudoku
+        .candidates()
+        .iter()
+        .enumerate()
+        .flat_map(|(row, candidates)| {
+          candidates
+            .iter()
+            .enumerate()
+            .filter(|(_, digits)| !digits.is_empty())
+            .map(move |(col, digits)| format!("({row}, {col}): {digits}"))
+        })
+        .join("\n"),
+      (Command::Assume(..), Some(Puzzle::Sudoku(_))) => {
+        "Sudokus have no letters to assume".to_string()
+      }
+      (Command::Solve, Some(Puzzle::Sudoku(sudoku))) => match sudoku.solved() {
+        Some(solved) => solved.to_string(),
+        None => "No solution".to_string(),
+      },
+      (Command::Hint, Some(Puzzle::Sudoku(sudoku))) => match sudoku.hint() {
+        Some(hint) => hint.to_string(),
+        None => "No hint: the grid is full or has no solution".to_string(),
+      },
+    }
+  }
+
+  fn load(&mut self, puzzle: Puzzle) {
+    self.puzzle = Some(puzzle);
+    self.letters = LetterAssignment::new();
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{parse_command, Command};
+
+  #[test]
+  fn test_parse_command() {
+    assert_eq!(parse_command("  "), Ok(None));
+    assert_eq!(parse_command("show"), Ok(Some(Command::Show)));
+    assert_eq!(parse_command(" solve "), Ok(Some(Command::Solve)));
+    assert_eq!(
+      parse_command("load kakuro 3,X,(vA),(vI)"),
+      Ok(Some(Command::LoadKakuro("3,X,(vA),(vI)".to_string())))
+    );
+    assert_eq!(
+      parse_command("load  sudoku  1.3"),
+      Ok(Some(Command::LoadSudoku("1.3".to_string())))
+    );
+    assert_eq!(
+      parse_command("assume A=3"),
+      Ok(Some(Command::Assume('A', 3)))
+    );
+    assert_eq!(
+      parse_command("assume b = 7"),
+      Ok(Some(Command::Assume('B', 7)))
+    );
+    assert_eq!(parse_command("exit"), Ok(Some(Command::Quit)));
+  }
+
+  #[test]
+  fn test_parse_command_errors() {
+    for line in [
+      "frobnicate",
+      "load",
+      "load kakuro",
+      "load nonogram 1,2",
+      "assume",
+      "assume A",
+      "assume AB=3",
+      "assume A=x",
+      "show me",
+    ] {
+      assert!(parse_command(line).is_err(), "{line}");
+    }
+  }
+}
@@ -1,151 +1,1385 @@
-use std::{collections::HashSet, fmt::Display};
+use std::{
+  error::Error,
+  fmt::Display,
+  fs::File,
+  io::{self, BufRead, BufReader},
+  iter,
+  ops::ControlFlow,
+  str::FromStr,
+};
 
-use crate::dlx::{Constraint, Dlx, HeaderType};
+use itertools::Itertools;
+use serde::Serialize;
 
+use crate::{
+  dlx::{ColorItem, Constraint, Dlx, HeaderType, Selection},
+  exact_cover::{build_dlx, solve_model_with, ExactCoverModel, SearchLimits},
+  kakuro::TotalClue,
+  util::DigitSet,
+};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseSudokuError {
+  /// The string didn't have exactly 81 characters.
+  WrongLength(usize),
+  /// The character at `pos` is neither a digit nor a blank.
+  InvalidChar { pos: usize, c: char },
+}
+
+impl Display for ParseSudokuError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      ParseSudokuError::WrongLength(len) => {
+        write!(f, "Expected 81 characters, found {len}")
+      }
+      ParseSudokuError::InvalidChar { pos, c } => {
+        write!(f, "Invalid character '{c}' at position {pos}")
+      }
+    }
+  }
+}
+
+impl Error for ParseSudokuError {}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SudokuError {
+  /// The given at (`row`, `col`) is not a digit from 1 to 9.
+  InvalidGiven { row: u32, col: u32, digit: u32 },
+  /// The given at (`row`, `col`) repeats a digit already given in the same
+  /// row, column, or box.
+  ConflictingGivens { row: u32, col: u32, digit: u32 },
+  /// The givens are consistent, but can't be completed to a full grid.
+  NoSolution,
+  /// The cell at (`row`, `col`) is in more than one killer cage.
+  OverlappingCages { row: u32, col: u32 },
+  /// The cell at (`row`, `col`) isn't in any killer cage.
+  UncagedCell { row: u32, col: u32 },
+  /// The killer cage sums add up to this instead of 405.
+  CageTotal(u32),
+  /// A killer cage contains (`row`, `col`), which is off the grid.
+  CageCellOffGrid { row: usize, col: usize },
+  /// Jigsaw region `region` has `size` cells instead of 9.
+  RegionSize { region: u8, size: usize },
+  /// The grid is `size`x`size`, where only 9x9 grids are supported.
+  GridSize(usize),
+}
+
+impl Display for SudokuError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      SudokuError::InvalidGiven { row, col, digit } => {
+        write!(f, "Invalid given {digit} at row {row}, column {col}")
+      }
+      SudokuError::ConflictingGivens { row, col, digit } => {
+        write!(
+          f,
+          "Given {digit} at row {row}, column {col} conflicts with another given"
+        )
+      }
+      SudokuError::NoSolution => write!(f, "No solution"),
+      SudokuError::OverlappingCages { row, col } => {
+        write!(f, "Row {row}, column {col} is in more than one cage")
+      }
+      SudokuError::UncagedCell { row, col } => {
+        write!(f, "Row {row}, column {col} isn't in any cage")
+      }
+      SudokuError::CageTotal(total) => {
+        write!(f, "Cage sums total {total}, expected 405")
+      }
+      SudokuError::CageCellOffGrid { row, col } => {
+        write!(f, "Cage cell at row {row}, column {col} is off the grid")
+      }
+      SudokuError::RegionSize { region, size } => {
+        write!(f, "Region {region} has {size} cells, expected 9")
+      }
+      SudokuError::GridSize(size) => {
+        write!(f, "Expected a 9x9 grid, found {size}x{size}")
+      }
+    }
+  }
+}
+
+impl Error for SudokuError {}
+
+/// The items of a sudoku's exact cover problem. The solver only works with
+/// their dense indices, see `Item::index`.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+enum Item {
+  Cell {
+    row: u32,
+    col: u32,
+  },
+  Row {
+    col: u32,
+    digit: u32,
+  },
+  Col {
+    row: u32,
+    digit: u32,
+  },
+  Box {
+    idx: u32,
+    digit: u32,
+  },
+  /// The main diagonal is `which` = 0, and the anti-diagonal is 1.
+  Diag {
+    which: u32,
+    digit: u32,
+  },
+  /// Killer cage `idx`, covered by a choice of digits for all its cells.
+  Cage {
+    idx: u32,
+  },
+  /// Secondary item colored with the digit in this cell, which ties the cage
+  /// choices to the cell choices.
+  CellDigit {
+    row: u32,
+    col: u32,
+  },
+}
+
+impl Item {
+  /// The index of this item in a grid `size` cells wide. Each kind of item
+  /// takes a contiguous range, and there are at most as many cages as cells.
+  fn index(&self, size: u32) -> u16 {
+    let cells = size * size;
+    let index = match *self {
+      Item::Cell { row, col } => row * size + col,
+      Item::Row { col, digit } => cells + col * size + digit - 1,
+      Item::Col { row, digit } => 2 * cells + row * size + digit - 1,
+      Item::Box { idx, digit } => 3 * cells + idx * size + digit - 1,
+      Item::Diag { which, digit } => 4 * cells + which * size + digit - 1,
+      Item::Cage { idx } => 4 * cells + 2 * size + idx,
+      Item::CellDigit { row, col } => 5 * cells + 2 * size + row * size + col,
+    };
+    u16::try_from(index)
+      .unwrap_or_else(|_| panic!("Item index {index} is too large for a {size}x{size} grid"))
+  }
+
+  /// The item with `index` in a grid `size` cells wide.
+  #[cfg(test)]
+  fn from_index(index: u16, size: u32) -> Self {
+    let cells = size * size;
+    let index = index as u32;
+    if index < 4 * cells {
+      let (a, b) = ((index % cells) / size, index % size);
+      match index / cells {
+        0 => Item::Cell { row: a, col: b },
+        1 => Item::Row {
+          col: a,
+          digit: b + 1,
+        },
+        2 => Item::Col {
+          row: a,
+          digit: b + 1,
+        },
+        _ => Item::Box {
+          idx: a,
+          digit: b + 1,
+        },
+      }
+    } else if index < 4 * cells + 2 * size {
+      let index = index - 4 * cells;
+      Item::Diag {
+        which: index / size,
+        digit: index % size + 1,
+      }
+    } else if index < 5 * cells + 2 * size {
+      Item::Cage {
+        idx: index - 4 * cells - 2 * size,
+      }
+    } else {
+      let index = index - 5 * cells - 2 * size;
+      Item::CellDigit {
+        row: index / size,
+        col: index % size,
+      }
+    }
+  }
+}
+
+/// Writing `digit` into the cell at (`row`, `col`).
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub struct Placement {
+  pub digit: u32,
+  pub row: u32,
+  pub col: u32,
+}
+
+/// A row, column, or region of a sudoku, which must hold every digit once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Unit {
+  Row(usize),
+  Col(usize),
+  Region(u32),
+}
+
+/// A next step toward solving a sudoku, along with the reason for it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Hint {
+  /// `digit` is the only candidate left in this cell.
+  NakedSingle { row: usize, col: usize, digit: u32 },
+  /// `cell` is the only place left in `unit` for `digit`.
+  HiddenSingle {
+    unit: Unit,
+    digit: u32,
+    cell: (usize, usize),
+  },
+  /// `cells` are the only two candidates for `digits` in `unit`, so those
+  /// digits can be removed from the other cells in `eliminations`.
+  NakedPair {
+    unit: Unit,
+    cells: [(usize, usize); 2],
+    digits: [u32; 2],
+    eliminations: Vec<(usize, usize, u32)>,
+  },
+  /// No logical step was found, so this placement is taken from a solution.
+  Guess(Placement),
+}
+
+impl Display for Unit {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Unit::Row(row) => write!(f, "row {row}"),
+      Unit::Col(col) => write!(f, "column {col}"),
+      Unit::Region(region) => write!(f, "region {region}"),
+    }
+  }
+}
+
+impl Display for Hint {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Hint::NakedSingle { row, col, digit } => {
+        write!(f, "{digit} is the only candidate at ({row}, {col})")
+      }
+      Hint::HiddenSingle {
+        unit,
+        digit,
+        cell: (row, col),
+      } => write!(f, "({row}, {col}) is the only place for {digit} in {unit}"),
+      Hint::NakedPair {
+        unit,
+        cells: [(row1, col1), (row2, col2)],
+        digits: [digit1, digit2],
+        eliminations,
+      } => write!(
+        f,
+        "({row1}, {col1}) and ({row2}, {col2}) must hold {digit1} and {digit2} in {unit}, \
+         removing {} candidates",
+        eliminations.len()
+      ),
+      Hint::Guess(Placement { digit, row, col }) => {
+        write!(f, "Guess {digit} at ({row}, {col})")
+      }
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Difficulty {
+  /// Solvable with singles alone.
+  Easy,
+  /// Needs naked pairs, but no guessing.
+  Medium,
+  /// Needs a few guesses.
+  Hard,
+  Extreme,
+}
+
+/// How many steps of each technique `Sudoku::difficulty` used.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DifficultyReport {
+  pub naked_singles: u32,
+  pub hidden_singles: u32,
+  pub naked_pairs: u32,
+  pub guesses: u32,
+  /// Whether the grid was filled, which it won't be if it has no solution.
+  pub solved: bool,
+}
+
+impl DifficultyReport {
+  /// The most guesses a `Hard` puzzle may need.
+  const HARD_GUESSES: u32 = 3;
+
+  pub fn rating(&self) -> Difficulty {
+    match self.guesses {
+      0 if self.naked_pairs == 0 => Difficulty::Easy,
+      0 => Difficulty::Medium,
+      1..=Self::HARD_GUESSES => Difficulty::Hard,
+      _ => Difficulty::Extreme,
+    }
+  }
+}
+
+/// Measurements of one solve, from `Sudoku::solve_instrumented`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct SudokuSolveStats {
+  /// The number of cells filled in by the puzzle.
+  pub givens: usize,
+  /// The placements left after the givens, which became subsets in the DLX
+  /// model.
+  pub choices: usize,
+  /// The subsets tried by the search.
+  pub nodes: u64,
+  /// The times the search chose a constraint no remaining placement could
+  /// satisfy, and so had to undo a guess.
+  pub backtracks: u64,
+  /// The solutions found by the search, which stops at the second, so 2 means
+  /// the puzzle isn't proper.
+  pub solutions: u64,
+}
+
+impl Display for SudokuSolveStats {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{} givens, {} choices, {} nodes, {} backtracks, {} solutions",
+      self.givens, self.choices, self.nodes, self.backtracks, self.solutions
+    )
+  }
+}
+
+/// Which rules a sudoku is played by, on top of the usual rows, columns, and
+/// boxes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SudokuVariant {
+  #[default]
+  Plain,
+  /// Sudoku X: both main diagonals must also contain every digit once.
+  Diagonal,
+}
+
+/// The character for `digit`: `1`-`9`, then `A`-`Z` for 10-35, so `A`-`G` in
+/// a 16x16 grid. Digits with no character are shown as `?`.
+fn digit_char(digit: u32) -> char {
+  match digit {
+    1..=35 => char::from_digit(digit, 36).unwrap().to_ascii_uppercase(),
+    _ => '?',
+  }
+}
+
+/// The items and named subsets of a sudoku's exact cover problem.
+type ExactCover = (Vec<u16>, Vec<(Placement, Vec<u16>)>);
+
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+enum KillerChoice {
+  Cell(Placement),
+  /// Placing `digits` in the cells of cage `idx`, in order.
+  Cage {
+    idx: u32,
+    digits: Vec<u32>,
+  },
+}
+
+/// The widest boxes a sudoku may have, so that its digits fit in a
+/// `DigitSet`.
+pub const MAX_BOX_SIZE: usize = 4;
+
+/// A sudoku with boxes of `box_size` x `box_size` cells, and a grid
+/// `box_size`² cells wide filled with digits from 1 to `box_size`². Blank
+/// cells are 0.
+#[derive(Clone)]
 pub struct Sudoku {
-  grid: [[u32; 9]; 9],
+  box_size: usize,
+  grid: Vec<Vec<u32>>,
+  /// Which cells were filled in when the puzzle was created.
+  given: Vec<Vec<bool>>,
+  /// The region each cell belongs to, which must hold every digit once.
+  /// These are the boxes, unless this is a jigsaw sudoku.
+  regions: Vec<Vec<u32>>,
+  variant: SudokuVariant,
 }
 
 impl Sudoku {
+  /// A standard 9x9 sudoku.
   pub fn new(grid: [[u32; 9]; 9]) -> Self {
-    Self { grid }
-  }
-
-  pub fn solve(&mut self) -> bool {
-    #[derive(PartialEq, Eq, Hash, Clone, Debug)]
-    enum Item {
-      Cell { row: u32, col: u32 },
-      Row { col: u32, digit: u32 },
-      Col { row: u32, digit: u32 },
-      Box { idx: u32, digit: u32 },
-    }
-
-    #[derive(PartialEq, Eq, Hash, Clone, Debug)]
-    struct Choice {
-      digit: u32,
-      row: u32,
-      col: u32,
-    }
-
-    let mut items: HashSet<Item> = (0..81)
-      .flat_map(|i| {
-        let row = i % 9;
-        let col = i / 9;
-        [
-          Item::Cell { row, col },
-          Item::Row {
-            col,
-            digit: row + 1,
-          },
-          Item::Col {
-            row,
-            digit: col + 1,
-          },
-          Item::Box {
-            idx: row,
-            digit: col + 1,
-          },
-        ]
-        .into_iter()
+    Self::with_box_size(3, grid.iter().map(|row| row.to_vec()).collect())
+  }
+
+  /// Panics if `box_size` is larger than `MAX_BOX_SIZE`, or if `grid` isn't
+  /// `box_size`² rows of `box_size`² cells.
+  pub fn with_box_size(box_size: usize, grid: Vec<Vec<u32>>) -> Self {
+    assert!(
+      box_size <= MAX_BOX_SIZE,
+      "Boxes can be at most {MAX_BOX_SIZE} cells wide, got {box_size}"
+    );
+    let size = box_size * box_size;
+    assert!(
+      grid.len() == size && grid.iter().all(|row| row.len() == size),
+      "Expected a {size}x{size} grid"
+    );
+    let regions = (0..size)
+      .map(|row| {
+        (0..size)
+          .map(|col| ((row / box_size) * box_size + col / box_size) as u32)
+          .collect()
       })
       .collect();
+    let given = grid
+      .iter()
+      .map(|row| row.iter().map(|&digit| digit != 0).collect())
+      .collect();
+    Self {
+      box_size,
+      grid,
+      given,
+      regions,
+      variant: SudokuVariant::default(),
+    }
+  }
+
+  /// A 9x9 jigsaw sudoku, where `regions` assigns each cell to one of nine
+  /// regions 0-8 of nine cells each, which replace the boxes.
+  pub fn with_regions(grid: [[u32; 9]; 9], regions: [[u8; 9]; 9]) -> Result<Self, SudokuError> {
+    let counts = regions.iter().flatten().counts();
+    if let Some(region) = (0..9).find(|region| counts.get(region) != Some(&9)) {
+      return Err(SudokuError::RegionSize {
+        region,
+        size: counts.get(&region).copied().unwrap_or(0),
+      });
+    }
+
+    let mut sudoku = Self::new(grid);
+    sudoku.regions = regions
+      .iter()
+      .map(|row| row.iter().map(|&region| region as u32).collect())
+      .collect();
+    Ok(sudoku)
+  }
+
+  pub fn set_variant(&mut self, variant: SudokuVariant) {
+    self.variant = variant;
+  }
+
+  /// The number of rows and columns in the grid.
+  pub fn size(&self) -> usize {
+    self.grid.len()
+  }
 
-    let valid = self
+  /// The digit in the cell at (`row`, `col`), or 0 if it's blank.
+  pub fn digit(&self, row: usize, col: usize) -> u32 {
+    self.grid[row][col]
+  }
+
+  /// Whether the cell at (`row`, `col`) was filled in when the puzzle was
+  /// created, rather than by solving.
+  pub fn is_given(&self, row: usize, col: usize) -> bool {
+    self.given[row][col]
+  }
+
+  /// The `(row, col, digit)` of each given.
+  pub fn givens(&self) -> impl Iterator<Item = (usize, usize, u32)> + '_ {
+    self.grid.iter().enumerate().flat_map(move |(row, digits)| {
+      digits
+        .iter()
+        .enumerate()
+        .filter(move |&(col, _)| self.is_given(row, col))
+        .map(move |(col, &digit)| (row, col, digit))
+    })
+  }
+
+  /// Reads every grid from a file in the format of Project Euler problem 96,
+  /// see `from_p096_reader`.
+  pub fn from_p096_file(path: &str) -> io::Result<Vec<(String, Sudoku)>> {
+    Self::from_p096_reader(BufReader::new(File::open(path)?))
+  }
+
+  /// Reads grids written as a `Grid NN` header line followed by nine rows of
+  /// nine digits, with 0 for blanks. Returns each grid along with its header.
+  pub fn from_p096_reader<R: BufRead>(f: R) -> io::Result<Vec<(String, Sudoku)>> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+    let mut puzzles = Vec::new();
+    let mut lines = f.lines();
+    while let Some(header) = lines.next() {
+      let header = header?.trim().to_string();
+      if header.is_empty() {
+        continue;
+      }
+      if !header.starts_with("Grid") {
+        return Err(invalid(format!(
+          "Expected a grid header, found \"{header}\""
+        )));
+      }
+
+      let mut grid = [[0; 9]; 9];
+      for (row, digits) in grid.iter_mut().enumerate() {
+        let line = lines
+          .next()
+          .transpose()?
+          .ok_or_else(|| invalid(format!("{header}: Missing row {}", row + 1)))?;
+        let line = line.trim_end();
+        let parsed = line
+          .chars()
+          .map(|c| c.to_digit(10))
+          .collect::<Option<Vec<_>>>()
+          .filter(|parsed| parsed.len() == 9)
+          .ok_or_else(|| invalid(format!("{header}, row {}: Invalid row \"{line}\"", row + 1)))?;
+        digits.copy_from_slice(&parsed);
+      }
+      puzzles.push((header, Sudoku::new(grid)));
+    }
+    Ok(puzzles)
+  }
+
+  /// Writes the grid as 81 characters in row-major order, with `.` for blank
+  /// cells. This is the format read by `from_str`.
+  pub fn to_line(&self) -> String {
+    self
       .grid
+      .iter()
+      .flatten()
+      .map(|&digit| if digit == 0 { '.' } else { digit_char(digit) })
+      .collect()
+  }
+
+  /// The lexicographically smallest grid, read row by row with blanks as 0,
+  /// that this grid can be turned into by relabeling the digits, reordering
+  /// the bands and stacks, reordering the rows within each band and columns
+  /// within each stack, and transposing. Isomorphic grids have the same
+  /// canonical form. The variant and regions are ignored. Panics if the grid
+  /// isn't 9x9.
+  pub fn canonical_form(&self) -> [u8; 81] {
+    assert_eq!(self.size(), 9, "Expected a 9x9 grid");
+    let grid: [[u8; 9]; 9] =
+      std::array::from_fn(|row| std::array::from_fn(|col| self.grid[row][col] as u8));
+    let transposed = std::array::from_fn(|row| std::array::from_fn(|col| grid[col][row]));
+
+    let mut best = [u8::MAX; 81];
+    let mut candidate = [0; 81];
+    for grid in [grid, transposed] {
+      for cols in line_permutations() {
+        let grid = grid.map(|digits| cols.map(|col| digits[col]));
+        smallest_row_order(&grid, 0, 0, [0; 10], &mut candidate, &mut best, true);
+      }
+    }
+    best
+  }
+
+  /// Whether `other` is this grid with its digits relabeled, lines reordered
+  /// or transposed, see `canonical_form`.
+  pub fn is_isomorphic_to(&self, other: &Sudoku) -> bool {
+    self.size() == 9 && other.size() == 9 && self.canonical_form() == other.canonical_form()
+  }
+
+  #[deprecated(note = "use `solve`, which reports why a puzzle has no solution")]
+  pub fn try_solve(&mut self) -> bool {
+    self.solve().is_ok()
+  }
+
+  /// Counts the solutions of this puzzle, stopping once `limit` have been
+  /// found. Invalid or conflicting givens have no solutions.
+  pub fn count_solutions(&self, limit: u64) -> u64 {
+    // Empty grids of size 9x9 and up have far more than `u64::MAX` solutions.
+    if self.box_size >= 3 && self.grid.iter().flatten().all(|&digit| digit == 0) {
+      return limit;
+    }
+
+    match self.model() {
+      Ok(model) => build_dlx(&model).count_solutions(limit as usize) as u64,
+      Err(_) => 0,
+    }
+  }
+
+  /// Whether this puzzle has exactly one solution.
+  pub fn is_proper(&self) -> bool {
+    self.count_solutions(2) == 1
+  }
+
+  /// Every completed grid, givens included, in a deterministic order. Yields
+  /// nothing if the givens are invalid. Each grid is only searched for once
+  /// the one before it has been taken.
+  pub fn solutions(&self) -> impl Iterator<Item = Vec<Vec<u32>>> {
+    let grid = self.grid.clone();
+    self
+      .model()
+      .ok()
+      .map(|model| build_dlx(&model).into_solution_names())
+      .into_iter()
+      .flatten()
+      .map(move |choices| {
+        choices.into_iter().fold(grid.clone(), |mut grid, choice| {
+          grid[choice.row as usize][choice.col as usize] = choice.digit;
+          grid
+        })
+      })
+  }
+
+  /// Calls `f` with each completed 9x9 grid, givens included, as soon as the
+  /// search finds it. The grid passed to `f` is reused between solutions, and
+  /// the search stops as soon as `f` breaks.
+  pub fn for_each_solution(
+    &self,
+    mut f: impl FnMut(&[[u32; 9]; 9]) -> ControlFlow<()>,
+  ) -> Result<(), SudokuError> {
+    if self.grid.len() != 9 {
+      return Err(SudokuError::GridSize(self.grid.len()));
+    }
+    let model = self.model()?;
+
+    let mut grid = [[0; 9]; 9];
+    for (row, digits) in grid.iter_mut().zip(&self.grid) {
+      row.copy_from_slice(digits);
+    }
+    let _ = build_dlx(&model).visit_solutions(|placements| {
+      for placement in placements {
+        grid[placement.row as usize][placement.col as usize] = placement.digit;
+      }
+      let flow = f(&grid);
+      for placement in placements {
+        grid[placement.row as usize][placement.col as usize] = 0;
+      }
+      flow
+    });
+    Ok(())
+  }
+
+  /// A solved copy of this puzzle, which remembers the same givens.
+  pub fn solved(&self) -> Option<Sudoku> {
+    let mut solved = self.clone();
+    solved.solve().ok().map(|()| solved)
+  }
+
+  /// Fills in the grid with a solution, the first one found if there are
+  /// several.
+  pub fn solve(&mut self) -> Result<(), SudokuError> {
+    *self = self.solve_instrumented()?.0;
+    Ok(())
+  }
+
+  /// Like `solve`, but returns a solved copy of the puzzle along with how
+  /// much work the search took. A puzzle with several solutions is solved to
+  /// the first one found.
+  pub fn solve_instrumented(&self) -> Result<(Sudoku, SudokuSolveStats), SudokuError> {
+    let model = self.model()?;
+    // Looking for a second solution shows whether the puzzle is proper,
+    // without searching every completion of grids with few givens.
+    let limits = SearchLimits {
+      solutions: Some(2),
+      ..SearchLimits::default()
+    };
+    let solved = solve_model_with(&model, limits);
+    let GridModel((_, choices)) = &model;
+    let stats = SudokuSolveStats {
+      givens: self.givens().count(),
+      choices: choices.len(),
+      nodes: solved.stats.nodes,
+      backtracks: solved.stats.dead_ends.values().sum(),
+      solutions: solved.stats.solutions,
+    };
+
+    let placements = solved
+      .solutions
+      .into_iter()
+      .next()
+      .ok_or(SudokuError::NoSolution)?;
+    let mut sudoku = self.clone();
+    for placement in placements {
+      sudoku.apply(placement);
+    }
+    Ok((sudoku, stats))
+  }
+
+  /// The placements which fill in the first solution found, in the order the
+  /// search committed to them. Backtracked attempts aren't included. Returns
+  /// None if the puzzle can't be solved.
+  pub fn solve_steps(&self) -> Option<impl Iterator<Item = Placement>> {
+    solve_model_with(&self.model().ok()?, SearchLimits::first())
+      .solutions
+      .pop()
+      .map(Vec::into_iter)
+  }
+
+  pub fn apply(&mut self, placement: Placement) {
+    self.grid[placement.row as usize][placement.col as usize] = placement.digit;
+  }
+
+  /// The pencil marks for every cell: the digits which don't repeat a
+  /// filled-in digit in any row, column, or region the cell is in. Filled
+  /// cells have no candidates.
+  pub fn candidates(&self) -> Vec<Vec<DigitSet>> {
+    let used = self.used_items();
+    (0..self.grid.len())
+      .map(|row| {
+        (0..self.grid.len())
+          .map(|col| self.cell_candidates(&used, row, col))
+          .collect()
+      })
+      .collect()
+  }
+
+  pub fn candidate_count(&self, row: usize, col: usize) -> u32 {
+    self.cell_candidates(&self.used_items(), row, col).len()
+  }
+
+  /// Whether the cell at (`row`, `col`) is blank and has only one candidate.
+  pub fn is_naked_single(&self, row: usize, col: usize) -> bool {
+    self.candidate_count(row, col) == 1
+  }
+
+  /// Finds the simplest next step: a naked single, then a hidden single, then
+  /// a naked pair which eliminates some candidates. If none of those apply,
+  /// falls back to a placement from a solution found by search. Returns None
+  /// if the grid is full or can't be solved.
+  pub fn hint(&self) -> Option<Hint> {
+    self.hint_with_eliminations(&vec![
+      vec![DigitSet::new(); self.grid.len()];
+      self.grid.len()
+    ])
+  }
+
+  /// Like `hint`, but with the candidates in `eliminated` removed, as if
+  /// earlier naked pairs had been applied.
+  fn hint_with_eliminations(&self, eliminated: &[Vec<DigitSet>]) -> Option<Hint> {
+    let mut candidates = self.candidates();
+    for (row, eliminated) in candidates.iter_mut().zip(eliminated) {
+      for (digits, eliminated) in row.iter_mut().zip(eliminated) {
+        *digits = digits.difference(*eliminated);
+      }
+    }
+
+    if let Some((row, col)) = (0..self.grid.len())
+      .cartesian_product(0..self.grid.len())
+      .find(|&(row, col)| candidates[row][col].len() == 1)
+    {
+      let digit = candidates[row][col].iter().next().unwrap();
+      return Some(Hint::NakedSingle { row, col, digit });
+    }
+
+    let units = self.units();
+    for (unit, cells) in &units {
+      for digit in 1..=self.grid.len() as u32 {
+        if let Ok(&cell) = cells
+          .iter()
+          .filter(|&&(row, col)| candidates[row][col].contains(digit))
+          .exactly_one()
+        {
+          return Some(Hint::HiddenSingle {
+            unit: *unit,
+            digit,
+            cell,
+          });
+        }
+      }
+    }
+
+    for (unit, cells) in &units {
+      for (&a, &b) in cells.iter().tuple_combinations() {
+        let pair = candidates[a.0][a.1];
+        if pair.len() != 2 || candidates[b.0][b.1] != pair {
+          continue;
+        }
+        let eliminations = cells
+          .iter()
+          .filter(|&&cell| cell != a && cell != b)
+          .flat_map(|&(row, col)| {
+            candidates[row][col]
+              .intersection(pair)
+              .iter()
+              .map(move |digit| (row, col, digit))
+              .collect_vec()
+          })
+          .collect_vec();
+        if !eliminations.is_empty() {
+          let (first, second) = pair.iter().collect_tuple().unwrap();
+          return Some(Hint::NakedPair {
+            unit: *unit,
+            cells: [a, b],
+            digits: [first, second],
+            eliminations,
+          });
+        }
+      }
+    }
+
+    self.solve_steps()?.next().map(Hint::Guess)
+  }
+
+  /// Solves a copy of the puzzle by repeatedly applying `hint`, and counts
+  /// the steps taken with each technique.
+  pub fn difficulty(&self) -> DifficultyReport {
+    let mut sudoku = self.clone();
+    let mut eliminated = vec![vec![DigitSet::new(); self.grid.len()]; self.grid.len()];
+    let mut report = DifficultyReport::default();
+
+    while let Some(hint) = sudoku.hint_with_eliminations(&eliminated) {
+      match hint {
+        Hint::NakedSingle { row, col, digit } => {
+          report.naked_singles += 1;
+          sudoku.grid[row][col] = digit;
+        }
+        Hint::HiddenSingle {
+          digit,
+          cell: (row, col),
+          ..
+        } => {
+          report.hidden_singles += 1;
+          sudoku.grid[row][col] = digit;
+        }
+        Hint::NakedPair { eliminations, .. } => {
+          report.naked_pairs += 1;
+          for (row, col, digit) in eliminations {
+            eliminated[row][col].insert(digit);
+          }
+        }
+        Hint::Guess(placement) => {
+          report.guesses += 1;
+          sudoku.apply(placement);
+        }
+      }
+    }
+
+    report.solved = sudoku.grid.iter().flatten().all(|&digit| digit != 0);
+    report
+  }
+
+  /// Every row, then every column, then every region, with their cells.
+  fn units(&self) -> Vec<(Unit, Vec<(usize, usize)>)> {
+    let size = self.grid.len();
+    let rows = (0..size).map(|row| (Unit::Row(row), (0..size).map(|col| (row, col)).collect()));
+    let cols = (0..size).map(|col| (Unit::Col(col), (0..size).map(|row| (row, col)).collect()));
+    let regions = (0..size)
+      .cartesian_product(0..size)
+      .into_group_map_by(|&(row, col)| self.regions[row][col])
+      .into_iter()
+      .sorted()
+      .map(|(region, cells)| (Unit::Region(region), cells));
+    rows.chain(cols).chain(regions).collect()
+  }
+
+  /// Which items are covered by the filled-in cells, by index.
+  fn used_items(&self) -> Vec<bool> {
+    let size = self.grid.len() as u32;
+    let mut used = vec![false; (4 * size * size + 2 * size) as usize];
+    for (row, digits) in self.grid.iter().enumerate() {
+      for (col, &digit) in digits.iter().enumerate() {
+        if (1..=size).contains(&digit) {
+          for item in self.digit_items(row as u32, col as u32, digit) {
+            used[item as usize] = true;
+          }
+        }
+      }
+    }
+    used
+  }
+
+  fn cell_candidates(&self, used: &[bool], row: usize, col: usize) -> DigitSet {
+    if self.grid[row][col] != 0 {
+      return DigitSet::new();
+    }
+    (1..=self.grid.len() as u32)
+      .filter(|&digit| {
+        self
+          .digit_items(row as u32, col as u32, digit)
+          .all(|item| !used[item as usize])
+      })
+      .collect()
+  }
+
+  /// The indices of the items besides the cell itself that placing `digit`
+  /// at (`row`, `col`) covers.
+  fn digit_items(&self, row: u32, col: u32, digit: u32) -> impl Iterator<Item = u16> {
+    let size = self.grid.len() as u32;
+    let idx = self.regions[row as usize][col as usize];
+    let diagonal = self.variant == SudokuVariant::Diagonal;
+
+    [
+      Some(Item::Row { col, digit }),
+      Some(Item::Col { row, digit }),
+      Some(Item::Box { idx, digit }),
+      (diagonal && row == col).then_some(Item::Diag { which: 0, digit }),
+      (diagonal && row + col == size - 1).then_some(Item::Diag { which: 1, digit }),
+    ]
+    .into_iter()
+    .flatten()
+    .map(move |item| item.index(size))
+  }
+
+  /// The exact cover problem for the blank cells, after checking the givens.
+  fn model(&self) -> Result<GridModel, SudokuError> {
+    self.exact_cover().map(GridModel)
+  }
+
+  fn exact_cover(&self) -> Result<ExactCover, SudokuError> {
+    let size = self.grid.len() as u32;
+    let cells = size * size;
+
+    // Every cell must be filled, and every row, column and region needs each
+    // digit. So do the diagonals in sudoku X.
+    let mut needed = vec![true; (4 * cells) as usize];
+    needed.resize(
+      (4 * cells + 2 * size) as usize,
+      self.variant == SudokuVariant::Diagonal,
+    );
+
+    grid_exact_cover(&self.grid, needed, |row, col, digit| {
+      self.digit_items(row, col, digit)
+    })
+  }
+}
+
+/// The 1296 orders of the columns of a 9x9 sudoku which keep every box
+/// intact: any order of the stacks, and any order of the columns within each
+/// stack.
+fn line_permutations() -> Vec<[usize; 9]> {
+  let orders = (0..3).permutations(3).collect_vec();
+  orders
+    .iter()
+    .cartesian_product(
+      orders
+        .iter()
+        .cartesian_product(&orders)
+        .cartesian_product(&orders),
+    )
+    .map(|(stacks, ((first, second), third))| {
+      let within = [first, second, third];
+      std::array::from_fn(|line| 3 * stacks[line / 3] + within[line / 3][line % 3])
+    })
+    .collect()
+}
+
+/// Searches the orders of the rows of `grid` which keep every box intact,
+/// having already placed the rows in `used` into the first `slot` rows of
+/// `candidate`, for one which relabels to a smaller grid than `best`. Digits
+/// are relabeled in the order they're first seen, with `labels` holding the
+/// labels handed out so far. `tied` is whether `candidate` so far matches
+/// `best`, rather than being smaller. Returns whether `best` was replaced.
+fn smallest_row_order(
+  grid: &[[u8; 9]; 9],
+  slot: usize,
+  used: u16,
+  labels: [u8; 10],
+  candidate: &mut [u8; 81],
+  best: &mut [u8; 81],
+  mut tied: bool,
+) -> bool {
+  let band_used = |band: usize| (used >> (3 * band) & 0b111).count_ones();
+  // A band must be finished before the next one is started.
+  let band = (0..3).find(|&band| matches!(band_used(band), 1 | 2));
+  let rows = (0..9).filter(|&row| match band {
+    Some(band) => row / 3 == band && used & 1 << row == 0,
+    None => band_used(row / 3) == 0,
+  });
+
+  let mut replaced = false;
+  for row in rows {
+    let mut labels = labels;
+    let mut next = labels.iter().max().unwrap() + 1;
+    let start = 9 * slot;
+    for (col, &digit) in grid[row].iter().enumerate() {
+      let digit = digit as usize;
+      if digit != 0 && labels[digit] == 0 {
+        labels[digit] = next;
+        next += 1;
+      }
+      candidate[start + col] = labels[digit];
+    }
+
+    let order = if tied {
+      candidate[start..start + 9].cmp(&best[start..start + 9])
+    } else {
+      std::cmp::Ordering::Less
+    };
+    if order.is_gt() {
+      continue;
+    }
+    if slot == 8 {
+      if order.is_lt() {
+        *best = *candidate;
+        replaced = true;
+        tied = true;
+      }
+    } else if smallest_row_order(
+      grid,
+      slot + 1,
+      used | 1 << row,
+      labels,
+      candidate,
+      best,
+      order.is_eq(),
+    ) {
+      replaced = true;
+      tied = true;
+    }
+  }
+  replaced
+}
+
+/// The primary items left after placing the givens in `grid`, and the
+/// choices of digits for each blank cell along with the items they cover.
+/// `needed` marks which items must be covered at all, and `digit_items`
+/// gives the items besides the cell that a digit covers.
+fn grid_exact_cover<F, I>(
+  grid: &[Vec<u32>],
+  mut needed: Vec<bool>,
+  digit_items: F,
+) -> Result<ExactCover, SudokuError>
+where
+  F: Fn(u32, u32, u32) -> I,
+  I: Iterator<Item = u16>,
+{
+  let size = grid.len() as u32;
+
+  grid.iter().enumerate().try_fold((), |_, (row, digits)| {
+    let row = row as u32;
+    digits
       .iter()
       .enumerate()
-      .fold(true, |valid, (row, digits)| {
-        let row = row as u32;
-        valid
-          && digits
-            .iter()
-            .enumerate()
-            .filter(|(_, digit)| **digit != 0)
-            .fold(true, |valid, (col, digit)| {
-              let col = col as u32;
-              let digit = *digit;
-              let idx = (row / 3) * 3 + col / 3;
-
-              valid
-                && (1..=9).contains(&digit)
-                && items.remove(&Item::Cell { row, col })
-                && items.remove(&Item::Row { col, digit })
-                && items.remove(&Item::Col { row, digit })
-                && items.remove(&Item::Box { idx, digit })
-            })
+      .filter(|(_, digit)| **digit != 0)
+      .try_fold((), |_, (col, digit)| {
+        let col = col as u32;
+        let digit = *digit;
+
+        if !(1..=size).contains(&digit) {
+          return Err(SudokuError::InvalidGiven { row, col, digit });
+        }
+        if iter::once(Item::Cell { row, col }.index(size))
+          .chain(digit_items(row, col, digit))
+          .all(|item| std::mem::replace(&mut needed[item as usize], false))
+        {
+          Ok(())
+        } else {
+          Err(SudokuError::ConflictingGivens { row, col, digit })
+        }
+      })
+  })?;
+
+  let needed_ref = &needed;
+  let digit_items = &digit_items;
+
+  // Enumerate all legal choices.
+  let choices = grid
+    .iter()
+    .enumerate()
+    .flat_map(|(row, digits)| {
+      let row = row as u32;
+      digits
+        .iter()
+        .enumerate()
+        .filter(|(_, digit)| **digit == 0)
+        .flat_map(move |(col, _)| {
+          let col = col as u32;
+
+          (1..=size).filter_map(move |digit| {
+            let choices = digit_items(row, col, digit)
+              .chain(iter::once(Item::Cell { row, col }.index(size)))
+              .collect_vec();
+            choices
+              .iter()
+              .all(|&item| needed_ref[item as usize])
+              .then_some((Placement { digit, row, col }, choices))
+          })
+        })
+    })
+    .collect();
+
+  // In increasing order, so the search doesn't depend on anything but the
+  // puzzle.
+  let items = (0..needed.len() as u16)
+    .filter(|&item| needed[item as usize])
+    .collect();
+  Ok((items, choices))
+}
+
+/// The model of a grid's exact cover problem, all of whose items are primary.
+/// Each solution is the placements filling in the blank cells, in the order
+/// the search committed to them.
+struct GridModel(ExactCover);
+
+impl ExactCoverModel for GridModel {
+  type Item = u16;
+  type Choice = Placement;
+  type Solution = Vec<Placement>;
+
+  fn items(&self) -> impl Iterator<Item = (u16, HeaderType)> + '_ {
+    let (items, _) = &self.0;
+    items.iter().map(|&item| (item, HeaderType::Primary))
+  }
+
+  fn choices(
+    &self,
+  ) -> impl Iterator<Item = (Placement, impl IntoIterator<Item = Constraint<u16>>)> + '_ {
+    let (_, choices) = &self.0;
+    choices
+      .iter()
+      .map(|(placement, items)| (*placement, items.iter().copied().map(Constraint::Primary)))
+  }
+
+  fn decode(&self, selection: Selection<u16, Placement>) -> Vec<Placement> {
+    selection.names
+  }
+}
+
+/// A 9x9 sudoku where the cells are also split into cages, each of which
+/// must hold distinct digits adding up to the cage's sum.
+pub struct KillerSudoku {
+  sudoku: Sudoku,
+  cages: Vec<(u32, Vec<(usize, usize)>)>,
+}
+
+impl KillerSudoku {
+  pub fn new(grid: [[u32; 9]; 9], cages: Vec<(u32, Vec<(usize, usize)>)>) -> Self {
+    Self {
+      sudoku: Sudoku::new(grid),
+      cages,
+    }
+  }
+
+  /// Checks that every cell is on the grid and in exactly one cage, and that
+  /// the cage sums add up to the sum of all digits in the grid.
+  fn check_cages(&self) -> Result<(), SudokuError> {
+    let mut caged = [[false; 9]; 9];
+    for &(row, col) in self.cages.iter().flat_map(|(_, cells)| cells) {
+      let Some(cell) = caged.get_mut(row).and_then(|cells| cells.get_mut(col)) else {
+        return Err(SudokuError::CageCellOffGrid { row, col });
+      };
+      if std::mem::replace(cell, true) {
+        return Err(SudokuError::OverlappingCages {
+          row: row as u32,
+          col: col as u32,
+        });
+      }
+    }
+    if let Some((row, col)) = (0..9)
+      .cartesian_product(0..9)
+      .find(|&(row, col)| !caged[row][col])
+    {
+      return Err(SudokuError::UncagedCell {
+        row: row as u32,
+        col: col as u32,
       });
+    }
 
-    if !valid {
-      return false;
+    let total = self.cages.iter().map(|(sum, _)| sum).sum();
+    if total != 405 {
+      return Err(SudokuError::CageTotal(total));
     }
+    Ok(())
+  }
+
+  /// Fills in the grid with a solution, the first one found if there are
+  /// several.
+  pub fn solve(&mut self) -> Result<(), SudokuError> {
+    self.check_cages()?;
+    let (items, choices) = self.sudoku.exact_cover()?;
+    let grid = &self.sudoku.grid;
 
-    let items_ref = &items;
+    let cage_choices = self
+      .cages
+      .iter()
+      .enumerate()
+      .flat_map(|(idx, (sum, cells))| {
+        let givens = cells.iter().map(|&(row, col)| grid[row][col]).collect_vec();
+        // The same combinations as a kakuro run with this sum, in every order
+        // which agrees with the givens.
+        TotalClue::all_combinations_for_range((*sum, *sum), cells.len() as u32)
+          .flat_map(move |(_, digits)| digits.into_iter().permutations(cells.len()))
+          .filter(move |digits| {
+            givens
+              .iter()
+              .zip(digits)
+              .all(|(&given, &digit)| given == 0 || given == digit)
+          })
+          .map(move |digits| {
+            let idx = idx as u32;
+            let name = KillerChoice::Cage {
+              idx,
+              digits: digits.clone(),
+            };
+            let subset = iter::once(Constraint::Primary(Item::Cage { idx }.index(9)))
+              .chain(cells.iter().zip(digits).map(|(&(row, col), digit)| {
+                let cell_digit = Item::CellDigit {
+                  row: row as u32,
+                  col: col as u32,
+                };
+                ColorItem::new(cell_digit.index(9), digit).into()
+              }))
+              .collect_vec();
+            (name, subset)
+          })
+      });
 
-    // Enumerate all legal choices, present them to the solver.
     let mut dlx = Dlx::new(
-      items.iter().map(|item| (item.clone(), HeaderType::Primary)),
-      self
-        .grid
-        .iter()
-        .enumerate()
-        .flat_map(|(row, digits)| {
-          let row = row as u32;
-          digits
-            .iter()
-            .enumerate()
-            .filter(|(_, digit)| **digit == 0)
-            .flat_map(move |(col, _)| {
-              let col = col as u32;
-              let idx = (row / 3) * 3 + col / 3;
-
-              (1..=9).filter_map(move |digit| {
-                let choices = [
-                  Item::Cell { row, col },
-                  Item::Row { col, digit },
-                  Item::Col { row, digit },
-                  Item::Box { idx, digit },
-                ];
-                if choices.iter().all(|choice| items_ref.contains(choice)) {
-                  Some((Choice { digit, row, col }, choices.into_iter()))
-                } else {
-                  None
-                }
-              })
-            })
+      items
+        .into_iter()
+        .map(|item| (item, HeaderType::Primary))
+        .chain(
+          (0..self.cages.len() as u32)
+            .map(|idx| (Item::Cage { idx }.index(9), HeaderType::Primary)),
+        )
+        .chain(
+          (0..9)
+            .cartesian_product(0..9)
+            .map(|(row, col)| (Item::CellDigit { row, col }.index(9), HeaderType::Secondary)),
+        ),
+      choices
+        .into_iter()
+        .map(|(choice, subset)| {
+          let cell_digit = Item::CellDigit {
+            row: choice.row,
+            col: choice.col,
+          };
+          let cell_digit = ColorItem::new(cell_digit.index(9), choice.digit);
+          (
+            KillerChoice::Cell(choice),
+            subset
+              .into_iter()
+              .map(Constraint::Primary)
+              .chain(iter::once(cell_digit.into()))
+              .collect_vec(),
+          )
         })
-        .map(|(choice, subset)| (choice, subset.map(Constraint::Primary))),
+        .chain(cage_choices),
     );
 
-    if let Some(choices) = dlx.find_solution_names() {
-      for choice in choices {
-        self.grid[choice.row as usize][choice.col as usize] = choice.digit;
+    let choices = dlx
+      .find_first_solution_names()
+      .ok_or(SudokuError::NoSolution)?
+      .into_iter()
+      .filter_map(|choice| match choice {
+        KillerChoice::Cell(choice) => Some(choice),
+        KillerChoice::Cage { .. } => None,
+      })
+      .collect_vec();
+    for placement in choices {
+      self.sudoku.apply(placement);
+    }
+    Ok(())
+  }
+}
+
+/// An n x n grid to fill with digits 1 to n, such that each row and column
+/// has every digit once. Blank cells are 0.
+pub struct LatinSquare {
+  grid: Vec<Vec<u32>>,
+}
+
+impl LatinSquare {
+  /// Panics if `givens` isn't `n` rows of `n` cells.
+  pub fn new(n: usize, givens: Vec<Vec<u32>>) -> Self {
+    assert!(
+      givens.len() == n && givens.iter().all(|row| row.len() == n),
+      "Expected a {n}x{n} grid"
+    );
+    Self { grid: givens }
+  }
+
+  pub fn solve(&mut self) -> Result<(), SudokuError> {
+    let placements = solve_model_with(&self.model()?, SearchLimits::first())
+      .solutions
+      .pop()
+      .ok_or(SudokuError::NoSolution)?;
+    for Placement { digit, row, col } in placements {
+      self.grid[row as usize][col as usize] = digit;
+    }
+    Ok(())
+  }
+
+  /// Counts the completions of this square, stopping once `limit` have been
+  /// found.
+  pub fn count_solutions(&self, limit: u64) -> u64 {
+    match self.model() {
+      Ok(model) => build_dlx(&model).count_solutions(limit as usize) as u64,
+      Err(_) => 0,
+    }
+  }
+
+  fn model(&self) -> Result<GridModel, SudokuError> {
+    let size = self.grid.len() as u32;
+    // Shares the sudoku item layout, without the regions or diagonals.
+    let mut needed = vec![true; (3 * size * size) as usize];
+    needed.resize((4 * size * size + 2 * size) as usize, false);
+
+    grid_exact_cover(&self.grid, needed, |row, col, digit| {
+      [Item::Row { col, digit }, Item::Col { row, digit }]
+        .into_iter()
+        .map(move |item| item.index(size))
+    })
+    .map(GridModel)
+  }
+}
+
+impl Display for LatinSquare {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let width = self.grid.len().to_string().len();
+    for (y, row) in self.grid.iter().enumerate() {
+      if y > 0 {
+        writeln!(f)?;
       }
-      return true;
+      let row = row
+        .iter()
+        .map(|&digit| {
+          if digit == 0 {
+            format!("{:>width$}", ".")
+          } else {
+            format!("{digit:>width$}")
+          }
+        })
+        .join(" ");
+      write!(f, "{row}")?;
+    }
+    Ok(())
+  }
+}
+
+/// Parses the common single-line format: 81 characters in row-major order,
+/// where `1`-`9` are givens and `0`, `.` or `_` are blank.
+impl FromStr for Sudoku {
+  type Err = ParseSudokuError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let len = s.chars().count();
+    if len != 81 {
+      return Err(ParseSudokuError::WrongLength(len));
     }
 
-    false
+    let mut grid = [[0; 9]; 9];
+    for (pos, c) in s.chars().enumerate() {
+      grid[pos / 9][pos % 9] = match c {
+        '0' | '.' | '_' => 0,
+        '1'..='9' => c.to_digit(10).unwrap(),
+        _ => return Err(ParseSudokuError::InvalidChar { pos, c }),
+      };
+    }
+    Ok(Self::new(grid))
   }
 }
 
+/// Solves each of `puzzles`, and sums the three-digit numbers formed by the
+/// first three digits of each solution's top row, as in Project Euler problem
+/// 96. Returns None if any puzzle can't be solved.
+pub fn solve_p096_sum(puzzles: Vec<(String, Sudoku)>) -> Option<u32> {
+  puzzles.into_iter().try_fold(0, |sum, (_, mut sudoku)| {
+    sudoku.solve().ok().map(|()| {
+      sum
+        + sudoku.grid[0][..3]
+          .iter()
+          .fold(0, |acc, digit| 10 * acc + digit)
+    })
+  })
+}
+
+/// The alternate form (`{:#}`) wraps givens in parentheses, to tell them
+/// apart from solved cells.
 impl Display for Sudoku {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let size = self.grid.len();
+
     write!(f, "+")?;
-    (0..9).try_fold((), |_, _| write!(f, "===+"))?;
+    (0..size).try_fold((), |_, _| write!(f, "===+"))?;
     writeln!(f)?;
 
-    self.grid.iter().enumerate().try_fold((), |_, (y, row)| {
-      write!(f, "H")?;
-      row.iter().enumerate().try_fold((), |_, (x, digit)| {
-        write!(
-          f,
-          " {} ",
-          if *digit == 0 {
-            " ".to_string()
-          } else {
-            digit.to_string()
-          }
-        )?;
-        if x % 3 == 2 {
+    self.grid.iter().enumerate().try_fold((), |_, (y, row)| {
+      write!(f, "H")?;
+      row.iter().enumerate().try_fold((), |_, (x, digit)| {
+        let c = if *digit == 0 { ' ' } else { digit_char(*digit) };
+        if f.alternate() && self.is_given(y, x) {
+          write!(f, "({c})")?;
+        } else {
+          write!(f, " {c} ")?;
+        }
+        if row.get(x + 1).is_none() || self.regions[y][x] != self.regions[y][x + 1] {
           write!(f, "H",)
         } else {
           write!(f, "|")
@@ -154,14 +1388,14 @@ impl Display for Sudoku {
       writeln!(f)?;
 
       write!(f, "+")?;
-      (0..9).try_fold((), |_, _| {
-        if y % 3 == 2 {
+      (0..size).try_fold((), |_, x| {
+        if y == size - 1 || self.regions[y][x] != self.regions[y + 1][x] {
           write!(f, "===+")
         } else {
           write!(f, "---+")
         }
       })?;
-      if y < 8 {
+      if y < size - 1 {
         writeln!(f)?;
       }
 
@@ -172,7 +1406,19 @@ impl Display for Sudoku {
 
 #[cfg(test)]
 mod test {
-  use super::Sudoku;
+  use std::{io, ops::ControlFlow, time::Instant};
+
+  use itertools::Itertools;
+
+  use crate::{exact_cover::build_dlx, util::DigitSet};
+
+  use super::{
+    digit_char, solve_p096_sum, Difficulty, Hint, Item, KillerSudoku, LatinSquare,
+    ParseSudokuError, Placement, Sudoku, SudokuError, SudokuVariant, Unit,
+  };
+
+  const EASY: &str =
+    "..4.5....9..7346....3.21.49.35.9.48..9.....3..76.1.92.31.97.2....9182..3....6.1..";
 
   #[test]
   fn test_easy() {
@@ -199,7 +1445,7 @@ mod test {
       [5, 2, 7, 4, 6, 3, 1, 9, 8],
     ];
 
-    sudoku.solve();
+    assert_eq!(sudoku.solve(), Ok(()));
     assert_eq!(sudoku.grid, SOLN);
   }
 
@@ -228,7 +1474,1020 @@ mod test {
       [5, 9, 8, 7, 3, 6, 2, 4, 1],
     ];
 
-    sudoku.solve();
+    assert_eq!(sudoku.solve(), Ok(()));
     assert_eq!(sudoku.grid, SOLN);
   }
+
+  #[test]
+  fn test_invalid_given() {
+    let mut sudoku: Sudoku = EASY.parse().unwrap();
+    sudoku.grid[4][0] = 10;
+    assert_eq!(
+      sudoku.solve(),
+      Err(SudokuError::InvalidGiven {
+        row: 4,
+        col: 0,
+        digit: 10
+      })
+    );
+  }
+
+  #[test]
+  fn test_conflicting_givens() {
+    let mut sudoku: Sudoku = EASY.parse().unwrap();
+    // Row 1 already has a 9 in column 0.
+    sudoku.grid[1][8] = 9;
+    assert_eq!(
+      sudoku.solve(),
+      Err(SudokuError::ConflictingGivens {
+        row: 1,
+        col: 8,
+        digit: 9
+      })
+    );
+  }
+
+  #[test]
+  fn test_no_solution() {
+    // The top-left cell can't be 1-8 (its row) or 9 (its column), though no
+    // two givens conflict.
+    let mut sudoku: Sudoku = format!(".12345678.........9{}", ".".repeat(62))
+      .parse()
+      .unwrap();
+    assert_eq!(sudoku.solve(), Err(SudokuError::NoSolution));
+  }
+
+  #[test]
+  #[allow(deprecated)]
+  fn test_try_solve() {
+    let mut sudoku: Sudoku = EASY.parse().unwrap();
+    assert!(sudoku.try_solve());
+    sudoku.grid[0][0] = 0;
+    sudoku.grid[0][1] = 2;
+    assert!(!sudoku.try_solve());
+  }
+
+  #[test]
+  fn test_count_solutions() {
+    let mut sudoku: Sudoku = EASY.parse().unwrap();
+    assert_eq!(sudoku.count_solutions(10), 1);
+    assert!(sudoku.is_proper());
+
+    sudoku.grid[1][6] = 0;
+    assert_eq!(sudoku.count_solutions(10), 5);
+    assert!(!sudoku.is_proper());
+
+    sudoku.grid[0][0] = 9;
+    assert_eq!(sudoku.count_solutions(10), 0);
+    assert!(!sudoku.is_proper());
+
+    let empty: Sudoku = ".".repeat(81).parse().unwrap();
+    assert_eq!(empty.count_solutions(u64::MAX), u64::MAX);
+  }
+
+  #[test]
+  fn test_canonical_form() {
+    let sudoku = Sudoku::new(EASY_SOLN);
+    // Swap the digits 1 and 9, transpose, then swap the first two bands and
+    // the last two columns of the middle stack.
+    let relabel = |digit: u32| {
+      if digit == 1 || digit == 9 {
+        10 - digit
+      } else {
+        digit
+      }
+    };
+    let rows = [3, 4, 5, 0, 1, 2, 6, 7, 8];
+    let cols = [0, 1, 2, 3, 5, 4, 6, 7, 8];
+    let mut grid = [[0; 9]; 9];
+    for (row, col) in (0..9).cartesian_product(0..9) {
+      grid[rows[col]][cols[row]] = relabel(EASY_SOLN[row][col]);
+    }
+    let shuffled = Sudoku::new(grid);
+    assert_eq!(shuffled.canonical_form(), sudoku.canonical_form());
+    assert!(shuffled.is_isomorphic_to(&sudoku));
+
+    let canonical = sudoku.canonical_form();
+    assert_eq!(&canonical[..9], &[1, 2, 3, 4, 5, 6, 7, 8, 9]);
+    let canonical = Sudoku::new(std::array::from_fn(|row| {
+      std::array::from_fn(|col| canonical[9 * row + col] as u32)
+    }));
+    assert_eq!(canonical.count_solutions(2), 1);
+    assert_eq!(canonical.canonical_form(), sudoku.canonical_form());
+
+    // Every row of this grid is the one above shifted, so it's far more
+    // symmetric than the puzzle's solution.
+    let shifted = Sudoku::new(std::array::from_fn(|row| {
+      std::array::from_fn(|col| ((3 * (row % 3) + row / 3 + col) % 9 + 1) as u32)
+    }));
+    assert!(!shifted.is_isomorphic_to(&sudoku));
+    assert_ne!(shifted.canonical_form(), sudoku.canonical_form());
+  }
+
+  #[test]
+  fn test_canonical_form_puzzle() {
+    let puzzle: Sudoku = EASY.parse().unwrap();
+    let transposed = Sudoku::new(std::array::from_fn(|row| {
+      std::array::from_fn(|col| match puzzle.digit(col, row) {
+        0 => 0,
+        digit => digit % 9 + 1,
+      })
+    }));
+    assert!(puzzle.is_isomorphic_to(&transposed));
+
+    let mut extra = puzzle.clone();
+    extra.grid[0][0] = 2;
+    assert!(!puzzle.is_isomorphic_to(&extra));
+    assert!(!puzzle.is_isomorphic_to(&Sudoku::with_box_size(2, vec![vec![0; 4]; 4])));
+  }
+
+  #[test]
+  fn test_solve_instrumented() {
+    let easy: Sudoku = EASY.parse().unwrap();
+    let (solved, stats) = easy.solve_instrumented().unwrap();
+    assert_eq!(solved.grid, EASY_SOLN);
+    // Every blank cell is forced in turn, so the search never guesses.
+    let blanks = 81 - stats.givens as u64;
+    assert_eq!(stats.givens, easy.givens().count());
+    assert_eq!(stats.backtracks, 0);
+    assert_eq!(stats.nodes, blanks);
+    assert_eq!(stats.solutions, 1);
+    assert!(stats.choices as u64 >= blanks);
+
+    // A hard puzzle with the fewest possible givens needs guesses.
+    let hard: Sudoku =
+      "000000012500008000000700000600120000700000450000030000030000800000500700020000000"
+        .parse()
+        .unwrap();
+    let (solved, hard) = hard.solve_instrumented().unwrap();
+    assert_eq!(
+      solved.to_line(),
+      "378694512564218397291753684643125978712869453859437261435971826186542739927386145"
+    );
+    assert_eq!(hard.givens, 17);
+    assert_eq!(hard.solutions, 1);
+    assert!(hard.backtracks > 0);
+    assert!(hard.nodes > stats.nodes);
+
+    assert_eq!(
+      stats.to_string(),
+      format!(
+        "{} givens, {} choices, {blanks} nodes, 0 backtracks, 1 solutions",
+        stats.givens, stats.choices
+      )
+    );
+
+    let mut unsolvable = easy.clone();
+    unsolvable.grid[0][0] = 4;
+    assert!(unsolvable.solve_instrumented().is_err());
+  }
+
+  #[test]
+  fn test_solve_ambiguous() {
+    let mut sudoku: Sudoku = EASY.parse().unwrap();
+    sudoku.grid[1][3] = 0;
+    let (solved, stats) = sudoku.solve_instrumented().unwrap();
+    assert_eq!(stats.solutions, 2);
+    assert!(sudoku.solutions().any(|grid| grid == solved.grid));
+    assert_eq!(sudoku.solve(), Ok(()));
+    assert_eq!(sudoku.grid, solved.grid);
+
+    let mut empty = Sudoku::new([[0; 9]; 9]);
+    let (_, stats) = empty.solve_instrumented().unwrap();
+    assert_eq!(stats.solutions, 2);
+    assert_eq!(empty.solve(), Ok(()));
+    assert_eq!(empty.count_solutions(2), 1);
+  }
+
+  #[test]
+  fn test_for_each_solution() {
+    let seventeen: Sudoku =
+      "000000010400000000020000000000050407008000300001090000300400200050100000000806000"
+        .parse()
+        .unwrap();
+    let mut count = 0;
+    seventeen
+      .for_each_solution(|grid| {
+        assert!(grid.iter().flatten().all(|&digit| digit != 0));
+        count += 1;
+        ControlFlow::Continue(())
+      })
+      .unwrap();
+    assert_eq!(count, seventeen.count_solutions(100));
+
+    let mut sudoku: Sudoku = EASY.parse().unwrap();
+    sudoku.grid[1][6] = 0;
+    let mut visited = Vec::new();
+    sudoku
+      .for_each_solution(|grid| {
+        visited.push(grid.iter().map(|row| row.to_vec()).collect_vec());
+        ControlFlow::Continue(())
+      })
+      .unwrap();
+    assert_eq!(visited.len() as u64, sudoku.count_solutions(10));
+    assert_eq!(visited, sudoku.solutions().collect_vec());
+
+    sudoku.grid[0][0] = 9;
+    assert_eq!(
+      sudoku.for_each_solution(|_| ControlFlow::Continue(())),
+      Err(SudokuError::ConflictingGivens {
+        row: 1,
+        col: 0,
+        digit: 9
+      })
+    );
+
+    let small = Sudoku::with_box_size(2, vec![vec![0; 4]; 4]);
+    assert_eq!(
+      small.for_each_solution(|_| ControlFlow::Continue(())),
+      Err(SudokuError::GridSize(4))
+    );
+  }
+
+  #[test]
+  fn test_for_each_solution_break() {
+    let mut sudoku: Sudoku = EASY.parse().unwrap();
+    sudoku.grid[1][6] = 0;
+    let mut count = 0;
+    sudoku
+      .for_each_solution(|_| {
+        count += 1;
+        ControlFlow::Break(())
+      })
+      .unwrap();
+    assert_eq!(count, 1);
+
+    // The empty grid has far too many solutions to visit, so this only
+    // returns if the search stops at the break.
+    let empty: Sudoku = ".".repeat(81).parse().unwrap();
+    let mut count = 0;
+    empty
+      .for_each_solution(|grid| {
+        assert!(grid.iter().flatten().all(|&digit| digit != 0));
+        count += 1;
+        if count == 3 {
+          ControlFlow::Break(())
+        } else {
+          ControlFlow::Continue(())
+        }
+      })
+      .unwrap();
+    assert_eq!(count, 3);
+  }
+
+  #[test]
+  fn test_solutions() {
+    let mut sudoku: Sudoku = EASY.parse().unwrap();
+    sudoku.grid[1][3] = 0;
+    let original = sudoku.grid.clone();
+
+    let solutions = sudoku.solutions().collect_vec();
+    assert_eq!(solutions.len(), 2);
+    assert_eq!(sudoku.grid, original);
+    assert_eq!(sudoku.solutions().collect_vec(), solutions);
+
+    // Grids are found as they're taken, so this doesn't enumerate every
+    // completion of the empty grid.
+    let empty = Sudoku::new([[0; 9]; 9]);
+    assert_eq!(empty.solutions().take(2).unique().count(), 2);
+
+    let differing = (0..9)
+      .cartesian_product(0..9)
+      .filter(|&(row, col)| solutions[0][row][col] != solutions[1][row][col])
+      .collect_vec();
+    // Without the 7, the two solutions only disagree in the top three rows and
+    // the bottom row.
+    assert_eq!(
+      differing,
+      vec![
+        (0, 0),
+        (0, 1),
+        (0, 3),
+        (0, 8),
+        (1, 1),
+        (1, 3),
+        (1, 8),
+        (2, 0),
+        (2, 1),
+        (2, 3),
+        (8, 0),
+        (8, 1)
+      ]
+    );
+    assert_eq!(
+      solutions.iter().map(|grid| grid[1][3]).collect_vec(),
+      vec![7, 8]
+    );
+  }
+
+  #[test]
+  fn test_4x4() {
+    let mut sudoku = Sudoku::with_box_size(
+      2,
+      vec![
+        vec![1, 2, 0, 0],
+        vec![0, 0, 1, 0],
+        vec![0, 1, 0, 4],
+        vec![0, 0, 3, 0],
+      ],
+    );
+    assert!(sudoku.is_proper());
+    assert_eq!(sudoku.solve(), Ok(()));
+    assert_eq!(
+      sudoku.grid,
+      [[1, 2, 4, 3], [4, 3, 1, 2], [3, 1, 2, 4], [2, 4, 3, 1]]
+    );
+    assert_eq!(
+      sudoku.to_string(),
+      "+===+===+===+===+
+H 1 | 2 H 4 | 3 H
++---+---+---+---+
+H 4 | 3 H 1 | 2 H
++===+===+===+===+
+H 3 | 1 H 2 | 4 H
++---+---+---+---+
+H 2 | 4 H 3 | 1 H
++===+===+===+===+"
+    );
+  }
+
+  /// Parses rows of a 16x16 grid, with `1`-`9` and `A`-`G` for digits and
+  /// `.` for blanks.
+  fn parse_16x16(rows: [&str; 16]) -> Vec<Vec<u32>> {
+    rows
+      .iter()
+      .map(|row| row.chars().map(|c| c.to_digit(17).unwrap_or(0)).collect())
+      .collect()
+  }
+
+  #[test]
+  fn test_16x16() {
+    let grid = parse_16x16([
+      ".6.E....1.7.8.CF",
+      ".1....83C....9B.",
+      ".8....E..4.6....",
+      ".C..21..8.9.7.D.",
+      "39..6..B......7.",
+      ".....C.E5..9....",
+      ".FA84..G.E....6.",
+      "...2.5...CF34...",
+      "5..6......2D.GA.",
+      ".E..F.A.G.B....8",
+      "4..........8.51.",
+      ".BGFD7.5EA.C....",
+      "......F.B.6GC...",
+      "B...1..AF.4.....",
+      "G..A.3...1..56.9",
+      "...DE6....5....4",
+    ]);
+    let soln = parse_16x16([
+      "D63E9BG415728ACF",
+      "21457A83CDEF69BG",
+      "789BCDEFA4G62153",
+      "ACFG2156839B74DE",
+      "39516FDB2G84AE7C",
+      "6D743C1E5BA9GF82",
+      "CFA8429G7ED1B365",
+      "EGB2A5786CF34D91",
+      "531689BC4F2DEGA7",
+      "9E27F4A1G6B5DC38",
+      "4ADCGE629738F51B",
+      "8BGFD735EA1C9246",
+      "14E358FDB96GC72A",
+      "B5691GCAF24738ED",
+      "G28AB347D1CE56F9",
+      "F7CDE629385A1BG4",
+    ]);
+
+    let mut sudoku = Sudoku::with_box_size(4, grid);
+    assert!(sudoku.is_proper());
+    assert_eq!(sudoku.solve(), Ok(()));
+    assert_eq!(sudoku.grid, soln);
+    assert!(sudoku
+      .to_string()
+      .starts_with("+===+===+===+===+===+===+===+===+===+===+===+===+===+===+===+===+\nH D | 6 | 3 | E H 9 | B | G | 4 H 1 | 5 | 7 | 2 H 8 | A | C | F H"));
+  }
+
+  #[test]
+  fn test_diagonal() {
+    let mut sudoku: Sudoku =
+      "....9......4.5.6.....3.6.8.....28....3......25.6...........4.....7.13.5....6...1."
+        .parse()
+        .unwrap();
+    assert!(sudoku.count_solutions(10) > 1);
+
+    sudoku.set_variant(SudokuVariant::Diagonal);
+    assert!(sudoku.is_proper());
+    assert_eq!(sudoku.solve(), Ok(()));
+    assert_eq!(
+      sudoku.to_line(),
+      "365897241784152639192346587471928365938465172526731498619584723847213956253679814"
+    );
+  }
+
+  #[test]
+  fn test_diagonal_conflict() {
+    // Two 5s on the main diagonal, in different rows, columns and boxes.
+    let mut sudoku: Sudoku = format!("5{}5{}", ".".repeat(39), ".".repeat(40))
+      .parse()
+      .unwrap();
+    sudoku.set_variant(SudokuVariant::Diagonal);
+    assert_eq!(
+      sudoku.solve(),
+      Err(SudokuError::ConflictingGivens {
+        row: 4,
+        col: 4,
+        digit: 5
+      })
+    );
+  }
+
+  /// The cages of the example killer sudoku on Wikipedia's "Killer sudoku"
+  /// page, whose published solution is `KILLER_SOLN`.
+  fn killer_cages() -> Vec<(u32, Vec<(usize, usize)>)> {
+    vec![
+      (3, vec![(0, 0), (0, 1)]),
+      (15, vec![(0, 2), (0, 3), (0, 4)]),
+      (22, vec![(0, 5), (1, 4), (1, 5), (2, 4)]),
+      (4, vec![(0, 6), (1, 6)]),
+      (16, vec![(0, 7), (1, 7)]),
+      (15, vec![(0, 8), (1, 8), (2, 8), (3, 8)]),
+      (25, vec![(1, 0), (1, 1), (2, 0), (2, 1)]),
+      (17, vec![(1, 2), (1, 3)]),
+      (9, vec![(2, 2), (2, 3), (3, 3)]),
+      (8, vec![(2, 5), (3, 5), (4, 5)]),
+      (20, vec![(2, 6), (2, 7), (3, 6)]),
+      (6, vec![(3, 0), (4, 0)]),
+      (14, vec![(3, 1), (3, 2)]),
+      (17, vec![(3, 4), (4, 4), (5, 4)]),
+      (17, vec![(3, 7), (4, 6), (4, 7)]),
+      (13, vec![(4, 1), (4, 2), (5, 1)]),
+      (20, vec![(4, 3), (5, 3), (6, 3)]),
+      (12, vec![(4, 8), (5, 8)]),
+      (27, vec![(5, 0), (6, 0), (7, 0), (8, 0)]),
+      (6, vec![(5, 2), (6, 1), (6, 2)]),
+      (20, vec![(5, 5), (6, 5), (6, 6)]),
+      (6, vec![(5, 6), (5, 7)]),
+      (10, vec![(6, 4), (7, 3), (7, 4), (8, 3)]),
+      (14, vec![(6, 7), (6, 8), (7, 7), (7, 8)]),
+      (8, vec![(7, 1), (8, 1)]),
+      (16, vec![(7, 2), (8, 2)]),
+      (15, vec![(7, 5), (7, 6)]),
+      (13, vec![(8, 4), (8, 5), (8, 6)]),
+      (17, vec![(8, 7), (8, 8)]),
+    ]
+  }
+
+  const KILLER_SOLN: &str =
+    "215647398368952174794381652586274931142593867973816425821739546659428713437165289";
+
+  #[test]
+  fn test_killer() {
+    let mut killer = KillerSudoku::new([[0; 9]; 9], killer_cages());
+    assert_eq!(killer.solve(), Ok(()));
+    assert_eq!(killer.sudoku.to_line(), KILLER_SOLN);
+  }
+
+  #[test]
+  fn test_killer_ambiguous() {
+    // Cages covering whole rows say nothing the rows don't already, so this
+    // has the two solutions of the givens.
+    let mut sudoku: Sudoku = EASY.parse().unwrap();
+    sudoku.grid[1][3] = 0;
+    let cages = (0..9)
+      .map(|row| (45, (0..9).map(|col| (row, col)).collect()))
+      .collect();
+    let mut grid = [[0; 9]; 9];
+    for (row, digits) in grid.iter_mut().zip(&sudoku.grid) {
+      row.copy_from_slice(digits);
+    }
+    let mut killer = KillerSudoku::new(grid, cages);
+    assert_eq!(killer.solve(), Ok(()));
+    assert!(sudoku.solutions().any(|grid| grid == killer.sudoku.grid));
+  }
+
+  #[test]
+  fn test_killer_cage_errors() {
+    let mut cages = killer_cages();
+    cages[1].1.push((0, 0));
+    assert_eq!(
+      KillerSudoku::new([[0; 9]; 9], cages).solve(),
+      Err(SudokuError::OverlappingCages { row: 0, col: 0 })
+    );
+
+    let mut cages = killer_cages();
+    cages[0].1.pop();
+    assert_eq!(
+      KillerSudoku::new([[0; 9]; 9], cages).solve(),
+      Err(SudokuError::UncagedCell { row: 0, col: 1 })
+    );
+
+    let mut cages = killer_cages();
+    cages[0].0 += 1;
+    assert_eq!(
+      KillerSudoku::new([[0; 9]; 9], cages).solve(),
+      Err(SudokuError::CageTotal(406))
+    );
+
+    let mut cages = killer_cages();
+    cages[0].1.push((9, 0));
+    assert_eq!(
+      KillerSudoku::new([[0; 9]; 9], cages).solve(),
+      Err(SudokuError::CageCellOffGrid { row: 9, col: 0 })
+    );
+  }
+
+  const JIGSAW_REGIONS: [[u8; 9]; 9] = [
+    [0, 0, 0, 1, 1, 1, 1, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 2, 2],
+    [3, 0, 0, 4, 4, 1, 1, 2, 2],
+    [3, 3, 3, 4, 4, 4, 2, 2, 2],
+    [3, 3, 3, 4, 4, 4, 5, 5, 5],
+    [3, 6, 6, 6, 4, 5, 5, 5, 5],
+    [3, 6, 6, 7, 7, 7, 8, 8, 5],
+    [6, 6, 6, 7, 7, 8, 8, 8, 5],
+    [6, 7, 7, 7, 7, 8, 8, 8, 8],
+  ];
+
+  /// The solution repeats digits within the classic boxes, e.g. 2 and 8 in the
+  /// bottom-left box, so it can only be found by using the regions.
+  #[test]
+  fn test_jigsaw() {
+    let mut sudoku = Sudoku::with_regions(
+      [
+        [0, 0, 3, 4, 0, 0, 8, 0, 0],
+        [0, 1, 0, 0, 0, 0, 0, 7, 9],
+        [0, 0, 0, 0, 1, 0, 0, 0, 0],
+        [0, 0, 0, 3, 0, 0, 0, 0, 0],
+        [0, 4, 0, 0, 0, 0, 0, 0, 2],
+        [0, 0, 7, 0, 0, 0, 0, 6, 0],
+        [0, 0, 0, 0, 0, 0, 0, 0, 0],
+        [9, 0, 0, 0, 0, 3, 0, 0, 0],
+        [0, 8, 0, 0, 0, 9, 0, 0, 0],
+      ],
+      JIGSAW_REGIONS,
+    )
+    .unwrap();
+    assert!(sudoku.is_proper());
+    assert_eq!(sudoku.solve(), Ok(()));
+    assert_eq!(
+      sudoku.to_line(),
+      "793451826514862379826517934679324518345786192157298463231945687968173245482639751"
+    );
+    assert!(sudoku.to_string().starts_with(
+      "+===+===+===+===+===+===+===+===+===+
+H 7 | 9 | 3 H 4 | 5 | 1 | 8 H 2 | 6 H
++---+---+---+===+---+---+---+---+---+"
+    ));
+  }
+
+  #[test]
+  fn test_jigsaw_region_size() {
+    let mut regions = JIGSAW_REGIONS;
+    regions[0][0] = 1;
+    assert_eq!(
+      Sudoku::with_regions([[0; 9]; 9], regions).err(),
+      Some(SudokuError::RegionSize { region: 0, size: 8 })
+    );
+  }
+
+  const EASY_SOLN: [[u32; 9]; 9] = [
+    [2, 6, 4, 8, 5, 9, 3, 1, 7],
+    [9, 8, 1, 7, 3, 4, 6, 5, 2],
+    [7, 5, 3, 6, 2, 1, 8, 4, 9],
+    [1, 3, 5, 2, 9, 7, 4, 8, 6],
+    [8, 9, 2, 5, 4, 6, 7, 3, 1],
+    [4, 7, 6, 3, 1, 8, 9, 2, 5],
+    [3, 1, 8, 9, 7, 5, 2, 6, 4],
+    [6, 4, 9, 1, 8, 2, 5, 7, 3],
+    [5, 2, 7, 4, 6, 3, 1, 9, 8],
+  ];
+
+  #[test]
+  fn test_solve_steps() {
+    let sudoku: Sudoku = EASY.parse().unwrap();
+    let steps = sudoku.solve_steps().unwrap().collect_vec();
+    assert_eq!(sudoku.to_line(), EASY);
+
+    // Every blank is filled exactly once.
+    assert_eq!(steps.len(), EASY.chars().filter(|&c| c == '.').count());
+    let mut replay: Sudoku = EASY.parse().unwrap();
+    for step in steps {
+      assert_eq!(replay.grid[step.row as usize][step.col as usize], 0);
+      replay.apply(step);
+    }
+    assert_eq!(replay.grid, EASY_SOLN);
+
+    let mut sudoku = sudoku;
+    sudoku.grid[0][0] = 4;
+    assert!(sudoku.solve_steps().is_none());
+  }
+
+  #[test]
+  fn test_solved() {
+    let sudoku: Sudoku = EASY.parse().unwrap();
+    let solved = sudoku.solved().unwrap();
+    assert_eq!(sudoku.to_line(), EASY);
+    assert_eq!(solved.grid, EASY_SOLN);
+
+    assert!(solved.is_given(0, 2));
+    assert!(!solved.is_given(0, 0));
+    assert_eq!(solved.givens().collect_vec(), sudoku.givens().collect_vec());
+    assert_eq!(
+      solved.givens().take(3).collect_vec(),
+      vec![(0, 2, 4), (0, 4, 5), (1, 0, 9)]
+    );
+    assert_eq!(
+      solved.givens().count(),
+      EASY.chars().filter(|&c| c != '.').count()
+    );
+
+    assert!(format!("{solved:#}").contains("H 2 | 6 |(4)H 8 |(5)| 9 H 3 | 1 | 7 H"));
+    assert!(solved
+      .to_string()
+      .contains("H 2 | 6 | 4 H 8 | 5 | 9 H 3 | 1 | 7 H"));
+
+    let mut unsolvable = sudoku.clone();
+    unsolvable.grid[0][0] = 4;
+    assert!(unsolvable.solved().is_none());
+  }
+
+  #[test]
+  fn test_candidates() {
+    let sudoku: Sudoku = EASY.parse().unwrap();
+    let candidates = sudoku.candidates();
+    assert_eq!(candidates[0][0], [1, 2, 6, 7, 8].into_iter().collect());
+    assert!(candidates[0][2].is_empty());
+    assert_eq!(candidates[0][3], [6, 8].into_iter().collect());
+    assert_eq!(sudoku.candidate_count(0, 0), 5);
+    assert_eq!(sudoku.candidate_count(0, 2), 0);
+
+    assert_eq!(candidates[4][4], DigitSet::new().with(4));
+    assert!(sudoku.is_naked_single(4, 4));
+    assert!(!sudoku.is_naked_single(0, 3));
+    assert!(!sudoku.is_naked_single(0, 2));
+  }
+
+  #[test]
+  fn test_candidates_regions() {
+    let mut grid = [[0; 9]; 9];
+    grid[0][0] = 1;
+    let sudoku = Sudoku::with_regions(grid, JIGSAW_REGIONS).unwrap();
+    let candidates = sudoku.candidates();
+    // (2, 2) shares a region with (0, 0), but (3, 1) doesn't.
+    assert_eq!(candidates[2][2], DigitSet::from_range(2..=9));
+    assert_eq!(candidates[3][1], DigitSet::from_range(1..=9));
+    assert_eq!(candidates[3][0], DigitSet::from_range(2..=9));
+  }
+
+  #[test]
+  fn test_candidates_16x16() {
+    let mut grid = vec![vec![0; 16]; 16];
+    grid[0][1] = 16;
+    let sudoku = Sudoku::with_box_size(4, grid);
+    let candidates = sudoku.candidates();
+    assert_eq!(candidates[0][0], DigitSet::from_range(1..=15));
+    assert_eq!(candidates[15][15], DigitSet::from_range(1..=16));
+    assert_eq!(sudoku.candidate_count(15, 15), 16);
+  }
+
+  #[test]
+  #[should_panic(expected = "Boxes can be at most 4 cells wide, got 5")]
+  fn test_box_size_too_large() {
+    Sudoku::with_box_size(5, vec![vec![0; 25]; 25]);
+  }
+
+  #[test]
+  fn test_hint_naked_single() {
+    let sudoku: Sudoku = EASY.parse().unwrap();
+    assert_eq!(
+      sudoku.hint(),
+      Some(Hint::NakedSingle {
+        row: 4,
+        col: 4,
+        digit: 4
+      })
+    );
+  }
+
+  #[test]
+  fn test_hint_display() {
+    assert_eq!(
+      Hint::NakedSingle {
+        row: 4,
+        col: 4,
+        digit: 4
+      }
+      .to_string(),
+      "4 is the only candidate at (4, 4)"
+    );
+    assert_eq!(
+      Hint::HiddenSingle {
+        unit: Unit::Row(0),
+        digit: 1,
+        cell: (0, 2)
+      }
+      .to_string(),
+      "(0, 2) is the only place for 1 in row 0"
+    );
+  }
+
+  #[test]
+  fn test_hint_hidden_single() {
+    // The 1s in rows 1 and 2 and columns 0 and 1 leave (0, 2) as the only
+    // place for a 1 in the top row.
+    let mut grid = [[0; 9]; 9];
+    grid[1][3] = 1;
+    grid[2][6] = 1;
+    grid[3][0] = 1;
+    grid[4][1] = 1;
+    assert_eq!(
+      Sudoku::new(grid).hint(),
+      Some(Hint::HiddenSingle {
+        unit: Unit::Row(0),
+        digit: 1,
+        cell: (0, 2)
+      })
+    );
+  }
+
+  #[test]
+  fn test_hint_naked_pair() {
+    // 3-5 in the top row and 6-9 in both of the first two columns leave
+    // only 1 and 2 for (0, 0) and (0, 1).
+    let mut grid = [[0; 9]; 9];
+    grid[0][6..].copy_from_slice(&[3, 4, 5]);
+    for (row, col, digit) in [
+      (3, 0, 6),
+      (4, 0, 7),
+      (6, 0, 8),
+      (7, 0, 9),
+      (3, 1, 8),
+      (4, 1, 9),
+      (6, 1, 6),
+      (7, 1, 7),
+    ] {
+      grid[row][col] = digit;
+    }
+    assert_eq!(
+      Sudoku::new(grid).hint(),
+      Some(Hint::NakedPair {
+        unit: Unit::Row(0),
+        cells: [(0, 0), (0, 1)],
+        digits: [1, 2],
+        eliminations: (2..6).flat_map(|col| [(0, col, 1), (0, col, 2)]).collect(),
+      })
+    );
+  }
+
+  #[test]
+  fn test_hint_guess() {
+    let empty = Sudoku::new([[0; 9]; 9]);
+    let Some(Hint::Guess(Placement { row, col, digit })) = empty.hint() else {
+      panic!("Expected a guess");
+    };
+    assert_eq!(empty.grid[row as usize][col as usize], 0);
+    assert!((1..=9).contains(&digit));
+
+    let solved = Sudoku::new(EASY_SOLN);
+    assert_eq!(solved.hint(), None);
+  }
+
+  #[test]
+  fn test_difficulty() {
+    let sudoku: Sudoku = EASY.parse().unwrap();
+    let report = sudoku.difficulty();
+    assert!(report.solved);
+    assert_eq!(report.guesses, 0);
+    assert_eq!(
+      (report.naked_singles + report.hidden_singles) as usize,
+      EASY.chars().filter(|&c| c == '.').count()
+    );
+    assert_eq!(report.rating(), Difficulty::Easy);
+    assert_eq!(sudoku.to_line(), EASY);
+  }
+
+  #[test]
+  fn test_difficulty_escargot() {
+    let sudoku: Sudoku =
+      "1....7.9..3..2...8..96..5....53..9...1..8...26....4...3......1..4......7..7...3.."
+        .parse()
+        .unwrap();
+    let report = sudoku.difficulty();
+    assert!(report.solved);
+    assert!(report.guesses > 0);
+    assert!(report.rating() >= Difficulty::Hard);
+  }
+
+  #[test]
+  fn test_item_index() {
+    for size in [4, 9, 16] {
+      let cells = size * size;
+      let items = (0..cells)
+        .map(|i| Item::Cell {
+          row: i / size,
+          col: i % size,
+        })
+        .chain((0..cells).map(|i| Item::Row {
+          col: i / size,
+          digit: i % size + 1,
+        }))
+        .chain((0..cells).map(|i| Item::Col {
+          row: i / size,
+          digit: i % size + 1,
+        }))
+        .chain((0..cells).map(|i| Item::Box {
+          idx: i / size,
+          digit: i % size + 1,
+        }))
+        .chain((0..2 * size).map(|i| Item::Diag {
+          which: i / size,
+          digit: i % size + 1,
+        }))
+        .chain((0..cells).map(|idx| Item::Cage { idx }))
+        .chain((0..cells).map(|i| Item::CellDigit {
+          row: i / size,
+          col: i % size,
+        }));
+
+      // The indices are dense, and each maps back to its item.
+      for (index, item) in items.enumerate() {
+        assert_eq!(item.index(size), index as u16);
+        assert_eq!(Item::from_index(index as u16, size), item);
+      }
+    }
+  }
+
+  #[test]
+  #[should_panic(expected = "Item index 65536 is too large for a 256x256 grid")]
+  fn test_item_index_too_large() {
+    Item::Row { col: 0, digit: 1 }.index(256);
+  }
+
+  #[test]
+  fn test_digit_char() {
+    assert_eq!(digit_char(9), '9');
+    assert_eq!(digit_char(10), 'A');
+    assert_eq!(digit_char(16), 'G');
+    assert_eq!(digit_char(35), 'Z');
+    assert_eq!(digit_char(36), '?');
+  }
+
+  #[test]
+  #[ignore = "prints timings for comparison"]
+  fn test_build_dlx_speed() {
+    // In release builds, building went from ~190µs per puzzle when items were
+    // hashed enums to ~105µs with dense indices.
+    const ROUNDS: u32 = 1000;
+    let puzzles = Sudoku::from_p096_file("testdata/p096_sample.txt").unwrap();
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+      for (_, sudoku) in &puzzles {
+        assert!(build_dlx(&sudoku.model().unwrap()).num_nodes() > 0);
+      }
+    }
+    let build = start.elapsed() / (ROUNDS * puzzles.len() as u32);
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+      for (_, sudoku) in &puzzles {
+        assert!(sudoku.solved().is_some());
+      }
+    }
+    let solve = start.elapsed() / (ROUNDS * puzzles.len() as u32);
+    println!("{build:?} to build, {solve:?} to build and solve, per puzzle");
+  }
+
+  #[test]
+  fn test_latin_square() {
+    let mut square = LatinSquare::new(
+      5,
+      vec![
+        vec![0, 3, 0, 0, 0],
+        vec![0, 0, 0, 1, 0],
+        vec![0, 0, 3, 2, 0],
+        vec![0, 5, 0, 0, 0],
+        vec![1, 0, 0, 0, 4],
+      ],
+    );
+    assert_eq!(square.count_solutions(10), 1);
+    assert_eq!(square.solve(), Ok(()));
+    assert_eq!(
+      square.grid,
+      [
+        [2, 3, 4, 5, 1],
+        [5, 4, 2, 1, 3],
+        [4, 1, 3, 2, 5],
+        [3, 5, 1, 4, 2],
+        [1, 2, 5, 3, 4],
+      ]
+    );
+    assert_eq!(
+      square.to_string(),
+      "2 3 4 5 1\n5 4 2 1 3\n4 1 3 2 5\n3 5 1 4 2\n1 2 5 3 4"
+    );
+  }
+
+  #[test]
+  fn test_latin_square_count() {
+    let square = LatinSquare::new(4, vec![vec![0; 4]; 4]);
+    assert_eq!(square.count_solutions(1000), 576);
+
+    let mut square = LatinSquare::new(12, vec![vec![0; 12]; 12]);
+    square.grid[0][0] = 12;
+    square.grid[0][5] = 12;
+    assert!(square.to_string().starts_with("12  .  .  .  . 12  ."));
+    assert_eq!(
+      square.solve(),
+      Err(SudokuError::ConflictingGivens {
+        row: 0,
+        col: 5,
+        digit: 12
+      })
+    );
+    square.grid[0][5] = 0;
+    assert_eq!(square.solve(), Ok(()));
+    assert!(square.grid.iter().all(|row| row.iter().all_unique()));
+  }
+
+  #[test]
+  fn test_parse() {
+    let sudoku: Sudoku = EASY.parse().unwrap();
+    assert_eq!(
+      sudoku.grid,
+      [
+        [0, 0, 4, 0, 5, 0, 0, 0, 0],
+        [9, 0, 0, 7, 3, 4, 6, 0, 0],
+        [0, 0, 3, 0, 2, 1, 0, 4, 9],
+        [0, 3, 5, 0, 9, 0, 4, 8, 0],
+        [0, 9, 0, 0, 0, 0, 0, 3, 0],
+        [0, 7, 6, 0, 1, 0, 9, 2, 0],
+        [3, 1, 0, 9, 7, 0, 2, 0, 0],
+        [0, 0, 9, 1, 8, 2, 0, 0, 3],
+        [0, 0, 0, 0, 6, 0, 1, 0, 0],
+      ]
+    );
+    assert_eq!(sudoku.to_line(), EASY);
+
+    let zeros_and_underscores = EASY.replacen('.', "0", 10).replace('.', "_");
+    assert_eq!(
+      zeros_and_underscores.parse::<Sudoku>().unwrap().to_line(),
+      EASY
+    );
+  }
+
+  #[test]
+  fn test_parse_errors() {
+    assert_eq!(
+      EASY[..80].parse::<Sudoku>().err(),
+      Some(ParseSudokuError::WrongLength(80))
+    );
+    assert_eq!(
+      EASY.replacen('4', "x", 1).parse::<Sudoku>().err(),
+      Some(ParseSudokuError::InvalidChar { pos: 2, c: 'x' })
+    );
+  }
+
+  #[test]
+  fn test_p096() {
+    let puzzles = Sudoku::from_p096_file("testdata/p096_sample.txt").unwrap();
+    assert_eq!(
+      puzzles
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>(),
+      vec!["Grid 01", "Grid 02"]
+    );
+    assert_eq!(
+      puzzles[0].1.to_line(),
+      "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3.."
+    );
+    assert_eq!(solve_p096_sum(puzzles), Some(483 + 245));
+  }
+
+  #[test]
+  fn test_p096_errors() {
+    let err = |text: &str| {
+      let err = Sudoku::from_p096_reader(text.as_bytes()).err().unwrap();
+      assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+      err.to_string()
+    };
+    let rows = "003020600\n".repeat(9);
+    assert_eq!(
+      err(
+        &format!("Grid 07\n{rows}")
+          .replace("\n003020600\n003020600\n0", "\n003020600\n003020600\nx")
+      ),
+      "Grid 07, row 3: Invalid row \"x03020600\""
+    );
+    assert_eq!(
+      err(&format!("Grid 07\n{}", &rows[..50])),
+      "Grid 07: Missing row 6"
+    );
+    assert_eq!(
+      err("003020600\n"),
+      "Expected a grid header, found \"003020600\""
+    );
+  }
 }
@@ -0,0 +1,250 @@
+use std::{
+  fmt::{self, Display},
+  iter,
+  ops::RangeInclusive,
+};
+
+use itertools::Itertools;
+
+/// A set of digits from 0 to `DigitSet::MAX`, stored as a bitmask. This is
+/// wide enough for the digits 0-9 of kakuro and the digits 1-16 of the
+/// largest sudokus alike.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug)]
+pub struct DigitSet(u32);
+
+impl DigitSet {
+  /// The largest digit a set can hold.
+  pub const MAX: u32 = 16;
+
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// The set of every digit in `range`, which must lie within 0-`MAX`.
+  pub fn from_range(range: RangeInclusive<u32>) -> Self {
+    range.collect()
+  }
+
+  pub fn contains(&self, digit: u32) -> bool {
+    digit <= Self::MAX && self.0 & (1 << digit) != 0
+  }
+
+  /// Panics if `digit` is larger than `MAX`.
+  pub fn insert(&mut self, digit: u32) {
+    assert!(
+      digit <= Self::MAX,
+      "Digit {digit} is larger than {}",
+      Self::MAX
+    );
+    self.0 |= 1 << digit;
+  }
+
+  pub fn remove(&mut self, digit: u32) {
+    if digit <= Self::MAX {
+      self.0 &= !(1 << digit);
+    }
+  }
+
+  pub fn with(mut self, digit: u32) -> Self {
+    self.insert(digit);
+    self
+  }
+
+  pub fn without(mut self, digit: u32) -> Self {
+    self.remove(digit);
+    self
+  }
+
+  pub fn union(&self, other: DigitSet) -> Self {
+    DigitSet(self.0 | other.0)
+  }
+
+  pub fn intersection(&self, other: DigitSet) -> Self {
+    DigitSet(self.0 & other.0)
+  }
+
+  /// The digits in this set but not in `other`.
+  pub fn difference(&self, other: DigitSet) -> Self {
+    DigitSet(self.0 & !other.0)
+  }
+
+  pub fn len(&self) -> u32 {
+    self.0.count_ones()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.0 == 0
+  }
+
+  /// Iterates over the digits in the set in increasing order.
+  pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+    let mut bits = self.0;
+    iter::from_fn(move || {
+      (bits != 0).then(|| {
+        let digit = bits.trailing_zeros();
+        bits &= bits - 1;
+        digit
+      })
+    })
+  }
+}
+
+impl FromIterator<u32> for DigitSet {
+  fn from_iter<T: IntoIterator<Item = u32>>(iter: T) -> Self {
+    iter
+      .into_iter()
+      .fold(DigitSet::new(), |set, digit| set.with(digit))
+  }
+}
+
+impl Display for DigitSet {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{{{}}}", self.iter().join(","))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use itertools::Itertools;
+
+  use super::DigitSet;
+
+  /// Every subset of 0-9, as its digits in increasing order.
+  fn all_subsets() -> Vec<Vec<u32>> {
+    (0..=9).powerset().collect()
+  }
+
+  #[test]
+  fn test_digit_set() {
+    let set: DigitSet = [1, 4, 9].into_iter().collect();
+    assert!(set.contains(4));
+    assert!(!set.contains(5));
+    assert_eq!(set.len(), 3);
+    assert_eq!(set.iter().collect_vec(), vec![1, 4, 9]);
+    assert_eq!(set.to_string(), "{1,4,9}");
+    assert_eq!(DigitSet::new().to_string(), "{}");
+
+    let other: DigitSet = [4, 5].into_iter().collect();
+    assert_eq!(set.union(other).iter().collect_vec(), vec![1, 4, 5, 9]);
+    assert_eq!(set.intersection(other).iter().collect_vec(), vec![4]);
+  }
+
+  #[test]
+  fn test_empty_and_full() {
+    let empty = DigitSet::new();
+    assert!(empty.is_empty());
+    assert_eq!(empty.len(), 0);
+    assert_eq!(empty.iter().next(), None);
+    assert!((0..=10).all(|digit| !empty.contains(digit)));
+
+    let full = DigitSet::from_range(0..=9);
+    assert!(!full.is_empty());
+    assert_eq!(full.len(), 10);
+    assert_eq!(full.iter().collect_vec(), (0..=9).collect_vec());
+    assert_eq!(full.to_string(), "{0,1,2,3,4,5,6,7,8,9}");
+    assert!(!full.contains(10));
+
+    assert_eq!(DigitSet::from_range(1..=9), full.without(0));
+    assert_eq!(DigitSet::from_range(3..=5).to_string(), "{3,4,5}");
+    #[allow(clippy::reversed_empty_ranges)]
+    let reversed = DigitSet::from_range(5..=3);
+    assert_eq!(reversed, empty);
+  }
+
+  #[test]
+  fn test_insert_remove() {
+    let mut set = DigitSet::new();
+    set.insert(7);
+    set.insert(7);
+    set.insert(0);
+    assert_eq!(set.iter().collect_vec(), vec![0, 7]);
+    set.remove(7);
+    set.remove(7);
+    set.remove(3);
+    assert_eq!(set.iter().collect_vec(), vec![0]);
+    set.remove(0);
+    assert!(set.is_empty());
+    // Digits outside of 0-`MAX` are never in the set.
+    set.remove(DigitSet::MAX + 1);
+    assert!(set.is_empty());
+    assert!(!set.contains(DigitSet::MAX + 1));
+  }
+
+  #[test]
+  #[should_panic(expected = "Digit 17 is larger than 16")]
+  fn test_insert_too_large() {
+    DigitSet::new().insert(DigitSet::MAX + 1);
+  }
+
+  #[test]
+  fn test_sudoku_digits() {
+    let set = DigitSet::from_range(1..=DigitSet::MAX);
+    assert_eq!(set.len(), 16);
+    assert_eq!(set.iter().last(), Some(16));
+    let odd: DigitSet = (1..=16).step_by(2).collect();
+    assert_eq!(
+      set.difference(odd).iter().collect_vec(),
+      (2..=16).step_by(2).collect_vec()
+    );
+  }
+
+  #[test]
+  fn test_every_subset() {
+    for digits in all_subsets() {
+      let set: DigitSet = digits.iter().copied().collect();
+      assert_eq!(set.iter().collect_vec(), digits);
+      assert_eq!(set.len() as usize, digits.len());
+      assert_eq!(set.is_empty(), digits.is_empty());
+      assert_eq!(set.to_string(), format!("{{{}}}", digits.iter().join(",")));
+      for digit in 0..=9 {
+        assert_eq!(set.contains(digit), digits.contains(&digit));
+        assert_eq!(
+          set.with(digit).iter().collect_vec(),
+          digits
+            .iter()
+            .copied()
+            .chain([digit])
+            .sorted()
+            .dedup()
+            .collect_vec()
+        );
+        assert_eq!(
+          set.without(digit).iter().collect_vec(),
+          digits.iter().copied().filter(|&d| d != digit).collect_vec()
+        );
+      }
+    }
+  }
+
+  #[test]
+  fn test_every_pair_of_subsets() {
+    // Sample the pairs, as all 2^20 of them are more than needed.
+    let subsets = all_subsets();
+    for (lhs, rhs) in subsets
+      .iter()
+      .step_by(7)
+      .cartesian_product(subsets.iter().step_by(11))
+    {
+      let (lhs_set, rhs_set): (DigitSet, DigitSet) =
+        (lhs.iter().copied().collect(), rhs.iter().copied().collect());
+      assert_eq!(
+        lhs_set.union(rhs_set).iter().collect_vec(),
+        lhs
+          .iter()
+          .chain(rhs)
+          .copied()
+          .sorted()
+          .dedup()
+          .collect_vec()
+      );
+      assert_eq!(
+        lhs_set.intersection(rhs_set).iter().collect_vec(),
+        lhs
+          .iter()
+          .copied()
+          .filter(|digit| rhs.contains(digit))
+          .collect_vec()
+      );
+    }
+  }
+}
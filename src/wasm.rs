@@ -0,0 +1,83 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::{kakuro::Kakuro, sudoku::Sudoku};
+
+/// A solved kakuro, as handed to JavaScript by `solve_kakuro`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct KakuroSolution {
+  /// The value of each letter the solution assigns.
+  pub letters: BTreeMap<char, u32>,
+  /// The letters' values concatenated from A to J, if every letter has one.
+  pub value: Option<u64>,
+  /// The digit in each tile, by row, or None for tiles which aren't unknowns.
+  pub digits: Vec<Vec<Option<u32>>>,
+}
+
+/// Solves the sudoku written as 81 characters in row-major order, with `.` or
+/// `0` for blanks, returning the filled grid in the same format. Returns None
+/// if the grid can't be parsed or solved.
+#[wasm_bindgen]
+pub fn solve_sudoku(grid: &str) -> Option<String> {
+  let mut sudoku: Sudoku = grid.parse().ok()?;
+  sudoku.solve().ok()?;
+  Some(sudoku.to_line())
+}
+
+/// Solves the kakuro written as a line of a Project Euler 424 puzzle file,
+/// returning an object with its `letters`, `value` and `digits` as in
+/// `KakuroSolution`, or null if the line can't be parsed or solved.
+#[wasm_bindgen]
+pub fn solve_kakuro(line: &str) -> JsValue {
+  kakuro_solution(line)
+    .and_then(|solution| {
+      solution
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .ok()
+    })
+    .unwrap_or(JsValue::NULL)
+}
+
+/// The first solution found to the kakuro on `line`.
+pub fn kakuro_solution(line: &str) -> Option<KakuroSolution> {
+  let kakuro: Kakuro = line.parse().ok()?;
+  let solution = kakuro.solve_first().ok()??;
+  Some(KakuroSolution {
+    letters: solution.letters().iter().collect(),
+    value: solution.letters().try_int_value().ok(),
+    digits: solution.digit_grid(),
+  })
+}
+
+#[cfg(test)]
+mod test {
+  use super::{kakuro_solution, solve_sudoku};
+
+  #[test]
+  fn test_solve_sudoku() {
+    let solution = solve_sudoku(
+      "..4.5....9..7346....3.21.49.35.9.48..9.....3..76.1.92.31.97.2....9182..3....6.1..",
+    )
+    .unwrap();
+    assert_eq!(solution.len(), 81);
+    assert!(solution.starts_with("264859317"));
+    assert_eq!(solve_sudoku("12"), None);
+  }
+
+  #[test]
+  fn test_kakuro_solution() {
+    let solution = kakuro_solution("3,X,(v4),(v3),(h3),O,O,(h4),O,O").unwrap();
+    assert!(solution.letters.is_empty());
+    assert_eq!(
+      solution.digits,
+      vec![
+        vec![None, None, None],
+        vec![None, Some(1), Some(2)],
+        vec![None, Some(3), Some(1)],
+      ]
+    );
+    assert_eq!(kakuro_solution("3,X"), None);
+  }
+}
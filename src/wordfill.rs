@@ -0,0 +1,350 @@
+use std::fmt::{self, Display};
+
+use itertools::Itertools;
+
+use crate::{
+  dlx::{ColorItem, Constraint, Dlx, HeaderType, Selection},
+  exact_cover::{solve_model_with, ExactCoverModel, SearchLimits},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Direction {
+  Across,
+  Down,
+}
+
+/// A slot of a crossword grid: a maximal line of at least two open cells,
+/// starting at `(row, col)`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct SlotId {
+  pub row: usize,
+  pub col: usize,
+  pub direction: Direction,
+}
+
+impl SlotId {
+  /// The `(row, col)` of each of the first `len` cells of the slot.
+  fn cells(&self, len: usize) -> impl Iterator<Item = (usize, usize)> {
+    let SlotId {
+      row,
+      col,
+      direction,
+    } = *self;
+    (0..len).map(move |offset| match direction {
+      Direction::Across => (row, col + offset),
+      Direction::Down => (row + offset, col),
+    })
+  }
+}
+
+impl Display for SlotId {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let direction = match self.direction {
+      Direction::Across => "across",
+      Direction::Down => "down",
+    };
+    write!(f, "({}, {}) {direction}", self.row, self.col)
+  }
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+enum WordFillItem {
+  /// A slot, which must be filled with a word.
+  Slot(usize),
+  /// The letter in a cell, as a color.
+  Cell { row: usize, col: usize },
+  /// A word, which may be used at most once.
+  Word(usize),
+}
+
+/// Placing word `word` in slot `slot`.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+struct Placement {
+  slot: usize,
+  word: usize,
+}
+
+/// A grid to fill with words, modeled like a kakuro: each slot is a primary
+/// item, each placement of a word colors the cells of its slot with their
+/// letters, so crossing words have to agree on the letter they share.
+struct WordFill {
+  /// Each cell of the grid, which is None for blocks, and otherwise holds
+  /// its given letter if it has one.
+  cells: Vec<Vec<Option<Option<char>>>>,
+  /// Each slot with its length, across slots before down slots.
+  slots: Vec<(SlotId, usize)>,
+  words: Vec<String>,
+}
+
+impl WordFill {
+  fn new(grid: &str, words: &[&str]) -> Self {
+    let cells = grid
+      .lines()
+      .map(str::trim)
+      .filter(|line| !line.is_empty())
+      .map(|line| {
+        line
+          .chars()
+          .map(|c| match c {
+            '#' => None,
+            '.' => Some(None),
+            'a'..='z' | 'A'..='Z' => Some(Some(c.to_ascii_uppercase())),
+            _ => panic!("Invalid character '{c}' in grid, expected '#', '.' or a letter"),
+          })
+          .collect_vec()
+      })
+      .collect_vec();
+
+    let open = |row: usize, col: usize| {
+      cells
+        .get(row)
+        .and_then(|cells: &Vec<_>| cells.get(col))
+        .is_some_and(Option::is_some)
+    };
+    let slots = [Direction::Across, Direction::Down]
+      .into_iter()
+      .flat_map(|direction| {
+        let cells = &cells;
+        cells.iter().enumerate().flat_map(move |(row, line)| {
+          (0..line.len()).filter_map(move |col| {
+            let (prev, next) = match direction {
+              Direction::Across => (col.checked_sub(1).map(|col| (row, col)), (row, col + 1)),
+              Direction::Down => (row.checked_sub(1).map(|row| (row, col)), (row + 1, col)),
+            };
+            let starts = open(row, col)
+              && !prev.is_some_and(|(row, col)| open(row, col))
+              && open(next.0, next.1);
+            starts.then(|| {
+              let slot = SlotId {
+                row,
+                col,
+                direction,
+              };
+              let len = (1..)
+                .take_while(|&len| slot.cells(len).all(|(row, col)| open(row, col)))
+                .last()
+                .unwrap();
+              (slot, len)
+            })
+          })
+        })
+      })
+      .collect_vec();
+
+    let words = words
+      .iter()
+      .map(|word| word.to_ascii_uppercase())
+      .filter(|word| !word.is_empty() && word.chars().all(|c| c.is_ascii_uppercase()))
+      .unique()
+      .collect_vec();
+
+    Self {
+      cells,
+      slots,
+      words,
+    }
+  }
+
+  fn slot_cells(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+    self
+      .slots
+      .iter()
+      .flat_map(|(slot, len)| slot.cells(*len))
+      .unique()
+  }
+}
+
+impl ExactCoverModel for WordFill {
+  type Item = WordFillItem;
+  type Choice = Placement;
+  type Solution = Vec<(SlotId, String)>;
+
+  fn items(&self) -> impl Iterator<Item = (WordFillItem, HeaderType)> + '_ {
+    (0..self.slots.len())
+      .map(|slot| (WordFillItem::Slot(slot), HeaderType::Primary))
+      .chain(
+        self
+          .slot_cells()
+          .map(|(row, col)| (WordFillItem::Cell { row, col }, HeaderType::Secondary)),
+      )
+      .chain((0..self.words.len()).map(|word| (WordFillItem::Word(word), HeaderType::Secondary)))
+  }
+
+  fn choices(
+    &self,
+  ) -> impl Iterator<
+    Item = (
+      Placement,
+      impl IntoIterator<Item = Constraint<WordFillItem>>,
+    ),
+  > + '_ {
+    self
+      .slots
+      .iter()
+      .enumerate()
+      .flat_map(move |(slot_idx, &(slot, len))| {
+        self
+          .words
+          .iter()
+          .enumerate()
+          .filter(move |(_, word)| word.len() == len)
+          .map(move |(word_idx, word)| {
+            let letters = slot
+              .cells(len)
+              .zip(word.bytes())
+              .map(|((row, col), letter)| {
+                ColorItem::new(WordFillItem::Cell { row, col }, (letter - b'A') as u32).into()
+              });
+            (
+              Placement {
+                slot: slot_idx,
+                word: word_idx,
+              },
+              [
+                Constraint::Primary(WordFillItem::Slot(slot_idx)),
+                Constraint::Uncolored(WordFillItem::Word(word_idx)),
+              ]
+              .into_iter()
+              .chain(letters)
+              .collect_vec(),
+            )
+          })
+      })
+  }
+
+  fn prepare(&self, dlx: &mut Dlx<WordFillItem, Placement>) {
+    for (row, col) in self.slot_cells().collect_vec() {
+      if let Some(Some(letter)) = self.cells[row][col] {
+        dlx.require_color(&WordFillItem::Cell { row, col }, letter as u32 - 'A' as u32);
+      }
+    }
+  }
+
+  fn decode(&self, selection: Selection<WordFillItem, Placement>) -> Vec<(SlotId, String)> {
+    selection
+      .names
+      .into_iter()
+      .sorted_by_key(|placement| placement.slot)
+      .map(|Placement { slot, word }| (self.slots[slot].0, self.words[word].clone()))
+      .collect()
+  }
+}
+
+/// Fills the slots of `grid` with distinct words from `words`, returning the
+/// word placed in each slot, across slots first. Each line of `grid` is a row,
+/// with `#` for a block, `.` for an empty cell, and a letter for a cell which
+/// must hold that letter. Words are matched ignoring case, and any with
+/// characters other than letters are skipped. Panics if `grid` contains any
+/// other characters.
+pub fn fill(grid: &str, words: &[&str]) -> Option<Vec<(SlotId, String)>> {
+  solve_model_with(&WordFill::new(grid, words), SearchLimits::first())
+    .solutions
+    .pop()
+}
+
+/// Draws `grid` with the words of `fill` written into their slots.
+pub fn render(grid: &str, fill: &[(SlotId, String)]) -> String {
+  let mut cells = WordFill::new(grid, &[])
+    .cells
+    .into_iter()
+    .map(|line| {
+      line
+        .into_iter()
+        .map(|cell| match cell {
+          None => '#',
+          Some(given) => given.unwrap_or('.'),
+        })
+        .collect_vec()
+    })
+    .collect_vec();
+  for (slot, word) in fill {
+    for ((row, col), letter) in slot.cells(word.len()).zip(word.chars()) {
+      cells[row][col] = letter;
+    }
+  }
+  cells
+    .iter()
+    .map(|line| line.iter().collect::<String>())
+    .join("\n")
+}
+
+#[cfg(test)]
+mod test {
+  use crate::exact_cover::solve_model;
+
+  use super::{fill, render, Direction, SlotId, WordFill};
+
+  const GRID: &str = "
+    .A..
+    .##.
+    .##.
+    ....";
+
+  const WORDS: &[&str] = &[
+    "cats", "cost", "sole", "tone", "cone", "oxen", "nose", "east", "ox",
+  ];
+
+  fn slot(row: usize, col: usize, direction: Direction) -> SlotId {
+    SlotId {
+      row,
+      col,
+      direction,
+    }
+  }
+
+  #[test]
+  fn test_slots() {
+    let fill = WordFill::new(GRID, WORDS);
+    assert_eq!(
+      fill.slots,
+      vec![
+        (slot(0, 0, Direction::Across), 4),
+        (slot(3, 0, Direction::Across), 4),
+        (slot(0, 0, Direction::Down), 4),
+        (slot(0, 3, Direction::Down), 4),
+      ]
+    );
+    assert_eq!(fill.words.len(), 9);
+    assert_eq!(slot(3, 0, Direction::Across).to_string(), "(3, 0) across");
+  }
+
+  #[test]
+  fn test_unique_fill() {
+    let expected = vec![
+      (slot(0, 0, Direction::Across), "CATS".to_string()),
+      (slot(3, 0, Direction::Across), "TONE".to_string()),
+      (slot(0, 0, Direction::Down), "COST".to_string()),
+      (slot(0, 3, Direction::Down), "SOLE".to_string()),
+    ];
+    assert_eq!(
+      solve_model(&WordFill::new(GRID, WORDS)).solutions,
+      vec![expected.clone()]
+    );
+
+    let filled = fill(GRID, WORDS).unwrap();
+    assert_eq!(filled, expected);
+    assert_eq!(render(GRID, &filled), "CATS\nO##O\nS##L\nTONE");
+    assert_eq!(render(GRID, &[]), ".A..\n.##.\n.##.\n....");
+  }
+
+  #[test]
+  fn test_given_letters() {
+    // Without the given A, the grid can also be filled with its transpose.
+    let open = GRID.replace('A', ".");
+    assert_eq!(solve_model(&WordFill::new(&open, WORDS)).solutions.len(), 2);
+    // A given letter nothing can match leaves no fill.
+    assert_eq!(fill(&GRID.replace('A', "Z"), WORDS), None);
+  }
+
+  #[test]
+  fn test_distinct_words() {
+    // Each word may only be used once, so a word square whose rows match its
+    // columns can't be filled.
+    let grid = "..\n..";
+    assert_eq!(fill(grid, &["ab", "ba"]), None);
+    assert_eq!(
+      fill(grid, &["ab", "ba", "ac", "cd", "bd"]).map(|fill| render(grid, &fill)),
+      Some("AB\nCD".to_string())
+    );
+  }
+}
@@ -0,0 +1,199 @@
+use std::{
+  io::Write,
+  process::{Command, Output, Stdio},
+};
+
+fn p424(args: &[&str]) -> Output {
+  Command::new(env!("CARGO_BIN_EXE_p424"))
+    .args(args)
+    .output()
+    .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+  String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn test_kakuro() {
+  let output = p424(&[
+    "kakuro",
+    "--file",
+    "testdata/p424_sample.txt",
+    "--range",
+    "1..3",
+    "--sum",
+  ]);
+  assert_eq!(output.status.code(), Some(0));
+  assert_eq!(
+    stdout(&output),
+    "Puzzle 1: 8519647302\nPuzzle 2: 3019652784\nSum: 11539300086\n"
+  );
+}
+
+#[test]
+fn test_kakuro_json() {
+  let output = p424(&["kakuro", "--file", "testdata/p424_sample.txt", "--json"]);
+  assert_eq!(output.status.code(), Some(0));
+  let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+  assert_eq!(json["sum"], 19965339657u64);
+  assert_eq!(json["puzzles"][0]["value"], 8426039571u64);
+  assert_eq!(json["puzzles"].as_array().unwrap().len(), 3);
+}
+
+#[test]
+fn test_kakuro_errors() {
+  let output = p424(&["kakuro", "--file", "testdata/missing.txt"]);
+  assert_eq!(output.status.code(), Some(2));
+
+  let output = p424(&["kakuro", "--file", "testdata/p096_sample.txt"]);
+  assert_eq!(output.status.code(), Some(2));
+
+  let output = p424(&[
+    "kakuro",
+    "--file",
+    "testdata/p424_sample.txt",
+    "--range",
+    "x",
+  ]);
+  assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_sudoku() {
+  let output = p424(&["sudoku", "--p096", "testdata/p096_sample.txt"]);
+  assert_eq!(output.status.code(), Some(0));
+  let stdout = stdout(&output);
+  assert!(stdout.starts_with("Grid 01: 483921657"));
+  assert!(stdout.ends_with("Sum: 728\n"));
+}
+
+#[test]
+fn test_sudoku_json() {
+  let output = p424(&[
+    "sudoku",
+    "--json",
+    "--grid",
+    "..4.5....9..7346....3.21.49.35.9.48..9.....3..76.1.92.31.97.2....9182..3....6.1..",
+  ]);
+  assert_eq!(output.status.code(), Some(0));
+  let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+  assert!(json["grids"][0]["solution"]
+    .as_str()
+    .unwrap()
+    .starts_with("264859317"));
+  assert!(json.get("sum").is_none());
+}
+
+#[test]
+fn test_sudoku_errors() {
+  let output = p424(&["sudoku", "--grid", "123"]);
+  assert_eq!(output.status.code(), Some(2));
+
+  // Two 1s in the first row.
+  let grid = format!("11{}", ".".repeat(79));
+  let output = p424(&["sudoku", "--grid", &grid]);
+  assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn test_sudoku_not_unique() {
+  // The grid from `test_sudoku_json` without the 7 in the second row has two
+  // solutions, and an empty grid has many. Either is solved to one of them.
+  let ambiguous =
+    "..4.5....9...346....3.21.49.35.9.48..9.....3..76.1.92.31.97.2....9182..3....6.1..";
+  let empty = ".".repeat(81);
+  for grid in [ambiguous, &empty] {
+    let output = p424(&["sudoku", "--grid", grid]);
+    assert_eq!(output.status.code(), Some(0), "{grid}");
+    let stdout = stdout(&output);
+    let solution = stdout.trim().strip_prefix("Grid: ").unwrap();
+    assert_eq!(solution.len(), 81);
+    assert!(solution.chars().all(|c| ('1'..='9').contains(&c)));
+    assert!(grid
+      .chars()
+      .zip(solution.chars())
+      .all(|(given, digit)| given == '.' || given == digit));
+  }
+}
+
+#[test]
+fn test_dlx() {
+  let output = p424(&["dlx", "--knuth-format", "testdata/sample.dlx"]);
+  assert_eq!(output.status.code(), Some(0));
+  assert_eq!(
+    stdout(&output),
+    "Solution 1:\n  p q x:A\n  r x:A\nSolution 2:\n  p r x:B\n  q\n"
+  );
+
+  let output = p424(&["dlx", "--knuth-format", "testdata/sample.dlx", "--count"]);
+  assert_eq!(stdout(&output), "2\n");
+
+  let output = p424(&[
+    "dlx",
+    "--knuth-format",
+    "testdata/sample.dlx",
+    "--count",
+    "--json",
+  ]);
+  let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+  assert_eq!(json, serde_json::json!({ "count": 2 }));
+}
+
+#[test]
+fn test_dlx_errors() {
+  let output = p424(&["dlx", "--knuth-format", "testdata/p424_sample.txt"]);
+  assert_eq!(output.status.code(), Some(2));
+  assert!(String::from_utf8(output.stderr).unwrap().contains("Line 2"));
+}
+
+#[test]
+fn test_repl() {
+  let script = "\
+    load kakuro 6,X,(vIJ),(vIE),X,X,X,(hF),O,C,(vIA),X,X,(hCA),O,O,D,(vIH),X,X,(hIB),E,O,O,(vF),X,X,(hD),O,A,O,X,X,X,(hID),O,G
+    assume A=4
+    assume B=4
+    bogus
+    solve
+    hint
+    load sudoku 003020600900305001001806400008102900700000008006708200002609500800203009005010300
+    hint
+    quit
+    show
+  ";
+  let mut child = Command::new(env!("CARGO_BIN_EXE_p424"))
+    .arg("repl")
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .spawn()
+    .unwrap();
+  child
+    .stdin
+    .take()
+    .unwrap()
+    .write_all(script.as_bytes())
+    .unwrap();
+  let output = child.wait_with_output().unwrap();
+  assert_eq!(output.status.code(), Some(0));
+
+  let transcript = stdout(&output);
+  let lines = transcript.lines().collect::<Vec<_>>();
+  assert_eq!(
+    lines[..3],
+    [
+      "Loaded a 6x6 kakuro",
+      "Assuming A=4",
+      "A=4 conflicts with B=4"
+    ]
+  );
+  assert_eq!(lines[3], "Unknown command \"bogus\"");
+  assert_eq!(lines[4], "Commands:");
+  for expected in [
+    "Value: 4027398516\nHints are only given for sudokus\nLoaded a sudoku\n",
+    "4 is the only candidate at (4, 5)\n",
+  ] {
+    assert!(transcript.contains(expected), "{transcript}");
+  }
+  // Nothing runs after `quit`.
+  assert!(transcript.ends_with("4 is the only candidate at (4, 5)\n"));
+}
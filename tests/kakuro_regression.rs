@@ -0,0 +1,105 @@
+//! End-to-end checks of the kakuro pipeline against known answers, from
+//! parsing a puzzle line to the value of its solution.
+
+use std::{env, fs::File, io::BufReader, iter};
+
+use itertools::Itertools;
+use p424::kakuro::{solve_p424, CombinationCache, Kakuro, P424Error};
+
+/// Puzzles with their values. The first three are the first puzzles of the
+/// Project Euler 424 file.
+const PUZZLES: &[(&str, u64)] = &[
+  (
+    "6,X,X,(vCC),(vI),X,X,X,(hH),B,O,(vCA),(vJE),X,(hFE,vD),O,O,O,O,(hA),O,I,(hJC,vB),O,O,(hJC),H,O,O,O,X,X,X,(hJE),O,O,X",
+    8426039571,
+  ),
+  (
+    "7,X,X,X,X,(vJJ),(vCD),X,X,X,X,(hCG),O,O,(vCE),X,X,X,(hCI,vJB),C,O,O,X,(vB),(hJF,vJF),O,F,O,O,(hJA),F,G,O,O,X,X,(hCA),O,A,O,X,X,X,X,(hCF),O,O,X,X,X",
+    8519647302,
+  ),
+  (
+    "7,X,X,X,(vE),(vCB),X,X,X,X,(hJ),O,O,(vCA),X,X,(vCH),(hCG,vCJ),O,O,O,(vJ),(hCE),O,O,O,(hJ,vGG),O,O,(hD),I,O,(hCD,vCB),H,O,O,X,(hCE),O,O,E,X,X,X,X,(hCE),O,O,X,X",
+    3019652784,
+  ),
+  (
+    "6,X,(vIJ),(vIE),X,X,X,(hF),O,C,(vIA),X,X,(hCA),O,O,D,(vIH),X,X,(hIB),E,O,O,(vF),X,X,(hD),O,A,O,X,X,X,(hID),O,G",
+    4027398516,
+  ),
+  (
+    "6,X,X,X,X,(vEH),(vEC),X,X,X,(hEB,vEJ),O,O,X,X,(hEC,vEF),O,O,B,X,(hDD,vEI),O,B,C,X,(hB),O,D,A,X,X,(hEC),O,O,X,X,X",
+    4762183509,
+  ),
+];
+
+/// The sum of the values of the first 10 puzzles of the Project Euler 424
+/// file.
+const FIRST_TEN_SUM: u64 = 64414157580;
+
+#[test]
+fn test_known_answers() {
+  for &(line, value) in PUZZLES {
+    let kakuro: Kakuro = line.parse().unwrap();
+    let solution = kakuro.solve_first().unwrap().unwrap();
+    assert_eq!(kakuro.verify(&solution), Ok(()), "{line}");
+    assert_eq!(solution.letters().int_value(), value, "{line}");
+  }
+}
+
+#[test]
+fn test_solutions_are_unique() {
+  for &(line, value) in PUZZLES {
+    let kakuro: Kakuro = line.parse().unwrap();
+    let solutions = kakuro.solve().unwrap();
+    assert_eq!(solutions.len(), 1, "{line}");
+    assert_eq!(solutions[0].int_value(), value, "{line}");
+  }
+}
+
+#[test]
+fn test_solve_p424() {
+  // testdata/p424_sample.txt holds the first three puzzles of the Project
+  // Euler 424 file.
+  let mut events = Vec::new();
+  let sum = solve_p424("testdata/p424_sample.txt", |event| events.push(event)).unwrap();
+  assert_eq!(
+    sum,
+    PUZZLES[..3].iter().map(|&(_, value)| value).sum::<u64>()
+  );
+  assert_eq!(
+    events.iter().map(|event| event.index).collect_vec(),
+    vec![0, 1, 2]
+  );
+  // Each puzzle's value is the difference between consecutive sums.
+  let values = iter::once(0)
+    .chain(events.iter().map(|event| event.sum))
+    .tuple_windows()
+    .map(|(prev, sum)| sum - prev)
+    .collect_vec();
+  assert_eq!(
+    values,
+    PUZZLES[..3].iter().map(|&(_, value)| value).collect_vec()
+  );
+
+  assert!(matches!(
+    solve_p424("testdata/missing.txt", |_| {}),
+    Err(P424Error::Io(_))
+  ));
+}
+
+/// Run with `P424_FILE=path/to/p424_kakuro200.txt cargo test -- --ignored`.
+#[test]
+#[ignore]
+fn test_first_ten_of_p424_file() {
+  let path = env::var("P424_FILE").expect("P424_FILE should point at the puzzle file");
+  let puzzles = Kakuro::from_reader(BufReader::new(File::open(path).unwrap())).unwrap();
+  let mut cache = CombinationCache::new();
+  let sum: u64 = puzzles
+    .iter()
+    .take(10)
+    .map(|kakuro| {
+      let letters = kakuro.solve_first_with_cache(&mut cache).unwrap().unwrap();
+      letters.int_value()
+    })
+    .sum();
+  assert_eq!(sum, FIRST_TEN_SUM);
+}
@@ -0,0 +1,82 @@
+use std::{
+  cell::{Cell, RefCell},
+  sync::Once,
+};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use p424::kakuro::Kakuro;
+
+const KAKURO: &str = "6,X,(vIJ),(vIE),X,X,X,(hF),O,C,(vIA),X,X,(hCA),O,O,D,(vIH),X,X,(hIB),E,O,O,(vF),X,X,(hD),O,A,O,X,X,X,(hID),O,G";
+
+thread_local! {
+  static LEVEL: Cell<LevelFilter> = const { Cell::new(LevelFilter::Off) };
+  static RECORDS: RefCell<Vec<(Level, String)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Records the messages logged by each thread at or above that thread's
+/// level, so tests running in parallel don't see each other's messages.
+struct CapturingLogger;
+
+impl Log for CapturingLogger {
+  fn enabled(&self, metadata: &Metadata) -> bool {
+    metadata.level() <= LEVEL.with(Cell::get)
+  }
+
+  fn log(&self, record: &Record) {
+    if self.enabled(record.metadata()) {
+      RECORDS.with_borrow_mut(|records| records.push((record.level(), record.args().to_string())));
+    }
+  }
+
+  fn flush(&self) {}
+}
+
+/// The messages logged while solving `KAKURO` with this thread's logs
+/// filtered to `level`.
+fn solve_logging_at(level: LevelFilter) -> Vec<(Level, String)> {
+  static INIT: Once = Once::new();
+  INIT.call_once(|| {
+    log::set_logger(&CapturingLogger).unwrap();
+    log::set_max_level(LevelFilter::Trace);
+  });
+
+  LEVEL.set(level);
+  let kakuro = Kakuro::from_reader(KAKURO.as_bytes())
+    .unwrap()
+    .pop()
+    .unwrap();
+  assert_eq!(kakuro.solve().unwrap().len(), 1);
+  LEVEL.set(LevelFilter::Off);
+  RECORDS.take()
+}
+
+#[test]
+fn test_debug_logs_each_run() {
+  let records = solve_logging_at(LevelFilter::Debug);
+  let kakuro = Kakuro::from_reader(KAKURO.as_bytes())
+    .unwrap()
+    .pop()
+    .unwrap();
+  let generating = records
+    .iter()
+    .filter(|(level, message)| *level == Level::Debug && message.starts_with("Generating subsets"))
+    .count();
+  assert_eq!(generating, kakuro.runs().len());
+  assert!(records
+    .iter()
+    .any(|(_, message)| message.starts_with("Search found 1 solutions")));
+  assert!(records.iter().all(|(level, _)| *level <= Level::Debug));
+}
+
+#[test]
+fn test_info_is_quiet() {
+  assert_eq!(solve_logging_at(LevelFilter::Info), vec![]);
+}
+
+#[test]
+fn test_trace_logs_search_steps() {
+  let records = solve_logging_at(LevelFilter::Trace);
+  assert!(records
+    .iter()
+    .any(|(level, message)| *level == Level::Trace && message.starts_with("Covering")));
+}
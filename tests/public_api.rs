@@ -0,0 +1,127 @@
+use itertools::Itertools;
+use p424::{
+  dlx::{Dlx, HeaderType},
+  futoshiki::Futoshiki,
+  kakuro::{Kakuro, LetterAssignment},
+  langford,
+  linear_solver::LinearSolver,
+  nonogram::Nonogram,
+  nqueens,
+  parenthesis_split::ParenthesesAwareSplit,
+  polyomino,
+  sudoku::Sudoku,
+};
+
+const KAKURO: &str = "6,X,(vIJ),(vIE),X,X,X,(hF),O,C,(vIA),X,X,(hCA),O,O,D,(vIH),X,X,(hIB),E,O,O,(vF),X,X,(hD),O,A,O,X,X,X,(hID),O,G";
+
+#[test]
+fn test_dlx() {
+  let mut dlx = Dlx::new(
+    [('a', HeaderType::Primary), ('b', HeaderType::Primary)],
+    [(0, vec!['a']), (1, vec!['a', 'b']), (2, vec!['b'])],
+  );
+  let solutions = dlx
+    .find_all_solution_names()
+    .map(|names| names.into_iter().sorted().collect_vec())
+    .sorted()
+    .collect_vec();
+  assert_eq!(solutions, vec![vec![0, 2], vec![1]]);
+}
+
+#[test]
+fn test_kakuro() {
+  let kakuro = Kakuro::from_reader(KAKURO.as_bytes())
+    .unwrap()
+    .pop()
+    .unwrap();
+  assert_eq!((kakuro.rows(), kakuro.cols()), (6, 6));
+
+  let letters = kakuro.solve().unwrap();
+  assert_eq!(letters.len(), 1);
+  assert_eq!(letters[0].int_value(), 4027398516);
+  assert_eq!(letters[0].letter_value('A'), Some(4));
+
+  let expected = LetterAssignment::from_pairs(&[
+    ('A', 4),
+    ('B', 0),
+    ('C', 2),
+    ('D', 7),
+    ('E', 3),
+    ('F', 9),
+    ('G', 8),
+    ('H', 5),
+    ('I', 1),
+    ('J', 6),
+  ])
+  .unwrap();
+  assert_eq!(letters[0], expected);
+}
+
+#[test]
+fn test_sudoku() {
+  let mut sudoku: Sudoku =
+    "..4.5....9..7346....3.21.49.35.9.48..9.....3..76.1.92.31.97.2....9182..3....6.1.."
+      .parse()
+      .unwrap();
+  assert!(sudoku.is_proper());
+  sudoku.solve().unwrap();
+  assert_eq!(sudoku.size(), 9);
+  assert_eq!(
+    (0..9).map(|col| sudoku.digit(0, col)).collect_vec(),
+    vec![2, 6, 4, 8, 5, 9, 3, 1, 7]
+  );
+}
+
+#[test]
+fn test_sudoku_solve_stats() {
+  let sudoku: Sudoku =
+    "..4.5....9..7346....3.21.49.35.9.48..9.....3..76.1.92.31.97.2....9182..3....6.1.."
+      .parse()
+      .unwrap();
+  let (solved, stats) = sudoku.solve_instrumented().unwrap();
+  assert_eq!(solved.digit(0, 0), 2);
+  let json: serde_json::Value = serde_json::to_value(&stats).unwrap();
+  assert_eq!(json["givens"], stats.givens);
+  assert_eq!(json["backtracks"], 0);
+  assert_eq!(json["solutions"], 1);
+}
+
+#[test]
+fn test_puzzles() {
+  assert_eq!(nqueens::count_solutions(6), 4);
+  assert_eq!(nqueens::solve(4).map(|cols| cols.len()), Some(4));
+  assert_eq!(langford::count(4), 1);
+  assert_eq!(langford::solve(4).map(|sequence| sequence.len()), Some(8));
+  assert_eq!(polyomino::tile(3, 20).map(|tiling| tiling.len()), Some(12));
+
+  let futoshiki: Futoshiki = "..\n..\n1,2 < 1,1".parse().unwrap();
+  assert_eq!(futoshiki.solve(), Some(vec![vec![2, 1], vec![1, 2]]));
+
+  // A plus sign.
+  let nonogram = Nonogram::new(
+    vec![vec![1], vec![3], vec![1]],
+    vec![vec![1], vec![3], vec![1]],
+  );
+  let grid = nonogram.solve().unwrap();
+  assert!(grid.filled(1, 0) && grid.filled(0, 1) && !grid.filled(0, 0));
+}
+
+#[test]
+fn test_linear_solver() {
+  // x + 2y = 7, with x and y in 1..=3.
+  let mut solver = LinearSolver::new();
+  solver.add_with_domain('x', 1, 1..=3);
+  solver.add_with_domain('y', 2, 1..=3);
+  assert_eq!(
+    solver.find_solutions_for_target(7).sorted().collect_vec(),
+    vec![vec![('x', 1), ('y', 3)], vec![('x', 3), ('y', 2)]]
+  );
+}
+
+#[test]
+fn test_split_paren() {
+  assert_eq!(
+    "6,X,(hA,vB),O".split_paren().collect_vec(),
+    vec!["6", "X", "(hA,vB)", "O"]
+  );
+}
@@ -0,0 +1,28 @@
+//! Run with `wasm-pack test --node -- --features wasm`.
+#![cfg(all(feature = "wasm", target_arch = "wasm32"))]
+
+use p424::wasm::{solve_kakuro, solve_sudoku};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+const KAKURO: &str = "6,X,(vIJ),(vIE),X,X,X,(hF),O,C,(vIA),X,X,(hCA),O,O,D,(vIH),X,X,(hIB),E,O,O,(vF),X,X,(hD),O,A,O,X,X,X,(hID),O,G";
+
+#[wasm_bindgen_test]
+fn test_solve_sudoku() {
+  let solution = solve_sudoku(
+    "..4.5....9..7346....3.21.49.35.9.48..9.....3..76.1.92.31.97.2....9182..3....6.1..",
+  )
+  .unwrap();
+  assert!(solution.starts_with("264859317"));
+  assert_eq!(solve_sudoku("not a grid"), None);
+}
+
+#[wasm_bindgen_test]
+fn test_solve_kakuro() {
+  let solution: serde_json::Value = serde_wasm_bindgen::from_value(solve_kakuro(KAKURO)).unwrap();
+  assert_eq!(solution["value"], 4027398516u64);
+  assert_eq!(solution["letters"]["A"], 4);
+  assert_eq!(solution["digits"].as_array().unwrap().len(), 6);
+  assert_eq!(solution["digits"][0][0], serde_json::Value::Null);
+
+  assert!(solve_kakuro("6,X").is_null());
+}